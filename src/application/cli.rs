@@ -1,3 +1,7 @@
+#[cfg(test)]
+#[path = "cli_test.rs"]
+mod tests;
+
 use std::env;
 use std::io;
 use std::path;
@@ -9,6 +13,7 @@ use clap::value_parser;
 use clap::Arg;
 use clap::ArgAction;
 use clap::ArgGroup;
+use clap::ArgMatches;
 use clap::Command;
 use clap_complete::generate;
 use clap_complete::Generator;
@@ -23,12 +28,19 @@ use yansi::Paint;
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
 use crate::domain::models::BackendName;
+use crate::domain::models::ContextStrategy;
 use crate::domain::models::EditorName;
 use crate::domain::models::Session;
+use crate::domain::models::ToolName;
+use crate::domain::models::Usage;
 use crate::domain::services::actions::help_text;
+use crate::domain::services::Indexes;
 use crate::domain::services::Sessions;
 use crate::domain::services::Syntaxes;
 use crate::domain::services::Themes;
+use crate::infrastructure::backends::mock::MockMode;
+use crate::infrastructure::backends::openai::StreamUsage;
+use crate::infrastructure::backends::BackendManager;
 
 fn print_completions<G: Generator>(gen: G, cmd: &mut Command) {
     generate(gen, cmd, cmd.get_name().to_string(), &mut io::stdout());
@@ -45,6 +57,18 @@ fn format_session(session: &Session) -> String {
         res = format!("{res}, Lang: {}", session.state.editor_language)
     }
 
+    if !session.state.profile.is_empty() {
+        res = format!("{res}, Profile: {}", session.state.profile)
+    }
+
+    let usage = Usage::total(&session.state.usage);
+    if usage.total_tokens() > 0 {
+        res = format!("{res}, Tokens: {}", usage.total_tokens());
+        if let Some(cost) = Usage::estimated_cost(&session.state.usage) {
+            res = format!("{res} (~${cost:.4})");
+        }
+    }
+
     if !session.state.messages.is_empty() {
         let mut line = session.state.messages[0]
             .text
@@ -104,16 +128,58 @@ async fn create_config_file() -> Result<()> {
     return Ok(());
 }
 
-async fn load_config_from_session(session_id: &str) -> Result<()> {
-    let session = Sessions::default().load(session_id).await?;
+async fn index_project(dir: &str) -> Result<()> {
+    let backend_name = Config::get(ConfigKey::EmbeddingsBackend);
+    let backend = match BackendName::parse(backend_name.to_string()) {
+        Some(name) => BackendManager::get(name).await?,
+        None => bail!(format!("Unknown embeddings backend {backend_name}")),
+    };
+    let model = Config::get(ConfigKey::EmbeddingsModel);
+
+    println!("Indexing {dir} with {backend_name} model {model}");
+    let index = Indexes::build(path::Path::new(dir), &backend, &model, |embedded, total| {
+        print!("\rEmbedded {embedded} of {total} chunks");
+        let _ = io::Write::flush(&mut io::stdout());
+    })
+    .await?;
+
+    let file_path = Indexes::default().save(&index).await?;
+    println!(
+        "\nIndexed {} chunks from {} files to {}",
+        index.chunks.len(),
+        index.files_len(),
+        file_path.to_string_lossy()
+    );
+
+    return Ok(());
+}
+
+async fn load_config_from_session(
+    sessions: &Sessions,
+    session_id: &str,
+    clap_arg_matches: &[&ArgMatches],
+) -> Result<()> {
+    let session = sessions.load(session_id).await?;
+    // The session's backend and model are kept over the profile's, and the
+    // command line arguments over both.
+    if Config::get_profile(&session.state.profile).is_some() {
+        Config::apply_profile(&session.state.profile)?;
+    }
     Config::set(ConfigKey::Backend, &session.state.backend_name);
     Config::set(ConfigKey::Model, &session.state.backend_model);
     Config::set(ConfigKey::SessionID, session_id);
 
+    if Config::get_persona(&session.state.persona).is_some() {
+        Config::set(ConfigKey::Persona, &session.state.persona);
+    }
+
+    session.state.generation_options.save_to_config();
+    Config::apply_args(clap_arg_matches)?;
+
     return Ok(());
 }
 
-async fn load_config_from_session_interactive() -> Result<()> {
+async fn load_config_from_session_interactive(clap_arg_matches: &[&ArgMatches]) -> Result<()> {
     let mut sessions = Sessions::default().list().await?;
     sessions.reverse();
 
@@ -136,7 +202,7 @@ async fn load_config_from_session_interactive() -> Result<()> {
         .interact_opt()?
         .unwrap();
 
-    load_config_from_session(&sessions[idx].id).await?;
+    load_config_from_session(&Sessions::default(), &sessions[idx].id, clap_arg_matches).await?;
 
     return Ok(());
 }
@@ -246,13 +312,59 @@ fn arg_backend_health_check_timeout() -> Arg {
         );
 }
 
+fn arg_backend_max_attempts() -> Arg {
+    return Arg::new(ConfigKey::BackendMaxAttempts.to_string())
+        .long(ConfigKey::BackendMaxAttempts.to_string())
+        .env("OATMEAL_BACKEND_MAX_ATTEMPTS")
+        .num_args(1)
+        .help(
+            format!("Number of attempts made for a backend request before giving up when it's rate limited, overloaded, or unreachable. [default: {}]", Config::default(ConfigKey::BackendMaxAttempts)),
+        );
+}
+
 fn arg_model() -> Arg {
     return Arg::new(ConfigKey::Model.to_string())
         .short('m')
         .long(ConfigKey::Model.to_string())
         .env("OATMEAL_MODEL")
         .num_args(1)
-        .help("The initial model on a backend to consume. Defaults to the first model available from the backend if not set.");
+        .help("The initial model on a backend to consume. Defaults to the first available model from the default models, or the first model available from the backend if not set.");
+}
+
+fn arg_default_models() -> Arg {
+    return Arg::new(ConfigKey::DefaultModels.to_string())
+        .long(ConfigKey::DefaultModels.to_string())
+        .env("OATMEAL_DEFAULT_MODELS")
+        .num_args(1)
+        .help("Comma separated list of preferred models, checked in order, used when no model is set.");
+}
+
+fn arg_compare() -> Arg {
+    return Arg::new(ConfigKey::Compare.to_string())
+        .long(ConfigKey::Compare.to_string())
+        .env("OATMEAL_COMPARE")
+        .num_args(1)
+        .help("Comma separated list of backend:model pairs to start in compare mode, sending each message to all of them and showing their answers side by side. For example: openai:gpt-4o,ollama:llama3");
+}
+
+fn arg_persona() -> Arg {
+    return Arg::new(ConfigKey::Persona.to_string())
+        .short('p')
+        .long(ConfigKey::Persona.to_string())
+        .env("OATMEAL_PERSONA")
+        .num_args(1)
+        .help(format!(
+            "The persona providing the system prompt sent to the model. Personas are defined in the config file. [default: {}]",
+            Config::default(ConfigKey::Persona)
+        ));
+}
+
+fn arg_profile() -> Arg {
+    return Arg::new(ConfigKey::Profile.to_string())
+        .long(ConfigKey::Profile.to_string())
+        .env("OATMEAL_PROFILE")
+        .num_args(1)
+        .help("The profile providing the backend, model, endpoint, credentials and generation options. Profiles are defined in the config file, and command line options take precedence over them.");
 }
 
 fn subcommand_chat() -> Command {
@@ -260,7 +372,23 @@ fn subcommand_chat() -> Command {
         .about("Start a new chat session.")
         .arg(arg_backend())
         .arg(arg_backend_health_check_timeout())
-        .arg(arg_model());
+        .arg(arg_backend_max_attempts())
+        .arg(arg_model())
+        .arg(arg_default_models())
+        .arg(arg_compare())
+        .arg(arg_persona())
+        .arg(arg_profile());
+}
+
+fn subcommand_index() -> Command {
+    return Command::new("index")
+        .about("Embed the text files of a project for `/rag` mode, replacing its previous index.")
+        .arg(
+            Arg::new("path")
+                .help("Directory of the project.")
+                .default_value(".")
+                .required(false),
+        );
 }
 
 fn subcommand_sessions() -> Command {
@@ -268,7 +396,7 @@ fn subcommand_sessions() -> Command {
         .about("Manage past chat sessions.")
         .arg_required_else_help(true)
         .subcommand(Command::new("dir").about("Print the sessions cache directory path."))
-        .subcommand(Command::new("list").about("List all previous sessions with their ids and models."))
+        .subcommand(Command::new("list").about("List all previous sessions with their ids, models and token usage."))
         .subcommand(
             Command::new("open")
                 .about("Open a previous session by ID. Omit passing any session ID to load an interactive selection.")
@@ -323,11 +451,91 @@ pub fn build() -> Command {
         .subcommand(subcommand_completions())
         .subcommand(subcommand_config())
         .subcommand(subcommand_debug())
+        .subcommand(subcommand_index())
         .subcommand(Command::new("manpages").about("Generates manpages and outputs to stdout."))
         .subcommand(subcommand_sessions())
         .arg(arg_backend())
         .arg(arg_backend_health_check_timeout())
+        .arg(arg_backend_max_attempts())
         .arg(arg_model())
+        .arg(arg_default_models())
+        .arg(arg_compare())
+        .arg(arg_persona())
+        .arg(arg_profile())
+        .arg(
+            Arg::new(ConfigKey::Temperature.to_string())
+                .long(ConfigKey::Temperature.to_string())
+                .env("OATMEAL_TEMPERATURE")
+                .num_args(1)
+                .help("Sampling temperature between 0 and 2 sent to the model. Lower values are more deterministic. Defaults to the backend's default if not set.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::MaxTokens.to_string())
+                .long(ConfigKey::MaxTokens.to_string())
+                .env("OATMEAL_MAX_TOKENS")
+                .num_args(1)
+                .help("Maximum number of tokens the model may generate per response. Defaults to the backend's default if not set.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::TopP.to_string())
+                .long(ConfigKey::TopP.to_string())
+                .env("OATMEAL_TOP_P")
+                .num_args(1)
+                .help("Nucleus sampling probability between 0 and 1 sent to the model. Defaults to the backend's default if not set.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::Stop.to_string())
+                .long(ConfigKey::Stop.to_string())
+                .env("OATMEAL_STOP")
+                .num_args(1)
+                .help("Comma separated list of sequences where the model will stop generating.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::ContextWindow.to_string())
+                .long(ConfigKey::ContextWindow.to_string())
+                .env("OATMEAL_CONTEXT_WINDOW")
+                .num_args(1)
+                .help("Number of tokens the model accepts. When a conversation grows past it, the history is fit using the context strategy. Sends the full history if not set.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::ContextStrategy.to_string())
+                .long(ConfigKey::ContextStrategy.to_string())
+                .env("OATMEAL_CONTEXT_STRATEGY")
+                .num_args(1)
+                .help(format!("How a conversation is fit in the context window. `truncate` drops the oldest messages, and `summarize` asks the model to summarize them. Pinned messages are always kept. [default: {}]", Config::default(ConfigKey::ContextStrategy)))
+                .value_parser(PossibleValuesParser::new(ContextStrategy::VARIANTS))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::EmbeddingsBackend.to_string())
+                .long(ConfigKey::EmbeddingsBackend.to_string())
+                .env("OATMEAL_EMBEDDINGS_BACKEND")
+                .num_args(1)
+                .help(format!("Backend used to embed a project with `oatmeal index`. Prompts in `/rag` mode are embedded with the backend and model the project was indexed with. Only ollama, openai and azure support embeddings. [default: {}]", Config::default(ConfigKey::EmbeddingsBackend)))
+                .value_parser(PossibleValuesParser::new(BackendName::VARIANTS))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::EmbeddingsModel.to_string())
+                .long(ConfigKey::EmbeddingsModel.to_string())
+                .env("OATMEAL_EMBEDDINGS_MODEL")
+                .num_args(1)
+                .help(format!("Embeddings model of the embeddings backend. A project has to be indexed again after changing it. [default: {}]", Config::default(ConfigKey::EmbeddingsModel)))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::RagTopK.to_string())
+                .long(ConfigKey::RagTopK.to_string())
+                .env("OATMEAL_RAG_TOP_K")
+                .num_args(1)
+                .help(format!("Number of the most relevant chunks of a project added to each prompt in `/rag` mode. [default: {}]", Config::default(ConfigKey::RagTopK)))
+                .global(true),
+        )
         .arg(
             Arg::new(ConfigKey::ConfigFile.to_string())
                 .short('c')
@@ -367,6 +575,22 @@ pub fn build() -> Command {
                 )
                 .global(true),
         )
+        .arg(
+            Arg::new(ConfigKey::Tools.to_string())
+                .long(ConfigKey::Tools.to_string())
+                .env("OATMEAL_TOOLS")
+                .num_args(1)
+                .help(format!("Comma separated list of local tools the model may call, each call being approved before it runs. Supported by the OpenAI, Azure, Claude, and Gemini backends. Possible values are: {}", ToolName::VARIANTS.join(", ")))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::ToolsTestCommand.to_string())
+                .long(ConfigKey::ToolsTestCommand.to_string())
+                .env("OATMEAL_TOOLS_TEST_COMMAND")
+                .num_args(1)
+                .help("Shell command run in the current directory by the run_tests tool, such as `cargo test`.")
+                .global(true),
+        )
         .arg(
             Arg::new(ConfigKey::LangChainURL.to_string())
                 .long(ConfigKey::LangChainURL.to_string())
@@ -375,6 +599,38 @@ pub fn build() -> Command {
                 .help(format!("LangChain Serve API URL when using the LangChain backend. [default: {}]", Config::default(ConfigKey::LangChainURL)))
                 .global(true),
         )
+        .arg(
+            Arg::new(ConfigKey::LangChainInputKey.to_string())
+                .long(ConfigKey::LangChainInputKey.to_string())
+                .env("OATMEAL_LANGCHAIN_INPUT_KEY")
+                .num_args(1)
+                .help(format!("Key of the chain's input holding the prompt when using the LangChain backend. [default: {}]", Config::default(ConfigKey::LangChainInputKey)))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::LangChainHistoryKey.to_string())
+                .long(ConfigKey::LangChainHistoryKey.to_string())
+                .env("OATMEAL_LANGCHAIN_HISTORY_KEY")
+                .num_args(1)
+                .help("Key of the chain's input holding the prior messages of the conversation when using the LangChain backend, such as chat_history. Only the latest prompt is sent if not set.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::LangChainExtraInput.to_string())
+                .long(ConfigKey::LangChainExtraInput.to_string())
+                .env("OATMEAL_LANGCHAIN_EXTRA_INPUT")
+                .num_args(1)
+                .help("JSON object of static values added to the chain's input when using the LangChain backend.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::LangChainConfig.to_string())
+                .long(ConfigKey::LangChainConfig.to_string())
+                .env("OATMEAL_LANGCHAIN_CONFIG")
+                .num_args(1)
+                .help("JSON object sent as the runnable's config when using the LangChain backend.")
+                .global(true),
+        )
         .arg(
             Arg::new(ConfigKey::OllamaURL.to_string())
                 .long(ConfigKey::OllamaURL.to_string())
@@ -383,6 +639,22 @@ pub fn build() -> Command {
                 .help(format!("Ollama API URL when using the Ollama backend. [default: {}]", Config::default(ConfigKey::OllamaURL)))
                 .global(true),
         )
+        .arg(
+            Arg::new(ConfigKey::OllamaNumCtx.to_string())
+                .long(ConfigKey::OllamaNumCtx.to_string())
+                .env("OATMEAL_OLLAMA_NUM_CTX")
+                .num_args(1)
+                .help("Size of the context window in tokens when using the Ollama backend. Defaults to the model's default if not set.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::OllamaKeepAlive.to_string())
+                .long(ConfigKey::OllamaKeepAlive.to_string())
+                .env("OATMEAL_OLLAMA_KEEP_ALIVE")
+                .num_args(1)
+                .help("How long Ollama keeps the model loaded after a request, either in seconds or as a duration such as 10m. Defaults to Ollama's default if not set.")
+                .global(true),
+        )
         .arg(
             Arg::new(ConfigKey::OpenAiURL.to_string())
                 .long(ConfigKey::OpenAiURL.to_string())
@@ -391,6 +663,15 @@ pub fn build() -> Command {
                 .help(format!("OpenAI API URL when using the OpenAI backend. Can be swapped to a compatible proxy. [default: {}]", Config::default(ConfigKey::OpenAiURL)))
                 .global(true),
         )
+        .arg(
+            Arg::new(ConfigKey::OpenAiStreamUsage.to_string())
+                .long(ConfigKey::OpenAiStreamUsage.to_string())
+                .env("OATMEAL_OPENAI_STREAM_USAGE")
+                .num_args(1)
+                .help(format!("Whether the OpenAI and Azure backends ask for the token usage of responses, which some compatible servers and Azure api-versions before 2024-09-01 reject. `auto` only asks api.openai.com and Azure api-versions which support it. [default: {}]", Config::default(ConfigKey::OpenAiStreamUsage)))
+                .value_parser(PossibleValuesParser::new(StreamUsage::VARIANTS))
+                .global(true),
+        )
         .arg(
             Arg::new(ConfigKey::OpenAiToken.to_string())
                 .long(ConfigKey::OpenAiToken.to_string())
@@ -399,6 +680,70 @@ pub fn build() -> Command {
                 .help("OpenAI API token when using the OpenAI backend.")
                 .global(true),
         )
+        .arg(
+            Arg::new(ConfigKey::OpenAiTokenFile.to_string())
+                .long(ConfigKey::OpenAiTokenFile.to_string())
+                .env("OATMEAL_OPENAI_TOKEN_FILE")
+                .num_args(1)
+                .help("Path to a file holding the OpenAI API token, read when the OpenAI backend is used.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::OpenAiTokenCommand.to_string())
+                .long(ConfigKey::OpenAiTokenCommand.to_string())
+                .env("OATMEAL_OPENAI_TOKEN_COMMAND")
+                .num_args(1)
+                .help("Command printing the OpenAI API token on its first line, such as `pass show openai`, run when the OpenAI backend is used.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::AzureURL.to_string())
+                .long(ConfigKey::AzureURL.to_string())
+                .env("OATMEAL_AZURE_URL")
+                .num_args(1)
+                .help("Azure OpenAI resource endpoint when using the Azure backend, such as https://NAME.openai.azure.com.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::AzureToken.to_string())
+                .long(ConfigKey::AzureToken.to_string())
+                .env("OATMEAL_AZURE_TOKEN")
+                .num_args(1)
+                .help("Azure OpenAI API key when using the Azure backend.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::AzureTokenFile.to_string())
+                .long(ConfigKey::AzureTokenFile.to_string())
+                .env("OATMEAL_AZURE_TOKEN_FILE")
+                .num_args(1)
+                .help("Path to a file holding the Azure OpenAI API key, read when the Azure backend is used.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::AzureTokenCommand.to_string())
+                .long(ConfigKey::AzureTokenCommand.to_string())
+                .env("OATMEAL_AZURE_TOKEN_COMMAND")
+                .num_args(1)
+                .help("Command printing the Azure OpenAI API key on its first line, such as `pass show azure`, run when the Azure backend is used.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::AzureApiVersion.to_string())
+                .long(ConfigKey::AzureApiVersion.to_string())
+                .env("OATMEAL_AZURE_API_VERSION")
+                .num_args(1)
+                .help(format!("Azure OpenAI API version when using the Azure backend. [default: {}]", Config::default(ConfigKey::AzureApiVersion)))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::AzureDeployments.to_string())
+                .long(ConfigKey::AzureDeployments.to_string())
+                .env("OATMEAL_AZURE_DEPLOYMENTS")
+                .num_args(1)
+                .help("Comma separated list of deployment names used as models on the Azure backend. Required with the Azure backend, as Azure OpenAI api-versions can't list them.")
+                .global(true),
+        )
         .arg(
             Arg::new(ConfigKey::ClaudeToken.to_string())
                 .long(ConfigKey::ClaudeToken.to_string())
@@ -407,6 +752,30 @@ pub fn build() -> Command {
                 .help("Anthropic's Claude API token when using the Claude backend.")
                 .global(true),
         )
+        .arg(
+            Arg::new(ConfigKey::ClaudeTokenFile.to_string())
+                .long(ConfigKey::ClaudeTokenFile.to_string())
+                .env("OATMEAL_CLAUDE_TOKEN_FILE")
+                .num_args(1)
+                .help("Path to a file holding the Claude API token, read when the Claude backend is used.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::ClaudeTokenCommand.to_string())
+                .long(ConfigKey::ClaudeTokenCommand.to_string())
+                .env("OATMEAL_CLAUDE_TOKEN_COMMAND")
+                .num_args(1)
+                .help("Command printing the Claude API token on its first line, such as `pass show claude`, run when the Claude backend is used.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::ClaudeThinkingBudget.to_string())
+                .long(ConfigKey::ClaudeThinkingBudget.to_string())
+                .env("OATMEAL_CLAUDE_THINKING_BUDGET")
+                .num_args(1)
+                .help("Tokens Claude may think with before answering, at least 1024. Extended thinking is off when not set.")
+                .global(true),
+        )
         .arg(
             Arg::new(ConfigKey::GeminiToken.to_string())
                 .long(ConfigKey::GeminiToken.to_string())
@@ -414,6 +783,127 @@ pub fn build() -> Command {
                 .num_args(1)
                 .help("Google Gemini API token when using the Gemini backend.")
                 .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::GeminiTokenFile.to_string())
+                .long(ConfigKey::GeminiTokenFile.to_string())
+                .env("OATMEAL_GEMINI_TOKEN_FILE")
+                .num_args(1)
+                .help("Path to a file holding the Google Gemini API token, read when the Gemini backend is used.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::GeminiTokenCommand.to_string())
+                .long(ConfigKey::GeminiTokenCommand.to_string())
+                .env("OATMEAL_GEMINI_TOKEN_COMMAND")
+                .num_args(1)
+                .help("Command printing the Google Gemini API token on its first line, such as `pass show gemini`, run when the Gemini backend is used.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::MockMode.to_string())
+                .long(ConfigKey::MockMode.to_string())
+                .env("OATMEAL_MOCK_MODE")
+                .num_args(1)
+                .help(format!("How the mock backend answers prompts, without a model server. `echo` streams the prompt back, `fixtures` replays markdown files from the mock fixtures directory, and `error` and `timeout` fail half way through a response. [default: {}]", Config::default(ConfigKey::MockMode)))
+                .value_parser(PossibleValuesParser::new(MockMode::VARIANTS))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::MockDelay.to_string())
+                .long(ConfigKey::MockDelay.to_string())
+                .env("OATMEAL_MOCK_DELAY")
+                .num_args(1)
+                .help(format!("Time in milliseconds the mock backend waits between each streamed word. [default: {}]", Config::default(ConfigKey::MockDelay)))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::MockFixturesDir.to_string())
+                .long(ConfigKey::MockFixturesDir.to_string())
+                .env("OATMEAL_MOCK_FIXTURES_DIR")
+                .num_args(1)
+                .help("Directory of markdown files replayed by the mock backend in fixtures mode, where each file is listed as a model. A built in answer with code blocks is replayed if not set.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::RecordCassette.to_string())
+                .long(ConfigKey::RecordCassette.to_string())
+                .env("OATMEAL_RECORD_CASSETTE")
+                .num_args(1)
+                .help("Path to a cassette file each backend request, its streamed response, and the responses shown in the chat are appended to, with secrets redacted. Useful for attaching to bug reports.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::ReplayCassette.to_string())
+                .long(ConfigKey::ReplayCassette.to_string())
+                .env("OATMEAL_REPLAY_CASSETTE")
+                .num_args(1)
+                .help("Path to a recorded cassette file the replay backend answers prompts from.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::HttpProxy.to_string())
+                .long(ConfigKey::HttpProxy.to_string())
+                .env("OATMEAL_HTTP_PROXY")
+                .num_args(1)
+                .help("Proxy URL used for all backend requests, such as http://proxy.example.com:8080. Defaults to the HTTP_PROXY and HTTPS_PROXY environment variables if not set.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::NoProxy.to_string())
+                .long(ConfigKey::NoProxy.to_string())
+                .env("OATMEAL_NO_PROXY")
+                .num_args(1)
+                .help("Comma separated list of hosts, domains and IP ranges which bypass the configured HTTP proxy.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::CaBundle.to_string())
+                .long(ConfigKey::CaBundle.to_string())
+                .env("OATMEAL_CA_BUNDLE")
+                .num_args(1)
+                .help("Path to a PEM file of CA certificates trusted in addition to the system's certificates.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::ClientCert.to_string())
+                .long(ConfigKey::ClientCert.to_string())
+                .env("OATMEAL_CLIENT_CERT")
+                .num_args(1)
+                .help("Path to a PEM client certificate presented to backends requiring mutual TLS. Can also contain the private key.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::ClientKey.to_string())
+                .long(ConfigKey::ClientKey.to_string())
+                .env("OATMEAL_CLIENT_KEY")
+                .num_args(1)
+                .help("Path to the PEM private key of the client certificate, when it's not in the certificate file.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::HttpConnectTimeout.to_string())
+                .long(ConfigKey::HttpConnectTimeout.to_string())
+                .env("OATMEAL_HTTP_CONNECT_TIMEOUT")
+                .num_args(1)
+                .help(format!("Time in milliseconds to wait for a connection to a backend. [default: {}]", Config::default(ConfigKey::HttpConnectTimeout)))
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::HttpReadTimeout.to_string())
+                .long(ConfigKey::HttpReadTimeout.to_string())
+                .env("OATMEAL_HTTP_READ_TIMEOUT")
+                .num_args(1)
+                .help("Time in milliseconds to wait for the next part of a streamed response before failing. Waits indefinitely if not set, as local models can take a while to load.")
+                .global(true),
+        )
+        .arg(
+            Arg::new(ConfigKey::HttpRequestTimeout.to_string())
+                .long(ConfigKey::HttpRequestTimeout.to_string())
+                .env("OATMEAL_HTTP_REQUEST_TIMEOUT")
+                .num_args(1)
+                .help(format!("Time in milliseconds to wait for a whole response which isn't streamed, such as model lists and embeddings, before failing. Waits indefinitely when set to 0. [default: {}]", Config::default(ConfigKey::HttpRequestTimeout)))
+                .global(true),
         );
 }
 
@@ -478,6 +968,11 @@ pub async fn parse() -> Result<bool> {
                 }
             }
         }
+        Some(("index", subcmd_matches)) => {
+            Config::load(build(), vec![&matches, subcmd_matches]).await?;
+            index_project(subcmd_matches.get_one::<String>("path").unwrap()).await?;
+            return Ok(false);
+        }
         Some(("manpages", _)) => {
             clap_mangen::Man::new(build()).render(&mut io::stdout())?;
             return Ok(false);
@@ -494,11 +989,13 @@ pub async fn parse() -> Result<bool> {
                     return Ok(false);
                 }
                 Some(("open", open_matches)) => {
-                    Config::load(build(), vec![&matches, open_matches]).await?;
+                    let arg_matches = vec![&matches, open_matches];
+                    Config::load(build(), arg_matches.clone()).await?;
                     if let Some(session_id) = open_matches.get_one::<String>("session-id") {
-                        load_config_from_session(session_id).await?;
+                        load_config_from_session(&Sessions::default(), session_id, &arg_matches)
+                            .await?;
                     } else {
-                        load_config_from_session_interactive().await?;
+                        load_config_from_session_interactive(&arg_matches).await?;
                     }
                 }
                 Some(("delete", delete_matches)) => {
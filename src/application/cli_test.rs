@@ -0,0 +1,86 @@
+use anyhow::Result;
+use tokio::fs;
+
+use super::build;
+use super::load_config_from_session;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::Session;
+use crate::domain::models::State;
+use crate::domain::services::Sessions;
+
+async fn create_session(sessions: &Sessions) -> Result<()> {
+    let session = Session {
+        id: "session-id".to_string(),
+        version: "1".to_string(),
+        timestamp: "2023-11-28T00:00:00Z".to_string(),
+        state: State {
+            backend_name: "openai".to_string(),
+            backend_model: "gpt-4o".to_string(),
+            editor_language: "".to_string(),
+            persona: "default".to_string(),
+            profile: "work-gpt".to_string(),
+            generation_options: GenerationOptions {
+                temperature: Some(0.9),
+                max_tokens: Some(100),
+                ..GenerationOptions::default()
+            },
+            usage: Default::default(),
+            messages: vec![],
+        },
+    };
+    fs::create_dir_all(&sessions.cache_dir).await?;
+    fs::write(
+        sessions.cache_dir.join("session-id.yaml"),
+        serde_yaml::to_string(&session)?,
+    )
+    .await?;
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_keeps_command_line_arguments_over_the_session() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let sessions = Sessions::new(dir.path().to_path_buf());
+    create_session(&sessions).await?;
+
+    let matches = build().try_get_matches_from(vec![
+        "oatmeal",
+        "-c",
+        "./test/profiles-config.toml",
+        "sessions",
+        "open",
+    ])?;
+    Config::load(build(), vec![&matches]).await?;
+    load_config_from_session(&sessions, "session-id", &[&matches]).await?;
+
+    assert_eq!(Config::get(ConfigKey::SessionID), "session-id");
+    assert_eq!(Config::get(ConfigKey::Profile), "work-gpt");
+    assert_eq!(Config::get(ConfigKey::Backend), "openai");
+    assert_eq!(Config::get(ConfigKey::Model), "gpt-4o");
+    assert_eq!(GenerationOptions::from_config()?.temperature, Some(0.9));
+
+    let matches = build().try_get_matches_from(vec![
+        "oatmeal",
+        "-c",
+        "./test/profiles-config.toml",
+        "--profile",
+        "local",
+        "--temperature",
+        "0.2",
+        "sessions",
+        "open",
+    ])?;
+    Config::load(build(), vec![&matches]).await?;
+    load_config_from_session(&sessions, "session-id", &[&matches]).await?;
+
+    assert_eq!(Config::get(ConfigKey::SessionID), "session-id");
+    assert_eq!(Config::get(ConfigKey::Profile), "local");
+    assert_eq!(Config::get(ConfigKey::Backend), "ollama");
+    assert_eq!(Config::get(ConfigKey::Model), "llama3");
+    let generation_options = GenerationOptions::from_config()?;
+    assert_eq!(generation_options.temperature, Some(0.2));
+    assert_eq!(generation_options.max_tokens, Some(100));
+    return Ok(());
+}
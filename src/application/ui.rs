@@ -1,3 +1,7 @@
+#[cfg(test)]
+#[path = "ui_test.rs"]
+mod tests;
+
 use std::io;
 
 use anyhow::Result;
@@ -22,13 +26,17 @@ use tokio::sync::mpsc;
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
 use crate::domain::models::Action;
+use crate::domain::models::Attachment;
 use crate::domain::models::Author;
 use crate::domain::models::BackendName;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::EditorName;
 use crate::domain::models::Event;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::HistoryMessage;
 use crate::domain::models::Loading;
 use crate::domain::models::Message;
+use crate::domain::models::MessageType;
 use crate::domain::models::SlashCommand;
 use crate::domain::models::TextArea;
 use crate::domain::services::events::EventsService;
@@ -61,9 +69,8 @@ async fn start_loop<B: Backend>(
     terminal: &mut Terminal<B>,
     app_state_props: AppStateProps,
     tx: mpsc::UnboundedSender<Action>,
-    rx: mpsc::UnboundedReceiver<Event>,
+    mut events: EventsService,
 ) -> Result<()> {
-    let mut events = EventsService::new(rx);
     let mut textarea = TextArea::default();
     let mut app_state = AppState::new(app_state_props).await?;
     let loading = Loading::default();
@@ -118,13 +125,57 @@ async fn start_loop<B: Backend>(
             }
         })?;
 
+        macro_rules! send_prompt {
+            ( $input_str:expr, $attachments:expr, $history:expr ) => {
+                let mut prompt = BackendPrompt::new($input_str.to_string(), $history);
+                prompt.attachments = $attachments;
+                if let Err(err) = prompt.load_attachments().await {
+                    app_state.add_message(Message::new_with_type(
+                        Author::Oatmeal,
+                        MessageType::Error,
+                        &err.to_string(),
+                    ));
+                    continue;
+                }
+
+                app_state.waiting_for_backend = true;
+                app_state.aborting = false;
+                app_state.pending_sources = vec![];
+
+                let persona =
+                    Config::get_persona(&Config::get(ConfigKey::Persona)).unwrap_or_default();
+                prompt.set_system_prompt(&persona, &app_state.editor_context);
+                prompt.generation_options = GenerationOptions::from_config()?;
+
+                if app_state.compare_targets.is_empty() {
+                    tx.send(Action::BackendRequest(prompt))?;
+                } else {
+                    app_state.start_comparison();
+                    tx.send(Action::BackendCompare(
+                        app_state.compare_targets.clone(),
+                        prompt,
+                    ))?;
+                }
+                app_state.save_session().await?;
+            };
+        }
+
         macro_rules! send_user_message {
-            ( $input_str:expr ) => {
+            ( $input_str:expr, $attachments:expr ) => {
                 let input_str = $input_str;
 
-                let msg = Message::new(Author::User, &input_str);
+                let mut msg = Message::new(Author::User, &input_str);
+                msg.attachments = $attachments;
+                let attachments = msg.attachments.clone();
                 textarea = TextArea::default();
-                app_state.add_message(msg);
+                if app_state.reject_during_comparison(input_str) {
+                    continue;
+                }
+                if SlashCommand::parse(input_str).is_none() {
+                    app_state.fork_from_edited_message(msg);
+                } else {
+                    app_state.add_message(msg);
+                }
 
                 let (should_break, should_continue) =
                     app_state.handle_slash_commands(input_str, &tx)?;
@@ -136,25 +187,39 @@ async fn start_loop<B: Backend>(
                     continue;
                 }
 
-                app_state.waiting_for_backend = true;
-                let mut prompt =
-                    BackendPrompt::new(input_str.to_string(), app_state.backend_context.clone());
-
-                if app_state.backend_context.is_empty() && SlashCommand::parse(&input_str).is_none()
-                {
-                    prompt.append_chat_context(&app_state.editor_context);
-                }
-
-                tx.send(Action::BackendRequest(prompt))?;
-                app_state.save_session().await?;
+                let history = HistoryMessage::from_messages(
+                    &app_state.messages[..app_state.messages.len() - 1],
+                );
+                send_prompt!(input_str, attachments, history);
             };
         }
 
-        match events.next().await? {
+        let event = events.next().await?;
+        match event {
+            Event::BackendCompareError(idx, err) => {
+                app_state.handle_compare_error(idx, &err);
+            }
+            Event::BackendCompareResponse(idx, msg) => {
+                app_state.handle_compare_response(idx, msg);
+            }
             Event::BackendMessage(msg) => {
+                app_state.discard_empty_response();
                 app_state.add_message(msg);
                 app_state.waiting_for_backend = false;
             }
+            Event::BackendSources(sources) => {
+                app_state.set_sources(sources);
+            }
+            Event::BackendStatus(msg) => {
+                app_state.add_message(msg);
+            }
+            Event::BackendSummary(msg) => {
+                app_state.add_summary(msg);
+                app_state.save_session().await?;
+            }
+            Event::BackendToolCall(approval) => {
+                app_state.request_tool_approval(approval);
+            }
             Event::BackendPromptResponse(msg) => {
                 app_state.handle_backend_response(msg.clone());
                 if msg.done {
@@ -162,10 +227,35 @@ async fn start_loop<B: Backend>(
                 }
             }
             Event::KeyboardCharInput(input) => {
+                if app_state.pending_tool_approval.is_some() {
+                    match input.key {
+                        tui_textarea::Key::Char('y') | tui_textarea::Key::Char('Y') => {
+                            app_state.respond_tool_approval(true);
+                        }
+                        tui_textarea::Key::Char('n')
+                        | tui_textarea::Key::Char('N')
+                        | tui_textarea::Key::Esc => {
+                            app_state.respond_tool_approval(false);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
                 if app_state.waiting_for_backend {
                     continue;
                 }
 
+                if input.key == tui_textarea::Key::Esc
+                    && (app_state.selected_message.is_some() || app_state.editing_message.is_some())
+                {
+                    if app_state.editing_message.is_some() {
+                        textarea = TextArea::default();
+                    }
+                    app_state.cancel_selection();
+                    continue;
+                }
+
                 // Windows submits a null event right after CTRL+C. Ignore it.
                 if input.key != tui_textarea::Key::Null {
                     app_state.exit_warning = false;
@@ -174,8 +264,16 @@ async fn start_loop<B: Backend>(
                 textarea.input(input);
             }
             Event::KeyboardCTRLC() => {
+                // The backend still sends what it had streamed before
+                // stopping, so prompts wait for its truncated `done`, unless
+                // it's aborted again when it doesn't stop.
                 if app_state.waiting_for_backend {
-                    app_state.waiting_for_backend = false;
+                    if app_state.aborting {
+                        app_state.waiting_for_backend = false;
+                        app_state.discard_empty_response();
+                    }
+                    app_state.aborting = true;
+                    app_state.pending_tool_approval = None;
                     tx.send(Action::BackendAbort())?;
                 } else if !app_state.exit_warning {
                     app_state.add_message(Message::new(
@@ -194,7 +292,56 @@ async fn start_loop<B: Backend>(
                 app_state.exit_warning = false;
                 textarea.insert_newline();
             }
+            Event::KeyboardCTRLT() => {
+                app_state.toggle_reasoning();
+            }
+            Event::KeyboardALTDown() | Event::KeyboardALTUp() => {
+                if app_state.waiting_for_backend {
+                    continue;
+                }
+                // Picking another message abandons the edit in progress.
+                let was_editing = app_state.editing_message.is_some();
+                let previous = matches!(event, Event::KeyboardALTUp());
+                if app_state.select_message(previous) && was_editing {
+                    textarea = TextArea::default();
+                }
+            }
+            Event::KeyboardALTLeft() | Event::KeyboardALTRight() => {
+                if app_state.waiting_for_backend {
+                    continue;
+                }
+                // Switches between the branches of the selected message, or
+                // the responses of the last regenerated message otherwise.
+                let forward = matches!(event, Event::KeyboardALTRight());
+                let was_editing = app_state.editing_message.is_some();
+                if app_state.selected_message.is_some() {
+                    if app_state.cycle_branches(forward) {
+                        if was_editing {
+                            textarea = TextArea::default();
+                        }
+                        app_state.save_session().await?;
+                    }
+                } else if app_state.cycle_alternates(forward) {
+                    app_state.save_session().await?;
+                }
+            }
             Event::KeyboardCTRLR() => {
+                if app_state.waiting_for_backend {
+                    continue;
+                }
+                if app_state.editing_message.is_some() {
+                    textarea = TextArea::default();
+                }
+                app_state.cancel_selection();
+                // The last response is regenerated in place, and only
+                // resubmitted as a new message when there isn't one.
+                if app_state.compare_targets.is_empty() {
+                    if let Some((message, history)) = app_state.regenerate() {
+                        send_prompt!(&message.text, message.attachments, history);
+                        continue;
+                    }
+                }
+
                 let last_message = app_state
                     .messages
                     .iter()
@@ -204,24 +351,38 @@ async fn start_loop<B: Backend>(
                     })
                     .last();
                 if let Some(message) = last_message.cloned() {
-                    send_user_message!(&message.text);
+                    send_user_message!(&message.text, message.attachments);
                 }
             }
             Event::KeyboardEnter() => {
                 if app_state.waiting_for_backend {
                     continue;
                 }
+                if app_state.editing_message.is_none() {
+                    if let Some(message) = app_state.edit_selected_message() {
+                        textarea = TextArea::default();
+                        textarea.insert_str(&message.text);
+                        continue;
+                    }
+                }
+
                 let input_str = &textarea.lines().join("\n");
                 if input_str.is_empty() {
                     continue;
                 }
-                send_user_message!(input_str);
+                let attachments = app_state.take_attachments(input_str);
+                send_user_message!(input_str, attachments);
             }
             Event::KeyboardPaste(text) => {
                 if app_state.waiting_for_backend {
                     continue;
                 }
                 app_state.exit_warning = false;
+                // Dropping a file on to the terminal pastes its path.
+                if let Some(attachment) = Attachment::from_dropped_text(&text) {
+                    app_state.add_attachment(attachment);
+                    continue;
+                }
                 textarea.set_yank_text(text.replace('\r', "\n"));
                 textarea.paste();
             }
@@ -284,7 +445,7 @@ pub async fn start(
     }
 
     let backend =
-        BackendManager::get(BackendName::parse(Config::get(ConfigKey::Backend)).unwrap())?;
+        BackendManager::get(BackendName::parse(Config::get(ConfigKey::Backend)).unwrap()).await?;
     let editor = EditorManager::get(EditorName::parse(Config::get(ConfigKey::Editor)).unwrap())?;
     let app_state_pros = AppStateProps {
         backend,
@@ -296,7 +457,7 @@ pub async fn start(
         sessions_service: Sessions::default(),
     };
 
-    start_loop(&mut terminal, app_state_pros, tx, rx).await?;
+    start_loop(&mut terminal, app_state_pros, tx, EventsService::new(rx)).await?;
     let editor = EditorManager::get(editor_name)?;
     if editor.health_check().await.is_ok() {
         editor.clear_context().await?;
@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+use tokio::sync::mpsc;
+
+use super::start_loop;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::domain::models::Action;
+use crate::domain::models::BackendName;
+use crate::domain::models::EditorName;
+use crate::domain::models::Event;
+use crate::domain::services::actions::ActionsService;
+use crate::domain::services::events::EventsService;
+use crate::domain::services::AppStateProps;
+use crate::domain::services::Sessions;
+use crate::infrastructure::backends::BackendManager;
+use crate::infrastructure::editors::EditorManager;
+
+fn screen_text(terminal: &Terminal<TestBackend>) -> String {
+    let buffer = terminal.backend().buffer();
+    return buffer
+        .content()
+        .chunks(buffer.area.width as usize)
+        .map(|row| {
+            return row
+                .iter()
+                .map(|cell| return cell.symbol.to_string())
+                .collect::<String>();
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+}
+
+#[tokio::test]
+async fn it_chats_with_the_mock_backend() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+
+    let (action_tx, mut action_rx) = mpsc::unbounded_channel::<Action>();
+    let (backend_event_tx, mut backend_event_rx) = mpsc::unbounded_channel::<Event>();
+    let (event_tx, event_rx) = mpsc::unbounded_channel::<Event>();
+
+    tokio::spawn(async move {
+        return ActionsService::start(
+            BackendManager::get(BackendName::Mock).await?,
+            backend_event_tx,
+            &mut action_rx,
+        )
+        .await;
+    });
+
+    // Forwards the backend's events, quitting once its answer is done.
+    let ui_event_tx = event_tx.clone();
+    tokio::spawn(async move {
+        while let Some(event) = backend_event_rx.recv().await {
+            let done = matches!(&event, Event::BackendPromptResponse(res) if res.done);
+            ui_event_tx.send(event)?;
+            if done {
+                ui_event_tx.send(Event::KeyboardPaste("/quit".to_string()))?;
+                ui_event_tx.send(Event::KeyboardEnter())?;
+            }
+        }
+        return anyhow::Ok(());
+    });
+
+    event_tx.send(Event::KeyboardPaste("Echo this back".to_string()))?;
+    event_tx.send(Event::KeyboardEnter())?;
+
+    let mut terminal = Terminal::new(TestBackend::new(80, 30))?;
+    let props = AppStateProps {
+        backend: BackendManager::get(BackendName::Mock).await?,
+        editor: EditorManager::get(EditorName::None)?,
+        model_name: "model-1".to_string(),
+        theme_name: "base16-onedark".to_string(),
+        theme_file: "".to_string(),
+        session_id: None,
+        sessions_service: Sessions::new(tempfile::tempdir()?.into_path()),
+    };
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        start_loop(
+            &mut terminal,
+            props,
+            action_tx,
+            EventsService::headless(event_rx),
+        ),
+    )
+    .await??;
+
+    assert_eq!(screen_text(&terminal).matches("Echo this back").count(), 2);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_regenerates_the_last_response() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+
+    let (action_tx, mut action_rx) = mpsc::unbounded_channel::<Action>();
+    let (backend_event_tx, mut backend_event_rx) = mpsc::unbounded_channel::<Event>();
+    let (event_tx, event_rx) = mpsc::unbounded_channel::<Event>();
+
+    tokio::spawn(async move {
+        return ActionsService::start(
+            BackendManager::get(BackendName::Mock).await?,
+            backend_event_tx,
+            &mut action_rx,
+        )
+        .await;
+    });
+
+    // Regenerates the first answer, and quits once the second is done.
+    let ui_event_tx = event_tx.clone();
+    tokio::spawn(async move {
+        let mut answers = 0;
+        while let Some(event) = backend_event_rx.recv().await {
+            let done = matches!(&event, Event::BackendPromptResponse(res) if res.done);
+            ui_event_tx.send(event)?;
+            if !done {
+                continue;
+            }
+
+            answers += 1;
+            if answers == 1 {
+                ui_event_tx.send(Event::KeyboardCTRLR())?;
+            } else {
+                ui_event_tx.send(Event::KeyboardPaste("/quit".to_string()))?;
+                ui_event_tx.send(Event::KeyboardEnter())?;
+            }
+        }
+        return anyhow::Ok(());
+    });
+
+    event_tx.send(Event::KeyboardPaste("Echo this back".to_string()))?;
+    event_tx.send(Event::KeyboardEnter())?;
+
+    let mut terminal = Terminal::new(TestBackend::new(80, 30))?;
+    let props = AppStateProps {
+        backend: BackendManager::get(BackendName::Mock).await?,
+        editor: EditorManager::get(EditorName::None)?,
+        model_name: "model-1".to_string(),
+        theme_name: "base16-onedark".to_string(),
+        theme_file: "".to_string(),
+        session_id: None,
+        sessions_service: Sessions::new(tempfile::tempdir()?.into_path()),
+    };
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        start_loop(
+            &mut terminal,
+            props,
+            action_tx,
+            EventsService::headless(event_rx),
+        ),
+    )
+    .await??;
+
+    let screen = screen_text(&terminal);
+    assert_eq!(screen.matches("Echo this back").count(), 2);
+    assert!(screen.contains("model-1 (2/2)"));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_edits_a_previous_message() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+
+    let (action_tx, mut action_rx) = mpsc::unbounded_channel::<Action>();
+    let (backend_event_tx, mut backend_event_rx) = mpsc::unbounded_channel::<Event>();
+    let (event_tx, event_rx) = mpsc::unbounded_channel::<Event>();
+
+    tokio::spawn(async move {
+        return ActionsService::start(
+            BackendManager::get(BackendName::Mock).await?,
+            backend_event_tx,
+            &mut action_rx,
+        )
+        .await;
+    });
+
+    // Edits the first message once it's answered, and quits once the edited
+    // message is answered.
+    let ui_event_tx = event_tx.clone();
+    tokio::spawn(async move {
+        let mut answers = 0;
+        while let Some(event) = backend_event_rx.recv().await {
+            let done = matches!(&event, Event::BackendPromptResponse(res) if res.done);
+            ui_event_tx.send(event)?;
+            if !done {
+                continue;
+            }
+
+            answers += 1;
+            if answers == 1 {
+                ui_event_tx.send(Event::KeyboardALTUp())?;
+                ui_event_tx.send(Event::KeyboardEnter())?;
+                ui_event_tx.send(Event::KeyboardPaste(" again".to_string()))?;
+                ui_event_tx.send(Event::KeyboardEnter())?;
+            } else {
+                ui_event_tx.send(Event::KeyboardPaste("/quit".to_string()))?;
+                ui_event_tx.send(Event::KeyboardEnter())?;
+            }
+        }
+        return anyhow::Ok(());
+    });
+
+    event_tx.send(Event::KeyboardPaste("Echo this back".to_string()))?;
+    event_tx.send(Event::KeyboardEnter())?;
+
+    let mut terminal = Terminal::new(TestBackend::new(80, 30))?;
+    let props = AppStateProps {
+        backend: BackendManager::get(BackendName::Mock).await?,
+        editor: EditorManager::get(EditorName::None)?,
+        model_name: "model-1".to_string(),
+        theme_name: "base16-onedark".to_string(),
+        theme_file: "".to_string(),
+        session_id: None,
+        sessions_service: Sessions::new(tempfile::tempdir()?.into_path()),
+    };
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        start_loop(
+            &mut terminal,
+            props,
+            action_tx,
+            EventsService::headless(event_rx),
+        ),
+    )
+    .await??;
+
+    // Only the edited branch of the conversation is shown.
+    let screen = screen_text(&terminal);
+    assert_eq!(screen.matches("Echo this back again").count(), 2);
+    assert_eq!(screen.matches("Echo this back").count(), 2);
+    assert!(screen.contains("(2/2)"));
+
+    return Ok(());
+}
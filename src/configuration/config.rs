@@ -16,28 +16,96 @@ use strum::EnumVariantNames;
 use strum::IntoEnumIterator;
 use tokio::fs;
 
+use super::Secrets;
 use crate::domain::models::BackendName;
+use crate::domain::models::CompareTarget;
+use crate::domain::models::ContextWindow;
 use crate::domain::models::EditorName;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::ModelPrice;
+use crate::domain::models::Persona;
+use crate::domain::models::Profile;
+use crate::domain::models::ToolName;
+use crate::domain::models::DEFAULT_PERSONA;
+use crate::domain::models::PROFILE_EXCLUDED_KEYS;
 
 static CONFIG: Lazy<DashMap<String, String>> = Lazy::new(DashMap::new);
-
-#[derive(Clone, Copy, Eq, PartialEq, EnumIter, EnumVariantNames, strum::Display)]
+static PERSONAS: Lazy<DashMap<String, Persona>> = Lazy::new(|| {
+    let personas = DashMap::new();
+    personas.insert(DEFAULT_PERSONA.to_string(), Persona::default());
+    return personas;
+});
+static PRICES: Lazy<DashMap<String, ModelPrice>> = Lazy::new(DashMap::new);
+static PROFILES: Lazy<DashMap<String, Profile>> = Lazy::new(DashMap::new);
+/// Values replaced by the active profile, restored when switching profiles.
+static PROFILE_OVERRIDES: Lazy<DashMap<String, String>> = Lazy::new(DashMap::new);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, EnumIter, EnumVariantNames, strum::Display)]
 #[strum(serialize_all = "kebab-case")]
 pub enum ConfigKey {
     Backend,
     BackendHealthCheckTimeout,
+    BackendMaxAttempts,
     Editor,
     Model,
+    DefaultModels,
+    Compare,
+    Persona,
+    Profile,
+    Temperature,
+    MaxTokens,
+    TopP,
+    Stop,
+    ContextWindow,
+    ContextStrategy,
+    EmbeddingsBackend,
+    EmbeddingsModel,
+    RagTopK,
     ConfigFile,
     LangChainURL,
+    LangChainInputKey,
+    LangChainHistoryKey,
+    LangChainExtraInput,
+    LangChainConfig,
     OllamaURL,
+    OllamaNumCtx,
+    OllamaKeepAlive,
     OpenAiToken,
+    OpenAiTokenFile,
+    OpenAiTokenCommand,
     OpenAiURL,
+    OpenAiStreamUsage,
+    AzureURL,
+    AzureToken,
+    AzureTokenFile,
+    AzureTokenCommand,
+    AzureApiVersion,
+    AzureDeployments,
     ClaudeToken,
+    ClaudeTokenFile,
+    ClaudeTokenCommand,
+    ClaudeThinkingBudget,
     GeminiToken,
+    GeminiTokenFile,
+    GeminiTokenCommand,
+    MockMode,
+    MockDelay,
+    MockFixturesDir,
+    RecordCassette,
+    ReplayCassette,
+    HttpProxy,
+    NoProxy,
+    CaBundle,
+    ClientCert,
+    ClientKey,
+    HttpConnectTimeout,
+    HttpReadTimeout,
+    HttpRequestTimeout,
     SessionID,
     Theme,
     ThemeFile,
+    Tools,
+    ToolsTestCommand,
     Username,
 }
 
@@ -56,6 +124,254 @@ impl Config {
         CONFIG.insert(key.to_string(), value.to_string());
     }
 
+    pub fn get_persona(name: &str) -> Option<Persona> {
+        return PERSONAS.get(name).map(|persona| return persona.clone());
+    }
+
+    pub fn set_persona(persona: Persona) {
+        PERSONAS.insert(persona.name.to_string(), persona);
+    }
+
+    pub fn list_personas() -> Vec<String> {
+        let mut names = PERSONAS
+            .iter()
+            .map(|persona| return persona.key().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+
+        return names;
+    }
+
+    fn load_personas(doc: &toml_edit::Document) -> Result<()> {
+        let personas = match doc.get("personas") {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+
+        let table = match personas.as_table_like() {
+            Some(table) => table,
+            None => bail!("config.toml has an invalid value for key 'personas': must be a table"),
+        };
+
+        for (name, item) in table.iter() {
+            let system_prompt = item
+                .get("system-prompt")
+                .and_then(|e| return e.as_str())
+                .unwrap_or("");
+
+            if system_prompt.is_empty() {
+                bail!(format!(
+                    "config.toml persona '{name}' is missing a value for 'system-prompt'"
+                ));
+            }
+
+            Config::set_persona(Persona::new(name, system_prompt));
+        }
+
+        return Ok(());
+    }
+
+    pub fn get_price(model: &str) -> Option<ModelPrice> {
+        return PRICES.get(model).map(|price| return *price);
+    }
+
+    pub fn set_price(model: &str, price: ModelPrice) {
+        PRICES.insert(model.to_string(), price);
+    }
+
+    fn load_prices(doc: &toml_edit::Document) -> Result<()> {
+        let prices = match doc.get("prices") {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+
+        let table = match prices.as_table_like() {
+            Some(table) => table,
+            None => bail!("config.toml has an invalid value for key 'prices': must be a table"),
+        };
+
+        for (model, item) in table.iter() {
+            let mut price = ModelPrice::default();
+            for (field, val) in [("input", &mut price.input), ("output", &mut price.output)] {
+                let parsed = item.get(field).and_then(|e| {
+                    return e
+                        .as_float()
+                        .or_else(|| return e.as_integer().map(|int| return int as f64));
+                });
+
+                match parsed {
+                    Some(parsed) => *val = parsed,
+                    None => {
+                        bail!(format!(
+                        "config.toml price for model '{model}' is missing a number for '{field}'"
+                    ))
+                    }
+                }
+            }
+
+            Config::set_price(model, price);
+        }
+
+        return Ok(());
+    }
+
+    pub fn get_profile(name: &str) -> Option<Profile> {
+        return PROFILES.get(name).map(|profile| return profile.clone());
+    }
+
+    pub fn set_profile(profile: Profile) {
+        PROFILES.insert(profile.name.to_string(), profile);
+    }
+
+    pub fn list_profiles() -> Vec<String> {
+        let mut names = PROFILES
+            .iter()
+            .map(|profile| return profile.key().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+
+        return names;
+    }
+
+    /// Applies the values of a profile, after restoring the values replaced
+    /// by the previously applied profile.
+    pub fn apply_profile(name: &str) -> Result<()> {
+        let profile = match Config::get_profile(name) {
+            Some(profile) => profile,
+            None => {
+                bail!(format!(
+                    "Profile '{name}' is not defined. Possible values are: {}",
+                    Config::list_profiles().join(", ")
+                ))
+            }
+        };
+
+        Config::clear_profile();
+        for (key, val) in profile.values.iter() {
+            // A token source set by the profile replaces the other sources of
+            // the token, so an inherited token can't take precedence over it.
+            for other_key in Secrets::other_sources(*key) {
+                if profile.get(other_key).is_none()
+                    && !PROFILE_OVERRIDES.contains_key(&other_key.to_string())
+                {
+                    PROFILE_OVERRIDES.insert(other_key.to_string(), Config::get(other_key));
+                    Config::set(other_key, "");
+                }
+            }
+
+            PROFILE_OVERRIDES.insert(key.to_string(), Config::get(*key));
+            Config::set(*key, val);
+        }
+        Config::set(ConfigKey::Profile, name);
+
+        return Ok(());
+    }
+
+    /// Restores the values replaced by the applied profile.
+    pub fn clear_profile() {
+        let overrides = PROFILE_OVERRIDES
+            .iter()
+            .map(|entry| return (entry.key().to_string(), entry.value().to_string()))
+            .collect::<Vec<(String, String)>>();
+        for (key, val) in overrides {
+            CONFIG.insert(key, val);
+        }
+
+        PROFILE_OVERRIDES.clear();
+        Config::set(ConfigKey::Profile, "");
+    }
+
+    fn load_profiles(cmd: &Command, doc: &toml_edit::Document) -> Result<()> {
+        let profiles = match doc.get("profiles") {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+
+        let table = match profiles.as_table_like() {
+            Some(table) => table,
+            None => bail!("config.toml has an invalid value for key 'profiles': must be a table"),
+        };
+
+        for (name, item) in table.iter() {
+            let profile_table = match item.as_table_like() {
+                Some(profile_table) => profile_table,
+                None => {
+                    bail!(format!(
+                        "config.toml has an invalid value for profile '{name}': must be a table"
+                    ))
+                }
+            };
+
+            let mut profile = Profile::new(name);
+            for (field, val) in profile_table.iter() {
+                let key = match ConfigKey::iter().find(|key| {
+                    return key.to_string() == field && !PROFILE_EXCLUDED_KEYS.contains(key);
+                }) {
+                    Some(key) => key,
+                    None => {
+                        bail!(format!(
+                            "config.toml profile '{name}' has an unknown key '{field}'"
+                        ))
+                    }
+                };
+
+                let context = format!("config.toml profile '{name}'");
+                if let Some(val) = Config::parse_value(cmd, key, val, &context)? {
+                    profile.set(key, &val);
+                }
+            }
+
+            Config::set_profile(profile);
+        }
+
+        return Ok(());
+    }
+
+    /// Reads a value of the config file as a string, using the clap value
+    /// parsers to do validation.
+    fn parse_value(
+        cmd: &Command,
+        key: ConfigKey,
+        val: &toml_edit::Item,
+        context: &str,
+    ) -> Result<Option<String>> {
+        let mut possible_values = vec![];
+        if let Some(arg) = cmd
+            .get_arguments()
+            .find(|e| return e.get_long().unwrap() == key.to_string())
+        {
+            if !arg.get_possible_values().is_empty() {
+                possible_values = arg
+                    .get_possible_values()
+                    .iter()
+                    .map(|e| return e.get_name().to_string())
+                    .collect::<Vec<String>>();
+            }
+        }
+
+        if let Some(val_int) = val.as_integer() {
+            return Ok(Some(val_int.to_string()));
+        } else if let Some(val_float) = val.as_float() {
+            return Ok(Some(val_float.to_string()));
+        } else if let Some(val_arr) = val.as_array() {
+            let vals = val_arr
+                .iter()
+                .filter_map(|e| return e.as_str())
+                .collect::<Vec<&str>>();
+            return Ok(Some(vals.join(",")));
+        } else if let Some(val_str) = val.as_str() {
+            if val_str.is_empty() {
+                return Ok(None);
+            }
+            if !possible_values.is_empty() && !possible_values.contains(&val_str.to_string()) {
+                bail!(format!("{context} has an invalid value for key '{key}': {val_str}\nPossible values are: {}", possible_values.join(", ")));
+            }
+            return Ok(Some(val_str.to_string()));
+        }
+
+        return Ok(None);
+    }
+
     pub fn default(key: ConfigKey) -> String {
         if key == ConfigKey::Username {
             let mut user = env::var("USER").unwrap_or_else(|_| return "".to_string());
@@ -95,16 +411,65 @@ impl Config {
         let res = match key {
             ConfigKey::Backend => &default_backend,
             ConfigKey::BackendHealthCheckTimeout => "1000",
+            ConfigKey::BackendMaxAttempts => "3",
             ConfigKey::Editor => &default_editor,
             ConfigKey::Model => "",
+            ConfigKey::DefaultModels => "",
+            ConfigKey::Compare => "",
+            ConfigKey::Persona => DEFAULT_PERSONA,
+            ConfigKey::Profile => "",
+            ConfigKey::Temperature => "",
+            ConfigKey::MaxTokens => "",
+            ConfigKey::TopP => "",
+            ConfigKey::Stop => "",
+            ConfigKey::ContextWindow => "",
+            ConfigKey::ContextStrategy => "truncate",
+            ConfigKey::EmbeddingsBackend => "ollama",
+            ConfigKey::EmbeddingsModel => "nomic-embed-text",
+            ConfigKey::RagTopK => "5",
             ConfigKey::LangChainURL => "http://localhost:8000",
+            ConfigKey::LangChainInputKey => "question",
+            ConfigKey::LangChainHistoryKey => "",
+            ConfigKey::LangChainExtraInput => "",
+            ConfigKey::LangChainConfig => "",
             ConfigKey::OllamaURL => "http://localhost:11434",
+            ConfigKey::OllamaNumCtx => "",
+            ConfigKey::OllamaKeepAlive => "",
             ConfigKey::OpenAiToken => "",
+            ConfigKey::OpenAiTokenFile => "",
+            ConfigKey::OpenAiTokenCommand => "",
             ConfigKey::OpenAiURL => "https://api.openai.com",
+            ConfigKey::OpenAiStreamUsage => "auto",
+            ConfigKey::AzureURL => "",
+            ConfigKey::AzureToken => "",
+            ConfigKey::AzureTokenFile => "",
+            ConfigKey::AzureTokenCommand => "",
+            ConfigKey::AzureApiVersion => "2024-06-01",
+            ConfigKey::AzureDeployments => "",
             ConfigKey::ClaudeToken => "",
+            ConfigKey::ClaudeTokenFile => "",
+            ConfigKey::ClaudeTokenCommand => "",
+            ConfigKey::ClaudeThinkingBudget => "",
             ConfigKey::GeminiToken => "",
+            ConfigKey::GeminiTokenFile => "",
+            ConfigKey::GeminiTokenCommand => "",
+            ConfigKey::MockMode => "echo",
+            ConfigKey::MockDelay => "20",
+            ConfigKey::MockFixturesDir => "",
+            ConfigKey::RecordCassette => "",
+            ConfigKey::ReplayCassette => "",
+            ConfigKey::HttpProxy => "",
+            ConfigKey::NoProxy => "",
+            ConfigKey::CaBundle => "",
+            ConfigKey::ClientCert => "",
+            ConfigKey::ClientKey => "",
+            ConfigKey::HttpConnectTimeout => "10000",
+            ConfigKey::HttpReadTimeout => "",
+            ConfigKey::HttpRequestTimeout => "120000",
             ConfigKey::Theme => "base16-onedark",
             ConfigKey::ThemeFile => "",
+            ConfigKey::Tools => "",
+            ConfigKey::ToolsTestCommand => "",
 
             // Special
             ConfigKey::ConfigFile => config_path.to_str().unwrap(),
@@ -119,6 +484,7 @@ impl Config {
         for key in ConfigKey::iter() {
             Config::set(key, &Config::default(key))
         }
+        PROFILE_OVERRIDES.clear();
 
         let mut config_file = Config::default(ConfigKey::ConfigFile);
         for matches in clap_arg_matches.as_slice() {
@@ -136,54 +502,117 @@ impl Config {
 
             for key in ConfigKey::iter() {
                 if let Some(val) = doc.get(&key.to_string()) {
-                    // Use clap value parsers to do validation.
-                    let mut possible_values = vec![];
-                    if let Some(arg) = cmd
-                        .get_arguments()
-                        .find(|e| return e.get_long().unwrap() == key.to_string())
-                    {
-                        if !arg.get_possible_values().is_empty() {
-                            possible_values = arg
-                                .get_possible_values()
-                                .iter()
-                                .map(|e| return e.get_name().to_string())
-                                .collect::<Vec<String>>();
-                        }
-                    }
-
-                    if let Some(val_int) = val.as_integer() {
-                        Config::set(key, &val_int.to_string());
-                    } else if let Some(val_str) = val.as_str() {
-                        if val_str.is_empty() {
-                            continue;
-                        }
-                        if !possible_values.is_empty()
-                            && !possible_values.contains(&val_str.to_string())
-                        {
-                            bail!(format!("config.toml has an invalid value for key '{key}': {val_str}\nPossible values are: {}", possible_values.join(", ")));
-                        }
-                        Config::set(key, val_str);
+                    if let Some(val) = Config::parse_value(&cmd, key, val, "config.toml")? {
+                        Config::set(key, &val);
                     }
                 }
             }
+
+            Config::load_personas(&doc)?;
+            Config::load_prices(&doc)?;
+            Config::load_profiles(&cmd, &doc)?;
         }
 
-        for key in ConfigKey::iter() {
-            for matches in clap_arg_matches.as_slice() {
-                if let Ok(Some(val)) = matches.try_get_one::<String>(&key.to_string()) {
-                    if val.is_empty() {
-                        continue;
-                    }
-                    Config::set(key, val)
-                }
+        // Profile values are applied over the config file, and command line
+        // arguments over both.
+        let profile = Config::get(ConfigKey::Profile);
+        if !profile.is_empty() && Config::arg_profile(&clap_arg_matches).is_none() {
+            Config::apply_profile(&profile)?;
+        }
+        Config::apply_args(&clap_arg_matches)?;
+
+        let persona = Config::get(ConfigKey::Persona);
+        if Config::get_persona(&persona).is_none() {
+            bail!(format!(
+                "Persona '{persona}' is not defined. Possible values are: {}",
+                Config::list_personas().join(", ")
+            ));
+        }
+
+        if let Err(err) = GenerationOptions::from_config() {
+            bail!(format!("Invalid generation option: {err}"));
+        }
+
+        if let Err(err) = ContextWindow::from_config() {
+            bail!(format!("Invalid context window: {err}"));
+        }
+
+        for key in [ConfigKey::LangChainExtraInput, ConfigKey::LangChainConfig] {
+            let val = Config::get(key);
+            if !val.is_empty()
+                && !matches!(
+                    serde_json::from_str::<serde_json::Value>(&val),
+                    Ok(serde_json::Value::Object(_))
+                )
+            {
+                bail!(format!(
+                    "Invalid value for '{key}': {val} is not a JSON object"
+                ));
             }
         }
 
+        let num_ctx = Config::get(ConfigKey::OllamaNumCtx);
+        if !num_ctx.is_empty() && num_ctx.parse::<u32>().is_err() {
+            bail!(format!(
+                "Invalid value for '{}': {num_ctx} is not a positive integer",
+                ConfigKey::OllamaNumCtx
+            ));
+        }
+
+        // Azure's GA api-versions can't list deployments, so they're named in
+        // the config.
+        if Config::get(ConfigKey::Backend) == BackendName::Azure.to_string()
+            && Config::get(ConfigKey::AzureDeployments).trim().is_empty()
+        {
+            bail!(format!(
+                "Invalid value for '{}': the deployments to use are needed with the azure backend",
+                ConfigKey::AzureDeployments
+            ));
+        }
+
+        let thinking_budget = Config::get(ConfigKey::ClaudeThinkingBudget);
+        if !thinking_budget.is_empty()
+            && !matches!(thinking_budget.parse::<u32>(), Ok(budget) if budget >= 1024)
+        {
+            bail!(format!(
+                "Invalid value for '{}': {thinking_budget} is not a number of tokens of at least 1024",
+                ConfigKey::ClaudeThinkingBudget
+            ));
+        }
+
+        for key in [
+            ConfigKey::HttpConnectTimeout,
+            ConfigKey::HttpReadTimeout,
+            ConfigKey::HttpRequestTimeout,
+            ConfigKey::MockDelay,
+        ] {
+            let val = Config::get(key);
+            if !val.is_empty() && val.parse::<u64>().is_err() {
+                bail!(format!(
+                    "Invalid value for '{key}': {val} is not a number of milliseconds"
+                ));
+            }
+        }
+
+        if let Err(err) = CompareTarget::from_config() {
+            bail!(format!("Invalid value for '{}': {err}", ConfigKey::Compare));
+        }
+
+        if let Err(err) = ToolName::from_config() {
+            bail!(format!("Invalid tool: {err}"));
+        }
+
         tracing::debug!(
             username = Config::get(ConfigKey::Username),
             backend = Config::get(ConfigKey::Backend),
             editor = Config::get(ConfigKey::Editor),
             model = Config::get(ConfigKey::Model),
+            persona = Config::get(ConfigKey::Persona),
+            profile = Config::get(ConfigKey::Profile),
+            temperature = Config::get(ConfigKey::Temperature),
+            max_tokens = Config::get(ConfigKey::MaxTokens),
+            top_p = Config::get(ConfigKey::TopP),
+            stop = Config::get(ConfigKey::Stop),
             theme = Config::get(ConfigKey::Theme),
             theme_file = Config::get(ConfigKey::ThemeFile),
             "config"
@@ -192,6 +621,51 @@ impl Config {
         return Ok(());
     }
 
+    fn arg_profile(clap_arg_matches: &[&ArgMatches]) -> Option<String> {
+        let mut profile = None;
+        for matches in clap_arg_matches {
+            if let Ok(Some(val)) = matches.try_get_one::<String>(&ConfigKey::Profile.to_string()) {
+                profile = Some(val.to_string());
+            }
+        }
+        return profile;
+    }
+
+    /// Applies the profile and values set on the command line, so they take
+    /// precedence over anything loaded before them.
+    pub fn apply_args(clap_arg_matches: &[&ArgMatches]) -> Result<()> {
+        if let Some(profile) = Config::arg_profile(clap_arg_matches) {
+            if !profile.is_empty() {
+                Config::apply_profile(&profile)?;
+            }
+        }
+
+        let mut arg_keys = vec![];
+        for key in ConfigKey::iter() {
+            for matches in clap_arg_matches {
+                if let Ok(Some(val)) = matches.try_get_one::<String>(&key.to_string()) {
+                    if val.is_empty() {
+                        continue;
+                    }
+                    Config::set(key, val);
+                    arg_keys.push(key);
+                }
+            }
+        }
+
+        // A token source set on the command line replaces the other sources
+        // of the token from the config file and profile.
+        for key in arg_keys.iter() {
+            for other_key in Secrets::other_sources(*key) {
+                if !arg_keys.contains(&other_key) {
+                    Config::set(other_key, "");
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
     pub fn serialize_default(cmd: Command) -> String {
         let toml_str = ConfigKey::iter()
             .filter_map(|key| {
@@ -244,6 +718,25 @@ impl Config {
             .collect::<Vec<String>>()
             .join("\n\n");
 
-        return toml_str;
+        let tables_str = r#"
+# Personas are named system prompts which can be selected with the `persona` option, or switched to mid-session with `/persona NAME`.
+# [personas.reviewer]
+# system-prompt = "You are a senior engineer reviewing code. Point out bugs before style issues."
+
+# Profiles are named connections bundling a backend, model, endpoint, credentials and generation options, which can be selected with the `profile` option, or switched to mid-session with `/profile NAME`. They accept any of the options above.
+# [profiles.work-gpt]
+# backend = "openai"
+# model = "gpt-4o"
+# open-ai-url = "https://api.openai.com"
+# open-ai-token-command = "pass show work/openai"
+# temperature = 0.2
+
+# Prices in USD per million tokens, used to estimate the cost of a session in `/usage` and `oatmeal sessions list`.
+# [prices."gpt-4o"]
+# input = 2.5
+# output = 10.0
+"#;
+
+        return format!("{toml_str}\n\n{}", tables_str.trim());
     }
 }
@@ -2,7 +2,10 @@ use anyhow::Result;
 use test_utils::insta_snapshot;
 
 use super::Config;
+use super::ConfigKey;
+use super::Secrets;
 use crate::application::cli;
+use crate::domain::models::GenerationOptions;
 
 #[test]
 fn it_serializes_to_valid_toml() {
@@ -30,3 +33,237 @@ async fn it_fails_to_loads_config_from_file() -> Result<()> {
     assert!(res.is_err());
     return Ok(());
 }
+
+#[tokio::test]
+async fn it_loads_personas_from_file() -> Result<()> {
+    let matches =
+        cli::build().try_get_matches_from(vec!["chat", "-c", "./test/personas-config.toml"])?;
+    Config::load(cli::build(), vec![&matches]).await?;
+
+    let persona = Config::get_persona("reviewer").unwrap();
+    assert_eq!(
+        persona.system_prompt,
+        "You are a senior engineer reviewing code."
+    );
+    assert!(Config::list_personas().contains(&"default".to_string()));
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_load_undefined_persona() -> Result<()> {
+    let matches =
+        cli::build().try_get_matches_from(vec!["chat", "-c", "./test/bad-persona-config.toml"])?;
+    let res = Config::load(cli::build(), vec![&matches]).await;
+    assert!(res.is_err());
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_loads_generation_options_from_file() -> Result<()> {
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./test/generation-options-config.toml",
+        "--temperature",
+        "0.7",
+    ])?;
+    Config::load(cli::build(), vec![&matches]).await?;
+
+    let options = GenerationOptions::from_config()?;
+    assert_eq!(options.temperature, Some(0.7));
+    assert_eq!(options.max_tokens, Some(512));
+    assert_eq!(options.stop, vec!["###".to_string(), "END".to_string()]);
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_loads_prices_from_file() -> Result<()> {
+    let matches =
+        cli::build().try_get_matches_from(vec!["chat", "-c", "./test/prices-config.toml"])?;
+    Config::load(cli::build(), vec![&matches]).await?;
+
+    let price = Config::get_price("price-test-model").unwrap();
+    assert_eq!(price.input, 3.0);
+    assert_eq!(price.output, 15.5);
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_load_invalid_lang_chain_json() -> Result<()> {
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./config.example.toml",
+        "--lang-chain-config",
+        "[\"configurable\"]",
+    ])?;
+    let res = Config::load(cli::build(), vec![&matches]).await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Invalid value for 'lang-chain-config': [\"configurable\"] is not a JSON object"
+    );
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_load_invalid_http_timeouts() -> Result<()> {
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./config.example.toml",
+        "--http-read-timeout",
+        "30s",
+    ])?;
+    let res = Config::load(cli::build(), vec![&matches]).await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Invalid value for 'http-read-timeout': 30s is not a number of milliseconds"
+    );
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_load_azure_without_deployments() -> Result<()> {
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./config.example.toml",
+        "--backend",
+        "azure",
+    ])?;
+    let res = Config::load(cli::build(), vec![&matches]).await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Invalid value for 'azure-deployments': the deployments to use are needed with the azure backend"
+    );
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_load_small_claude_thinking_budgets() -> Result<()> {
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./config.example.toml",
+        "--claude-thinking-budget",
+        "512",
+    ])?;
+    let res = Config::load(cli::build(), vec![&matches]).await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Invalid value for 'claude-thinking-budget': 512 is not a number of tokens of at least 1024"
+    );
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_load_invalid_compare_targets() -> Result<()> {
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./config.example.toml",
+        "--compare",
+        "openai:gpt-4o",
+    ])?;
+    let res = Config::load(cli::build(), vec![&matches]).await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Invalid value for 'compare': At least two backend:model pairs are needed to compare"
+    );
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_loads_profiles_from_file() -> Result<()> {
+    let matches =
+        cli::build().try_get_matches_from(vec!["chat", "-c", "./test/profiles-config.toml"])?;
+    Config::load(cli::build(), vec![&matches]).await?;
+
+    assert_eq!(Config::get(ConfigKey::Profile), "local");
+    assert_eq!(Config::get(ConfigKey::Backend), "ollama");
+    assert_eq!(Config::get(ConfigKey::Model), "llama3");
+    assert_eq!(Config::list_profiles(), vec!["local", "work-gpt"]);
+
+    // Command line arguments take precedence over the profile.
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./test/profiles-config.toml",
+        "--profile",
+        "work-gpt",
+        "--model",
+        "gpt-4o-mini",
+    ])?;
+    Config::load(cli::build(), vec![&matches]).await?;
+
+    assert_eq!(Config::get(ConfigKey::Profile), "work-gpt");
+    assert_eq!(Config::get(ConfigKey::Backend), "openai");
+    assert_eq!(Config::get(ConfigKey::Model), "gpt-4o-mini");
+    assert_eq!(
+        Config::get(ConfigKey::OpenAiURL),
+        "https://vllm.example.com"
+    );
+    assert_eq!(GenerationOptions::from_config()?.temperature, Some(0.2));
+
+    // Switching profiles restores the values replaced by the previous one.
+    Config::apply_profile("local")?;
+    assert_eq!(Config::get(ConfigKey::Profile), "local");
+    assert_eq!(Config::get(ConfigKey::Backend), "ollama");
+    assert_eq!(Config::get(ConfigKey::Model), "llama3");
+    assert_eq!(Config::get(ConfigKey::OpenAiURL), "https://api.openai.com");
+    assert_eq!(GenerationOptions::from_config()?.temperature, None);
+
+    assert_eq!(
+        Config::apply_profile("missing").unwrap_err().to_string(),
+        "Profile 'missing' is not defined. Possible values are: local, work-gpt"
+    );
+    return Ok(());
+}
+
+#[cfg(not(target_os = "windows"))]
+#[tokio::test]
+async fn it_prefers_profile_token_commands_over_global_tokens() -> Result<()> {
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./test/token-profiles-config.toml",
+    ])?;
+    Config::load(cli::build(), vec![&matches]).await?;
+
+    assert_eq!(Config::get(ConfigKey::OpenAiToken), "");
+    assert_eq!(
+        Secrets::resolve(ConfigKey::OpenAiToken).await?,
+        "profile-token"
+    );
+    assert_eq!(Secrets::get(ConfigKey::OpenAiToken), "profile-token");
+
+    // Switching profiles restores the global token.
+    Config::apply_profile("local")?;
+    assert_eq!(Config::get(ConfigKey::OpenAiToken), "global-token");
+    assert_eq!(Config::get(ConfigKey::OpenAiTokenCommand), "");
+
+    // Command line arguments take precedence over the profile.
+    let matches = cli::build().try_get_matches_from(vec![
+        "chat",
+        "-c",
+        "./test/token-profiles-config.toml",
+        "--open-ai-token",
+        "cli-token",
+    ])?;
+    Config::load(cli::build(), vec![&matches]).await?;
+    assert_eq!(Config::get(ConfigKey::OpenAiTokenCommand), "");
+    assert_eq!(Secrets::resolve(ConfigKey::OpenAiToken).await?, "cli-token");
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_load_profiles_with_unknown_keys() -> Result<()> {
+    let matches =
+        cli::build().try_get_matches_from(vec!["chat", "-c", "./test/bad-profile-config.toml"])?;
+    let res = Config::load(cli::build(), vec![&matches]).await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "config.toml profile 'work-gpt' has an unknown key 'open-ai-endpoint'"
+    );
+    return Ok(());
+}
@@ -1,3 +1,5 @@
 mod config;
+mod secrets;
 
 pub use config::*;
+pub use secrets::*;
@@ -0,0 +1,226 @@
+#[cfg(test)]
+#[path = "secrets_test.rs"]
+mod tests;
+
+use std::future::Future;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use tokio::fs;
+use tokio::process::Command;
+
+use super::Config;
+use super::ConfigKey;
+
+/// Secrets read from files and commands, keyed by where they were read from
+/// so each command only runs once.
+static SECRETS: Lazy<DashMap<String, String>> = Lazy::new(DashMap::new);
+
+/// How long a token command, such as a password manager waiting to be
+/// unlocked, can run before it's given up on.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Config keys of API tokens, with the keys of the file and command they can
+/// be read from instead.
+const TOKEN_SOURCES: [(ConfigKey, ConfigKey, ConfigKey); 4] = [
+    (
+        ConfigKey::OpenAiToken,
+        ConfigKey::OpenAiTokenFile,
+        ConfigKey::OpenAiTokenCommand,
+    ),
+    (
+        ConfigKey::AzureToken,
+        ConfigKey::AzureTokenFile,
+        ConfigKey::AzureTokenCommand,
+    ),
+    (
+        ConfigKey::ClaudeToken,
+        ConfigKey::ClaudeTokenFile,
+        ConfigKey::ClaudeTokenCommand,
+    ),
+    (
+        ConfigKey::GeminiToken,
+        ConfigKey::GeminiTokenFile,
+        ConfigKey::GeminiTokenCommand,
+    ),
+];
+
+/// Resolves API tokens, which can be set in plain text, read from a file, or
+/// printed by a command such as a password manager. Resolved tokens are only
+/// kept in memory, and never logged.
+pub struct Secrets {}
+
+impl Secrets {
+    /// Returns the token for a config key. The token itself takes precedence
+    /// over its file, and the file over its command, when they're set by the
+    /// same layer of config. A profile or command line argument setting one
+    /// of them replaces the others set by the layers below it.
+    pub async fn resolve(key: ConfigKey) -> Result<String> {
+        return match Secrets::sources(key) {
+            Some((file_key, command_key)) => {
+                Secrets::resolve_from(
+                    key,
+                    &Config::get(key),
+                    &Config::get(file_key),
+                    &Config::get(command_key),
+                )
+                .await
+            }
+            None => Ok(Config::get(key)),
+        };
+    }
+
+    fn sources(key: ConfigKey) -> Option<(ConfigKey, ConfigKey)> {
+        return TOKEN_SOURCES
+            .iter()
+            .find(|(token_key, _, _)| return *token_key == key)
+            .map(|(_, file_key, command_key)| return (*file_key, *command_key));
+    }
+
+    async fn resolve_from(
+        key: ConfigKey,
+        token: &str,
+        file_path: &str,
+        command: &str,
+    ) -> Result<String> {
+        if !token.is_empty() {
+            return Ok(token.to_string());
+        }
+
+        if !file_path.is_empty() {
+            return Secrets::cached(&format!("file:{file_path}"), async {
+                return match fs::read_to_string(file_path).await {
+                    Ok(contents) => Ok(contents),
+                    Err(err) => bail!(format!("Failed to read {key} from file {file_path}: {err}")),
+                };
+            })
+            .await;
+        }
+
+        if !command.is_empty() {
+            return Secrets::cached(&format!("command:{command}"), Secrets::run(key, command))
+                .await;
+        }
+
+        return Ok("".to_string());
+    }
+
+    /// Returns the other keys of the token a key is a source of, such as the
+    /// token and command keys for a token file key.
+    pub fn other_sources(key: ConfigKey) -> Vec<ConfigKey> {
+        return TOKEN_SOURCES
+            .iter()
+            .map(|(token_key, file_key, command_key)| {
+                return [*token_key, *file_key, *command_key];
+            })
+            .find(|sources| return sources.contains(&key))
+            .map(|sources| {
+                return sources
+                    .into_iter()
+                    .filter(|source| return *source != key)
+                    .collect();
+            })
+            .unwrap_or_default();
+    }
+
+    /// Returns the token for a config key, or an empty token when its file or
+    /// command hasn't been resolved yet. Nothing is read or run here, so it's
+    /// safe to call from synchronous code once `resolve` has been awaited.
+    pub fn get(key: ConfigKey) -> String {
+        let token = Config::get(key);
+        if !token.is_empty() {
+            return token;
+        }
+
+        let (file_key, command_key) = match Secrets::sources(key) {
+            Some(sources) => sources,
+            None => return token,
+        };
+        let file_path = Config::get(file_key);
+        let source = if !file_path.is_empty() {
+            format!("file:{file_path}")
+        } else {
+            format!("command:{}", Config::get(command_key))
+        };
+
+        return SECRETS
+            .get(&source)
+            .map(|secret| return secret.to_string())
+            .unwrap_or_default();
+    }
+
+    /// Returns every token resolved from a file or command so far.
+    pub fn resolved() -> Vec<String> {
+        return SECRETS
+            .iter()
+            .map(|secret| return secret.value().to_string())
+            .collect();
+    }
+
+    async fn cached(source: &str, read: impl Future<Output = Result<String>>) -> Result<String> {
+        if let Some(secret) = SECRETS.get(source) {
+            return Ok(secret.to_string());
+        }
+
+        let secret = read.await?.trim().to_string();
+        SECRETS.insert(source.to_string(), secret.to_string());
+        return Ok(secret);
+    }
+
+    async fn run(key: ConfigKey, command_str: &str) -> Result<String> {
+        #[cfg(target_os = "windows")]
+        let mut command = Command::new("cmd");
+        #[cfg(target_os = "windows")]
+        command.arg("/C");
+
+        #[cfg(not(target_os = "windows"))]
+        let mut command = Command::new("sh");
+        #[cfg(not(target_os = "windows"))]
+        command.arg("-c");
+
+        let output = command
+            .arg(command_str)
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output();
+        let output = match tokio::time::timeout(COMMAND_TIMEOUT, output).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(err)) => {
+                bail!(format!(
+                    "Failed to run the command for {key}: {command_str}: {err}"
+                ))
+            }
+            Err(_) => {
+                bail!(format!(
+                    "The command for {key} timed out after {} seconds: {command_str}",
+                    COMMAND_TIMEOUT.as_secs()
+                ))
+            }
+        };
+
+        // Only stderr is reported, as stdout can hold part of the secret.
+        if !output.status.success() {
+            bail!(format!(
+                "The command for {key} failed: {command_str}\n{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        // Password managers such as pass print other details after the
+        // password.
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let secret = stdout.trim().lines().next().unwrap_or_default().to_string();
+        if secret.is_empty() {
+            bail!(format!(
+                "The command for {key} printed nothing: {command_str}"
+            ));
+        }
+
+        return Ok(secret);
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+
+use super::Secrets;
+use crate::configuration::ConfigKey;
+
+#[tokio::test]
+async fn it_prefers_the_token() -> Result<()> {
+    let token = Secrets::resolve_from(
+        ConfigKey::OpenAiToken,
+        "plain-token",
+        "/does/not/exist",
+        "exit 1",
+    )
+    .await?;
+    assert_eq!(token, "plain-token");
+    assert_eq!(
+        Secrets::resolve_from(ConfigKey::OpenAiToken, "", "", "").await?,
+        ""
+    );
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_reads_tokens_from_files() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let file_path = dir.path().join("token");
+    std::fs::write(&file_path, "file-token\n")?;
+    let file_path = file_path.to_str().unwrap();
+
+    let token = Secrets::resolve_from(ConfigKey::ClaudeToken, "", file_path, "").await?;
+    assert_eq!(token, "file-token");
+    assert!(Secrets::resolved().contains(&"file-token".to_string()));
+
+    // The token is cached, rather than read again.
+    std::fs::write(file_path, "changed-token")?;
+    let token = Secrets::resolve_from(ConfigKey::ClaudeToken, "", file_path, "").await?;
+    assert_eq!(token, "file-token");
+
+    assert_eq!(
+        Secrets::resolve_from(ConfigKey::ClaudeToken, "", "/does/not/exist", "").await
+            .unwrap_err()
+            .to_string(),
+        "Failed to read claude-token from file /does/not/exist: No such file or directory (os error 2)"
+    );
+    return Ok(());
+}
+
+#[cfg(not(target_os = "windows"))]
+#[tokio::test]
+async fn it_reads_tokens_from_commands() -> Result<()> {
+    let token = Secrets::resolve_from(
+        ConfigKey::GeminiToken,
+        "",
+        "",
+        "printf 'command-token\\nurl: example.com\\n'",
+    )
+    .await?;
+    assert_eq!(token, "command-token");
+
+    // Only stderr is reported when the command fails.
+    let command = "echo $((40 + 2)); echo not found >&2; exit 1";
+    let err = Secrets::resolve_from(ConfigKey::GeminiToken, "", "", command)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert_eq!(
+        err,
+        format!("The command for gemini-token failed: {command}\nnot found")
+    );
+    assert!(!err.contains("42"));
+
+    assert_eq!(
+        Secrets::resolve_from(ConfigKey::GeminiToken, "", "", "true")
+            .await
+            .unwrap_err()
+            .to_string(),
+        "The command for gemini-token printed nothing: true"
+    );
+    return Ok(());
+}
+
+#[test]
+fn it_lists_the_other_sources_of_a_token() {
+    assert_eq!(
+        Secrets::other_sources(ConfigKey::AzureTokenFile),
+        vec![ConfigKey::AzureToken, ConfigKey::AzureTokenCommand]
+    );
+    assert_eq!(Secrets::other_sources(ConfigKey::Model), vec![]);
+}
@@ -1,11 +1,13 @@
 use super::AcceptType;
 use super::BackendPrompt;
+use super::CompareTarget;
 use super::EditorContext;
 use super::Message;
 
 pub enum Action {
     AcceptCodeBlock(Option<EditorContext>, String, AcceptType),
     BackendAbort(),
+    BackendCompare(Vec<CompareTarget>, BackendPrompt),
     BackendRequest(BackendPrompt),
     CopyMessages(Vec<Message>),
 }
@@ -0,0 +1,177 @@
+#[cfg(test)]
+#[path = "attachment_test.rs"]
+mod tests;
+
+use std::path;
+
+use anyhow::bail;
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD as b64;
+use base64::Engine;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::fs;
+
+/// Largest file that can be attached to a prompt.
+const MAX_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentKind {
+    Image,
+    Text,
+}
+
+/// A file attached to a user message. Sessions only store a reference to the
+/// file, and its content is read from disk each time it's sent to a backend.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub kind: AttachmentKind,
+    pub path: String,
+    pub mime_type: String,
+    /// Loaded before being sent to a backend. Images are base64 encoded.
+    #[serde(skip)]
+    pub content: String,
+}
+
+fn image_mime_type(file_path: &path::Path) -> Option<&'static str> {
+    let extension = file_path
+        .extension()
+        .and_then(|e| return e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    let mime_type = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => return None,
+    };
+
+    return Some(mime_type);
+}
+
+impl Attachment {
+    /// Creates an attachment from a file path, detecting whether it's an image
+    /// or a text file.
+    pub fn from_path(file_path: &str) -> Result<Attachment> {
+        let file_path = match path::Path::new(file_path.trim()).canonicalize() {
+            Ok(file_path) => file_path,
+            Err(_) => bail!(format!("File {file_path} does not exist")),
+        };
+
+        let metadata = file_path.metadata()?;
+        if !metadata.is_file() {
+            bail!(format!("{} is not a file", file_path.display()));
+        }
+        if metadata.len() > MAX_ATTACHMENT_BYTES {
+            bail!(format!(
+                "{} is larger than the {}MB attachment limit",
+                file_path.display(),
+                MAX_ATTACHMENT_BYTES / 1024 / 1024
+            ));
+        }
+
+        if let Some(mime_type) = image_mime_type(&file_path) {
+            return Ok(Attachment {
+                kind: AttachmentKind::Image,
+                path: file_path.to_string_lossy().to_string(),
+                mime_type: mime_type.to_string(),
+                content: "".to_string(),
+            });
+        }
+
+        if String::from_utf8(std::fs::read(&file_path)?).is_err() {
+            bail!(format!(
+                "{} is not an image or text file",
+                file_path.display()
+            ));
+        }
+
+        return Ok(Attachment {
+            kind: AttachmentKind::Text,
+            path: file_path.to_string_lossy().to_string(),
+            mime_type: "text/plain".to_string(),
+            content: "".to_string(),
+        });
+    }
+
+    /// Terminals paste the path of a file dropped on to them, optionally
+    /// quoted, escaped, or as a `file://` URL. Returns an attachment when the
+    /// text is the path of an existing file.
+    pub fn from_dropped_text(text: &str) -> Option<Attachment> {
+        let mut file_path = text.trim();
+        if file_path.is_empty() || file_path.contains('\n') {
+            return None;
+        }
+
+        for quote in ['\'', '"'] {
+            if file_path.len() > 1 && file_path.starts_with(quote) && file_path.ends_with(quote) {
+                file_path = &file_path[1..file_path.len() - 1];
+            }
+        }
+        file_path = file_path.strip_prefix("file://").unwrap_or(file_path);
+
+        let file_path = file_path.replace("\\ ", " ");
+        if !path::Path::new(&file_path).is_file() {
+            return None;
+        }
+
+        return Attachment::from_path(&file_path).ok();
+    }
+
+    pub fn name(&self) -> String {
+        return path::Path::new(&self.path)
+            .file_name()
+            .map(|name| return name.to_string_lossy().to_string())
+            .unwrap_or_else(|| return self.path.to_string());
+    }
+
+    /// Placeholder shown in place of the attachment in a chat bubble.
+    pub fn chip(&self) -> String {
+        let kind = match self.kind {
+            AttachmentKind::Image => "image",
+            AttachmentKind::Text => "file",
+        };
+
+        return format!("[{kind}: {}]", self.name());
+    }
+
+    /// Reads the file from disk in to `content`.
+    pub async fn load(&mut self) -> Result<()> {
+        let bytes = match fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(err) => bail!(format!("Failed to read attachment {}: {err}", self.path)),
+        };
+
+        self.content = match self.kind {
+            AttachmentKind::Image => b64.encode(bytes),
+            AttachmentKind::Text => String::from_utf8(bytes)?,
+        };
+
+        return Ok(());
+    }
+
+    /// Text sent to a backend in place of a text file.
+    pub fn to_prompt_text(&self) -> String {
+        return format!(
+            "File: {}\n```\n{}\n```",
+            self.name(),
+            self.content.trim_end()
+        );
+    }
+
+    /// Prepends all text attachments to the text of a message. Images are left
+    /// out.
+    pub fn text_with_attachments(text: &str, attachments: &[Attachment]) -> String {
+        let mut parts = attachments
+            .iter()
+            .filter(|attachment| return attachment.kind == AttachmentKind::Text)
+            .map(|attachment| return attachment.to_prompt_text())
+            .collect::<Vec<String>>();
+        parts.push(text.to_string());
+
+        return parts.join("\n\n");
+    }
+}
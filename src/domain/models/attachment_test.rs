@@ -0,0 +1,132 @@
+use std::fs;
+
+use anyhow::Result;
+
+use super::Attachment;
+use super::AttachmentKind;
+
+fn write_file(suffix: &str, content: &[u8]) -> Result<tempfile::NamedTempFile> {
+    let file = tempfile::Builder::new().suffix(suffix).tempfile()?;
+    fs::write(file.path(), content)?;
+    return Ok(file);
+}
+
+#[test]
+fn it_creates_image_attachments() -> Result<()> {
+    let file = write_file(".PNG", &[0x89, 0x50, 0x4e, 0x47])?;
+    let attachment = Attachment::from_path(&file.path().to_string_lossy())?;
+
+    assert_eq!(attachment.kind, AttachmentKind::Image);
+    assert_eq!(attachment.mime_type, "image/png");
+    assert!(attachment.content.is_empty());
+
+    return Ok(());
+}
+
+#[test]
+fn it_creates_text_attachments() -> Result<()> {
+    let file = write_file(".rs", b"fn main() {}")?;
+    let attachment = Attachment::from_path(&file.path().to_string_lossy())?;
+
+    assert_eq!(attachment.kind, AttachmentKind::Text);
+    assert_eq!(attachment.mime_type, "text/plain");
+
+    return Ok(());
+}
+
+#[test]
+fn it_fails_on_missing_files() {
+    let res = Attachment::from_path("/oatmeal/does/not/exist.txt");
+
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "File /oatmeal/does/not/exist.txt does not exist"
+    );
+}
+
+#[test]
+fn it_fails_on_binary_files() -> Result<()> {
+    let file = write_file(".bin", &[0xff, 0xfe, 0x00, 0x81])?;
+    let res = Attachment::from_path(&file.path().to_string_lossy());
+
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .ends_with("is not an image or text file"));
+
+    return Ok(());
+}
+
+#[test]
+fn it_creates_attachments_from_dropped_paths() -> Result<()> {
+    let file = write_file(".txt", b"Hello")?;
+    let file_path = file.path().to_string_lossy().to_string();
+
+    for text in [
+        file_path.to_string(),
+        format!("'{file_path}'"),
+        format!("\"{file_path}\" "),
+        format!("file://{file_path}"),
+    ] {
+        let attachment = Attachment::from_dropped_text(&text);
+        assert!(attachment.is_some(), "{text} was not attached");
+    }
+
+    return Ok(());
+}
+
+#[test]
+fn it_ignores_dropped_text_that_is_not_a_file() {
+    assert!(Attachment::from_dropped_text("Hello world").is_none());
+    assert!(Attachment::from_dropped_text("/oatmeal/does/not/exist.txt").is_none());
+    assert!(Attachment::from_dropped_text("/tmp\n/tmp").is_none());
+}
+
+#[test]
+fn it_creates_chips() {
+    let mut attachment = Attachment {
+        kind: AttachmentKind::Image,
+        path: "/home/user/cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        content: "".to_string(),
+    };
+    assert_eq!(attachment.chip(), "[image: cat.png]");
+
+    attachment.kind = AttachmentKind::Text;
+    attachment.path = "/home/user/main.rs".to_string();
+    assert_eq!(attachment.chip(), "[file: main.rs]");
+}
+
+#[tokio::test]
+async fn it_loads_images_as_base64() -> Result<()> {
+    let file = write_file(".png", b"oatmeal")?;
+    let mut attachment = Attachment::from_path(&file.path().to_string_lossy())?;
+    attachment.load().await?;
+
+    assert_eq!(attachment.content, "b2F0bWVhbA==");
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_prepends_text_attachments() -> Result<()> {
+    let file = write_file(".rs", b"fn main() {}\n")?;
+    let mut attachment = Attachment::from_path(&file.path().to_string_lossy())?;
+    attachment.load().await?;
+    let image = Attachment {
+        kind: AttachmentKind::Image,
+        path: "/home/user/cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        content: "b2F0bWVhbA==".to_string(),
+    };
+
+    let text = Attachment::text_with_attachments("What does this do?", &[attachment, image]);
+    let file_name = file.path().file_name().unwrap().to_string_lossy();
+
+    assert_eq!(
+        text,
+        format!("File: {file_name}\n```\nfn main() {{}}\n```\n\nWhat does this do?")
+    );
+
+    return Ok(());
+}
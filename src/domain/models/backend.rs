@@ -2,16 +2,26 @@
 #[path = "backend_test.rs"]
 mod tests;
 
+use anyhow::bail;
 use anyhow::Result;
 use async_trait::async_trait;
 use strum::EnumIter;
 use strum::EnumVariantNames;
 use strum::IntoEnumIterator;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
+use super::Attachment;
 use super::Author;
 use super::EditorContext;
 use super::Event;
+use super::GenerationOptions;
+use super::HistoryMessage;
+use super::ModelInfo;
+use super::Persona;
+use super::Usage;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
 
 #[derive(Clone, Debug, PartialEq, Eq, EnumIter, EnumVariantNames, strum::Display)]
 #[strum(serialize_all = "lowercase")]
@@ -19,8 +29,11 @@ pub enum BackendName {
     LangChain,
     Ollama,
     OpenAI,
+    Azure,
     Claude,
     Gemini,
+    Mock,
+    Replay,
 }
 
 impl BackendName {
@@ -29,35 +42,62 @@ impl BackendName {
     }
 }
 
+#[derive(Clone)]
 pub struct BackendPrompt {
     pub text: String,
-    pub backend_context: String,
+    pub attachments: Vec<Attachment>,
+    pub history: Vec<HistoryMessage>,
+    pub system_prompt: String,
+    pub generation_options: GenerationOptions,
+    /// Model the prompt is sent to instead of the configured model, so
+    /// several models can be prompted at once.
+    pub model: Option<String>,
 }
 
 impl BackendPrompt {
-    pub fn new(text: String, backend_context: String) -> BackendPrompt {
+    pub fn new(text: String, history: Vec<HistoryMessage>) -> BackendPrompt {
         return BackendPrompt {
             text,
-            backend_context,
+            attachments: vec![],
+            history,
+            system_prompt: "".to_string(),
+            generation_options: GenerationOptions::default(),
+            model: None,
         };
     }
 
-    pub fn append_chat_context(&mut self, editor_context: &Option<EditorContext>) {
-        if let Some(context) = editor_context {
-            let lang = &context.language;
-            let code = &context.code;
+    /// Returns the model the prompt is sent to.
+    pub fn model(&self) -> String {
+        return match &self.model {
+            Some(model) => model.to_string(),
+            None => Config::get(ConfigKey::Model),
+        };
+    }
 
-            let system_prompt =
-                format!(". The coding language is {lang}. Add language to any code blocks.");
-            self.text += &system_prompt;
+    pub fn set_system_prompt(&mut self, persona: &Persona, editor_context: &Option<EditorContext>) {
+        self.system_prompt = persona.system_prompt_with_context(editor_context);
+    }
 
-            if !code.is_empty() {
-                let code_prompt = format!(" The code is the following:\n{code}");
-                self.text += &code_prompt;
+    /// Reads the content of all attachments from disk. Attachments in the
+    /// history which can no longer be read are dropped rather than failing the
+    /// prompt.
+    pub async fn load_attachments(&mut self) -> Result<()> {
+        for attachment in self.attachments.iter_mut() {
+            attachment.load().await?;
+        }
+
+        for message in self.history.iter_mut() {
+            let mut attachments = vec![];
+            for mut attachment in message.attachments.drain(..) {
+                match attachment.load().await {
+                    Ok(_) => attachments.push(attachment),
+                    Err(err) => tracing::warn!(error = ?err, "Dropping attachment from history"),
+                }
             }
-        } else {
-            self.text += ". Add language to any code blocks."
+            message.attachments = attachments;
         }
+
+        return Ok(());
     }
 }
 
@@ -65,8 +105,15 @@ impl BackendPrompt {
 pub struct BackendResponse {
     pub author: Author,
     pub text: String,
+    /// Thinking the model did before answering. It's streamed separately from
+    /// the answer, and is neither part of the answer's code blocks nor sent
+    /// back to the model.
+    pub reasoning: String,
     pub done: bool,
-    pub context: Option<String>,
+    pub usage: Option<Usage>,
+    /// Set on the `done` message when the completion was cancelled before the
+    /// backend finished its response.
+    pub truncated: bool,
 }
 
 #[async_trait]
@@ -79,8 +126,9 @@ pub trait Backend {
     async fn health_check(&self) -> Result<()>;
 
     /// Called when using the `/modellist` slash commands to provide all
-    /// available models for the backend.
-    async fn list_models<'a>(&'a self) -> Result<Vec<String>>;
+    /// available models for the backend, along with whatever details the
+    /// backend reports about them.
+    async fn list_models<'a>(&'a self) -> Result<Vec<ModelInfo>>;
 
     /// Requests completions from the backend. Completion results may be
     /// streamed back to the UI by passing each response through a channel.
@@ -88,14 +136,45 @@ pub trait Backend {
     /// Upon receiving all results, a final `done` boolean
     /// is provided as the last message to the channel.
     ///
-    /// In order for a backend to maintain history, a context array may be
-    /// provided by the backend. This can be passed alongside the `done`
-    /// boolean, and will be provided on the next prompt to the backend.
+    /// When a `system_prompt` is set on the prompt, it should be sent through
+    /// the backend's native system field rather than added to the history.
+    ///
+    /// Any `generation_options` which are set should be mapped to the
+    /// backend's equivalent request fields.
+    ///
+    /// Token usage reported by the backend's API should be provided on the
+    /// `done` message when available.
+    ///
+    /// Previous turns of the conversation are provided as a provider neutral
+    /// `history`, which the backend converts into its own message format. This
+    /// allows a session to move between backends and models.
+    ///
+    /// When the `cancel` token is cancelled, the backend should stop reading
+    /// its response and mark the `done` message as `truncated`, so the partial
+    /// response is kept in the conversation. The `done` message is sent even
+    /// when cancelled before the backend responds, as the UI waits for it.
+    ///
+    /// Backends supporting tool calls offer the enabled tools with the
+    /// request. When the model calls one, the user is asked to approve it
+    /// through a `BackendToolCall` event, and the completion continues with
+    /// its result.
     async fn get_completion<'a>(
         &self,
         prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
         tx: &'a mpsc::UnboundedSender<Event>,
     ) -> Result<()>;
+
+    /// Embeds each text with an embeddings model, returning a vector per text
+    /// in the same order. Used to index a project, and to find the parts of it
+    /// relevant to a prompt. Backends without an embeddings API fail.
+    #[allow(clippy::implicit_return)]
+    async fn get_embeddings(&self, _model: &str, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        bail!(format!(
+            "The {} backend doesn't support embeddings",
+            self.name()
+        ));
+    }
 }
 
 pub type BackendBox = Box<dyn Backend + Send + Sync>;
@@ -1,41 +1,35 @@
 use super::super::EditorContext;
+use super::super::Persona;
 use super::BackendPrompt;
 
 #[test]
-fn it_adds_default_system_prompt() {
-    let mut prompt = BackendPrompt::new("Hello world".to_string(), "".to_string());
-    prompt.append_chat_context(&None);
+fn it_sets_default_system_prompt() {
+    let mut prompt = BackendPrompt::new("Hello world".to_string(), vec![]);
+    prompt.set_system_prompt(&Persona::default(), &None);
 
-    insta::assert_snapshot!(prompt.text, @"Hello world. Add language to any code blocks.");
+    assert_eq!(prompt.text, "Hello world");
+    insta::assert_snapshot!(prompt.system_prompt, @"Add language to any code blocks.");
 }
 
 #[test]
-fn it_adds_language_system_prompt() {
-    let mut prompt = BackendPrompt::new("Hello world".to_string(), "".to_string());
-    prompt.append_chat_context(&Some(EditorContext {
-        file_path: "./test.rs".to_string(),
-        language: "rust".to_string(),
-        code: "".to_string(),
-        start_line: 0,
-        end_line: None,
-    }));
+fn it_sets_persona_system_prompt_with_editor_context() {
+    let mut prompt = BackendPrompt::new("Hello world".to_string(), vec![]);
+    prompt.set_system_prompt(
+        &Persona::new("sql-expert", "You are an expert in SQL."),
+        &Some(EditorContext {
+            file_path: "./test.sql".to_string(),
+            language: "sql".to_string(),
+            code: "SELECT 1;".to_string(),
+            start_line: 0,
+            end_line: None,
+        }),
+    );
 
-    insta::assert_snapshot!(prompt.text, @"Hello world. The coding language is rust. Add language to any code blocks.");
-}
-
-#[test]
-fn it_adds_language_and_code_system_prompt() {
-    let mut prompt = BackendPrompt::new("Hello world".to_string(), "".to_string());
-    prompt.append_chat_context(&Some(EditorContext {
-        file_path: "./test.rs".to_string(),
-        language: "rust".to_string(),
-        code: "println!(\"Test!\")".to_string(),
-        start_line: 0,
-        end_line: None,
-    }));
-
-    insta::assert_snapshot!(prompt.text, @r###"
-    Hello world. The coding language is rust. Add language to any code blocks. The code is the following:
-    println!("Test!")
+    assert_eq!(prompt.text, "Hello world");
+    insta::assert_snapshot!(prompt.system_prompt, @r###"
+    You are an expert in SQL.
+    The coding language is sql.
+    The code is the following:
+    SELECT 1;
     "###);
 }
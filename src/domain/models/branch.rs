@@ -0,0 +1,85 @@
+#[cfg(test)]
+#[path = "branch_test.rs"]
+mod tests;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::Message;
+
+/// Another continuation of the conversation, kept when the conversation is
+/// forked from an edited message. It starts with the message which was
+/// edited, and its messages can have branches of their own, making a tree.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Branch {
+    pub messages: Vec<Message>,
+}
+
+impl Branch {
+    /// Replaces the messages from the given position onwards with a message,
+    /// keeping them as a branch of the new message.
+    pub fn fork(messages: &mut Vec<Message>, idx: usize, mut message: Message) {
+        let mut continuation = messages.split_off(idx);
+        if let Some(first) = continuation.first_mut() {
+            message.branches = std::mem::take(&mut first.branches);
+            let branch_idx = first.branch_idx.min(message.branches.len());
+            message.branches.insert(
+                branch_idx,
+                Branch {
+                    messages: continuation,
+                },
+            );
+        }
+
+        message.branch_idx = message.branches.len();
+        messages.push(message);
+    }
+
+    /// Shows the branch at the given position, for the message at the given
+    /// position. Returns false when the branch doesn't exist or is already
+    /// shown.
+    pub fn select(messages: &mut Vec<Message>, idx: usize, branch_idx: usize) -> bool {
+        let first = match messages.get_mut(idx) {
+            Some(first) => first,
+            None => return false,
+        };
+        if branch_idx >= first.branches_len() || branch_idx == first.branch_idx {
+            return false;
+        }
+
+        let shown_idx = first.branch_idx;
+        let mut branches = std::mem::take(&mut first.branches);
+        branches.insert(
+            shown_idx,
+            Branch {
+                messages: messages.split_off(idx),
+            },
+        );
+
+        let mut selected = branches.remove(branch_idx).messages;
+        selected[0].branches = branches;
+        selected[0].branch_idx = branch_idx;
+        messages.extend(selected);
+
+        return true;
+    }
+
+    /// Shows the next or previous branch, for the message at the given
+    /// position, wrapping around.
+    pub fn cycle(messages: &mut Vec<Message>, idx: usize, forward: bool) -> bool {
+        let (shown_idx, len) = match messages.get(idx) {
+            Some(message) => (message.branch_idx, message.branches_len()),
+            None => return false,
+        };
+        if len < 2 {
+            return false;
+        }
+
+        let mut branch_idx = (shown_idx + len - 1) % len;
+        if forward {
+            branch_idx = (shown_idx + 1) % len;
+        }
+
+        return Branch::select(messages, idx, branch_idx);
+    }
+}
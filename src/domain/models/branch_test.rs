@@ -0,0 +1,130 @@
+use anyhow::Result;
+
+use super::Branch;
+use crate::domain::models::Author;
+use crate::domain::models::Message;
+
+fn conversation() -> Vec<Message> {
+    return vec![
+        Message::new(Author::User, "Hello"),
+        Message::new(Author::Model, "Hi"),
+        Message::new(Author::User, "Tell me a joke"),
+        Message::new(Author::Model, "A joke"),
+    ];
+}
+
+fn texts(messages: &[Message]) -> Vec<String> {
+    return messages
+        .iter()
+        .map(|message| return message.text.to_string())
+        .collect();
+}
+
+#[test]
+fn it_forks_from_a_message() {
+    let mut messages = conversation();
+    Branch::fork(
+        &mut messages,
+        2,
+        Message::new(Author::User, "Tell me a poem"),
+    );
+
+    assert_eq!(texts(&messages), vec!["Hello", "Hi", "Tell me a poem"]);
+    let message = &messages[2];
+    assert_eq!(message.branches.len(), 1);
+    assert_eq!(message.branch_idx, 1);
+    assert_eq!(
+        texts(&message.branches[0].messages),
+        vec!["Tell me a joke", "A joke"]
+    );
+}
+
+#[test]
+fn it_keeps_every_branch_when_forking_again() {
+    let mut messages = conversation();
+    Branch::fork(
+        &mut messages,
+        2,
+        Message::new(Author::User, "Tell me a poem"),
+    );
+    Branch::fork(
+        &mut messages,
+        2,
+        Message::new(Author::User, "Tell me a story"),
+    );
+
+    let message = &messages[2];
+    assert_eq!(message.text, "Tell me a story");
+    assert_eq!(message.branch_idx, 2);
+    assert_eq!(message.branches_len(), 3);
+    assert_eq!(message.branches[0].messages[0].text, "Tell me a joke");
+    assert_eq!(message.branches[1].messages[0].text, "Tell me a poem");
+    assert!(message.branches[1].messages[0].branches.is_empty());
+}
+
+#[test]
+fn it_switches_between_branches() {
+    let mut messages = conversation();
+    Branch::fork(
+        &mut messages,
+        2,
+        Message::new(Author::User, "Tell me a poem"),
+    );
+    messages.push(Message::new(Author::Model, "A poem"));
+
+    assert!(Branch::select(&mut messages, 2, 0));
+    assert_eq!(
+        texts(&messages),
+        vec!["Hello", "Hi", "Tell me a joke", "A joke"]
+    );
+    assert_eq!(messages[2].branch_idx, 0);
+    assert_eq!(
+        texts(&messages[2].branches[0].messages),
+        vec!["Tell me a poem", "A poem"]
+    );
+
+    assert!(!Branch::select(&mut messages, 2, 0));
+    assert!(!Branch::select(&mut messages, 2, 2));
+
+    assert!(Branch::cycle(&mut messages, 2, true));
+    assert_eq!(
+        texts(&messages),
+        vec!["Hello", "Hi", "Tell me a poem", "A poem"]
+    );
+    assert!(Branch::cycle(&mut messages, 2, true));
+    assert_eq!(messages[2].text, "Tell me a joke");
+    assert!(Branch::cycle(&mut messages, 2, false));
+    assert_eq!(messages[2].text, "Tell me a poem");
+
+    assert!(!Branch::cycle(&mut messages, 0, true));
+    assert!(!Branch::cycle(&mut messages, 10, true));
+}
+
+#[test]
+fn it_saves_branches_as_a_tree() -> Result<()> {
+    let mut messages = conversation();
+    Branch::fork(
+        &mut messages,
+        2,
+        Message::new(Author::User, "Tell me a poem"),
+    );
+    messages.push(Message::new(Author::Model, "A poem"));
+    Branch::fork(&mut messages, 0, Message::new(Author::User, "Bonjour"));
+
+    let yaml = serde_yaml::to_string(&messages)?;
+    let parsed: Vec<Message> = serde_yaml::from_str(&yaml)?;
+
+    assert_eq!(texts(&parsed), vec!["Bonjour"]);
+    let branch = &parsed[0].branches[0].messages;
+    assert_eq!(
+        texts(branch),
+        vec!["Hello", "Hi", "Tell me a poem", "A poem"]
+    );
+    assert_eq!(branch[2].branch_idx, 1);
+    assert_eq!(
+        texts(&branch[2].branches[0].messages),
+        vec!["Tell me a joke", "A joke"]
+    );
+
+    return Ok(());
+}
@@ -0,0 +1,202 @@
+#[cfg(test)]
+#[path = "cassette_test.rs"]
+mod tests;
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::Author;
+use super::BackendResponse;
+use super::Usage;
+
+/// Replaces secrets in recorded traffic.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Headers holding credentials, which are never recorded.
+const SECRET_HEADERS: [&str; 5] = [
+    "api-key",
+    "authorization",
+    "cookie",
+    "x-api-key",
+    "x-goog-api-key",
+];
+
+/// Query parameters holding credentials.
+const SECRET_PARAMS: [&str; 3] = ["api-key", "key", "token"];
+
+/// A line of a streamed response body.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedChunk {
+    pub elapsed_ms: u64,
+    pub data: String,
+}
+
+/// An HTTP request sent to a backend, and its response.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub elapsed_ms: u64,
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Body of a response the backend failed with.
+    #[serde(default)]
+    pub error_body: String,
+    #[serde(default)]
+    pub chunks: Vec<RecordedChunk>,
+}
+
+impl RecordedRequest {
+    pub fn is_secret_header(name: &str) -> bool {
+        return SECRET_HEADERS.contains(&name.to_lowercase().as_str());
+    }
+
+    /// Redacts credentials passed as query parameters, such as Gemini's API
+    /// key.
+    pub fn redact_url(url: &str) -> String {
+        let (path, query) = match url.split_once('?') {
+            Some((path, query)) => (path, query),
+            None => return url.to_string(),
+        };
+
+        let query = query
+            .split('&')
+            .map(|param| {
+                return match param.split_once('=') {
+                    Some((name, _)) if SECRET_PARAMS.contains(&name.to_lowercase().as_str()) => {
+                        format!("{name}={REDACTED}")
+                    }
+                    _ => param.to_string(),
+                };
+            })
+            .collect::<Vec<String>>()
+            .join("&");
+
+        return format!("{path}?{query}");
+    }
+}
+
+/// A response sent to the chat while a completion streamed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub elapsed_ms: u64,
+    pub author: Author,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reasoning: String,
+    pub done: bool,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl RecordedResponse {
+    pub fn new(elapsed_ms: u64, res: &BackendResponse) -> RecordedResponse {
+        return RecordedResponse {
+            elapsed_ms,
+            author: res.author.clone(),
+            text: res.text.to_string(),
+            reasoning: res.reasoning.to_string(),
+            done: res.done,
+            usage: res.usage,
+            truncated: res.truncated,
+        };
+    }
+
+    pub fn to_backend_response(&self) -> BackendResponse {
+        return BackendResponse {
+            author: self.author.clone(),
+            text: self.text.to_string(),
+            reasoning: self.reasoning.to_string(),
+            done: self.done,
+            usage: self.usage,
+            truncated: self.truncated,
+        };
+    }
+}
+
+/// A prompt sent to a backend, with the traffic and responses of its
+/// completion.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub backend: String,
+    pub model: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub requests: Vec<RecordedRequest>,
+    #[serde(default)]
+    pub responses: Vec<RecordedResponse>,
+    /// Error the completion failed with.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl Interaction {
+    /// Replaces each secret wherever it appears, in case a backend echoes it
+    /// back or sends it somewhere other than a header.
+    pub fn redact(&mut self, secrets: &[String]) {
+        let redact = |text: &mut String| {
+            for secret in secrets.iter().filter(|secret| return !secret.is_empty()) {
+                *text = text.replace(secret, REDACTED);
+            }
+        };
+
+        for request in self.requests.iter_mut() {
+            request.url = RecordedRequest::redact_url(&request.url);
+            redact(&mut request.url);
+            redact(&mut request.body);
+            redact(&mut request.error_body);
+            for value in request.headers.values_mut() {
+                redact(value);
+            }
+            for chunk in request.chunks.iter_mut() {
+                redact(&mut chunk.data);
+            }
+        }
+        for response in self.responses.iter_mut() {
+            redact(&mut response.text);
+        }
+        if let Some(error) = self.error.as_mut() {
+            redact(error);
+        }
+    }
+}
+
+/// Backend traffic recorded to a file, which can be attached to bug reports
+/// and replayed with the replay backend.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn parse(text: &str) -> Result<Cassette> {
+        if text.trim().is_empty() {
+            return Ok(Cassette::default());
+        }
+
+        return Ok(serde_yaml::from_str(text)?);
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        return Ok(serde_yaml::to_string(self)?);
+    }
+
+    /// Finds the interaction recorded for a prompt, falling back to the
+    /// interaction recorded at the same turn of the conversation.
+    pub fn find(&self, prompt: &str, turn: usize) -> Option<&Interaction> {
+        return self
+            .interactions
+            .iter()
+            .find(|interaction| return interaction.prompt == prompt)
+            .or_else(|| return self.interactions.get(turn));
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use super::Cassette;
+use super::Interaction;
+use super::RecordedChunk;
+use super::RecordedRequest;
+use super::RecordedResponse;
+use crate::domain::models::Author;
+use crate::domain::models::BackendResponse;
+
+fn interaction(prompt: &str) -> Interaction {
+    return Interaction {
+        backend: "openai".to_string(),
+        model: "gpt-4o".to_string(),
+        prompt: prompt.to_string(),
+        ..Interaction::default()
+    };
+}
+
+#[test]
+fn it_detects_secret_headers() {
+    assert!(RecordedRequest::is_secret_header("Authorization"));
+    assert!(RecordedRequest::is_secret_header("x-api-key"));
+    assert!(!RecordedRequest::is_secret_header("content-type"));
+}
+
+#[test]
+fn it_redacts_secret_query_params() {
+    assert_eq!(
+        RecordedRequest::redact_url("https://example.com/v1beta/models?alt=sse&key=abc123"),
+        "https://example.com/v1beta/models?alt=sse&key=[REDACTED]"
+    );
+    assert_eq!(
+        RecordedRequest::redact_url("https://example.com/v1/chat"),
+        "https://example.com/v1/chat"
+    );
+}
+
+#[test]
+fn it_redacts_secrets_everywhere() {
+    let mut interaction = interaction("Hello");
+    interaction.requests.push(RecordedRequest {
+        url: "https://example.com/abc123/chat?key=abc123".to_string(),
+        headers: BTreeMap::from([("x-token".to_string(), "abc123".to_string())]),
+        body: "{\"token\":\"abc123\"}".to_string(),
+        error_body: "Invalid token abc123".to_string(),
+        chunks: vec![RecordedChunk {
+            elapsed_ms: 1,
+            data: "data: abc123".to_string(),
+        }],
+        ..RecordedRequest::default()
+    });
+    interaction.error = Some("Invalid token abc123".to_string());
+
+    interaction.redact(&["abc123".to_string(), "".to_string()]);
+
+    let request = &interaction.requests[0];
+    assert_eq!(
+        request.url,
+        "https://example.com/[REDACTED]/chat?key=[REDACTED]"
+    );
+    assert_eq!(request.headers["x-token"], "[REDACTED]");
+    assert_eq!(request.body, "{\"token\":\"[REDACTED]\"}");
+    assert_eq!(request.error_body, "Invalid token [REDACTED]");
+    assert_eq!(request.chunks[0].data, "data: [REDACTED]");
+    assert_eq!(
+        interaction.error,
+        Some("Invalid token [REDACTED]".to_string())
+    );
+}
+
+#[test]
+fn it_converts_responses() {
+    let res = BackendResponse {
+        author: Author::Model,
+        text: "Hello".to_string(),
+        reasoning: "".to_string(),
+        done: true,
+        usage: None,
+        truncated: true,
+    };
+    let recorded = RecordedResponse::new(12, &res);
+
+    assert_eq!(recorded.elapsed_ms, 12);
+    let converted = recorded.to_backend_response();
+    assert_eq!(converted.text, "Hello");
+    assert!(converted.done);
+    assert!(converted.truncated);
+}
+
+#[test]
+fn it_round_trips_yaml() -> Result<()> {
+    let cassette = Cassette {
+        interactions: vec![interaction("Hello")],
+    };
+    let parsed = Cassette::parse(&cassette.to_yaml()?)?;
+
+    assert_eq!(parsed, cassette);
+    assert_eq!(Cassette::parse("")?, Cassette::default());
+
+    return Ok(());
+}
+
+#[test]
+fn it_finds_interactions() {
+    let cassette = Cassette {
+        interactions: vec![interaction("First"), interaction("Second")],
+    };
+
+    assert_eq!(cassette.find("Second", 0).unwrap().prompt, "Second");
+    assert_eq!(cassette.find("Other", 1).unwrap().prompt, "Second");
+    assert!(cassette.find("Other", 2).is_none());
+}
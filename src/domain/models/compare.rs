@@ -0,0 +1,155 @@
+#[cfg(test)]
+#[path = "compare_test.rs"]
+mod tests;
+
+use std::fmt;
+
+use anyhow::bail;
+use anyhow::Result;
+
+use super::Author;
+use super::BackendName;
+use super::BackendResponse;
+use super::Message;
+use super::MessageType;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+
+/// Most models compared at once, so each column stays readable.
+pub const MAX_COMPARE_TARGETS: usize = 4;
+
+/// A backend and model answering prompts in compare mode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompareTarget {
+    pub backend: BackendName,
+    pub model: String,
+}
+
+impl fmt::Display for CompareTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}:{}", self.backend, self.model);
+    }
+}
+
+impl CompareTarget {
+    /// Parses a `backend:model` pair. Only the first colon separates the
+    /// two, as model names such as `llama3:8b` can contain colons too.
+    pub fn parse(text: &str) -> Result<CompareTarget> {
+        let text = text.trim();
+        let (backend_name, model) = match text.split_once(':') {
+            Some((backend_name, model)) if !model.trim().is_empty() => {
+                (backend_name.trim(), model.trim())
+            }
+            _ => bail!(format!("{text} is not a backend:model pair")),
+        };
+
+        let backend = match BackendName::parse(backend_name.to_string()) {
+            Some(backend) => backend,
+            None => bail!(format!("No backend named {backend_name} exists")),
+        };
+
+        return Ok(CompareTarget {
+            backend,
+            model: model.to_string(),
+        });
+    }
+
+    /// Parses a comma separated list of `backend:model` pairs.
+    pub fn parse_list(text: &str) -> Result<Vec<CompareTarget>> {
+        let targets = text
+            .split(',')
+            .filter(|e| return !e.trim().is_empty())
+            .map(CompareTarget::parse)
+            .collect::<Result<Vec<CompareTarget>>>()?;
+
+        if targets.len() < 2 {
+            bail!("At least two backend:model pairs are needed to compare");
+        }
+        if targets.len() > MAX_COMPARE_TARGETS {
+            bail!(format!(
+                "At most {MAX_COMPARE_TARGETS} backend:model pairs can be compared"
+            ));
+        }
+
+        return Ok(targets);
+    }
+
+    /// Returns no targets when compare mode isn't configured.
+    pub fn from_config() -> Result<Vec<CompareTarget>> {
+        let text = Config::get(ConfigKey::Compare);
+        if text.trim().is_empty() {
+            return Ok(vec![]);
+        }
+
+        return CompareTarget::parse_list(&text);
+    }
+
+    pub fn join(targets: &[CompareTarget]) -> String {
+        return targets
+            .iter()
+            .map(|target| return target.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+    }
+}
+
+/// One model's answer to a compared prompt.
+pub struct CompareAnswer {
+    pub target: CompareTarget,
+    pub message: Message,
+    pub done: bool,
+}
+
+/// The answers of each model to the same prompt, shown side by side until one
+/// of them is kept.
+pub struct Comparison {
+    pub answers: Vec<CompareAnswer>,
+}
+
+impl Comparison {
+    pub fn new(targets: &[CompareTarget]) -> Comparison {
+        return Comparison {
+            answers: targets
+                .iter()
+                .map(|target| {
+                    return CompareAnswer {
+                        target: target.clone(),
+                        message: Message::new(Author::Model, ""),
+                        done: false,
+                    };
+                })
+                .collect(),
+        };
+    }
+
+    /// Adds a streamed response to an answer. Returns false when the answer
+    /// was already done, so the response is ignored.
+    pub fn handle_response(&mut self, idx: usize, res: &BackendResponse) -> bool {
+        let answer = match self.answers.get_mut(idx) {
+            Some(answer) if !answer.done => answer,
+            _ => return false,
+        };
+
+        answer.message.append(&res.text);
+        answer.message.append_reasoning(&res.reasoning);
+        answer.message.truncated = res.truncated;
+        if res.done {
+            answer.done = true;
+            answer.message.usage = res.usage;
+        }
+
+        return true;
+    }
+
+    /// Replaces an answer with the error its backend failed with.
+    pub fn handle_error(&mut self, idx: usize, err: &str) {
+        if let Some(answer) = self.answers.get_mut(idx) {
+            answer.message = Message::new_with_type(Author::Model, MessageType::Error, err);
+            answer.done = true;
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        return self.answers.iter().all(|answer| return answer.done);
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::Result;
+
+use super::CompareTarget;
+use super::Comparison;
+use crate::domain::models::Author;
+use crate::domain::models::BackendName;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::MessageType;
+use crate::domain::models::Usage;
+
+fn response(text: &str, done: bool) -> BackendResponse {
+    return BackendResponse {
+        author: Author::Model,
+        text: text.to_string(),
+        reasoning: "".to_string(),
+        done,
+        usage: None,
+        truncated: false,
+    };
+}
+
+#[test]
+fn it_parses_targets() -> Result<()> {
+    let target = CompareTarget::parse(" ollama:llama3:8b ")?;
+
+    assert_eq!(target.backend, BackendName::Ollama);
+    assert_eq!(target.model, "llama3:8b");
+    assert_eq!(target.to_string(), "ollama:llama3:8b");
+
+    return Ok(());
+}
+
+#[test]
+fn it_fails_to_parse_invalid_targets() {
+    assert_eq!(
+        CompareTarget::parse("gpt-4o").unwrap_err().to_string(),
+        "gpt-4o is not a backend:model pair"
+    );
+    assert_eq!(
+        CompareTarget::parse("openai:").unwrap_err().to_string(),
+        "openai: is not a backend:model pair"
+    );
+    assert_eq!(
+        CompareTarget::parse("mistral:large")
+            .unwrap_err()
+            .to_string(),
+        "No backend named mistral exists"
+    );
+}
+
+#[test]
+fn it_parses_target_lists() -> Result<()> {
+    let targets = CompareTarget::parse_list("openai:gpt-4o, ollama:llama3,")?;
+
+    assert_eq!(
+        targets,
+        vec![
+            CompareTarget {
+                backend: BackendName::OpenAI,
+                model: "gpt-4o".to_string(),
+            },
+            CompareTarget {
+                backend: BackendName::Ollama,
+                model: "llama3".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        CompareTarget::join(&targets),
+        "openai:gpt-4o, ollama:llama3"
+    );
+
+    return Ok(());
+}
+
+#[test]
+fn it_requires_two_to_four_targets() {
+    assert_eq!(
+        CompareTarget::parse_list("openai:gpt-4o")
+            .unwrap_err()
+            .to_string(),
+        "At least two backend:model pairs are needed to compare"
+    );
+    assert_eq!(
+        CompareTarget::parse_list("ollama:a,ollama:b,ollama:c,ollama:d,ollama:e")
+            .unwrap_err()
+            .to_string(),
+        "At most 4 backend:model pairs can be compared"
+    );
+}
+
+#[test]
+fn it_collects_answers() -> Result<()> {
+    let targets = CompareTarget::parse_list("openai:gpt-4o,ollama:llama3")?;
+    let mut comparison = Comparison::new(&targets);
+
+    assert!(comparison.handle_response(0, &response("Hello ", false)));
+    assert!(comparison.handle_response(1, &response("Hi", false)));
+    assert!(!comparison.is_done());
+
+    let mut done = response("World", true);
+    done.usage = Some(Usage {
+        prompt_tokens: 10,
+        completion_tokens: 2,
+    });
+    assert!(comparison.handle_response(0, &done));
+    assert!(!comparison.handle_response(0, &response("!", true)));
+    assert!(!comparison.handle_response(5, &response("!", true)));
+    comparison.handle_error(1, "The backend failed");
+
+    assert!(comparison.is_done());
+    assert_eq!(comparison.answers[0].message.text, "Hello World");
+    assert_eq!(comparison.answers[0].message.usage, done.usage);
+    assert_eq!(comparison.answers[1].message.text, "The backend failed");
+    assert_eq!(
+        comparison.answers[1].message.message_type(),
+        MessageType::Error
+    );
+
+    return Ok(());
+}
@@ -0,0 +1,157 @@
+#[cfg(test)]
+#[path = "context_window_test.rs"]
+mod tests;
+
+use anyhow::bail;
+use anyhow::Result;
+use strum::EnumIter;
+use strum::EnumVariantNames;
+use strum::IntoEnumIterator;
+use strum::VariantNames;
+
+use super::AttachmentKind;
+use super::BackendPrompt;
+use super::HistoryMessage;
+use super::HistoryRole;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+
+/// Rough number of characters per token, which holds for English text with
+/// most tokenizers.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Tokens added by backends around each message for roles and separators.
+const TOKENS_PER_MESSAGE: usize = 4;
+
+/// Images are billed by size, which isn't known here, so a typical size is
+/// used.
+const TOKENS_PER_IMAGE: usize = 1000;
+
+/// How the history is made to fit when it grows past the context window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, EnumVariantNames, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ContextStrategy {
+    /// Drops the oldest turns.
+    Truncate,
+    /// Asks the model to summarize earlier turns into a compact note.
+    Summarize,
+}
+
+impl ContextStrategy {
+    pub fn parse(text: &str) -> Option<ContextStrategy> {
+        return ContextStrategy::iter().find(|e| return e.to_string() == text);
+    }
+}
+
+/// Keeps prompts within the number of tokens a model accepts. Token counts are
+/// estimated, as tokenizers differ between models.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContextWindow {
+    pub max_tokens: usize,
+    pub strategy: ContextStrategy,
+}
+
+impl ContextWindow {
+    /// Returns `None` when no context window is configured.
+    pub fn from_config() -> Result<Option<ContextWindow>> {
+        let max_tokens = Config::get(ConfigKey::ContextWindow);
+        if max_tokens.is_empty() {
+            return Ok(None);
+        }
+
+        let max_tokens = match max_tokens.parse::<usize>() {
+            Ok(max_tokens) if max_tokens > 0 => max_tokens,
+            _ => bail!(format!("{max_tokens} is not a positive number of tokens")),
+        };
+
+        let strategy = Config::get(ConfigKey::ContextStrategy);
+        let strategy = match ContextStrategy::parse(&strategy) {
+            Some(strategy) => strategy,
+            None => {
+                bail!(format!(
+                    "{strategy} is not a context strategy. Possible values are: {}",
+                    ContextStrategy::VARIANTS.join(", ")
+                ))
+            }
+        };
+
+        return Ok(Some(ContextWindow {
+            max_tokens,
+            strategy,
+        }));
+    }
+
+    pub fn estimate_text(text: &str) -> usize {
+        return text.chars().count().div_ceil(CHARS_PER_TOKEN);
+    }
+
+    pub fn estimate_message(message: &HistoryMessage) -> usize {
+        let attachments = message
+            .attachments
+            .iter()
+            .map(|attachment| {
+                return match attachment.kind {
+                    AttachmentKind::Image => TOKENS_PER_IMAGE,
+                    AttachmentKind::Text => ContextWindow::estimate_text(&attachment.content),
+                };
+            })
+            .sum::<usize>();
+
+        return TOKENS_PER_MESSAGE + ContextWindow::estimate_text(&message.text) + attachments;
+    }
+
+    /// Estimates the tokens of everything sent with a prompt, including room
+    /// for the response when a max tokens option is set.
+    pub fn estimate_prompt(prompt: &BackendPrompt) -> usize {
+        let mut message = HistoryMessage::new(HistoryRole::User, &prompt.text);
+        message.attachments = prompt.attachments.clone();
+
+        let history = prompt
+            .history
+            .iter()
+            .map(ContextWindow::estimate_message)
+            .sum::<usize>();
+        let system_prompt = if prompt.system_prompt.is_empty() {
+            0
+        } else {
+            TOKENS_PER_MESSAGE + ContextWindow::estimate_text(&prompt.system_prompt)
+        };
+        let response = prompt.generation_options.max_tokens.unwrap_or(0) as usize;
+
+        return history + system_prompt + response + ContextWindow::estimate_message(&message);
+    }
+
+    pub fn fits(&self, prompt: &BackendPrompt) -> bool {
+        return ContextWindow::estimate_prompt(prompt) <= self.max_tokens;
+    }
+
+    /// Drops the oldest turns of the history until the prompt fits, returning
+    /// the number of messages dropped. A turn is a user message and the
+    /// replies to it, and turns with a pinned message are kept, so the history
+    /// still alternates roles. The prompt may still not fit once only pinned
+    /// turns are left.
+    pub fn truncate(&self, prompt: &mut BackendPrompt) -> usize {
+        let mut dropped = 0;
+        let mut idx = 0;
+
+        while !self.fits(prompt) && idx < prompt.history.len() {
+            let mut end = idx + 1;
+            while end < prompt.history.len() && prompt.history[end].role != HistoryRole::User {
+                end += 1;
+            }
+
+            if prompt.history[idx..end]
+                .iter()
+                .any(|message| return message.pinned)
+            {
+                idx = end;
+                continue;
+            }
+
+            prompt.history.drain(idx..end);
+            dropped += end - idx;
+        }
+
+        return dropped;
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+
+use super::ContextStrategy;
+use super::ContextWindow;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+
+fn turns(count: usize) -> Vec<HistoryMessage> {
+    return (0..count)
+        .flat_map(|idx| {
+            return [
+                HistoryMessage::new(HistoryRole::User, &format!("Question {idx:02}")),
+                HistoryMessage::new(HistoryRole::Assistant, &format!("Answer {idx:02}")),
+            ];
+        })
+        .collect();
+}
+
+fn context_window(max_tokens: usize) -> ContextWindow {
+    return ContextWindow {
+        max_tokens,
+        strategy: ContextStrategy::Truncate,
+    };
+}
+
+#[test]
+fn it_estimates_tokens() {
+    assert_eq!(ContextWindow::estimate_text(""), 0);
+    assert_eq!(ContextWindow::estimate_text("Hello"), 2);
+    assert_eq!(ContextWindow::estimate_text("Hello World!"), 3);
+
+    let mut message = HistoryMessage::new(HistoryRole::User, "Hello World!");
+    assert_eq!(ContextWindow::estimate_message(&message), 7);
+
+    message.attachments = vec![
+        Attachment {
+            kind: AttachmentKind::Image,
+            path: "/tmp/cat.png".to_string(),
+            mime_type: "image/png".to_string(),
+            content: "b2F0bWVhbA==".to_string(),
+        },
+        Attachment {
+            kind: AttachmentKind::Text,
+            path: "/tmp/notes.md".to_string(),
+            mime_type: "text/markdown".to_string(),
+            content: "# Notes".to_string(),
+        },
+    ];
+    assert_eq!(ContextWindow::estimate_message(&message), 1009);
+}
+
+#[test]
+fn it_estimates_prompts() {
+    let mut prompt = BackendPrompt::new("Hello World!".to_string(), turns(1));
+    assert_eq!(ContextWindow::estimate_prompt(&prompt), 7 + 7 + 7);
+
+    prompt.system_prompt = "You are a test.".to_string();
+    prompt.generation_options.max_tokens = Some(100);
+    assert_eq!(ContextWindow::estimate_prompt(&prompt), 21 + 8 + 100);
+}
+
+#[test]
+fn it_keeps_prompts_which_fit() {
+    let mut prompt = BackendPrompt::new("Hello".to_string(), turns(3));
+
+    assert!(context_window(100).fits(&prompt));
+    assert_eq!(context_window(100).truncate(&mut prompt), 0);
+    assert_eq!(prompt.history, turns(3));
+}
+
+#[test]
+fn it_drops_the_oldest_turns() {
+    let mut prompt = BackendPrompt::new("Hello".to_string(), turns(3));
+
+    // Each turn is 14 tokens, and the prompt 6.
+    assert_eq!(context_window(40).truncate(&mut prompt), 2);
+    assert_eq!(prompt.history, turns(3)[2..]);
+    assert!(context_window(40).fits(&prompt));
+}
+
+#[test]
+fn it_keeps_pinned_turns() {
+    let mut history = turns(3);
+    history[1].pinned = true;
+    let mut prompt = BackendPrompt::new("Hello".to_string(), history.clone());
+
+    assert_eq!(context_window(40).truncate(&mut prompt), 2);
+    assert_eq!(prompt.history, [&history[..2], &history[4..]].concat());
+
+    // Pinned turns are kept even when the prompt still doesn't fit.
+    assert_eq!(context_window(10).truncate(&mut prompt), 2);
+    assert_eq!(prompt.history, history[..2]);
+    assert!(!context_window(10).fits(&prompt));
+}
+
+#[test]
+fn it_loads_from_config() -> Result<()> {
+    Config::set(ConfigKey::ContextWindow, "");
+    assert_eq!(ContextWindow::from_config()?, None);
+
+    Config::set(ConfigKey::ContextWindow, "8192");
+    Config::set(ConfigKey::ContextStrategy, "summarize");
+    assert_eq!(
+        ContextWindow::from_config()?,
+        Some(ContextWindow {
+            max_tokens: 8192,
+            strategy: ContextStrategy::Summarize
+        })
+    );
+
+    Config::set(ConfigKey::ContextWindow, "0");
+    assert_eq!(
+        ContextWindow::from_config().unwrap_err().to_string(),
+        "0 is not a positive number of tokens"
+    );
+
+    Config::set(ConfigKey::ContextWindow, "");
+    Config::set(ConfigKey::ContextStrategy, "truncate");
+
+    return Ok(());
+}
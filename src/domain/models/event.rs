@@ -2,14 +2,28 @@ use tui_textarea::Input;
 
 use super::BackendResponse;
 use super::Message;
+use super::ToolApproval;
 
 pub enum Event {
+    BackendCompareError(usize, String),
+    BackendCompareResponse(usize, BackendResponse),
     BackendMessage(Message),
     BackendPromptResponse(BackendResponse),
+    /// Citations of the chunks of a project added to the context of the
+    /// prompt being answered.
+    BackendSources(Vec<String>),
+    BackendStatus(Message),
+    BackendSummary(Message),
+    BackendToolCall(ToolApproval),
     KeyboardCharInput(Input),
+    KeyboardALTDown(),
+    KeyboardALTLeft(),
+    KeyboardALTRight(),
+    KeyboardALTUp(),
     KeyboardCTRLC(),
     KeyboardCTRLO(),
     KeyboardCTRLR(),
+    KeyboardCTRLT(),
     KeyboardEnter(),
     KeyboardPaste(String),
     UITick(),
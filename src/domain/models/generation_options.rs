@@ -0,0 +1,139 @@
+#[cfg(test)]
+#[path = "generation_options_test.rs"]
+mod tests;
+
+use anyhow::bail;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+
+pub const GENERATION_OPTION_KEYS: [ConfigKey; 4] = [
+    ConfigKey::Temperature,
+    ConfigKey::MaxTokens,
+    ConfigKey::TopP,
+    ConfigKey::Stop,
+];
+
+/// Sampling parameters sent alongside a prompt. Options left unset fall back
+/// to the backend's own defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+}
+
+impl GenerationOptions {
+    /// Matches the name of an option as used with `/set`, accepting both
+    /// kebab-case and snake_case.
+    pub fn parse_key(name: &str) -> Option<ConfigKey> {
+        let name = name.trim().to_lowercase().replace('_', "-");
+        return GENERATION_OPTION_KEYS
+            .iter()
+            .find(|key| return key.to_string() == name)
+            .copied();
+    }
+
+    pub fn from_config() -> Result<GenerationOptions> {
+        let mut options = GenerationOptions::default();
+        for key in GENERATION_OPTION_KEYS {
+            options.set(key, &Config::get(key))?;
+        }
+
+        return Ok(options);
+    }
+
+    /// Writes the options which are set back to the global config, used when
+    /// restoring a session.
+    pub fn save_to_config(&self) {
+        for key in GENERATION_OPTION_KEYS {
+            let value = self.get(key);
+            if !value.is_empty() {
+                Config::set(key, &value);
+            }
+        }
+    }
+
+    /// Returns the config representation of an option. Unset options are
+    /// returned as an empty string.
+    pub fn get(&self, key: ConfigKey) -> String {
+        return match key {
+            ConfigKey::Temperature => {
+                self.temperature
+                    .map(|e| return e.to_string())
+                    .unwrap_or_default()
+            }
+            ConfigKey::MaxTokens => {
+                self.max_tokens
+                    .map(|e| return e.to_string())
+                    .unwrap_or_default()
+            }
+            ConfigKey::TopP => self.top_p.map(|e| return e.to_string()).unwrap_or_default(),
+            ConfigKey::Stop => self.stop.join(","),
+            _ => "".to_string(),
+        };
+    }
+
+    /// Validates and sets a single option from its config representation. An
+    /// empty value unsets the option. Stop sequences are comma separated, and
+    /// are not trimmed so whitespace sequences such as new lines can be used.
+    pub fn set(&mut self, key: ConfigKey, value: &str) -> Result<()> {
+        let trimmed = value.trim();
+
+        match key {
+            ConfigKey::Temperature => {
+                self.temperature = parse_float(key, trimmed, 2.0)?;
+            }
+            ConfigKey::MaxTokens => {
+                if trimmed.is_empty() {
+                    self.max_tokens = None;
+                } else {
+                    match trimmed.parse::<u32>() {
+                        Ok(val) if val > 0 => self.max_tokens = Some(val),
+                        _ => {
+                            bail!(format!(
+                                "{key} must be a whole number above 0, got {trimmed}"
+                            ))
+                        }
+                    }
+                }
+            }
+            ConfigKey::TopP => {
+                self.top_p = parse_float(key, trimmed, 1.0)?;
+            }
+            ConfigKey::Stop => {
+                self.stop = value
+                    .split(',')
+                    .map(|e| return e.to_string())
+                    .filter(|e| return !e.is_empty())
+                    .collect();
+            }
+            _ => bail!(format!("{key} is not a generation option")),
+        }
+
+        return Ok(());
+    }
+}
+
+fn parse_float(key: ConfigKey, value: &str, max: f32) -> Result<Option<f32>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    match value.parse::<f32>() {
+        Ok(val) if (0.0..=max).contains(&val) => return Ok(Some(val)),
+        _ => {
+            bail!(format!(
+                "{key} must be a number between 0 and {max}, got {value}"
+            ))
+        }
+    }
+}
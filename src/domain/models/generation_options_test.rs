@@ -0,0 +1,66 @@
+use anyhow::Result;
+
+use super::GenerationOptions;
+use crate::configuration::ConfigKey;
+
+#[test]
+fn it_parses_keys() {
+    assert!(GenerationOptions::parse_key("temperature") == Some(ConfigKey::Temperature));
+    assert!(GenerationOptions::parse_key("max_tokens") == Some(ConfigKey::MaxTokens));
+    assert!(GenerationOptions::parse_key("top-p") == Some(ConfigKey::TopP));
+    assert!(GenerationOptions::parse_key("stop") == Some(ConfigKey::Stop));
+    assert!(GenerationOptions::parse_key("model").is_none());
+}
+
+#[test]
+fn it_sets_options() -> Result<()> {
+    let mut options = GenerationOptions::default();
+    options.set(ConfigKey::Temperature, "0.2")?;
+    options.set(ConfigKey::MaxTokens, "512")?;
+    options.set(ConfigKey::TopP, "0.9")?;
+    options.set(ConfigKey::Stop, "###,END")?;
+
+    assert_eq!(
+        options,
+        GenerationOptions {
+            temperature: Some(0.2),
+            max_tokens: Some(512),
+            top_p: Some(0.9),
+            stop: vec!["###".to_string(), "END".to_string()],
+        }
+    );
+    assert_eq!(options.get(ConfigKey::Temperature), "0.2");
+    assert_eq!(options.get(ConfigKey::Stop), "###,END");
+
+    return Ok(());
+}
+
+#[test]
+fn it_unsets_options_with_empty_values() -> Result<()> {
+    let mut options = GenerationOptions {
+        temperature: Some(0.2),
+        max_tokens: Some(512),
+        top_p: Some(0.9),
+        stop: vec!["END".to_string()],
+    };
+
+    for key in super::GENERATION_OPTION_KEYS {
+        options.set(key, "")?;
+    }
+
+    assert_eq!(options, GenerationOptions::default());
+
+    return Ok(());
+}
+
+#[test]
+fn it_fails_on_invalid_values() {
+    let mut options = GenerationOptions::default();
+
+    assert!(options.set(ConfigKey::Temperature, "hot").is_err());
+    assert!(options.set(ConfigKey::Temperature, "2.5").is_err());
+    assert!(options.set(ConfigKey::TopP, "1.1").is_err());
+    assert!(options.set(ConfigKey::MaxTokens, "0").is_err());
+    assert!(options.set(ConfigKey::MaxTokens, "-1").is_err());
+    assert!(options.set(ConfigKey::Model, "gpt-4").is_err());
+}
@@ -0,0 +1,130 @@
+#[cfg(test)]
+#[path = "history_test.rs"]
+mod tests;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::Attachment;
+use super::Author;
+use super::Message;
+use super::MessageType;
+use super::SlashCommand;
+
+/// Sent as the user's turn before a conversation summary, so the history keeps
+/// alternating roles.
+pub const SUMMARY_REQUEST: &str = "Summarize our conversation so far.";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryRole {
+    User,
+    Assistant,
+}
+
+/// A single turn of a conversation, independent of any backend's wire format.
+/// Each backend converts these into its own request messages.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryMessage {
+    pub role: HistoryRole,
+    pub text: String,
+    pub attachments: Vec<Attachment>,
+    /// Kept when older turns are dropped to fit the model's context window.
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl HistoryMessage {
+    pub fn new(role: HistoryRole, text: &str) -> HistoryMessage {
+        return HistoryMessage {
+            role,
+            text: text.to_string(),
+            attachments: vec![],
+            pinned: false,
+        };
+    }
+
+    /// Builds the conversation history from chat messages. Oatmeal messages,
+    /// errors, slash commands and their replies are left out, apart from
+    /// replies to `/continue`. The history always starts with a user message,
+    /// and consecutive messages from the same role are merged as most backends
+    /// require alternating roles.
+    ///
+    /// Once the conversation has been summarized, the latest summary replaces
+    /// the messages before it, apart from pinned messages.
+    pub fn from_messages(messages: &[Message]) -> Vec<HistoryMessage> {
+        let mut history: Vec<HistoryMessage> = vec![];
+        let mut in_slash_command = false;
+
+        let summary_idx = messages.iter().rposition(|message| return message.summary);
+        if let Some(summary_idx) = summary_idx {
+            history = HistoryMessage::summarized(&[], &messages[summary_idx].text);
+        }
+
+        for (idx, message) in messages.iter().enumerate() {
+            if message.author == Author::User {
+                let command = SlashCommand::parse(&message.text);
+                // Replies to `/continue` carry on the response before it, so
+                // they're merged with it rather than left out.
+                in_slash_command = command
+                    .as_ref()
+                    .is_some_and(|command| return !command.is_continue());
+                if command.is_some() {
+                    continue;
+                }
+            }
+
+            if in_slash_command
+                || message.summary
+                || message.author == Author::Oatmeal
+                || message.message_type() == MessageType::Error
+                || message.text.trim().is_empty()
+                || (summary_idx.is_some_and(|summary_idx| return idx < summary_idx)
+                    && !message.pinned)
+            {
+                continue;
+            }
+
+            let role = match message.author {
+                Author::User => HistoryRole::User,
+                _ => HistoryRole::Assistant,
+            };
+
+            if history.is_empty() && role != HistoryRole::User {
+                continue;
+            }
+
+            let mut history_message = HistoryMessage::new(role, &message.text);
+            history_message.attachments = message.attachments.clone();
+            history_message.pinned = message.pinned;
+            HistoryMessage::push(&mut history, history_message);
+        }
+
+        return history;
+    }
+
+    /// Replaces a history with a summary of it, keeping its pinned messages.
+    pub fn summarized(history: &[HistoryMessage], summary: &str) -> Vec<HistoryMessage> {
+        let mut res = vec![
+            HistoryMessage::new(HistoryRole::User, SUMMARY_REQUEST),
+            HistoryMessage::new(HistoryRole::Assistant, summary),
+        ];
+        for message in history.iter().filter(|message| return message.pinned) {
+            HistoryMessage::push(&mut res, message.clone());
+        }
+
+        return res;
+    }
+
+    fn push(history: &mut Vec<HistoryMessage>, message: HistoryMessage) {
+        if let Some(last) = history.last_mut() {
+            if last.role == message.role {
+                last.text = format!("{}\n\n{}", last.text, message.text);
+                last.attachments.extend(message.attachments);
+                last.pinned = last.pinned || message.pinned;
+                return;
+            }
+        }
+
+        history.push(message);
+    }
+}
@@ -0,0 +1,206 @@
+use super::HistoryMessage;
+use super::HistoryRole;
+use super::SUMMARY_REQUEST;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
+use crate::domain::models::Author;
+use crate::domain::models::Message;
+use crate::domain::models::MessageType;
+
+#[test]
+fn it_builds_history_from_messages() {
+    let messages = vec![
+        Message::new(Author::User, "Hello"),
+        Message::new(Author::Model, "Hi there"),
+        Message::new(Author::User, "How are you?"),
+    ];
+
+    assert_eq!(
+        HistoryMessage::from_messages(&messages),
+        vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "Hi there"),
+            HistoryMessage::new(HistoryRole::User, "How are you?"),
+        ]
+    );
+}
+
+#[test]
+fn it_skips_leading_model_messages() {
+    let messages = vec![
+        Message::new(Author::Model, "Hey there! What can I do for you?"),
+        Message::new(Author::User, "Hello"),
+    ];
+
+    assert_eq!(
+        HistoryMessage::from_messages(&messages),
+        vec![HistoryMessage::new(HistoryRole::User, "Hello")]
+    );
+}
+
+#[test]
+fn it_skips_oatmeal_and_error_messages() {
+    let messages = vec![
+        Message::new(Author::User, "Hello"),
+        Message::new_with_type(Author::Oatmeal, MessageType::Error, "It broke!"),
+        Message::new(
+            Author::Oatmeal,
+            "If you wish to quit, hit CTRL+C one more time",
+        ),
+        Message::new(Author::Model, "Hi there"),
+    ];
+
+    assert_eq!(
+        HistoryMessage::from_messages(&messages),
+        vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "Hi there"),
+        ]
+    );
+}
+
+#[test]
+fn it_skips_slash_commands_and_their_replies() {
+    let messages = vec![
+        Message::new(Author::User, "Hello"),
+        Message::new(Author::Model, "Hi there"),
+        Message::new(Author::User, "/model llama2"),
+        Message::new(Author::Model, "llama2 has entered the chat."),
+        Message::new(Author::User, "How are you?"),
+    ];
+
+    assert_eq!(
+        HistoryMessage::from_messages(&messages),
+        vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "Hi there"),
+            HistoryMessage::new(HistoryRole::User, "How are you?"),
+        ]
+    );
+}
+
+#[test]
+fn it_merges_consecutive_roles() {
+    let messages = vec![
+        Message::new(Author::User, "Hello"),
+        Message::new(Author::User, "Are you there?"),
+    ];
+
+    assert_eq!(
+        HistoryMessage::from_messages(&messages),
+        vec![HistoryMessage::new(
+            HistoryRole::User,
+            "Hello\n\nAre you there?"
+        )]
+    );
+}
+
+#[test]
+fn it_keeps_truncated_model_messages() {
+    let mut truncated = Message::new(Author::Model, "Hi th");
+    truncated.truncated = true;
+    let messages = vec![Message::new(Author::User, "Hello"), truncated];
+
+    assert_eq!(
+        HistoryMessage::from_messages(&messages),
+        vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "Hi th"),
+        ]
+    );
+}
+
+#[test]
+fn it_merges_continued_responses() {
+    let messages = vec![
+        Message::new(Author::User, "Hello"),
+        Message::new(Author::Model, "Hi th"),
+        Message::new(Author::User, "/continue"),
+        Message::new(Author::Model, "ere!"),
+        Message::new(Author::User, "/compact"),
+        Message::new(Author::Model, "We said hi."),
+    ];
+
+    assert_eq!(
+        HistoryMessage::from_messages(&messages),
+        vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "Hi th\n\nere!"),
+        ]
+    );
+}
+
+#[test]
+fn it_keeps_attachments() {
+    let attachment = Attachment {
+        kind: AttachmentKind::Image,
+        path: "/tmp/cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        content: "".to_string(),
+    };
+    let mut message = Message::new(Author::User, "What is this?");
+    message.attachments = vec![attachment.clone()];
+
+    let history = HistoryMessage::from_messages(&[message]);
+
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].attachments, vec![attachment]);
+}
+
+#[test]
+fn it_replaces_messages_before_a_summary() {
+    let mut pinned = Message::new(Author::User, "Always answer in French.");
+    pinned.pinned = true;
+    let messages = vec![
+        pinned,
+        Message::new(Author::Model, "D'accord."),
+        Message::new(Author::User, "Hello"),
+        Message::new(Author::Model, "Bonjour"),
+        Message::new(Author::User, "/compact"),
+        Message::new_summary("The user wants answers in French, and said hello."),
+        Message::new(Author::Oatmeal, "Compacted 4 messages into a summary."),
+        Message::new(Author::User, "How are you?"),
+    ];
+
+    // The pinned message is merged with the following user message.
+    let mut expected_pinned = HistoryMessage::new(
+        HistoryRole::User,
+        "Always answer in French.\n\nHow are you?",
+    );
+    expected_pinned.pinned = true;
+    assert_eq!(
+        HistoryMessage::from_messages(&messages),
+        vec![
+            HistoryMessage::new(HistoryRole::User, SUMMARY_REQUEST),
+            HistoryMessage::new(
+                HistoryRole::Assistant,
+                "The user wants answers in French, and said hello."
+            ),
+            expected_pinned,
+        ]
+    );
+}
+
+#[test]
+fn it_summarizes_history_keeping_pinned_messages() {
+    let mut history = vec![
+        HistoryMessage::new(HistoryRole::User, "Hello"),
+        HistoryMessage::new(HistoryRole::Assistant, "Hi there"),
+        HistoryMessage::new(HistoryRole::User, "Remember the number 42."),
+        HistoryMessage::new(HistoryRole::Assistant, "I will."),
+    ];
+    history[3].pinned = true;
+
+    let mut expected_summary = HistoryMessage::new(
+        HistoryRole::Assistant,
+        "Greetings were exchanged.\n\nI will.",
+    );
+    expected_summary.pinned = true;
+    assert_eq!(
+        HistoryMessage::summarized(&history, "Greetings were exchanged."),
+        vec![
+            HistoryMessage::new(HistoryRole::User, SUMMARY_REQUEST),
+            expected_summary,
+        ]
+    );
+}
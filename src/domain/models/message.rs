@@ -4,7 +4,10 @@ mod tests;
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::Attachment;
 use super::Author;
+use super::Branch;
+use super::Usage;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MessageType {
@@ -12,11 +15,69 @@ pub enum MessageType {
     Error,
 }
 
+/// Another response to the same prompt, kept when a response is regenerated.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alternate {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reasoning: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+fn is_zero(num: &usize) -> bool {
+    return *num == 0;
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Message {
     pub author: Author,
     pub text: String,
+    /// Thinking the model did before its answer. It's shown apart from the
+    /// answer, and never sent back to the model.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reasoning: String,
     mtype: MessageType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Files and lines of a project added to the context of the response in
+    /// `/rag` mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// Set when the response was cancelled before the model finished it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Pinned messages are never dropped to fit the model's context window.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Set on a summary of the conversation written by the model. Only the
+    /// summary and pinned messages before it are sent to the backend.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub summary: bool,
+    /// Other responses to the same prompt. Only the shown response is part
+    /// of the conversation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternates: Vec<Alternate>,
+    /// Position of the shown response among all of the responses.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub alternate_idx: usize,
+    /// Position of the response shown before regenerating, shown again when
+    /// the regenerated response comes back empty.
+    #[serde(skip)]
+    regenerated_from: Option<usize>,
+    /// Other continuations of the conversation from this message, kept when
+    /// an earlier version of the message was edited.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<Branch>,
+    /// Position of the shown continuation among all of the continuations.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub branch_idx: usize,
 }
 
 impl Message {
@@ -24,7 +85,19 @@ impl Message {
         return Message {
             author: author.clone(),
             text: text.to_string().replace('\t', "  "),
+            reasoning: "".to_string(),
             mtype: MessageType::Normal,
+            usage: None,
+            attachments: vec![],
+            sources: vec![],
+            truncated: false,
+            pinned: false,
+            summary: false,
+            alternates: vec![],
+            alternate_idx: 0,
+            regenerated_from: None,
+            branches: vec![],
+            branch_idx: 0,
         };
     }
 
@@ -32,18 +105,133 @@ impl Message {
         return Message {
             author: author.clone(),
             text: text.to_string().replace('\t', "  "),
+            reasoning: "".to_string(),
             mtype,
+            usage: None,
+            attachments: vec![],
+            sources: vec![],
+            truncated: false,
+            pinned: false,
+            summary: false,
+            alternates: vec![],
+            alternate_idx: 0,
+            regenerated_from: None,
+            branches: vec![],
+            branch_idx: 0,
         };
     }
 
+    pub fn new_summary(text: &str) -> Message {
+        let mut message = Message::new(Author::Model, text);
+        message.summary = true;
+        return message;
+    }
+
     pub fn message_type(&self) -> MessageType {
         return self.mtype;
     }
 
+    /// Number of responses to the prompt, including the shown response.
+    pub fn responses_len(&self) -> usize {
+        return self.alternates.len() + 1;
+    }
+
+    /// Number of continuations from the message, including the shown one.
+    pub fn branches_len(&self) -> usize {
+        return self.branches.len() + 1;
+    }
+
+    fn to_alternate(&self) -> Alternate {
+        return Alternate {
+            text: self.text.to_string(),
+            reasoning: self.reasoning.to_string(),
+            usage: self.usage,
+            sources: self.sources.clone(),
+            truncated: self.truncated,
+        };
+    }
+
+    fn show_alternate(&mut self, alternate: Alternate) {
+        self.text = alternate.text;
+        self.reasoning = alternate.reasoning;
+        self.usage = alternate.usage;
+        self.sources = alternate.sources;
+        self.truncated = alternate.truncated;
+    }
+
+    /// Keeps the shown response as an alternate, and clears the message for
+    /// a regenerated response shown after the others.
+    pub fn start_regenerating(&mut self) {
+        self.regenerated_from = Some(self.alternate_idx);
+        self.alternates
+            .insert(self.alternate_idx, self.to_alternate());
+        self.alternate_idx = self.alternates.len();
+        self.show_alternate(Alternate {
+            text: "".to_string(),
+            reasoning: "".to_string(),
+            usage: None,
+            sources: vec![],
+            truncated: false,
+        });
+    }
+
+    /// Drops a regenerated response which came back empty, such as when it
+    /// was cancelled or failed, showing the previous response again.
+    pub fn discard_empty_response(&mut self) -> bool {
+        let regenerated_from = self.regenerated_from.take();
+        if !self.text.is_empty() || self.alternates.is_empty() {
+            return false;
+        }
+
+        let idx = regenerated_from
+            .unwrap_or(self.alternates.len() - 1)
+            .min(self.alternates.len() - 1);
+        let alternate = self.alternates.remove(idx);
+        self.show_alternate(alternate);
+        self.alternate_idx = idx;
+
+        return true;
+    }
+
+    /// Shows the response at the given position. Returns false when the
+    /// position doesn't exist or is already shown.
+    pub fn select_alternate(&mut self, idx: usize) -> bool {
+        if idx >= self.responses_len() || idx == self.alternate_idx {
+            return false;
+        }
+
+        self.alternates
+            .insert(self.alternate_idx, self.to_alternate());
+        let alternate = self.alternates.remove(idx);
+        self.show_alternate(alternate);
+        self.alternate_idx = idx;
+
+        return true;
+    }
+
+    /// Shows the next or previous response, wrapping around.
+    pub fn cycle_alternates(&mut self, forward: bool) -> bool {
+        let len = self.responses_len();
+        if len < 2 {
+            return false;
+        }
+
+        let mut idx = (self.alternate_idx + len - 1) % len;
+        if forward {
+            idx = (self.alternate_idx + 1) % len;
+        }
+
+        return self.select_alternate(idx);
+    }
+
     pub fn append(&mut self, text: &str) {
         self.text += &text.replace('\t', "  ");
     }
 
+    pub fn append_reasoning(&mut self, reasoning: &str) {
+        self.reasoning += &reasoning.replace('\t', "  ");
+    }
+
     pub fn codeblocks(&self) -> Vec<String> {
         let mut codeblocks: Vec<String> = vec![];
         let mut current_codeblock: Vec<&str> = vec![];
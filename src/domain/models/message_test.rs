@@ -1,5 +1,6 @@
 use test_utils::codeblock_fixture;
 
+use super::Alternate;
 use super::Author;
 use super::Message;
 use super::MessageType;
@@ -94,3 +95,114 @@ fn it_executes_codeblocks() {
         print(i)
     "###);
 }
+
+#[test]
+fn it_keeps_alternates_when_regenerating() {
+    let mut msg = Message::new(Author::Model, "First");
+    msg.truncated = true;
+    msg.start_regenerating();
+    msg.append("Second");
+
+    assert_eq!(msg.text, "Second");
+    assert!(!msg.truncated);
+    assert_eq!(msg.responses_len(), 2);
+    assert_eq!(msg.alternate_idx, 1);
+    assert_eq!(
+        msg.alternates,
+        vec![Alternate {
+            text: "First".to_string(),
+            reasoning: "".to_string(),
+            usage: None,
+            sources: vec![],
+            truncated: true,
+        }]
+    );
+}
+
+#[test]
+fn it_cycles_alternates() {
+    let mut msg = Message::new(Author::Model, "First");
+    for text in ["Second", "Third"] {
+        msg.start_regenerating();
+        msg.append(text);
+    }
+
+    assert!(msg.cycle_alternates(true));
+    assert_eq!((msg.text.as_str(), msg.alternate_idx), ("First", 0));
+    assert!(msg.cycle_alternates(false));
+    assert_eq!((msg.text.as_str(), msg.alternate_idx), ("Third", 2));
+    assert!(msg.cycle_alternates(false));
+    assert_eq!((msg.text.as_str(), msg.alternate_idx), ("Second", 1));
+
+    assert!(!msg.select_alternate(1));
+    assert!(!msg.select_alternate(3));
+    assert!(msg.select_alternate(0));
+    assert_eq!(msg.text, "First");
+    assert_eq!(
+        msg.alternates
+            .iter()
+            .map(|alternate| return alternate.text.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["Second", "Third"]
+    );
+
+    // Regenerated responses are added after the others.
+    msg.start_regenerating();
+    assert_eq!(msg.alternate_idx, 3);
+    assert_eq!(msg.alternates[0].text, "First");
+}
+
+#[test]
+fn it_does_not_cycle_without_alternates() {
+    let mut msg = Message::new(Author::Model, "First");
+    assert!(!msg.cycle_alternates(true));
+    assert_eq!(msg.responses_len(), 1);
+}
+
+#[test]
+fn it_discards_empty_regenerated_responses() {
+    let mut msg = Message::new(Author::Model, "First");
+    assert!(!msg.discard_empty_response());
+
+    msg.start_regenerating();
+    assert!(msg.discard_empty_response());
+    assert_eq!(msg.text, "First");
+    assert_eq!(msg.responses_len(), 1);
+    assert_eq!(msg.alternate_idx, 0);
+}
+
+#[test]
+fn it_shows_the_selected_response_again_when_discarding() {
+    let mut msg = Message::new(Author::Model, "First");
+    for text in ["Second", "Third"] {
+        msg.start_regenerating();
+        msg.append(text);
+    }
+    assert!(msg.select_alternate(0));
+
+    msg.start_regenerating();
+    assert!(msg.discard_empty_response());
+    assert_eq!(msg.text, "First");
+    assert_eq!(msg.alternate_idx, 0);
+    assert_eq!(
+        msg.alternates
+            .iter()
+            .map(|alternate| return alternate.text.to_string())
+            .collect::<Vec<String>>(),
+        vec!["Second", "Third"]
+    );
+}
+
+#[test]
+fn it_serializes_alternates() {
+    let mut msg = Message::new(Author::Model, "First");
+    assert!(!serde_yaml::to_string(&msg).unwrap().contains("alternate"));
+
+    msg.start_regenerating();
+    msg.append("Second");
+    let parsed: Message = serde_yaml::from_str(&serde_yaml::to_string(&msg).unwrap()).unwrap();
+
+    assert_eq!(parsed.text, "Second");
+    assert_eq!(parsed.alternate_idx, 1);
+    assert_eq!(parsed.alternates, msg.alternates);
+}
@@ -1,21 +1,47 @@
 mod action;
+mod attachment;
 mod author;
 mod backend;
+mod branch;
+mod cassette;
+mod compare;
+mod context_window;
 mod editor;
 mod event;
+mod generation_options;
+mod history;
 mod loading;
 mod message;
+mod model_info;
+mod persona;
+mod profile;
 mod session;
 mod slash_commands;
 mod textarea;
+mod tool;
+mod usage;
+mod vector_index;
 
 pub use action::*;
+pub use attachment::*;
 pub use author::*;
 pub use backend::*;
+pub use branch::*;
+pub use cassette::*;
+pub use compare::*;
+pub use context_window::*;
 pub use editor::*;
 pub use event::*;
+pub use generation_options::*;
+pub use history::*;
 pub use loading::*;
 pub use message::*;
+pub use model_info::*;
+pub use persona::*;
+pub use profile::*;
 pub use session::*;
 pub use slash_commands::*;
 pub use textarea::*;
+pub use tool::*;
+pub use usage::*;
+pub use vector_index::*;
@@ -0,0 +1,115 @@
+#[cfg(test)]
+#[path = "model_info_test.rs"]
+mod tests;
+
+use chrono::DateTime;
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+
+/// A model available on a backend. Apart from the name, details are only set
+/// when the backend's API reports them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub name: String,
+    /// Maximum number of input tokens.
+    pub context_length: Option<u64>,
+    /// Kinds of input accepted, such as `text` and `image`.
+    pub modalities: Vec<String>,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    pub quantization: Option<String>,
+    /// Date the model was released or created, as `YYYY-MM-DD`.
+    pub created: Option<String>,
+}
+
+impl ModelInfo {
+    pub fn new(name: &str) -> ModelInfo {
+        return ModelInfo {
+            name: name.to_string(),
+            ..ModelInfo::default()
+        };
+    }
+
+    /// Formats a Unix timestamp in seconds as a created date.
+    pub fn date_from_timestamp(secs: i64) -> Option<String> {
+        return NaiveDateTime::from_timestamp_opt(secs, 0)
+            .map(|date| return date.format("%Y-%m-%d").to_string());
+    }
+
+    /// Formats an RFC 3339 timestamp as a created date.
+    pub fn date_from_rfc3339(timestamp: &str) -> Option<String> {
+        return DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|date| return date.format("%Y-%m-%d").to_string());
+    }
+
+    pub fn names(models: &[ModelInfo]) -> Vec<String> {
+        return models
+            .iter()
+            .map(|model| return model.name.to_string())
+            .collect();
+    }
+
+    /// Picks the model used when none is set, being the first model from the
+    /// `default-models` config option which is available, otherwise the first
+    /// model listed.
+    pub fn preferred(models: &[ModelInfo]) -> Option<&ModelInfo> {
+        let preferences = Config::get(ConfigKey::DefaultModels);
+        for preference in preferences.split(',').map(|e| return e.trim()) {
+            if preference.is_empty() {
+                continue;
+            }
+            if let Some(model) = models.iter().find(|model| return model.name == preference) {
+                return Some(model);
+            }
+        }
+
+        return models.first();
+    }
+
+    /// Short summary of the model's details for `/modellist`, empty when
+    /// there are none.
+    pub fn describe(&self) -> String {
+        let mut details = vec![];
+        if let Some(family) = &self.family {
+            details.push(family.to_string());
+        }
+        if let Some(parameter_size) = &self.parameter_size {
+            details.push(parameter_size.to_string());
+        }
+        if let Some(quantization) = &self.quantization {
+            details.push(quantization.to_string());
+        }
+        if let Some(context_length) = self.context_length {
+            details.push(format!("{} context", format_tokens(context_length)));
+        }
+        if !self.modalities.is_empty() {
+            details.push(self.modalities.join(" + "));
+        }
+        if let Some(created) = &self.created {
+            details.push(format!("created {created}"));
+        }
+
+        return details.join(", ");
+    }
+}
+
+/// Formats a number of tokens as `8k` or `1M` where it's exact, which is the
+/// case for most context lengths.
+fn format_tokens(tokens: u64) -> String {
+    if tokens >= 1_000_000 && tokens % 1_000_000 == 0 {
+        return format!("{}M", tokens / 1_000_000);
+    }
+    if tokens >= 1000 && tokens % 1000 == 0 {
+        return format!("{}k", tokens / 1000);
+    }
+    if tokens >= 1024 && tokens % 1024 == 0 {
+        return format!("{}k", tokens / 1024);
+    }
+
+    return tokens.to_string();
+}
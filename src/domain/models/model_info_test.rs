@@ -0,0 +1,71 @@
+use super::ModelInfo;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+
+#[test]
+fn it_describes_models() {
+    let model = ModelInfo {
+        name: "llava:7b".to_string(),
+        context_length: Some(4096),
+        modalities: vec!["text".to_string(), "image".to_string()],
+        family: Some("llama".to_string()),
+        parameter_size: Some("7B".to_string()),
+        quantization: Some("Q4_0".to_string()),
+        created: Some("2024-02-01".to_string()),
+    };
+
+    assert_eq!(
+        model.describe(),
+        "llama, 7B, Q4_0, 4k context, text + image, created 2024-02-01"
+    );
+    assert_eq!(ModelInfo::new("llava:7b").describe(), "");
+}
+
+#[test]
+fn it_formats_context_lengths() {
+    let describe = |context_length: u64| {
+        let mut model = ModelInfo::new("model");
+        model.context_length = Some(context_length);
+        return model.describe();
+    };
+
+    assert_eq!(describe(128000), "128k context");
+    assert_eq!(describe(32768), "32k context");
+    assert_eq!(describe(1048576), "1024k context");
+    assert_eq!(describe(2000000), "2M context");
+    assert_eq!(describe(4097), "4097 context");
+}
+
+#[test]
+fn it_formats_created_dates() {
+    assert_eq!(
+        ModelInfo::date_from_timestamp(1686935002),
+        Some("2023-06-16".to_string())
+    );
+    assert_eq!(
+        ModelInfo::date_from_rfc3339("2024-10-22T00:00:00Z"),
+        Some("2024-10-22".to_string())
+    );
+    assert_eq!(ModelInfo::date_from_rfc3339("yesterday"), None);
+}
+
+#[test]
+fn it_picks_preferred_models() {
+    let models = vec![
+        ModelInfo::new("codellama"),
+        ModelInfo::new("llama3"),
+        ModelInfo::new("mistral"),
+    ];
+
+    Config::set(ConfigKey::DefaultModels, "");
+    assert_eq!(ModelInfo::preferred(&models).unwrap().name, "codellama");
+
+    Config::set(ConfigKey::DefaultModels, "gpt-4o, mistral,llama3");
+    assert_eq!(ModelInfo::preferred(&models).unwrap().name, "mistral");
+
+    Config::set(ConfigKey::DefaultModels, "gpt-4o");
+    assert_eq!(ModelInfo::preferred(&models).unwrap().name, "codellama");
+    assert_eq!(ModelInfo::preferred(&[]), None);
+
+    Config::set(ConfigKey::DefaultModels, "");
+}
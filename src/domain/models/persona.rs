@@ -0,0 +1,50 @@
+#[cfg(test)]
+#[path = "persona_test.rs"]
+mod tests;
+
+use super::EditorContext;
+
+pub const DEFAULT_PERSONA: &str = "default";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Persona {
+    pub name: String,
+    pub system_prompt: String,
+}
+
+impl Default for Persona {
+    fn default() -> Persona {
+        return Persona {
+            name: DEFAULT_PERSONA.to_string(),
+            system_prompt: "Add language to any code blocks.".to_string(),
+        };
+    }
+}
+
+impl Persona {
+    pub fn new(name: &str, system_prompt: &str) -> Persona {
+        return Persona {
+            name: name.to_string(),
+            system_prompt: system_prompt.trim().to_string(),
+        };
+    }
+
+    /// Builds the system prompt sent to backends, including the coding
+    /// language and selected code from the editor when available.
+    pub fn system_prompt_with_context(&self, editor_context: &Option<EditorContext>) -> String {
+        let mut system_prompt = self.system_prompt.to_string();
+
+        if let Some(context) = editor_context {
+            let lang = &context.language;
+            let code = &context.code;
+            if !lang.is_empty() {
+                system_prompt += &format!("\nThe coding language is {lang}.");
+            }
+            if !code.is_empty() {
+                system_prompt += &format!("\nThe code is the following:\n{code}");
+            }
+        }
+
+        return system_prompt.trim().to_string();
+    }
+}
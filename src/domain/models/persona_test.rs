@@ -0,0 +1,44 @@
+use super::Persona;
+use crate::domain::models::EditorContext;
+
+#[test]
+fn it_builds_default_system_prompt() {
+    let persona = Persona::default();
+    insta::assert_snapshot!(persona.system_prompt_with_context(&None), @"Add language to any code blocks.");
+}
+
+#[test]
+fn it_builds_system_prompt_with_language() {
+    let persona = Persona::new("reviewer", "You review code.");
+    let system_prompt = persona.system_prompt_with_context(&Some(EditorContext {
+        file_path: "./test.rs".to_string(),
+        language: "rust".to_string(),
+        code: "".to_string(),
+        start_line: 0,
+        end_line: None,
+    }));
+
+    insta::assert_snapshot!(system_prompt, @r###"
+    You review code.
+    The coding language is rust.
+    "###);
+}
+
+#[test]
+fn it_builds_system_prompt_with_language_and_code() {
+    let persona = Persona::new("reviewer", "You review code.");
+    let system_prompt = persona.system_prompt_with_context(&Some(EditorContext {
+        file_path: "./test.rs".to_string(),
+        language: "rust".to_string(),
+        code: "println!(\"Test!\")".to_string(),
+        start_line: 0,
+        end_line: None,
+    }));
+
+    insta::assert_snapshot!(system_prompt, @r###"
+    You review code.
+    The coding language is rust.
+    The code is the following:
+    println!("Test!")
+    "###);
+}
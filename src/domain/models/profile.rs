@@ -0,0 +1,44 @@
+#[cfg(test)]
+#[path = "profile_test.rs"]
+mod tests;
+
+use crate::configuration::ConfigKey;
+
+/// Config keys which can't be set by a profile, as they pick the config or
+/// session themselves.
+pub const PROFILE_EXCLUDED_KEYS: [ConfigKey; 3] = [
+    ConfigKey::ConfigFile,
+    ConfigKey::Profile,
+    ConfigKey::SessionID,
+];
+
+/// A named connection, such as a backend with its URL, token, model and
+/// generation options, whose config values are applied together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub values: Vec<(ConfigKey, String)>,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Profile {
+        return Profile {
+            name: name.to_string(),
+            values: vec![],
+        };
+    }
+
+    pub fn get(&self, key: ConfigKey) -> Option<&str> {
+        return self
+            .values
+            .iter()
+            .find(|(profile_key, _)| return *profile_key == key)
+            .map(|(_, val)| return val.as_str());
+    }
+
+    pub fn set(&mut self, key: ConfigKey, value: &str) {
+        self.values
+            .retain(|(profile_key, _)| return *profile_key != key);
+        self.values.push((key, value.to_string()));
+    }
+}
@@ -0,0 +1,15 @@
+use super::Profile;
+use crate::configuration::ConfigKey;
+
+#[test]
+fn it_sets_values() {
+    let mut profile = Profile::new("work-gpt");
+    profile.set(ConfigKey::Backend, "openai");
+    profile.set(ConfigKey::Model, "gpt-4o");
+    profile.set(ConfigKey::Model, "gpt-4o-mini");
+
+    assert_eq!(profile.get(ConfigKey::Backend), Some("openai"));
+    assert_eq!(profile.get(ConfigKey::Model), Some("gpt-4o-mini"));
+    assert_eq!(profile.get(ConfigKey::OpenAiURL), None);
+    assert_eq!(profile.values.len(), 2);
+}
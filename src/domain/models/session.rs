@@ -1,14 +1,23 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::GenerationOptions;
 use super::Message;
+use super::UsageByModel;
 
 #[derive(Serialize, Deserialize)]
 pub struct State {
     pub backend_name: String,
     pub backend_model: String,
-    pub backend_context: String,
     pub editor_language: String,
+    #[serde(default)]
+    pub persona: String,
+    #[serde(default)]
+    pub profile: String,
+    #[serde(default)]
+    pub generation_options: GenerationOptions,
+    #[serde(default)]
+    pub usage: UsageByModel,
     pub messages: Vec<Message>,
 }
 
@@ -24,10 +24,22 @@ impl SlashCommand {
         if cmd.is_quit()
             || cmd.is_model_list()
             || cmd.is_model_set()
+            || cmd.is_backend_set()
             || cmd.is_append_code_block()
             || cmd.is_replace_code_block()
             || cmd.is_copy_code_block()
             || cmd.is_copy_chat()
+            || cmd.is_persona()
+            || cmd.is_profile()
+            || cmd.is_attach()
+            || cmd.is_set()
+            || cmd.is_usage()
+            || cmd.is_pin()
+            || cmd.is_compact()
+            || cmd.is_continue()
+            || cmd.is_compare()
+            || cmd.is_keep()
+            || cmd.is_rag()
             || cmd.is_help()
         {
             return Some(cmd);
@@ -48,6 +60,10 @@ impl SlashCommand {
         return ["/m", "/model"].contains(&self.command.as_str());
     }
 
+    pub fn is_backend_set(&self) -> bool {
+        return ["/b", "/backend"].contains(&self.command.as_str());
+    }
+
     pub fn is_append_code_block(&self) -> bool {
         return ["/a", "/append"].contains(&self.command.as_str());
     }
@@ -64,6 +80,50 @@ impl SlashCommand {
         return ["/c", "/copy"].contains(&self.command.as_str()) && self.args.is_empty();
     }
 
+    pub fn is_persona(&self) -> bool {
+        return ["/p", "/persona"].contains(&self.command.as_str());
+    }
+
+    pub fn is_profile(&self) -> bool {
+        return ["/pr", "/profile"].contains(&self.command.as_str());
+    }
+
+    pub fn is_attach(&self) -> bool {
+        return ["/at", "/attach"].contains(&self.command.as_str());
+    }
+
+    pub fn is_set(&self) -> bool {
+        return ["/s", "/set"].contains(&self.command.as_str());
+    }
+
+    pub fn is_usage(&self) -> bool {
+        return ["/u", "/usage"].contains(&self.command.as_str());
+    }
+
+    pub fn is_pin(&self) -> bool {
+        return ["/pin"].contains(&self.command.as_str());
+    }
+
+    pub fn is_compact(&self) -> bool {
+        return ["/compact"].contains(&self.command.as_str());
+    }
+
+    pub fn is_continue(&self) -> bool {
+        return ["/continue"].contains(&self.command.as_str());
+    }
+
+    pub fn is_compare(&self) -> bool {
+        return ["/compare"].contains(&self.command.as_str());
+    }
+
+    pub fn is_keep(&self) -> bool {
+        return ["/keep"].contains(&self.command.as_str());
+    }
+
+    pub fn is_rag(&self) -> bool {
+        return ["/rag"].contains(&self.command.as_str());
+    }
+
     pub fn is_help(&self) -> bool {
         return ["/h", "/help"].contains(&self.command.as_str());
     }
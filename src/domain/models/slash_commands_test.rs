@@ -86,6 +86,27 @@ fn it_is_not_is_model_set() {
     assert!(!cmd.is_model_set());
 }
 
+#[test]
+fn it_is_short_backend_set() {
+    let cmd = SlashCommand::parse("/b openai").unwrap();
+    assert!(cmd.is_backend_set());
+    assert_eq!(cmd.args, vec!["openai".to_string()]);
+}
+#[test]
+fn it_is_backend_set() {
+    let cmd = SlashCommand::parse("/backend claude claude-2.1").unwrap();
+    assert!(cmd.is_backend_set());
+    assert_eq!(
+        cmd.args,
+        vec!["claude".to_string(), "claude-2.1".to_string()]
+    );
+}
+#[test]
+fn it_is_not_backend_set() {
+    let cmd = SlashCommand::parse("/ml").unwrap();
+    assert!(!cmd.is_backend_set());
+}
+
 #[test]
 fn it_is_short_append_code_block() {
     let cmd = SlashCommand::parse("/a").unwrap();
@@ -134,6 +155,62 @@ fn it_is_not_help() {
     assert!(!cmd.is_help());
 }
 
+#[test]
+fn it_is_short_persona() {
+    let cmd = SlashCommand::parse("/p reviewer").unwrap();
+    assert!(cmd.is_persona());
+    assert_eq!(cmd.args, vec!["reviewer".to_string()]);
+}
+
+#[test]
+fn it_is_persona() {
+    let cmd = SlashCommand::parse("/persona").unwrap();
+    assert!(cmd.is_persona());
+}
+
+#[test]
+fn it_is_not_persona() {
+    let cmd = SlashCommand::parse("/ml").unwrap();
+    assert!(!cmd.is_persona());
+}
+
+#[test]
+fn it_is_short_set() {
+    let cmd = SlashCommand::parse("/s temperature 0.2").unwrap();
+    assert!(cmd.is_set());
+    assert_eq!(cmd.args, vec!["temperature".to_string(), "0.2".to_string()]);
+}
+
+#[test]
+fn it_is_set() {
+    let cmd = SlashCommand::parse("/set").unwrap();
+    assert!(cmd.is_set());
+}
+
+#[test]
+fn it_is_not_set() {
+    let cmd = SlashCommand::parse("/ml").unwrap();
+    assert!(!cmd.is_set());
+}
+
+#[test]
+fn it_is_short_usage() {
+    let cmd = SlashCommand::parse("/u").unwrap();
+    assert!(cmd.is_usage());
+}
+
+#[test]
+fn it_is_usage() {
+    let cmd = SlashCommand::parse("/usage").unwrap();
+    assert!(cmd.is_usage());
+}
+
+#[test]
+fn it_is_not_usage() {
+    let cmd = SlashCommand::parse("/ml").unwrap();
+    assert!(!cmd.is_usage());
+}
+
 #[test]
 fn it_is_short_copy_chat() {
     let cmd = SlashCommand::parse("/c").unwrap();
@@ -165,3 +242,52 @@ fn it_is_not_copy_code() {
     let cmd = SlashCommand::parse("/copy").unwrap();
     assert!(!cmd.is_copy_code_block());
 }
+
+#[test]
+fn it_is_short_attach() {
+    let cmd = SlashCommand::parse("/at ./cat.png").unwrap();
+    assert!(cmd.is_attach());
+    assert_eq!(cmd.args, vec!["./cat.png"]);
+}
+#[test]
+fn it_is_attach() {
+    let cmd = SlashCommand::parse("/attach").unwrap();
+    assert!(cmd.is_attach());
+}
+
+#[test]
+fn it_is_compare() {
+    let cmd = SlashCommand::parse("/compare openai:gpt-4o,ollama:llama3").unwrap();
+    assert!(cmd.is_compare());
+    assert_eq!(cmd.args, vec!["openai:gpt-4o,ollama:llama3"]);
+}
+#[test]
+fn it_is_keep() {
+    let cmd = SlashCommand::parse("/keep 2").unwrap();
+    assert!(cmd.is_keep());
+    assert_eq!(cmd.args, vec!["2"]);
+}
+
+#[test]
+fn it_is_profile() {
+    let cmd = SlashCommand::parse("/profile work-gpt").unwrap();
+    assert!(cmd.is_profile());
+    assert_eq!(cmd.args, vec!["work-gpt"]);
+
+    let cmd = SlashCommand::parse("/pr").unwrap();
+    assert!(cmd.is_profile());
+}
+
+#[test]
+fn it_is_continue() {
+    let cmd = SlashCommand::parse("/continue").unwrap();
+    assert!(cmd.is_continue());
+    assert!(!cmd.is_copy_chat());
+}
+
+#[test]
+fn it_is_rag() {
+    let cmd = SlashCommand::parse("/rag on ./src").unwrap();
+    assert!(cmd.is_rag());
+    assert_eq!(cmd.args, vec!["on", "./src"]);
+}
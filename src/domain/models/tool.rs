@@ -0,0 +1,218 @@
+#[cfg(test)]
+#[path = "tool_test.rs"]
+mod tests;
+
+use anyhow::bail;
+use anyhow::Result;
+use serde_json::json;
+use serde_json::Value;
+use strum::EnumIter;
+use strum::EnumVariantNames;
+use strum::IntoEnumIterator;
+use strum::VariantNames;
+use tokio::sync::oneshot;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+
+/// Local tools a model may call during a completion. Every call is approved by
+/// the user before it runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, EnumVariantNames, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum ToolName {
+    ReadFile,
+    ListDirectory,
+    Grep,
+    RunTests,
+}
+
+impl ToolName {
+    pub fn parse(text: &str) -> Option<ToolName> {
+        let text = text.trim().to_lowercase().replace('-', "_");
+        return ToolName::iter().find(|e| return e.to_string() == text);
+    }
+
+    /// Tools enabled with the `tools` config option. `run_tests` is only
+    /// enabled when a `tools-test-command` is set.
+    pub fn from_config() -> Result<Vec<ToolName>> {
+        let mut names = vec![];
+        for name in Config::get(ConfigKey::Tools).split(',') {
+            if name.trim().is_empty() {
+                continue;
+            }
+
+            let tool_name = match ToolName::parse(name) {
+                Some(tool_name) => tool_name,
+                None => {
+                    bail!(format!(
+                        "{name} is not a tool. Possible values are: {}",
+                        ToolName::VARIANTS.join(", ")
+                    ))
+                }
+            };
+
+            if tool_name == ToolName::RunTests
+                && Config::get(ConfigKey::ToolsTestCommand).is_empty()
+            {
+                continue;
+            }
+            if !names.contains(&tool_name) {
+                names.push(tool_name);
+            }
+        }
+
+        return Ok(names);
+    }
+
+    pub fn description(&self) -> &'static str {
+        return match self {
+            ToolName::ReadFile => "Reads a text file from the user's workspace.",
+            ToolName::ListDirectory => {
+                "Lists the files and directories in a directory of the user's workspace. Directories end with a slash."
+            }
+            ToolName::Grep => {
+                "Searches the text files of the user's workspace for lines matching a regular expression."
+            }
+            ToolName::RunTests => {
+                "Runs the test command configured for the user's workspace, returning its exit code and output."
+            }
+        };
+    }
+
+    /// JSON schema of the tool's arguments.
+    pub fn parameters(&self) -> Value {
+        return match self {
+            ToolName::ReadFile => {
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {"type": "string", "description": "Path of the file, relative to the workspace root."}
+                    },
+                    "required": ["path"]
+                })
+            }
+            ToolName::ListDirectory => {
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {"type": "string", "description": "Path of the directory, relative to the workspace root. Defaults to the workspace root."}
+                    }
+                })
+            }
+            ToolName::Grep => {
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "pattern": {"type": "string", "description": "Regular expression to search for."},
+                        "path": {"type": "string", "description": "File or directory to search, relative to the workspace root. Defaults to the workspace root."}
+                    },
+                    "required": ["pattern"]
+                })
+            }
+            ToolName::RunTests => {
+                json!({
+                    "type": "object",
+                    "properties": {}
+                })
+            }
+        };
+    }
+}
+
+/// A request from a model to run a tool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    pub fn new(id: &str, name: &str, arguments: Value) -> ToolCall {
+        return ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            arguments,
+        };
+    }
+
+    /// Reads a tool call's arguments from the JSON string sent by a backend.
+    /// Invalid JSON is kept as a string so the tool can report the error back
+    /// to the model.
+    pub fn parse_arguments(arguments: &str) -> Value {
+        if arguments.trim().is_empty() {
+            return json!({});
+        }
+
+        return serde_json::from_str(arguments)
+            .unwrap_or_else(|_| return Value::String(arguments.to_string()));
+    }
+
+    /// Optional string argument of the call.
+    pub fn argument(&self, name: &str) -> Option<String> {
+        return self
+            .arguments
+            .get(name)
+            .and_then(|value| return value.as_str())
+            .map(|value| return value.to_string());
+    }
+
+    /// Human readable form of the call shown when asking for approval, such
+    /// as `read_file(path: "src/main.rs")`.
+    pub fn summary(&self) -> String {
+        let arguments = match &self.arguments {
+            Value::Object(map) => {
+                map.iter()
+                    .map(|(key, value)| return format!("{key}: {value}"))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            }
+            Value::Null => "".to_string(),
+            value => value.to_string(),
+        };
+
+        return format!("{}({arguments})", self.name);
+    }
+}
+
+/// Output of a tool call, sent back to the model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToolResult {
+    pub content: String,
+    pub is_error: bool,
+}
+
+impl ToolResult {
+    pub fn new(content: &str) -> ToolResult {
+        return ToolResult {
+            content: content.to_string(),
+            is_error: false,
+        };
+    }
+
+    pub fn error(content: &str) -> ToolResult {
+        return ToolResult {
+            content: content.to_string(),
+            is_error: true,
+        };
+    }
+}
+
+/// A tool call waiting on the user to approve or deny it.
+#[derive(Debug)]
+pub struct ToolApproval {
+    pub call: ToolCall,
+    responder: oneshot::Sender<bool>,
+}
+
+impl ToolApproval {
+    pub fn new(call: ToolCall) -> (ToolApproval, oneshot::Receiver<bool>) {
+        let (responder, rx) = oneshot::channel();
+        return (ToolApproval { call, responder }, rx);
+    }
+
+    pub fn respond(self, approved: bool) {
+        // The completion may have been cancelled while waiting.
+        let _ = self.responder.send(approved);
+    }
+}
@@ -0,0 +1,65 @@
+use serde_json::json;
+
+use super::ToolApproval;
+use super::ToolCall;
+use super::ToolName;
+
+#[test]
+fn it_parses_tool_names() {
+    assert_eq!(ToolName::parse("read_file"), Some(ToolName::ReadFile));
+    assert_eq!(
+        ToolName::parse("list-directory"),
+        Some(ToolName::ListDirectory)
+    );
+    assert_eq!(ToolName::parse(" GREP "), Some(ToolName::Grep));
+    assert_eq!(ToolName::parse("rm_rf"), None);
+}
+
+#[test]
+fn it_provides_object_schemas_for_all_tools() {
+    for tool_name in [
+        ToolName::ReadFile,
+        ToolName::ListDirectory,
+        ToolName::Grep,
+        ToolName::RunTests,
+    ] {
+        assert_eq!(tool_name.parameters()["type"], "object");
+        assert!(!tool_name.description().is_empty());
+    }
+}
+
+#[test]
+fn it_parses_arguments() {
+    assert_eq!(
+        ToolCall::parse_arguments(r#"{"path": "src/main.rs"}"#),
+        json!({"path": "src/main.rs"})
+    );
+    assert_eq!(ToolCall::parse_arguments(""), json!({}));
+    assert_eq!(ToolCall::parse_arguments("{\"path"), json!("{\"path"));
+}
+
+#[test]
+fn it_reads_string_arguments() {
+    let call = ToolCall::new("1", "grep", json!({"pattern": "fn main", "max": 3}));
+
+    assert_eq!(call.argument("pattern"), Some("fn main".to_string()));
+    assert_eq!(call.argument("max"), None);
+    assert_eq!(call.argument("path"), None);
+}
+
+#[test]
+fn it_summarizes_calls() {
+    let call = ToolCall::new("1", "read_file", json!({"path": "src/main.rs"}));
+    assert_eq!(call.summary(), r#"read_file(path: "src/main.rs")"#);
+
+    let call = ToolCall::new("2", "run_tests", json!({}));
+    assert_eq!(call.summary(), "run_tests()");
+}
+
+#[tokio::test]
+async fn it_responds_to_approvals() {
+    let (approval, rx) = ToolApproval::new(ToolCall::new("1", "run_tests", json!({})));
+    approval.respond(true);
+
+    assert!(rx.await.unwrap());
+}
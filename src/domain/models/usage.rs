@@ -0,0 +1,75 @@
+#[cfg(test)]
+#[path = "usage_test.rs"]
+mod tests;
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::configuration::Config;
+
+/// Token counts reported by a backend for a completion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Price of a model in USD per million tokens, as set in the `prices` table
+/// of the config file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// Running token usage for a session, keyed by model name.
+pub type UsageByModel = BTreeMap<String, Usage>;
+
+impl Usage {
+    pub fn new(prompt_tokens: u64, completion_tokens: u64) -> Usage {
+        return Usage {
+            prompt_tokens,
+            completion_tokens,
+        };
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        return self.prompt_tokens + self.completion_tokens;
+    }
+
+    pub fn add(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+
+    pub fn cost(&self, price: &ModelPrice) -> f64 {
+        return (self.prompt_tokens as f64 * price.input
+            + self.completion_tokens as f64 * price.output)
+            / 1_000_000.0;
+    }
+
+    /// Sums the usage across all models.
+    pub fn total(usage: &UsageByModel) -> Usage {
+        let mut total = Usage::default();
+        for model_usage in usage.values() {
+            total.add(model_usage);
+        }
+
+        return total;
+    }
+
+    /// Estimates the cost of all usage for models with a configured price.
+    /// Returns `None` when none of the models have a price.
+    pub fn estimated_cost(usage: &UsageByModel) -> Option<f64> {
+        let mut cost: Option<f64> = None;
+        for (model, model_usage) in usage.iter() {
+            if let Some(price) = Config::get_price(model) {
+                cost = Some(cost.unwrap_or_default() + model_usage.cost(&price));
+            }
+        }
+
+        return cost;
+    }
+}
@@ -0,0 +1,61 @@
+use super::ModelPrice;
+use super::Usage;
+use super::UsageByModel;
+use crate::configuration::Config;
+
+#[test]
+fn it_adds_usage() {
+    let mut usage = Usage::new(10, 5);
+    usage.add(&Usage::new(3, 2));
+
+    assert_eq!(usage, Usage::new(13, 7));
+    assert_eq!(usage.total_tokens(), 20);
+}
+
+#[test]
+fn it_calculates_cost() {
+    let usage = Usage::new(1_000_000, 500_000);
+    let price = ModelPrice {
+        input: 2.5,
+        output: 10.0,
+    };
+
+    assert_eq!(usage.cost(&price), 7.5);
+}
+
+#[test]
+fn it_totals_usage_by_model() {
+    let mut usage = UsageByModel::new();
+    usage.insert("model-1".to_string(), Usage::new(10, 5));
+    usage.insert("model-2".to_string(), Usage::new(20, 10));
+
+    assert_eq!(Usage::total(&usage), Usage::new(30, 15));
+}
+
+#[test]
+fn it_estimates_cost_for_priced_models() {
+    Config::set_price(
+        "usage-test-priced",
+        ModelPrice {
+            input: 1.0,
+            output: 2.0,
+        },
+    );
+
+    let mut usage = UsageByModel::new();
+    usage.insert(
+        "usage-test-priced".to_string(),
+        Usage::new(1_000_000, 1_000_000),
+    );
+    usage.insert("usage-test-unpriced".to_string(), Usage::new(1_000_000, 0));
+
+    assert_eq!(Usage::estimated_cost(&usage), Some(3.0));
+}
+
+#[test]
+fn it_does_not_estimate_cost_without_prices() {
+    let mut usage = UsageByModel::new();
+    usage.insert("usage-test-unpriced".to_string(), Usage::new(1_000_000, 0));
+
+    assert_eq!(Usage::estimated_cost(&usage), None);
+}
@@ -0,0 +1,135 @@
+#[cfg(test)]
+#[path = "vector_index_test.rs"]
+mod tests;
+
+use std::cmp::Ordering;
+
+use itertools::Itertools;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Lines in each chunk of a file.
+pub const CHUNK_LINES: usize = 40;
+
+/// Lines each chunk shares with the one before it, so code near the edge of a
+/// chunk keeps some of its context.
+pub const CHUNK_OVERLAP_LINES: usize = 10;
+
+/// A range of lines from a file in an indexed project, with its embedding.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndexChunk {
+    /// Path of the file, relative to the root of the project.
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+impl IndexChunk {
+    /// Splits a file into overlapping chunks of lines, without embeddings.
+    /// Blank chunks are left out.
+    pub fn split(path: &str, text: &str) -> Vec<IndexChunk> {
+        let lines = text.lines().collect::<Vec<&str>>();
+        let mut chunks = vec![];
+        let mut start = 0;
+        while start < lines.len() {
+            let end = (start + CHUNK_LINES).min(lines.len());
+            let chunk_text = lines[start..end].join("\n");
+            if !chunk_text.trim().is_empty() {
+                chunks.push(IndexChunk {
+                    path: path.to_string(),
+                    start_line: start + 1,
+                    end_line: end,
+                    text: chunk_text,
+                    embedding: vec![],
+                });
+            }
+            if end == lines.len() {
+                break;
+            }
+            start = end - CHUNK_OVERLAP_LINES;
+        }
+
+        return chunks;
+    }
+
+    /// Where the chunk comes from, such as `src/main.rs:1-40`.
+    pub fn citation(&self) -> String {
+        return format!("{}:{}-{}", self.path, self.start_line, self.end_line);
+    }
+
+    pub fn to_prompt_text(&self) -> String {
+        return format!("File: {}\n```\n{}\n```", self.citation(), self.text);
+    }
+
+    /// Cosine similarity between the chunk and another embedding.
+    fn similarity(&self, embedding: &[f32]) -> f32 {
+        let dot: f32 = self
+            .embedding
+            .iter()
+            .zip(embedding)
+            .map(|(a, b)| return a * b)
+            .sum();
+        let norm = |vector: &[f32]| return vector.iter().map(|e| return e * e).sum::<f32>().sqrt();
+        let norms = norm(&self.embedding) * norm(embedding);
+        if norms == 0.0 {
+            return 0.0;
+        }
+
+        return dot / norms;
+    }
+}
+
+/// Embeddings of every text file in a project, for finding the parts of it
+/// which are relevant to a prompt.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VectorIndex {
+    /// Absolute path of the project.
+    pub root: String,
+    pub backend: String,
+    pub model: String,
+    pub chunks: Vec<IndexChunk>,
+}
+
+impl VectorIndex {
+    /// Returns the chunks most similar to an embedding, most similar first.
+    pub fn search(&self, embedding: &[f32], top_k: usize) -> Vec<&IndexChunk> {
+        return self
+            .chunks
+            .iter()
+            .map(|chunk| return (chunk.similarity(embedding), chunk))
+            .sorted_by(|(a, _), (b, _)| return b.partial_cmp(a).unwrap_or(Ordering::Equal))
+            .take(top_k)
+            .map(|(_, chunk)| return chunk)
+            .collect();
+    }
+
+    pub fn files_len(&self) -> usize {
+        return self
+            .chunks
+            .iter()
+            .map(|chunk| return &chunk.path)
+            .unique()
+            .count();
+    }
+
+    /// Instructions for the model with the chunks retrieved for a prompt, sent
+    /// as part of the system prompt.
+    pub fn to_prompt_text(&self, chunks: &[&IndexChunk]) -> String {
+        let excerpts = chunks
+            .iter()
+            .map(|chunk| return chunk.to_prompt_text())
+            .collect::<Vec<String>>()
+            .join("\n\n");
+
+        return format!(
+            "The following excerpts from the project at {} may be relevant to the next message. When you use one, cite it by its file and lines, such as `{}`.\n\n{excerpts}",
+            self.root,
+            chunks
+                .first()
+                .map(|chunk| return chunk.citation())
+                .unwrap_or_default()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use super::IndexChunk;
+use super::VectorIndex;
+
+fn chunk(path: &str, embedding: Vec<f32>) -> IndexChunk {
+    return IndexChunk {
+        path: path.to_string(),
+        start_line: 1,
+        end_line: 2,
+        text: "fn main() {}".to_string(),
+        embedding,
+    };
+}
+
+#[test]
+fn it_splits_files_into_overlapping_chunks() {
+    let text = (1..=75)
+        .map(|line| return format!("line {line}"))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let chunks = IndexChunk::split("src/main.rs", &text);
+
+    assert_eq!(
+        chunks
+            .iter()
+            .map(|chunk| return chunk.citation())
+            .collect::<Vec<String>>(),
+        vec!["src/main.rs:1-40", "src/main.rs:31-70", "src/main.rs:61-75"]
+    );
+    assert!(chunks[1].text.starts_with("line 31\n"));
+    assert!(chunks[1].text.ends_with("\nline 70"));
+}
+
+#[test]
+fn it_skips_blank_chunks() {
+    assert_eq!(IndexChunk::split("empty.txt", "\n\n  \n"), vec![]);
+    assert_eq!(IndexChunk::split("empty.txt", ""), vec![]);
+}
+
+#[test]
+fn it_formats_chunks_for_prompts() {
+    insta::assert_snapshot!(chunk("src/main.rs", vec![]).to_prompt_text(), @r###"
+    File: src/main.rs:1-2
+    ```
+    fn main() {}
+    ```
+    "###);
+}
+
+#[test]
+fn it_searches_most_similar_chunks_first() {
+    let index = VectorIndex {
+        root: "/tmp/project".to_string(),
+        backend: "mock".to_string(),
+        model: "mock".to_string(),
+        chunks: vec![
+            chunk("a.rs", vec![1.0, 0.0]),
+            chunk("b.rs", vec![0.0, 1.0]),
+            chunk("c.rs", vec![1.0, 1.0]),
+            chunk("d.rs", vec![0.0, 0.0]),
+        ],
+    };
+
+    let paths = index
+        .search(&[0.0, 2.0], 2)
+        .iter()
+        .map(|chunk| return chunk.path.to_string())
+        .collect::<Vec<String>>();
+
+    assert_eq!(paths, vec!["b.rs", "c.rs"]);
+    assert_eq!(index.files_len(), 4);
+}
+
+#[test]
+fn it_formats_retrieved_chunks_with_citations() {
+    let index = VectorIndex {
+        root: "/tmp/project".to_string(),
+        backend: "mock".to_string(),
+        model: "mock".to_string(),
+        chunks: vec![chunk("src/main.rs", vec![1.0])],
+    };
+    let chunks = index.search(&[1.0], 5);
+
+    insta::assert_snapshot!(index.to_prompt_text(&chunks), @r###"
+    The following excerpts from the project at /tmp/project may be relevant to the next message. When you use one, cite it by its file and lines, such as `src/main.rs:1-2`.
+
+    File: src/main.rs:1-2
+    ```
+    fn main() {}
+    ```
+    "###);
+}
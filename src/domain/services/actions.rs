@@ -1,33 +1,66 @@
+use std::path;
 use std::sync::Arc;
 
+use anyhow::bail;
 use anyhow::Result;
+use strum::IntoEnumIterator;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 use super::clipboard::ClipboardService;
+use super::Indexes;
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
 use crate::domain::models::AcceptType;
 use crate::domain::models::Action;
 use crate::domain::models::Author;
 use crate::domain::models::BackendBox;
+use crate::domain::models::BackendName;
 use crate::domain::models::BackendPrompt;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::CompareTarget;
+use crate::domain::models::ContextStrategy;
+use crate::domain::models::ContextWindow;
 use crate::domain::models::EditorContext;
 use crate::domain::models::EditorName;
 use crate::domain::models::Event;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::HistoryMessage;
 use crate::domain::models::Message;
 use crate::domain::models::MessageType;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::Profile;
 use crate::domain::models::SlashCommand;
+use crate::domain::models::VectorIndex;
+use crate::infrastructure::backends::recorder::Recorder;
+use crate::infrastructure::backends::BackendManager;
 use crate::infrastructure::editors::EditorManager;
 
+const SUMMARY_INSTRUCTIONS: &str = "Summarize our conversation so far into a compact note which will replace it. Keep the facts, decisions, open questions, code, and file names which are still relevant. Reply with only the summary.";
+
+const CONTINUE_INSTRUCTIONS: &str = "Your last response was cut off. Continue it from exactly where it stopped, without repeating anything or adding an introduction.";
+
 pub fn help_text() -> String {
     let text = r#"
 COMMANDS:
-- /modellist (/ml) - Lists all available models from the backend.
+- /modellist (/ml) - Lists all available models from the backend, with details such as context length when the backend provides them.
 - /model (/model) [MODEL_NAME,MODEL_INDEX] - Sets the specified model as the active model. You can pass either the model name, or the index from `/modellist`.
+- /backend (/b) [BACKEND_NAME] [MODEL_NAME?] - Switches to the specified backend, keeping the conversation. Uses the preferred model from the default models, or the first model available from the backend, when no model is provided.
 - /append (/a) [CODE_BLOCK_NUMBER?] - Appends code blocks to an editor. See Code Actions for more details.
 - /replace (/r) [CODE_BLOCK_NUMBER?] - Replaces selections with code blocks in an editor. See Code Actions for more details.
 - /copy (/c) [CODE_BLOCK_NUMBER?] - Copies the entire chat history to your clipboard. When a `CODE_BLOCK_NUMBER` is used, only the specified copy blocks are copied to clipboard. See Code Actions for more details.
+- /persona (/p) [PERSONA_NAME?] - Switches the system prompt to the specified persona from your config file. Lists all available personas when no name is provided.
+- /profile (/pr) [PROFILE_NAME?] - Switches to the specified profile from your config file, with its backend, model, endpoint, credentials and generation options, keeping the conversation. Lists all available profiles when no name is provided.
+- /attach (/at) [PATH?] - Attaches an image or text file to your next message. Dropping a file on to the terminal does the same. Lists the pending attachments when no path is provided.
+- /set (/s) [OPTION_NAME?] [VALUE?] - Sets a generation option (temperature, max-tokens, top-p, stop) for the following prompts. Omitting the value resets the option to the backend default, and omitting both lists the current options.
+- /usage (/u) - Shows the tokens used by the session for each model, and the estimated cost when prices are set in your config file.
+- /pin - Pins or unpins the last message, so it's always sent to the model when the conversation no longer fits its context window.
+- /compact - Asks the model to summarize the conversation. Only the summary and pinned messages are sent to the model afterwards.
+- /continue - Asks the model to carry on from where its last response stopped, such as when it was truncated by the max tokens limit.
+- /compare [BACKEND:MODEL,BACKEND:MODEL...?] - Starts compare mode, sending your next message to each model and showing their answers side by side. Use `off` to stop comparing, or omit the models to show the ones being compared.
+- /keep [ANSWER_NUMBER] - Keeps an answer from compare mode, continuing the conversation with the model which wrote it.
+- /rag [on,off?] [PATH?] - Adds the parts of a project most relevant to each message to the context, citing their files and lines. The project, or the current directory when no path is provided, must be indexed with `oatmeal index` first. Shows whether RAG is on when no argument is provided.
 - /quit /exit (/q) - Exit Oatmeal.
 - /help (/h) - Provides this help menu.
 
@@ -38,7 +71,12 @@ HOTKEYS:
 - CTRL+D - Page down.
 - CTRL+C - Interrupt waiting for prompt response if in progress, otherwise exit.
 - CTRL+O - Insert a line break at the cursor position.
-- CTRL+R - Resubmit your last message to the backend.
+- CTRL+R - Regenerate the last response, keeping the previous responses as alternates.
+- CTRL+T - Expand or collapse the reasoning of models which think before answering. Reasoning is never copied or numbered as code blocks.
+- ALT+← / ALT+→ - Cycle through the responses of the last regenerated message. The shown response is the one kept in the conversation. When a message is selected, switches between its branches instead.
+- ALT+↑ / ALT+↓ - Select one of your earlier messages. ALT+↓ past the last message, or ESC, leaves selection mode.
+- Enter (with a message selected) - Loads the selected message in to the input to edit it. Sending it forks the conversation from that message, keeping the later messages as a branch.
+- Y / N - Allow or deny a tool call requested by the model.
 
 CODE ACTIONS:
 When working with models that provide code, and using an editor integration, Oatmeal has the capabilities to read selected code from an editor, and submit model provided code back in to an editor. Each code block provided by a model is indexed with a (NUMBER) at the beginning of the block to make it easily identifiable.
@@ -59,14 +97,19 @@ The `CODE_BLOCK_NUMBER` allows you to select several code blocks to send back to
 
 async fn model_list(backend: &BackendBox, tx: &mpsc::UnboundedSender<Event>) -> Result<()> {
     let mut models = backend.list_models().await?;
-    models.sort();
+    models.sort_by(|a, b| return a.name.cmp(&b.name));
 
     let res = models
         .iter()
         .enumerate()
         .map(|(idx, model)| {
             let n = idx + 1;
-            return format!("- ({n}) {model}");
+            let name = &model.name;
+            let details = model.describe();
+            if details.is_empty() {
+                return format!("- ({n}) {name}");
+            }
+            return format!("- ({n}) {name} ({details})");
         })
         .collect::<Vec<String>>();
 
@@ -95,7 +138,7 @@ async fn model_set(
     }
 
     let mut models = backend.list_models().await?;
-    models.sort();
+    models.sort_by(|a, b| return a.name.cmp(&b.name));
 
     if let Ok(idx) = model_name.parse::<usize>() {
         if idx < 1 || idx > models.len() {
@@ -107,10 +150,10 @@ async fn model_set(
             tx.send(Event::BackendMessage(msg))?;
             return Ok(());
         }
-        model_name = models[idx - 1].to_string();
+        model_name = models[idx - 1].name.to_string();
     }
 
-    if !models.contains(&model_name) {
+    if !ModelInfo::names(&models).contains(&model_name) {
         let backend_name = Config::get(ConfigKey::Backend);
         let msg = Message::new_with_type(
             Author::Oatmeal,
@@ -133,6 +176,228 @@ async fn model_set(
     return Ok(());
 }
 
+async fn backend_set(
+    backend_arc: &mut Arc<BackendBox>,
+    tx: &mpsc::UnboundedSender<Event>,
+    args: &[String],
+) -> Result<()> {
+    let backend_names = BackendName::iter()
+        .map(|name| return name.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    if args.is_empty() {
+        let msg = Message::new_with_type(
+            Author::Oatmeal,
+            MessageType::Error,
+            &format!("You must specify a backend name with `/backend` or `/b`. Possible values are: {backend_names}"),
+        );
+        tx.send(Event::BackendMessage(msg))?;
+        return Ok(());
+    }
+
+    let backend_name = match BackendName::parse(args[0].to_string()) {
+        Some(name) => name,
+        None => {
+            let msg = Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                &format!(
+                    "No backend named {} exists. Possible values are: {backend_names}",
+                    args[0]
+                ),
+            );
+            tx.send(Event::BackendMessage(msg))?;
+            return Ok(());
+        }
+    };
+
+    // Tokens read from files and commands can fail to resolve.
+    let backend = match BackendManager::get(backend_name.clone()).await {
+        Ok(backend) => backend,
+        Err(err) => {
+            let msg = Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                &format!("Failed to set up backend {backend_name}, staying on the current backend.\n\nError: {err}"),
+            );
+            tx.send(Event::BackendMessage(msg))?;
+            return Ok(());
+        }
+    };
+    if let Err(err) = backend.health_check().await {
+        let msg = Message::new_with_type(
+            Author::Oatmeal,
+            MessageType::Error,
+            &format!("Backend {backend_name} isn't reachable, staying on the current backend.\n\nError: {err}"),
+        );
+        tx.send(Event::BackendMessage(msg))?;
+        return Ok(());
+    }
+
+    let models = match backend.list_models().await {
+        Ok(models) => models,
+        Err(err) => {
+            let msg = Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                &format!("Failed to list the models of backend {backend_name}, staying on the current backend.\n\nError: {err}"),
+            );
+            tx.send(Event::BackendMessage(msg))?;
+            return Ok(());
+        }
+    };
+    let model_name = match args.get(1) {
+        Some(model_name) => model_name.to_string(),
+        None => {
+            ModelInfo::preferred(&models)
+                .map(|model| return model.name.to_string())
+                .unwrap_or_default()
+        }
+    };
+    if !ModelInfo::names(&models).contains(&model_name) {
+        let msg = Message::new_with_type(
+            Author::Oatmeal,
+            MessageType::Error,
+            &format!(
+                "No model named {model_name} found in backend {backend_name}. Did you mistype it?"
+            ),
+        );
+        tx.send(Event::BackendMessage(msg))?;
+        return Ok(());
+    }
+
+    Config::set(ConfigKey::Backend, &backend_name.to_string());
+    Config::set(ConfigKey::Model, &model_name);
+    *backend_arc = Arc::new(backend);
+
+    tx.send(Event::BackendMessage(Message::new(
+        Author::Model,
+        &format!("{model_name} has entered the chat from {backend_name}."),
+    )))?;
+
+    return Ok(());
+}
+
+/// Builds the backend of the applied profile, and picks its model.
+async fn profile_backend(profile: &Profile) -> Result<(BackendBox, String)> {
+    if let Err(err) = GenerationOptions::from_config() {
+        bail!(format!("Invalid generation option: {err}"));
+    }
+
+    let backend_name = BackendName::parse(Config::get(ConfigKey::Backend)).unwrap();
+    let backend = match BackendManager::get(backend_name.clone()).await {
+        Ok(backend) => backend,
+        Err(err) => {
+            bail!(format!(
+                "Failed to set up backend {backend_name}, staying on the current profile.\n\nError: {err}"
+            ));
+        }
+    };
+    if let Err(err) = backend.health_check().await {
+        bail!(format!(
+            "Backend {backend_name} isn't reachable, staying on the current profile.\n\nError: {err}"
+        ));
+    }
+
+    let models = match backend.list_models().await {
+        Ok(models) => models,
+        Err(err) => {
+            bail!(format!(
+                "Failed to list the models of backend {backend_name}, staying on the current profile.\n\nError: {err}"
+            ));
+        }
+    };
+    let model_name = match profile.get(ConfigKey::Model) {
+        Some(model_name) => model_name.to_string(),
+        None => {
+            ModelInfo::preferred(&models)
+                .map(|model| return model.name.to_string())
+                .unwrap_or_default()
+        }
+    };
+    if !ModelInfo::names(&models).contains(&model_name) {
+        bail!(format!(
+            "No model named {model_name} found in backend {backend_name}. Did you mistype it?"
+        ));
+    }
+
+    return Ok((backend, model_name));
+}
+
+async fn profile_set(
+    backend_arc: &mut Arc<BackendBox>,
+    tx: &mpsc::UnboundedSender<Event>,
+    args: &[String],
+) -> Result<()> {
+    let active_profile = Config::get(ConfigKey::Profile);
+    let profile_name = args.join(" ").trim().to_string();
+
+    if profile_name.is_empty() {
+        let profiles = Config::list_profiles()
+            .iter()
+            .map(|name| {
+                if *name == active_profile {
+                    return format!("- {name} (active)");
+                }
+                return format!("- {name}");
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let mut text = format!("Available profiles:\n\n{profiles}");
+        if profiles.is_empty() {
+            text = "There are no profiles defined in your config file.".to_string();
+        }
+        tx.send(Event::BackendMessage(Message::new(Author::Oatmeal, &text)))?;
+        return Ok(());
+    }
+
+    let profile = match Config::get_profile(&profile_name) {
+        Some(profile) => profile,
+        None => {
+            let msg = Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                &format!("No profile named {profile_name} exists. Run `/profile` to list all available profiles."),
+            );
+            tx.send(Event::BackendMessage(msg))?;
+            return Ok(());
+        }
+    };
+
+    let active_model = Config::get(ConfigKey::Model);
+    Config::apply_profile(&profile_name)?;
+    let (backend, model_name) = match profile_backend(&profile).await {
+        Ok(res) => res,
+        Err(err) => {
+            if active_profile.is_empty() {
+                Config::clear_profile();
+            } else {
+                Config::apply_profile(&active_profile)?;
+            }
+            Config::set(ConfigKey::Model, &active_model);
+
+            let msg = Message::new_with_type(Author::Oatmeal, MessageType::Error, &err.to_string());
+            tx.send(Event::BackendMessage(msg))?;
+            return Ok(());
+        }
+    };
+
+    let backend_name = backend.name();
+    Config::set(ConfigKey::Model, &model_name);
+    *backend_arc = Arc::new(backend);
+
+    tx.send(Event::BackendMessage(Message::new(
+        Author::Model,
+        &format!(
+            "{model_name} has entered the chat from {backend_name}, using profile {profile_name}."
+        ),
+    )))?;
+
+    return Ok(());
+}
+
 async fn accept_codeblock(
     context: Option<EditorContext>,
     codeblock: String,
@@ -210,17 +475,389 @@ fn worker_error(err: anyhow::Error, tx: &mpsc::UnboundedSender<Event>) -> Result
     return Ok(());
 }
 
-async fn completions(
+/// Asks the model for a summary of the prompt's history, forwarding any other
+/// events such as tool calls. Returns `None` when cancelled.
+async fn summarize(
+    backend: &BackendBox,
+    prompt: &BackendPrompt,
+    cancel: &CancellationToken,
+    tx: &mpsc::UnboundedSender<Event>,
+) -> Result<Option<Message>> {
+    let mut summary_prompt =
+        BackendPrompt::new(SUMMARY_INSTRUCTIONS.to_string(), prompt.history.clone());
+    summary_prompt.generation_options = prompt.generation_options.clone();
+
+    let (summary_tx, mut summary_rx) = mpsc::unbounded_channel::<Event>();
+    let completion = async move {
+        return backend
+            .get_completion(summary_prompt, cancel, &summary_tx)
+            .await;
+    };
+
+    let mut summary = Message::new_summary("");
+    let mut truncated = false;
+    let responses = async {
+        while let Some(event) = summary_rx.recv().await {
+            match event {
+                Event::BackendPromptResponse(res) => {
+                    summary.append(&res.text);
+                    truncated = truncated || res.truncated;
+                    if res.usage.is_some() {
+                        summary.usage = res.usage;
+                    }
+                }
+                event => tx.send(event)?,
+            }
+        }
+
+        return Ok::<(), anyhow::Error>(());
+    };
+
+    let (completion_res, responses_res) = tokio::join!(completion, responses);
+    completion_res?;
+    responses_res?;
+
+    if truncated || cancel.is_cancelled() {
+        return Ok(None);
+    }
+    if summary.text.trim().is_empty() {
+        bail!("The model replied with an empty summary");
+    }
+
+    summary.text = summary.text.trim().to_string();
+    return Ok(Some(summary));
+}
+
+async fn compact(
     backend: &BackendBox,
     prompt: BackendPrompt,
+    cancel: &CancellationToken,
+    tx: &mpsc::UnboundedSender<Event>,
+) -> Result<()> {
+    if prompt.history.is_empty() {
+        tx.send(Event::BackendMessage(Message::new(
+            Author::Oatmeal,
+            "There's nothing to compact yet.",
+        )))?;
+        return Ok(());
+    }
+
+    let summary = match summarize(backend, &prompt, cancel, tx).await? {
+        Some(summary) => summary,
+        None => {
+            tx.send(Event::BackendMessage(Message::new(
+                Author::Oatmeal,
+                "Compacting the conversation was cancelled.",
+            )))?;
+            return Ok(());
+        }
+    };
+
+    tx.send(Event::BackendSummary(summary))?;
+    tx.send(Event::BackendMessage(Message::new(
+        Author::Oatmeal,
+        &format!(
+            "Compacted {} messages into a summary. Only the summary and pinned messages are sent to the model from now on.",
+            prompt.history.len()
+        ),
+    )))?;
+
+    return Ok(());
+}
+
+/// Loads the index of a directory, or of the current directory when none is
+/// given.
+async fn find_index(dir: Option<&String>) -> Result<VectorIndex> {
+    let dir = match dir {
+        Some(dir) => path::PathBuf::from(dir),
+        None => std::env::current_dir()?,
+    };
+
+    return match Indexes::default().find(&dir).await? {
+        Some(index) => Ok(index),
+        None => {
+            let dir = dir.to_string_lossy();
+            bail!(format!(
+                "There's no index for {dir}. Create one with `oatmeal index {dir}` first."
+            ));
+        }
+    };
+}
+
+/// Turns retrieval from a project's index on or off.
+async fn rag(
+    rag_index: &mut Option<Arc<VectorIndex>>,
     tx: &mpsc::UnboundedSender<Event>,
+    args: &[String],
 ) -> Result<()> {
+    let text = match args.first().map(|arg| return arg.as_str()) {
+        Some("on") => {
+            let index = match find_index(args.get(1)).await {
+                Ok(index) => index,
+                Err(err) => {
+                    let msg = Message::new_with_type(
+                        Author::Oatmeal,
+                        MessageType::Error,
+                        &err.to_string(),
+                    );
+                    tx.send(Event::BackendMessage(msg))?;
+                    return Ok(());
+                }
+            };
+            let text = format!(
+                "RAG is on, using the index of {} ({} chunks from {} files).",
+                index.root,
+                index.chunks.len(),
+                index.files_len()
+            );
+            *rag_index = Some(Arc::new(index));
+            text
+        }
+        Some("off") => {
+            *rag_index = None;
+            "RAG is off.".to_string()
+        }
+        Some(arg) => {
+            let msg = Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                &format!("Unknown argument {arg} for `/rag`, use `on` or `off`."),
+            );
+            tx.send(Event::BackendMessage(msg))?;
+            return Ok(());
+        }
+        None => {
+            match rag_index {
+                Some(index) => format!("RAG is on, using the index of {}.", index.root),
+                None => "RAG is off. Turn it on with `/rag on`.".to_string(),
+            }
+        }
+    };
+
+    tx.send(Event::BackendMessage(Message::new(Author::Oatmeal, &text)))?;
+    return Ok(());
+}
+
+/// Adds the chunks of the index most relevant to the prompt to its system
+/// prompt. The prompt is embedded with the backend and model which embedded
+/// the index, as embeddings from different models can't be compared.
+async fn retrieve(
+    index: &VectorIndex,
+    prompt: &mut BackendPrompt,
+    tx: &mpsc::UnboundedSender<Event>,
+) -> Result<()> {
+    let backend = match BackendName::parse(index.backend.to_string()) {
+        Some(name) => BackendManager::get(name).await?,
+        None => bail!(format!("Unknown embeddings backend {}", index.backend)),
+    };
+    let top_k = match Config::get(ConfigKey::RagTopK).parse::<usize>() {
+        Ok(top_k) => top_k,
+        Err(_) => bail!(format!("{} must be a number of chunks", ConfigKey::RagTopK)),
+    };
+
+    let embeddings = backend
+        .get_embeddings(&index.model, &[prompt.text.to_string()])
+        .await?;
+    let chunks = match embeddings.first() {
+        Some(embedding) => index.search(embedding, top_k),
+        None => bail!("The backend returned no embeddings for the prompt"),
+    };
+    if chunks.is_empty() {
+        return Ok(());
+    }
+
+    let context = index.to_prompt_text(&chunks);
+    if prompt.system_prompt.is_empty() {
+        prompt.system_prompt = context;
+    } else {
+        prompt.system_prompt = format!("{}\n\n{context}", prompt.system_prompt);
+    }
+    tx.send(Event::BackendSources(
+        chunks.iter().map(|chunk| return chunk.citation()).collect(),
+    ))?;
+
+    return Ok(());
+}
+
+/// Fits the prompt in the configured context window, either by summarizing
+/// the history or by dropping its oldest turns.
+async fn fit_context_window(
+    backend: &BackendBox,
+    prompt: &mut BackendPrompt,
+    cancel: &CancellationToken,
+    tx: &mpsc::UnboundedSender<Event>,
+) -> Result<()> {
+    let context_window = match ContextWindow::from_config()? {
+        Some(context_window) => context_window,
+        None => return Ok(()),
+    };
+    if context_window.fits(prompt) {
+        return Ok(());
+    }
+
+    if context_window.strategy == ContextStrategy::Summarize && !prompt.history.is_empty() {
+        tx.send(Event::BackendStatus(Message::new(
+            Author::Oatmeal,
+            "The conversation no longer fits the model's context window, summarizing it first.",
+        )))?;
+
+        if let Some(summary) = summarize(backend, prompt, cancel, tx).await? {
+            prompt.history = HistoryMessage::summarized(&prompt.history, &summary.text);
+            tx.send(Event::BackendSummary(summary))?;
+        }
+    }
+
+    // Summaries and pinned messages can still be too long.
+    let dropped = context_window.truncate(prompt);
+    if dropped > 0 {
+        tracing::info!(
+            dropped,
+            max_tokens = context_window.max_tokens,
+            "Dropped messages to fit the context window"
+        );
+    }
+
+    return Ok(());
+}
+
+async fn completions(
+    backend: &BackendBox,
+    mut prompt: BackendPrompt,
+    rag_index: Option<Arc<VectorIndex>>,
+    cancel: &CancellationToken,
+    tx: &mpsc::UnboundedSender<Event>,
+) -> Result<()> {
+    // Listing models and embedding the prompt don't stop by themselves when
+    // cancelled.
     if Config::get(ConfigKey::Model).is_empty() {
-        let models = backend.list_models().await?;
-        Config::set(ConfigKey::Model, &models[0]);
+        let models = tokio::select! {
+            models = backend.list_models() => models?,
+            _ = cancel.cancelled() => vec![],
+        };
+        if let Some(model) = ModelInfo::preferred(&models) {
+            Config::set(ConfigKey::Model, &model.name);
+        }
+    }
+
+    if let Some(index) = rag_index {
+        tokio::select! {
+            res = retrieve(&index, &mut prompt, tx) => res?,
+            _ = cancel.cancelled() => {}
+        }
     }
 
-    backend.get_completion(prompt, tx).await?;
+    fit_context_window(backend, &mut prompt, cancel, tx).await?;
+    if cancel.is_cancelled() {
+        tx.send(Event::BackendPromptResponse(BackendResponse {
+            author: Author::Model,
+            text: "".to_string(),
+            reasoning: "".to_string(),
+            done: true,
+            usage: None,
+            truncated: true,
+        }))?;
+        return Ok(());
+    }
+
+    Recorder::get_completion(backend, prompt, cancel, tx).await?;
+
+    return Ok(());
+}
+
+/// Sends a prompt to one of the models in compare mode, tagging its
+/// responses with the index of its answer.
+async fn compare_completions(
+    idx: usize,
+    target: &CompareTarget,
+    mut prompt: BackendPrompt,
+    cancel: &CancellationToken,
+    tx: &mpsc::UnboundedSender<Event>,
+) -> Result<()> {
+    let backend = BackendManager::get(target.backend.clone()).await?;
+    prompt.model = Some(target.model.to_string());
+
+    // A summary would replace the history of every compared model, so the
+    // history is only truncated to fit.
+    if let Some(context_window) = ContextWindow::from_config()? {
+        context_window.truncate(&mut prompt);
+    }
+
+    let (compare_tx, mut compare_rx) = mpsc::unbounded_channel::<Event>();
+    let completion = async move {
+        return Recorder::get_completion(&backend, prompt, cancel, &compare_tx).await;
+    };
+
+    let responses = async {
+        while let Some(event) = compare_rx.recv().await {
+            match event {
+                Event::BackendPromptResponse(res) => {
+                    tx.send(Event::BackendCompareResponse(idx, res))?;
+                }
+                event => tx.send(event)?,
+            }
+        }
+
+        return Ok::<(), anyhow::Error>(());
+    };
+
+    let (completion_res, responses_res) = tokio::join!(completion, responses);
+    completion_res?;
+    responses_res?;
+
+    return Ok(());
+}
+
+/// Continues the conversation with the backend and model of the kept answer.
+/// The answer itself is added to the conversation by the app state.
+async fn keep(
+    backend_arc: &mut Arc<BackendBox>,
+    targets: &[CompareTarget],
+    tx: &mpsc::UnboundedSender<Event>,
+    args: &[String],
+) -> Result<()> {
+    let target = args
+        .first()
+        .and_then(|arg| return arg.parse::<usize>().ok())
+        .and_then(|idx| return targets.get(idx.wrapping_sub(1)));
+    let target = match target {
+        Some(target) => target,
+        None => {
+            let msg = Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                "There's no answer to keep. Use `/compare` to compare models first.",
+            );
+            tx.send(Event::BackendMessage(msg))?;
+            return Ok(());
+        }
+    };
+
+    let backend = match BackendManager::get(target.backend.clone()).await {
+        Ok(backend) => backend,
+        Err(err) => {
+            let msg = Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                &format!(
+                    "Failed to set up backend {}, staying on the current backend.\n\nError: {err}",
+                    target.backend
+                ),
+            );
+            tx.send(Event::BackendMessage(msg))?;
+            return Ok(());
+        }
+    };
+    Config::set(ConfigKey::Backend, &target.backend.to_string());
+    Config::set(ConfigKey::Model, &target.model);
+    *backend_arc = Arc::new(backend);
+
+    tx.send(Event::BackendMessage(Message::new(
+        Author::Model,
+        &format!(
+            "{} has entered the chat from {}.",
+            target.model, target.backend
+        ),
+    )))?;
 
     return Ok(());
 }
@@ -234,6 +871,18 @@ fn help(tx: &mpsc::UnboundedSender<Event>) -> Result<()> {
     return Ok(());
 }
 
+/// Waits for the UI to abort the slash command running in the actions loop.
+/// Nothing else is sent while the UI waits for the command.
+async fn aborted(rx: &mut mpsc::UnboundedReceiver<Action>) {
+    while let Some(action) = rx.recv().await {
+        if matches!(action, Action::BackendAbort()) {
+            return;
+        }
+    }
+
+    std::future::pending::<()>().await;
+}
+
 pub struct ActionsService {}
 
 impl ActionsService {
@@ -242,12 +891,36 @@ impl ActionsService {
         tx: mpsc::UnboundedSender<Event>,
         rx: &mut mpsc::UnboundedReceiver<Action>,
     ) -> Result<()> {
-        let backend_arc = Arc::new(backend);
+        let mut backend_arc = Arc::new(backend);
 
-        // Lazy default.
-        let mut worker: JoinHandle<Result<()>> = tokio::spawn(async {
-            return Ok(());
-        });
+        // Replaced for each backend request, and cancelled on abort.
+        let mut cancel = CancellationToken::new();
+
+        // Tasks of the last backend request, stopped when aborted again after
+        // being cancelled.
+        let mut workers: Vec<JoinHandle<Result<()>>> = vec![];
+
+        // Slash commands run in the loop as they can replace the backend, so
+        // they're stopped by racing them with an abort.
+        macro_rules! abortable {
+            ( $command:expr ) => {
+                tokio::select! {
+                    res = $command => res?,
+                    _ = aborted(rx) => {
+                        tx.send(Event::BackendMessage(Message::new(
+                            Author::Oatmeal,
+                            "The command was cancelled.",
+                        )))?;
+                    }
+                }
+            };
+        }
+
+        // Models of the last comparison, one of which is picked with `/keep`.
+        let mut compare_targets: Vec<CompareTarget> = vec![];
+
+        // Index of the project retrieved from for each prompt in `/rag` mode.
+        let mut rag_index: Option<Arc<VectorIndex>> = None;
 
         loop {
             let event = rx.recv().await;
@@ -264,16 +937,68 @@ impl ActionsService {
                     copy_messages(messages, &tx)?;
                 }
                 Action::BackendAbort() => {
-                    worker.abort();
+                    if cancel.is_cancelled() {
+                        for worker in workers.drain(..) {
+                            worker.abort();
+                        }
+                    }
+                    cancel.cancel();
+                }
+                Action::BackendCompare(targets, prompt) => {
+                    cancel = CancellationToken::new();
+                    workers = vec![];
+                    compare_targets = targets.clone();
+                    for (idx, target) in targets.into_iter().enumerate() {
+                        let worker_tx = tx.clone();
+                        let worker_cancel = cancel.clone();
+                        let worker_prompt = prompt.clone();
+                        workers.push(tokio::spawn(async move {
+                            let res = compare_completions(
+                                idx,
+                                &target,
+                                worker_prompt,
+                                &worker_cancel,
+                                &worker_tx,
+                            )
+                            .await;
+                            if let Err(err) = res {
+                                worker_tx.send(Event::BackendCompareError(
+                                    idx,
+                                    format!(
+                                        "The backend failed with the following error: {:?}",
+                                        err
+                                    ),
+                                ))?;
+                            }
+                            return Ok::<(), anyhow::Error>(());
+                        }));
+                    }
                 }
-                Action::BackendRequest(prompt) => {
+                Action::BackendRequest(mut prompt) => {
                     if let Some(command) = SlashCommand::parse(&prompt.text) {
                         if command.is_model_list() {
-                            model_list(&backend_arc, &tx).await?;
+                            abortable!(model_list(&backend_arc, &tx));
                             continue;
                         }
                         if command.is_model_set() {
-                            model_set(&backend_arc, &tx, &prompt.text).await?;
+                            abortable!(model_set(&backend_arc, &tx, &prompt.text));
+                            continue;
+                        }
+                        if command.is_backend_set() {
+                            abortable!(backend_set(&mut backend_arc, &tx, &command.args));
+                            continue;
+                        }
+                        if command.is_profile() {
+                            abortable!(profile_set(&mut backend_arc, &tx, &command.args));
+                            continue;
+                        }
+                        if command.is_keep() {
+                            keep(&mut backend_arc, &compare_targets, &tx, &command.args).await?;
+                            compare_targets = vec![];
+                            continue;
+                        }
+                        if command.is_rag() {
+                            rag(&mut rag_index, &tx, &command.args).await?;
                             continue;
                         }
                         if command.is_help() {
@@ -282,13 +1007,38 @@ impl ActionsService {
                         }
                     }
 
+                    let is_compact = SlashCommand::parse(&prompt.text)
+                        .map(|command| return command.is_compact())
+                        .unwrap_or(false);
+                    let is_continue = SlashCommand::parse(&prompt.text)
+                        .map(|command| return command.is_continue())
+                        .unwrap_or(false);
+                    if is_continue {
+                        prompt.text = CONTINUE_INSTRUCTIONS.to_string();
+                    }
                     let backend_worker = backend_arc.clone();
-                    worker = tokio::spawn(async move {
-                        if let Err(err) = completions(&backend_worker, prompt, &worker_tx).await {
+                    // The instructions to continue a response have nothing to retrieve.
+                    let worker_rag_index = if is_continue { None } else { rag_index.clone() };
+                    cancel = CancellationToken::new();
+                    let worker_cancel = cancel.clone();
+                    workers = vec![tokio::spawn(async move {
+                        let res = if is_compact {
+                            compact(&backend_worker, prompt, &worker_cancel, &worker_tx).await
+                        } else {
+                            completions(
+                                &backend_worker,
+                                prompt,
+                                worker_rag_index,
+                                &worker_cancel,
+                                &worker_tx,
+                            )
+                            .await
+                        };
+                        if let Err(err) = res {
                             worker_error(err, &worker_tx)?;
                         }
-                        return Ok(());
-                    });
+                        return Ok::<(), anyhow::Error>(());
+                    })];
                 }
             }
         }
@@ -12,14 +12,26 @@ use crate::configuration::Config;
 use crate::configuration::ConfigKey;
 use crate::domain::models::AcceptType;
 use crate::domain::models::Action;
+use crate::domain::models::Attachment;
 use crate::domain::models::Author;
 use crate::domain::models::BackendBox;
 use crate::domain::models::BackendResponse;
+use crate::domain::models::Branch;
+use crate::domain::models::CompareTarget;
+use crate::domain::models::Comparison;
 use crate::domain::models::EditorBox;
 use crate::domain::models::EditorContext;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
 use crate::domain::models::Message;
 use crate::domain::models::MessageType;
+use crate::domain::models::ModelInfo;
 use crate::domain::models::SlashCommand;
+use crate::domain::models::ToolApproval;
+use crate::domain::models::Usage;
+use crate::domain::models::UsageByModel;
+use crate::domain::models::GENERATION_OPTION_KEYS;
 
 #[cfg(test)]
 #[path = "app_state_test.rs"]
@@ -36,17 +48,34 @@ pub struct AppStateProps {
 }
 
 pub struct AppState<'a> {
-    pub backend_context: String,
+    /// Set once the response being waited for is aborted. Aborting it again
+    /// stops waiting for the backend.
+    pub aborting: bool,
     pub bubble_list: BubbleList<'a>,
     pub codeblocks: CodeBlocks,
+    /// Models each message is sent to in compare mode, empty otherwise.
+    pub compare_targets: Vec<CompareTarget>,
+    /// Answers to the last message in compare mode, until one is kept.
+    pub comparison: Option<Comparison>,
+    /// The earlier message loaded in to the textarea. Sending it forks the
+    /// conversation from that message.
+    pub editing_message: Option<usize>,
     pub editor_context: Option<EditorContext>,
     pub exit_warning: bool,
     pub last_known_height: usize,
     pub last_known_width: usize,
     pub messages: Vec<Message>,
+    pub pending_attachments: Vec<Attachment>,
+    /// Sources retrieved for the response being waited on in `/rag` mode,
+    /// until the response arrives.
+    pub pending_sources: Vec<String>,
+    pub pending_tool_approval: Option<ToolApproval>,
     pub scroll: Scroll,
+    /// The earlier message picked in message selection mode.
+    pub selected_message: Option<usize>,
     pub session_id: String,
     pub sessions_service: Sessions,
+    pub usage: UsageByModel,
     pub waiting_for_backend: bool,
 }
 
@@ -64,17 +93,25 @@ impl<'a> AppState<'a> {
         let theme = Themes::get(&props.theme_name, &props.theme_file)?;
 
         let mut app_state = AppState {
-            backend_context: "".to_string(),
+            aborting: false,
             bubble_list: BubbleList::new(theme),
             codeblocks: CodeBlocks::default(),
+            compare_targets: CompareTarget::from_config().unwrap_or_default(),
+            comparison: None,
+            editing_message: None,
             editor_context: None,
             exit_warning: false,
             last_known_height: 0,
             last_known_width: 0,
             messages: vec![],
+            pending_attachments: vec![],
+            pending_sources: vec![],
+            pending_tool_approval: None,
             scroll: Scroll::default(),
+            selected_message: None,
             session_id: Sessions::create_id(),
             sessions_service: props.sessions_service,
+            usage: UsageByModel::new(),
             waiting_for_backend: false,
         };
 
@@ -90,10 +127,12 @@ impl<'a> AppState<'a> {
         } else {
             let models = props.backend.list_models().await?;
             if model_name.is_empty() {
-                model_name = models[0].to_string();
+                model_name = ModelInfo::preferred(&models)
+                    .map(|model| return model.name.to_string())
+                    .unwrap_or_default();
                 // TODO refactor this out later.
                 Config::set(ConfigKey::Model, &model_name);
-            } else if !models.contains(&model_name.to_string()) {
+            } else if !ModelInfo::names(&models).contains(&model_name.to_string()) {
                 app_state
                 .messages
                 .push(Message::new_with_type(
@@ -112,6 +151,14 @@ impl<'a> AppState<'a> {
             ));
         }
 
+        if !app_state.compare_targets.is_empty() {
+            let targets = CompareTarget::join(&app_state.compare_targets);
+            app_state.messages.push(Message::new(
+                Author::Oatmeal,
+                &format!("Comparing {targets}. Your next message is sent to each of them, then use `/keep N` to continue the conversation with one of their answers."),
+            ));
+        }
+
         return Ok(app_state);
     }
 
@@ -121,17 +168,25 @@ impl<'a> AppState<'a> {
         let theme = Themes::get(&props.theme_name, &props.theme_file)?;
 
         let mut app_state = AppState {
-            backend_context: session.state.backend_context,
+            aborting: false,
             bubble_list: BubbleList::new(theme),
             codeblocks: CodeBlocks::default(),
+            compare_targets: CompareTarget::from_config().unwrap_or_default(),
+            comparison: None,
+            editing_message: None,
             editor_context: None,
             exit_warning: false,
             last_known_height: 0,
             last_known_width: 0,
             messages: session.state.messages,
+            pending_attachments: vec![],
+            pending_sources: vec![],
+            pending_tool_approval: None,
             scroll: Scroll::default(),
+            selected_message: None,
             session_id,
             sessions_service: props.sessions_service,
+            usage: session.state.usage,
             waiting_for_backend: false,
         };
 
@@ -179,31 +234,291 @@ impl<'a> AppState<'a> {
 
     pub fn handle_backend_response(&mut self, msg: BackendResponse) {
         let last_message = self.messages.last_mut().unwrap();
-        if last_message.author != Author::User {
+        if last_message.author == msg.author {
             last_message.append(&msg.text);
-        } else {
-            self.messages.push(Message::new(msg.author, &msg.text));
+            last_message.append_reasoning(&msg.reasoning);
+            last_message.truncated = msg.truncated;
+        } else if !msg.truncated || !msg.text.is_empty() || !msg.reasoning.is_empty() {
+            let mut message = Message::new(msg.author, &msg.text);
+            message.append_reasoning(&msg.reasoning);
+            message.truncated = msg.truncated;
+            self.messages.push(message);
+        }
+
+        let last_message = self.messages.last_mut().unwrap();
+        if last_message.author == Author::Model && !self.pending_sources.is_empty() {
+            last_message.sources = std::mem::take(&mut self.pending_sources);
         }
 
         self.sync_dependants();
 
         if msg.done {
             self.waiting_for_backend = false;
-            if let Some(ctx) = msg.context {
-                self.backend_context = ctx;
+            self.pending_sources = vec![];
+
+            if let Some(usage) = msg.usage {
+                self.messages.last_mut().unwrap().usage = Some(usage);
+                self.usage
+                    .entry(Config::get(ConfigKey::Model))
+                    .or_default()
+                    .add(&usage);
             }
 
-            if self.backend_context.is_empty() {
-                self.add_message(Message::new_with_type(
-                    Author::Oatmeal,
-                    MessageType::Error,
-                    "Error: No context was provided by the backend upon completion. Please report this bug on Github."
-                ));
+            self.discard_empty_response();
+            self.codeblocks.replace_from_messages(&self.messages);
+        }
+    }
+
+    /// Keeps the sources retrieved for the next response, which are shown
+    /// with it once it arrives.
+    pub fn set_sources(&mut self, sources: Vec<String>) {
+        self.pending_sources = sources;
+    }
+
+    /// Clears the last response for a regenerated one, keeping it as an
+    /// alternate. Returns the prompt it answered with the history before the
+    /// prompt, or `None` when the last message isn't a response from the
+    /// model.
+    pub fn regenerate(&mut self) -> Option<(Message, Vec<HistoryMessage>)> {
+        let prompt_idx = self.messages.iter().rposition(|message| {
+            return message.author == Author::User && SlashCommand::parse(&message.text).is_none();
+        })?;
+
+        let last_idx = self.messages.len() - 1;
+        let last_message = self.messages.last_mut()?;
+        if last_idx <= prompt_idx
+            || last_message.author != Author::Model
+            || last_message.message_type() == MessageType::Error
+            || last_message.summary
+            || last_message.text.is_empty()
+        {
+            return None;
+        }
+
+        last_message.start_regenerating();
+        self.sync_dependants();
+        self.codeblocks.replace_from_messages(&self.messages);
+
+        return Some((
+            self.messages[prompt_idx].clone(),
+            HistoryMessage::from_messages(&self.messages[..prompt_idx]),
+        ));
+    }
+
+    /// Shows the previous response again when a regenerated response came
+    /// back empty.
+    pub fn discard_empty_response(&mut self) {
+        if let Some(message) = self.messages.last_mut() {
+            if message.discard_empty_response() {
+                self.bubble_list.clear_cache();
                 self.sync_dependants();
             }
+        }
+    }
 
-            self.codeblocks.replace_from_messages(&self.messages);
+    /// Shows or hides the reasoning of the model's responses.
+    pub fn toggle_reasoning(&mut self) {
+        self.bubble_list.toggle_reasoning();
+        self.sync_dependants();
+    }
+
+    /// Cycles through the responses of the latest regenerated message. The
+    /// shown response is the one sent as part of the conversation.
+    pub fn cycle_alternates(&mut self, forward: bool) -> bool {
+        let idx = match self
+            .messages
+            .iter()
+            .rposition(|message| return !message.alternates.is_empty())
+        {
+            Some(idx) => idx,
+            None => return false,
+        };
+
+        if !self.messages[idx].cycle_alternates(forward) {
+            return false;
         }
+
+        self.bubble_list.clear_cache();
+        self.sync_dependants();
+        self.codeblocks.replace_from_messages(&self.messages);
+
+        return true;
+    }
+
+    /// Moves the selection to the previous or next message sent by the user,
+    /// starting from the last one. Moving past the last message leaves
+    /// selection mode. Returns false when nothing was selected.
+    pub fn select_message(&mut self, previous: bool) -> bool {
+        let candidates = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| {
+                return message.author == Author::User
+                    && SlashCommand::parse(&message.text).is_none();
+            })
+            .map(|(idx, _)| return idx)
+            .collect::<Vec<usize>>();
+
+        let selected = match (self.selected_message, previous) {
+            (None, true) => candidates.last().copied(),
+            (None, false) => return false,
+            (Some(current), true) => {
+                candidates
+                    .iter()
+                    .rev()
+                    .find(|idx| return **idx < current)
+                    .copied()
+                    .or(Some(current))
+            }
+            (Some(current), false) => {
+                candidates
+                    .iter()
+                    .find(|idx| return **idx > current)
+                    .copied()
+            }
+        };
+        if selected.is_none() && self.selected_message.is_none() {
+            return false;
+        }
+
+        self.editing_message = None;
+        self.set_selected_message(selected);
+        return true;
+    }
+
+    fn set_selected_message(&mut self, selected: Option<usize>) {
+        self.selected_message = selected;
+        self.bubble_list.set_selected(selected);
+        self.sync_dependants();
+
+        match selected {
+            Some(idx) => self.scroll.to(self.bubble_list.line_offset(idx)),
+            None => self.scroll.last(),
+        }
+    }
+
+    /// Starts editing the selected message, returning it so it can be loaded
+    /// in to the textarea. Its attachments are sent with the edited message.
+    pub fn edit_selected_message(&mut self) -> Option<Message> {
+        let idx = self.selected_message?;
+        let message = self.messages.get(idx)?.clone();
+
+        self.editing_message = Some(idx);
+        self.pending_attachments = message.attachments.clone();
+
+        return Some(message);
+    }
+
+    /// Leaves message selection mode, without editing the selected message.
+    pub fn cancel_selection(&mut self) {
+        if self.selected_message.is_none() && self.editing_message.is_none() {
+            return;
+        }
+
+        if self.editing_message.take().is_some() {
+            self.pending_attachments = vec![];
+        }
+        self.set_selected_message(None);
+    }
+
+    /// Sends the edited message in place of the message being edited. The
+    /// messages from the edited message onwards are kept as a branch.
+    pub fn fork_from_edited_message(&mut self, message: Message) {
+        let idx = match self.editing_message.take() {
+            Some(idx) if idx < self.messages.len() => idx,
+            _ => {
+                self.add_message(message);
+                return;
+            }
+        };
+
+        Branch::fork(&mut self.messages, idx, message);
+        self.selected_message = None;
+        self.bubble_list.set_selected(None);
+        self.bubble_list.clear_cache();
+        self.sync_dependants();
+        self.codeblocks.replace_from_messages(&self.messages);
+        self.scroll.last();
+    }
+
+    /// Switches between the branches of the selected message.
+    pub fn cycle_branches(&mut self, forward: bool) -> bool {
+        let idx = match self.selected_message {
+            Some(idx) => idx,
+            None => return false,
+        };
+
+        if !Branch::cycle(&mut self.messages, idx, forward) {
+            return false;
+        }
+
+        self.editing_message = None;
+        self.bubble_list.clear_cache();
+        self.sync_dependants();
+        self.codeblocks.replace_from_messages(&self.messages);
+        self.scroll.to(self.bubble_list.line_offset(idx));
+
+        return true;
+    }
+
+    /// Shows an empty answer for each compared model, filled in as their
+    /// responses stream.
+    pub fn start_comparison(&mut self) {
+        self.comparison = Some(Comparison::new(&self.compare_targets));
+        self.sync_dependants();
+        self.scroll.last();
+    }
+
+    pub fn handle_compare_response(&mut self, idx: usize, msg: BackendResponse) {
+        let comparison = match self.comparison.as_mut() {
+            Some(comparison) => comparison,
+            None => return,
+        };
+
+        if comparison.handle_response(idx, &msg) && msg.done {
+            if let Some(usage) = msg.usage {
+                self.usage
+                    .entry(comparison.answers[idx].target.model.to_string())
+                    .or_default()
+                    .add(&usage);
+            }
+        }
+        if comparison.is_done() {
+            self.waiting_for_backend = false;
+        }
+
+        self.sync_dependants();
+    }
+
+    pub fn handle_compare_error(&mut self, idx: usize, err: &str) {
+        let comparison = match self.comparison.as_mut() {
+            Some(comparison) => comparison,
+            None => return,
+        };
+
+        comparison.handle_error(idx, err);
+        if comparison.is_done() {
+            self.waiting_for_backend = false;
+        }
+
+        self.sync_dependants();
+    }
+
+    /// Messages can't be sent until an answer to the last compared message is
+    /// kept, as the conversation would otherwise miss a reply. Returns true
+    /// when the message is rejected.
+    pub fn reject_during_comparison(&mut self, input_str: &str) -> bool {
+        if self.comparison.is_none() || SlashCommand::parse(input_str).is_some() {
+            return false;
+        }
+
+        self.add_message(Message::new_with_type(
+            Author::Oatmeal,
+            MessageType::Error,
+            "Keep one of the answers with `/keep N` before sending another message, or stop comparing with `/compare off`.",
+        ));
+        return true;
     }
 
     pub fn handle_slash_commands(
@@ -245,6 +560,7 @@ impl<'a> AppState<'a> {
                     )]))?;
 
                     self.waiting_for_backend = true;
+                    self.aborting = false;
                     return Ok((should_break, should_continue));
                 }
 
@@ -260,19 +576,485 @@ impl<'a> AppState<'a> {
                 ))?;
             }
 
+            if command.is_persona() {
+                should_continue = true;
+                self.set_persona(&command.args);
+            }
+
+            if command.is_attach() {
+                should_continue = true;
+                self.attach(&command.args);
+            }
+
+            if command.is_set() {
+                should_continue = true;
+                self.set_generation_option(&command.args);
+            }
+
+            if command.is_pin() {
+                should_continue = true;
+                self.toggle_pin();
+            }
+
+            if command.is_compare() {
+                should_continue = true;
+                self.set_compare_targets(&command.args);
+            }
+
+            // The backend switches to the kept answer's model once it's been
+            // added to the conversation.
+            if command.is_keep() && !self.keep_answer(&command.args) {
+                should_continue = true;
+            }
+
+            if command.is_continue() && !self.can_continue() {
+                should_continue = true;
+            }
+
+            if command.is_usage() {
+                should_continue = true;
+                self.add_message(Message::new(Author::Oatmeal, &self.usage_report()));
+            }
+
             if command.is_copy_chat() {
                 should_continue = true;
                 tx.send(Action::CopyMessages(self.messages.clone()))?;
                 self.waiting_for_backend = true;
+                self.aborting = false;
             }
+        }
 
-            // Reset backend context on model switch.
-            if command.is_model_set() {
-                self.backend_context = "".to_string();
+        return Ok((should_break, should_continue));
+    }
+
+    /// Checks the model has a response to carry on from, outside of compare
+    /// mode as compared models each have their own answer.
+    fn can_continue(&mut self) -> bool {
+        let mut error = "";
+        if !self.compare_targets.is_empty() {
+            error = "Responses can't be continued in compare mode. Keep one of the answers with `/keep N`, or stop comparing with `/compare off`.";
+        } else if HistoryMessage::from_messages(&self.messages[..self.messages.len() - 1])
+            .last()
+            .map(|message| return message.role != HistoryRole::Assistant)
+            .unwrap_or(true)
+        {
+            error = "There's no response from the model to continue yet.";
+        }
+
+        if error.is_empty() {
+            return true;
+        }
+
+        self.add_message(Message::new_with_type(
+            Author::Oatmeal,
+            MessageType::Error,
+            error,
+        ));
+        return false;
+    }
+
+    fn set_persona(&mut self, args: &[String]) {
+        let active_persona = Config::get(ConfigKey::Persona);
+        let persona_name = args.join(" ").trim().to_string();
+
+        if persona_name.is_empty() {
+            let personas = Config::list_personas()
+                .iter()
+                .map(|name| {
+                    if *name == active_persona {
+                        return format!("- {name} (active)");
+                    }
+                    return format!("- {name}");
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            self.add_message(Message::new(
+                Author::Oatmeal,
+                &format!("Available personas:\n\n{personas}"),
+            ));
+            return;
+        }
+
+        if Config::get_persona(&persona_name).is_none() {
+            self.add_message(Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                &format!("No persona named {persona_name} exists. Run `/persona` to list all available personas."),
+            ));
+            return;
+        }
+
+        Config::set(ConfigKey::Persona, &persona_name);
+        self.add_message(Message::new(
+            Author::Oatmeal,
+            &format!("Switched to persona {persona_name}."),
+        ));
+    }
+
+    fn attach(&mut self, args: &[String]) {
+        let file_path = args.join(" ");
+        if file_path.trim().is_empty() {
+            if self.pending_attachments.is_empty() {
+                self.add_message(Message::new(
+                    Author::Oatmeal,
+                    "Nothing is attached to your next message. Use `/attach PATH` to attach an image or text file.",
+                ));
+                return;
             }
+
+            let attachments = self
+                .pending_attachments
+                .iter()
+                .map(|attachment| return format!("- {}", attachment.chip()))
+                .collect::<Vec<String>>()
+                .join("\n");
+            self.add_message(Message::new(
+                Author::Oatmeal,
+                &format!("Attached to your next message:\n\n{attachments}"),
+            ));
+            return;
         }
 
-        return Ok((should_break, should_continue));
+        match Attachment::from_path(&file_path) {
+            Ok(attachment) => self.add_attachment(attachment),
+            Err(err) => {
+                self.add_message(Message::new_with_type(
+                    Author::Oatmeal,
+                    MessageType::Error,
+                    &format!("There was an error trying to attach your file:\n\n{err}"),
+                ));
+            }
+        }
+    }
+
+    fn set_compare_targets(&mut self, args: &[String]) {
+        let text = args.join(" ").trim().to_string();
+        if text.is_empty() {
+            let msg = if self.compare_targets.is_empty() {
+                "Not comparing any models. Use `/compare BACKEND:MODEL,BACKEND:MODEL` to compare them.".to_string()
+            } else {
+                format!("Comparing {}.", CompareTarget::join(&self.compare_targets))
+            };
+            self.add_message(Message::new(Author::Oatmeal, &msg));
+            return;
+        }
+
+        if text == "off" {
+            self.compare_targets = vec![];
+            self.comparison = None;
+            self.add_message(Message::new(Author::Oatmeal, "Stopped comparing models."));
+            return;
+        }
+
+        match CompareTarget::parse_list(&text) {
+            Ok(targets) => {
+                self.compare_targets = targets;
+                self.add_message(Message::new(
+                    Author::Oatmeal,
+                    &format!(
+                        "Comparing {}. Your next message is sent to each of them, then use `/keep N` to continue the conversation with one of their answers.",
+                        CompareTarget::join(&self.compare_targets)
+                    ),
+                ));
+            }
+            Err(err) => {
+                self.add_message(Message::new_with_type(
+                    Author::Oatmeal,
+                    MessageType::Error,
+                    &format!("There was an error trying to parse your command:\n\n{err}"),
+                ));
+            }
+        }
+    }
+
+    /// Adds the chosen answer of a comparison to the conversation and leaves
+    /// compare mode. Returns false when there's no such answer.
+    fn keep_answer(&mut self, args: &[String]) -> bool {
+        let answers_len = match &self.comparison {
+            Some(comparison) if comparison.is_done() => comparison.answers.len(),
+            Some(_) => {
+                self.add_message(Message::new_with_type(
+                    Author::Oatmeal,
+                    MessageType::Error,
+                    "Wait for every model to finish answering before keeping an answer.",
+                ));
+                return false;
+            }
+            None => {
+                self.add_message(Message::new_with_type(
+                    Author::Oatmeal,
+                    MessageType::Error,
+                    "There's no answer to keep. Use `/compare` to compare models first.",
+                ));
+                return false;
+            }
+        };
+
+        let idx = args
+            .first()
+            .and_then(|arg| return arg.parse::<usize>().ok())
+            .filter(|idx| return (1..=answers_len).contains(idx));
+        let idx = match idx {
+            Some(idx) => idx,
+            None => {
+                self.add_message(Message::new_with_type(
+                    Author::Oatmeal,
+                    MessageType::Error,
+                    &format!("You must pick an answer between 1 and {answers_len} with `/keep N`."),
+                ));
+                return false;
+            }
+        };
+
+        let answer = self.comparison.take().unwrap().answers.remove(idx - 1);
+        self.compare_targets = vec![];
+
+        // The answer replies to the compared message, so it's placed right
+        // after it rather than after any `/keep` commands.
+        let prompt_idx = self.messages.iter().rposition(|message| {
+            return message.author == Author::User && SlashCommand::parse(&message.text).is_none();
+        });
+        match prompt_idx {
+            Some(idx) => self.messages.insert(idx + 1, answer.message),
+            None => self.messages.push(answer.message),
+        }
+        self.bubble_list.clear_cache();
+        self.sync_dependants();
+        self.codeblocks.replace_from_messages(&self.messages);
+
+        return true;
+    }
+
+    /// Pins or unpins the last message sent to the model, skipping slash
+    /// commands and messages from Oatmeal.
+    fn toggle_pin(&mut self) {
+        let mut last_idx = None;
+        let mut in_slash_command = false;
+        // The `/pin` command itself is the last message.
+        for (idx, message) in self.messages[..self.messages.len().saturating_sub(1)]
+            .iter()
+            .enumerate()
+        {
+            if message.author == Author::User {
+                in_slash_command = SlashCommand::parse(&message.text).is_some();
+            }
+            if !in_slash_command
+                && message.author != Author::Oatmeal
+                && message.message_type() == MessageType::Normal
+            {
+                last_idx = Some(idx);
+            }
+        }
+
+        let message = match last_idx {
+            Some(idx) => &mut self.messages[idx],
+            None => {
+                self.add_message(Message::new_with_type(
+                    Author::Oatmeal,
+                    MessageType::Error,
+                    "There's no message to pin yet.",
+                ));
+                return;
+            }
+        };
+
+        message.pinned = !message.pinned;
+        let text = if message.pinned {
+            "Pinned the last message. It's kept when the conversation no longer fits the model's context window."
+        } else {
+            "Unpinned the last message."
+        };
+        self.add_message(Message::new(Author::Oatmeal, text));
+    }
+
+    /// Adds a summary of the conversation from the model. A summary made
+    /// before answering a prompt covers the messages before that prompt, so
+    /// it's placed ahead of it.
+    pub fn add_summary(&mut self, summary: Message) {
+        if let Some(usage) = summary.usage {
+            self.usage
+                .entry(Config::get(ConfigKey::Model))
+                .or_default()
+                .add(&usage);
+        }
+
+        let prompt_idx = self
+            .messages
+            .iter()
+            .rposition(|message| return message.author == Author::User);
+        match prompt_idx {
+            Some(idx) if SlashCommand::parse(&self.messages[idx].text).is_none() => {
+                self.messages.insert(idx, summary);
+                self.bubble_list.clear_cache();
+            }
+            _ => self.messages.push(summary),
+        }
+
+        self.sync_dependants();
+        self.codeblocks.replace_from_messages(&self.messages);
+    }
+
+    /// Asks the user to approve a tool call from the model. Only one call
+    /// waits on approval at a time, so a call still waiting is denied.
+    pub fn request_tool_approval(&mut self, approval: ToolApproval) {
+        if let Some(previous) = self.pending_tool_approval.take() {
+            previous.respond(false);
+        }
+
+        self.add_message(Message::new(
+            Author::Oatmeal,
+            &format!(
+                "{} wants to run `{}`. Press Y to allow, or N to deny.",
+                Config::get(ConfigKey::Model),
+                approval.call.summary()
+            ),
+        ));
+        self.pending_tool_approval = Some(approval);
+    }
+
+    pub fn respond_tool_approval(&mut self, approved: bool) {
+        if let Some(approval) = self.pending_tool_approval.take() {
+            let verb = if approved { "Allowed" } else { "Denied" };
+            self.add_message(Message::new(
+                Author::Oatmeal,
+                &format!("{verb} `{}`.", approval.call.summary()),
+            ));
+            approval.respond(approved);
+        }
+    }
+
+    /// Queues a file to be sent with the next message.
+    pub fn add_attachment(&mut self, attachment: Attachment) {
+        let chip = attachment.chip();
+        self.pending_attachments.push(attachment);
+        self.add_message(Message::new(
+            Author::Oatmeal,
+            &format!("Attached {chip} to your next message."),
+        ));
+    }
+
+    /// Takes the pending attachments for a message, unless it's a slash
+    /// command.
+    pub fn take_attachments(&mut self, input_str: &str) -> Vec<Attachment> {
+        if SlashCommand::parse(input_str).is_some() {
+            return vec![];
+        }
+
+        return self.pending_attachments.drain(..).collect();
+    }
+
+    fn set_generation_option(&mut self, args: &[String]) {
+        if args.is_empty() {
+            let options = GENERATION_OPTION_KEYS
+                .iter()
+                .map(|key| {
+                    let mut value = Config::get(*key);
+                    if value.is_empty() {
+                        value = "(backend default)".to_string();
+                    }
+                    return format!("- {key}: {value}");
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            self.add_message(Message::new(
+                Author::Oatmeal,
+                &format!("Generation options:\n\n{options}"),
+            ));
+            return;
+        }
+
+        let key = match GenerationOptions::parse_key(&args[0]) {
+            Some(key) => key,
+            None => {
+                let names = GENERATION_OPTION_KEYS
+                    .iter()
+                    .map(|key| return key.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                self.add_message(Message::new_with_type(
+                    Author::Oatmeal,
+                    MessageType::Error,
+                    &format!(
+                        "No generation option named {} exists. Possible values are: {names}",
+                        args[0]
+                    ),
+                ));
+                return;
+            }
+        };
+
+        let value = args[1..].join(" ");
+        let res = GenerationOptions::from_config().and_then(|mut options| {
+            return options.set(key, &value);
+        });
+        if let Err(err) = res {
+            self.add_message(Message::new_with_type(
+                Author::Oatmeal,
+                MessageType::Error,
+                &format!("There was an error trying to parse your command:\n\n{err}"),
+            ));
+            return;
+        }
+
+        Config::set(key, &value);
+        if value.trim().is_empty() {
+            self.add_message(Message::new(
+                Author::Oatmeal,
+                &format!("Reset {key} to the backend default."),
+            ));
+        } else {
+            self.add_message(Message::new(
+                Author::Oatmeal,
+                &format!("Set {key} to {value}."),
+            ));
+        }
+    }
+
+    fn usage_report(&self) -> String {
+        let total = Usage::total(&self.usage);
+        if total.total_tokens() == 0 {
+            return "No token usage has been reported by the backend for this session yet."
+                .to_string();
+        }
+
+        let format_cost = |cost: Option<f64>| {
+            if let Some(cost) = cost {
+                return format!(", ~${cost:.4}");
+            }
+            return "".to_string();
+        };
+
+        let models = self
+            .usage
+            .iter()
+            .map(|(model, usage)| {
+                let cost = Config::get_price(model).map(|price| return usage.cost(&price));
+                return format!(
+                    "- {model}: {} prompt tokens, {} completion tokens{}",
+                    usage.prompt_tokens,
+                    usage.completion_tokens,
+                    format_cost(cost)
+                );
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let mut report = format!(
+            "Session usage:\n\n{models}\n\nTotal: {} tokens{}",
+            total.total_tokens(),
+            format_cost(Usage::estimated_cost(&self.usage))
+        );
+
+        if let Some(usage) = self.messages.iter().rev().find_map(|e| return e.usage) {
+            report += &format!(
+                "\nLast response: {} prompt tokens, {} completion tokens",
+                usage.prompt_tokens, usage.completion_tokens
+            );
+        }
+
+        return report;
     }
 
     pub fn set_rect(&mut self, rect: Rect) {
@@ -290,6 +1072,8 @@ impl<'a> AppState<'a> {
     fn sync_dependants(&mut self) {
         self.bubble_list
             .set_messages(&self.messages, self.last_known_width);
+        self.bubble_list
+            .set_comparison(self.comparison.as_ref(), self.last_known_width);
 
         let scrollbar_at_bottom = self.scroll.is_position_at_last();
         self.scroll
@@ -304,9 +1088,9 @@ impl<'a> AppState<'a> {
         self.sessions_service
             .save(
                 &self.session_id,
-                &self.backend_context,
                 &self.editor_context,
                 &self.messages,
+                &self.usage,
             )
             .await?;
 
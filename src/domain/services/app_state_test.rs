@@ -1,18 +1,33 @@
 use anyhow::bail;
 use anyhow::Result;
+use serde_json::json;
 use test_utils::codeblock_fixture;
 use test_utils::insta_snapshot;
 use tokio::sync::mpsc;
 
 use super::AppState;
+use crate::configuration::Config;
 use crate::domain::models::AcceptType;
 use crate::domain::models::Action;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::BackendName;
 use crate::domain::models::BackendResponse;
+use crate::domain::models::CompareTarget;
+use crate::domain::models::Comparison;
 use crate::domain::models::EditorName;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
 use crate::domain::models::Message;
 use crate::domain::models::MessageType;
+use crate::domain::models::ModelPrice;
+use crate::domain::models::Persona;
+use crate::domain::models::SlashCommand;
+use crate::domain::models::ToolApproval;
+use crate::domain::models::ToolCall;
+use crate::domain::models::Usage;
+use crate::domain::models::UsageByModel;
 use crate::domain::services::AppStateProps;
 use crate::domain::services::BubbleList;
 use crate::domain::services::CodeBlocks;
@@ -26,17 +41,25 @@ impl Default for AppState<'static> {
     fn default() -> AppState<'static> {
         let theme = Themes::get("base16-onedark", "").unwrap();
         return AppState {
-            backend_context: "".to_string(),
+            aborting: false,
             bubble_list: BubbleList::new(theme),
             codeblocks: CodeBlocks::default(),
+            compare_targets: vec![],
+            comparison: None,
+            editing_message: None,
             editor_context: None,
             exit_warning: false,
             last_known_height: 300,
             last_known_width: 100,
             messages: vec![],
+            pending_attachments: vec![],
+            pending_sources: vec![],
+            pending_tool_approval: None,
             session_id: "test".to_string(),
             scroll: Scroll::default(),
+            selected_message: None,
             sessions_service: Sessions::default(),
+            usage: UsageByModel::new(),
             waiting_for_backend: false,
         };
     }
@@ -202,7 +225,242 @@ mod handle_slash_commands {
         assert!(!should_break);
         assert!(!should_continue);
         assert!(!app_state.waiting_for_backend);
-        assert!(app_state.backend_context.is_empty());
+        assert_eq!(
+            HistoryMessage::from_messages(&app_state.messages),
+            vec![HistoryMessage::new(HistoryRole::User, "Hello world")]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_switches_persona() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+        Config::set_persona(Persona::new("tester", "You are a test."));
+
+        let (should_break, should_continue) =
+            app_state.handle_slash_commands("/persona tester", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert!(!app_state.waiting_for_backend);
+        assert_eq!(last_message.author, Author::Oatmeal);
+        insta::assert_snapshot!(last_message.text, @"Switched to persona tester.");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_returns_error_message_on_unknown_persona() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        let (should_break, should_continue) =
+            app_state.handle_slash_commands("/persona not-a-real-persona", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert_eq!(last_message.author, Author::Oatmeal);
+        assert_eq!(last_message.message_type(), MessageType::Error);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_sets_generation_option() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        let (should_break, should_continue) =
+            app_state.handle_slash_commands("/set top-p 0.5", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert!(!app_state.waiting_for_backend);
+        assert_eq!(last_message.author, Author::Oatmeal);
+        insta::assert_snapshot!(last_message.text, @"Set top-p to 0.5.");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_returns_error_message_on_invalid_generation_option() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        let (should_break, should_continue) =
+            app_state.handle_slash_commands("/set top-p 5", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert_eq!(last_message.author, Author::Oatmeal);
+        assert_eq!(last_message.message_type(), MessageType::Error);
+        insta::assert_snapshot!(last_message.text, @r###"
+        There was an error trying to parse your command:
+
+        top-p must be a number between 0 and 1, got 5
+        "###);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_returns_error_message_on_unknown_generation_option() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        app_state.handle_slash_commands("/set creativity 100", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert_eq!(last_message.author, Author::Oatmeal);
+        assert_eq!(last_message.message_type(), MessageType::Error);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_reports_usage() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+        Config::set_price(
+            "usage-report-priced",
+            ModelPrice {
+                input: 2.5,
+                output: 10.0,
+            },
+        );
+        app_state
+            .usage
+            .insert("usage-report-priced".to_string(), Usage::new(1000, 200));
+        app_state
+            .usage
+            .insert("usage-report-unpriced".to_string(), Usage::new(50, 10));
+        let mut message = Message::new(Author::Model, "Hi!");
+        message.usage = Some(Usage::new(50, 10));
+        app_state.messages.push(message);
+
+        let (should_break, should_continue) = app_state.handle_slash_commands("/usage", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert_eq!(last_message.author, Author::Oatmeal);
+        insta::assert_snapshot!(last_message.text, @r###"
+        Session usage:
+
+        - usage-report-priced: 1000 prompt tokens, 200 completion tokens, ~$0.0045
+        - usage-report-unpriced: 50 prompt tokens, 10 completion tokens
+
+        Total: 1260 tokens, ~$0.0045
+        Last response: 50 prompt tokens, 10 completion tokens
+        "###);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_reports_no_usage() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        app_state.handle_slash_commands("/usage", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        insta::assert_snapshot!(last_message.text, @"No token usage has been reported by the backend for this session yet.");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_lists_personas() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        let (should_break, should_continue) = app_state.handle_slash_commands("/persona", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert_eq!(last_message.author, Author::Oatmeal);
+        assert!(last_message.text.contains("- default"));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_attaches_files_to_the_next_message() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+        let file = tempfile::Builder::new().suffix(".txt").tempfile()?;
+        std::fs::write(file.path(), "Hello from a file")?;
+
+        let (should_break, should_continue) =
+            app_state.handle_slash_commands(&format!("/attach {}", file.path().display()), &tx)?;
+        let file_name = file.path().file_name().unwrap().to_string_lossy();
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert_eq!(app_state.pending_attachments.len(), 1);
+        assert_eq!(
+            app_state.messages.last().unwrap().text,
+            format!("Attached [file: {file_name}] to your next message.")
+        );
+
+        assert!(app_state.take_attachments("/model").is_empty());
+        assert_eq!(app_state.pending_attachments.len(), 1);
+
+        let attachments = app_state.take_attachments("What's in this file?");
+        assert_eq!(attachments.len(), 1);
+        assert!(app_state.pending_attachments.is_empty());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_reports_missing_attachments() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        let (should_break, should_continue) =
+            app_state.handle_slash_commands("/at /oatmeal/does/not/exist.png", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert!(app_state.pending_attachments.is_empty());
+        assert_eq!(last_message.message_type(), MessageType::Error);
+        insta::assert_snapshot!(last_message.text, @r###"
+        There was an error trying to attach your file:
+
+        File /oatmeal/does/not/exist.png does not exist
+        "###);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_lists_pending_attachments() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+        app_state.pending_attachments.push(Attachment {
+            kind: AttachmentKind::Image,
+            path: "/tmp/cat.png".to_string(),
+            mime_type: "image/png".to_string(),
+            content: "".to_string(),
+        });
+
+        app_state.handle_slash_commands("/attach", &tx)?;
+
+        insta::assert_snapshot!(app_state.messages.last().unwrap().text, @r###"
+        Attached to your next message:
+
+        - [image: cat.png]
+        "###);
 
         return Ok(());
     }
@@ -220,8 +478,10 @@ mod handle_backend_response {
         let backend_response = BackendResponse {
             author: Author::Model,
             text: "All done!".to_string(),
+            reasoning: "".to_string(),
             done: true,
-            context: Some("icanrememberthingsnow".to_string()),
+            usage: None,
+            truncated: false,
         };
         app_state.handle_backend_response(backend_response);
 
@@ -229,24 +489,152 @@ mod handle_backend_response {
     }
 
     #[test]
-    fn it_handles_bad_backend_response() {
+    fn it_attaches_sources_to_the_response() {
+        let mut app_state = AppState::default();
+        app_state
+            .messages
+            .push(Message::new(Author::User, "Where does it start?"));
+        app_state.set_sources(vec!["src/main.rs:1-40".to_string()]);
+        for (text, done) in [("In ", false), ("main.", true)] {
+            app_state.handle_backend_response(BackendResponse {
+                author: Author::Model,
+                text: text.to_string(),
+                reasoning: "".to_string(),
+                done,
+                usage: None,
+                truncated: false,
+            });
+        }
+
+        assert_eq!(app_state.messages[0].sources, Vec::<String>::new());
+        assert_eq!(app_state.messages[1].sources, vec!["src/main.rs:1-40"]);
+        assert!(app_state.pending_sources.is_empty());
+    }
+
+    #[test]
+    fn it_keeps_reasoning_apart_from_the_answer() {
+        let mut app_state = AppState::default();
+        app_state
+            .messages
+            .push(Message::new(Author::User, "Do something for me!"));
+        for (text, reasoning, done) in [
+            ("", "They want ", false),
+            ("", "something.", false),
+            ("```\nAll done!\n```", "", true),
+        ] {
+            app_state.handle_backend_response(BackendResponse {
+                author: Author::Model,
+                text: text.to_string(),
+                reasoning: reasoning.to_string(),
+                done,
+                usage: None,
+                truncated: false,
+            });
+        }
+
+        assert_eq!(app_state.messages.len(), 2);
+        assert_eq!(app_state.messages[1].text, "```\nAll done!\n```");
+        assert_eq!(app_state.messages[1].reasoning, "They want something.");
+        assert_eq!(
+            app_state
+                .codeblocks
+                .blocks_from_slash_commands(&SlashCommand::parse("/copy 1").unwrap())
+                .unwrap(),
+            "All done!"
+        );
+    }
+
+    #[test]
+    fn it_handles_backend_response_after_status_message() {
         let mut app_state = AppState::default();
         app_state
             .messages
             .push(Message::new(Author::User, "Do something for me!"));
+        app_state.messages.push(Message::new(
+            Author::Oatmeal,
+            "openai responded with 429 Too Many Requests. Retrying in 0.5s (attempt 2 of 3)...",
+        ));
         let backend_response = BackendResponse {
             author: Author::Model,
             text: "All done!".to_string(),
+            reasoning: "".to_string(),
             done: true,
-            context: Some("".to_string()),
+            usage: None,
+            truncated: false,
         };
         app_state.handle_backend_response(backend_response);
 
         assert_eq!(app_state.messages.len(), 3);
+        assert_eq!(app_state.messages[2].author, Author::Model);
+        assert_eq!(app_state.messages[2].text, "All done!");
+    }
+
+    #[test]
+    fn it_marks_cancelled_backend_responses_as_truncated() {
+        let mut app_state = AppState::default();
+        app_state
+            .messages
+            .push(Message::new(Author::User, "Do something for me!"));
+
+        for (text, done, truncated) in [("All do", false, false), ("", true, true)] {
+            app_state.handle_backend_response(BackendResponse {
+                author: Author::Model,
+                text: text.to_string(),
+                reasoning: "".to_string(),
+                done,
+                usage: None,
+                truncated,
+            });
+        }
+
+        assert_eq!(app_state.messages.len(), 2);
+        assert_eq!(app_state.messages[1].text, "All do");
+        assert!(app_state.messages[1].truncated);
+        assert!(!app_state.waiting_for_backend);
+    }
+
+    #[test]
+    fn it_ignores_cancelled_backend_responses_without_text() {
+        let mut app_state = AppState::default();
+        app_state
+            .messages
+            .push(Message::new(Author::User, "Do something for me!"));
+        app_state.handle_backend_response(BackendResponse {
+            author: Author::Model,
+            text: "".to_string(),
+            reasoning: "".to_string(),
+            done: true,
+            usage: None,
+            truncated: true,
+        });
+
+        assert_eq!(app_state.messages.len(), 1);
+    }
+
+    #[test]
+    fn it_handles_backend_response_with_usage() {
+        let mut app_state = AppState::default();
+        app_state
+            .messages
+            .push(Message::new(Author::User, "Do something for me!"));
+
+        for _ in 0..2 {
+            let backend_response = BackendResponse {
+                author: Author::Model,
+                text: "All done!".to_string(),
+                reasoning: "".to_string(),
+                done: true,
+                usage: Some(Usage::new(10, 5)),
+                truncated: false,
+            };
+            app_state.handle_backend_response(backend_response);
+        }
+
         assert_eq!(
-            app_state.messages.last().unwrap().message_type(),
-            MessageType::Error
+            app_state.messages.last().unwrap().usage,
+            Some(Usage::new(10, 5))
         );
+        assert_eq!(Usage::total(&app_state.usage), Usage::new(20, 10));
     }
 }
 
@@ -256,7 +644,7 @@ mod init {
 
     #[tokio::test]
     async fn it_inits_and_reloads_from_session() -> Result<()> {
-        let backend = BackendManager::get(BackendName::Ollama)?;
+        let backend = BackendManager::get(BackendName::Ollama).await?;
         let editor = EditorManager::get(EditorName::None)?;
         let sessions_dir = tempfile::tempdir()?.into_path();
 
@@ -273,7 +661,7 @@ mod init {
         app_state.save_session().await?;
 
         let session_id = app_state.session_id;
-        let backend = BackendManager::get(BackendName::Ollama)?;
+        let backend = BackendManager::get(BackendName::Ollama).await?;
         let editor = EditorManager::get(EditorName::None)?;
 
         AppState::new(AppStateProps {
@@ -291,3 +679,556 @@ mod init {
         return Ok(());
     }
 }
+
+mod tool_approval {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_asks_to_approve_tool_calls() -> Result<()> {
+        let mut app_state = AppState::default();
+        let call = ToolCall::new("1", "read_file", json!({"path": "src/main.rs"}));
+        let (approval, rx) = ToolApproval::new(call);
+
+        app_state.request_tool_approval(approval);
+
+        assert!(app_state.pending_tool_approval.is_some());
+        assert!(app_state.messages.last().unwrap().text.ends_with(
+            "wants to run `read_file(path: \"src/main.rs\")`. Press Y to allow, or N to deny."
+        ));
+
+        app_state.respond_tool_approval(true);
+
+        assert!(app_state.pending_tool_approval.is_none());
+        assert_eq!(
+            app_state.messages.last().unwrap().text,
+            "Allowed `read_file(path: \"src/main.rs\")`."
+        );
+        assert!(rx.await?);
+
+        return Ok(());
+    }
+
+    #[tokio::test]
+    async fn it_denies_calls_replaced_by_a_new_request() -> Result<()> {
+        let mut app_state = AppState::default();
+        let (first, first_rx) = ToolApproval::new(ToolCall::new("1", "run_tests", json!({})));
+        let (second, _second_rx) = ToolApproval::new(ToolCall::new("2", "run_tests", json!({})));
+
+        app_state.request_tool_approval(first);
+        app_state.request_tool_approval(second);
+
+        assert!(!first_rx.await?);
+        assert_eq!(app_state.pending_tool_approval.unwrap().call.id, "2");
+
+        return Ok(());
+    }
+}
+
+mod context_window {
+    use super::*;
+
+    #[test]
+    fn it_pins_the_last_message() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState {
+            messages: vec![
+                Message::new(Author::User, "Remember the number 42."),
+                Message::new(Author::Model, "I will."),
+                Message::new(Author::User, "/persona default"),
+                Message::new(Author::Oatmeal, "Switched to persona default."),
+                Message::new(Author::User, "/pin"),
+            ],
+            ..AppState::default()
+        };
+
+        let (should_break, should_continue) = app_state.handle_slash_commands("/pin", &tx)?;
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert!(app_state.messages[1].pinned);
+        assert_eq!(app_state.messages.last().unwrap().author, Author::Oatmeal);
+
+        app_state.add_message(Message::new(Author::User, "/pin"));
+        app_state.handle_slash_commands("/pin", &tx)?;
+        assert!(!app_state.messages[1].pinned);
+        assert_eq!(
+            app_state.messages.last().unwrap().text,
+            "Unpinned the last message."
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_returns_error_message_with_nothing_to_pin() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState {
+            messages: vec![Message::new(Author::User, "/pin")],
+            ..AppState::default()
+        };
+
+        app_state.handle_slash_commands("/pin", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert_eq!(last_message.message_type(), MessageType::Error);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_continues_the_last_response() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState {
+            messages: vec![
+                Message::new(Author::User, "Hello"),
+                Message::new(Author::Model, "Hi th"),
+                Message::new(Author::User, "/continue"),
+            ],
+            ..AppState::default()
+        };
+
+        let (should_break, should_continue) = app_state.handle_slash_commands("/continue", &tx)?;
+
+        assert!(!should_break);
+        assert!(!should_continue);
+        assert_eq!(app_state.messages.len(), 3);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_returns_error_message_with_nothing_to_continue() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState {
+            messages: vec![
+                Message::new(Author::User, "Hello"),
+                Message::new(Author::User, "/continue"),
+            ],
+            ..AppState::default()
+        };
+
+        let (_, should_continue) = app_state.handle_slash_commands("/continue", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(should_continue);
+        assert_eq!(last_message.message_type(), MessageType::Error);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_adds_summaries_before_the_prompt_being_answered() {
+        let mut app_state = AppState {
+            messages: vec![
+                Message::new(Author::User, "Hello"),
+                Message::new(Author::Model, "Hi there"),
+                Message::new(Author::User, "How are you?"),
+                Message::new(Author::Oatmeal, "Summarizing..."),
+            ],
+            ..AppState::default()
+        };
+        let mut summary = Message::new_summary("The user said hello.");
+        summary.usage = Some(Usage::new(20, 5));
+
+        app_state.add_summary(summary);
+
+        assert!(app_state.messages[2].summary);
+        assert_eq!(app_state.messages[3].text, "How are you?");
+        assert_eq!(Usage::total(&app_state.usage), Usage::new(20, 5));
+        assert_eq!(
+            HistoryMessage::from_messages(&app_state.messages)
+                .last()
+                .unwrap()
+                .text,
+            "How are you?"
+        );
+    }
+
+    #[test]
+    fn it_adds_summaries_after_compact() {
+        let mut app_state = AppState {
+            messages: vec![
+                Message::new(Author::User, "Hello"),
+                Message::new(Author::Model, "Hi there"),
+                Message::new(Author::User, "/compact"),
+            ],
+            ..AppState::default()
+        };
+
+        app_state.add_summary(Message::new_summary("The user said hello."));
+
+        assert!(app_state.messages.last().unwrap().summary);
+    }
+}
+
+mod compare {
+    use super::*;
+
+    fn compare_response(text: &str, done: bool) -> BackendResponse {
+        return BackendResponse {
+            author: Author::Model,
+            text: text.to_string(),
+            reasoning: "".to_string(),
+            done,
+            usage: None,
+            truncated: false,
+        };
+    }
+
+    fn compared_app_state() -> Result<AppState<'static>> {
+        let compare_targets = CompareTarget::parse_list("openai:gpt-4o,ollama:llama3")?;
+        let mut app_state = AppState {
+            messages: vec![Message::new(Author::User, "Write a haiku")],
+            compare_targets,
+            waiting_for_backend: true,
+            ..AppState::default()
+        };
+        app_state.start_comparison();
+
+        return Ok(app_state);
+    }
+
+    #[test]
+    fn it_starts_compare_mode() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        let (should_break, should_continue) =
+            app_state.handle_slash_commands("/compare openai:gpt-4o,ollama:llama3", &tx)?;
+
+        assert!(!should_break);
+        assert!(should_continue);
+        assert_eq!(app_state.compare_targets.len(), 2);
+        assert!(app_state
+            .messages
+            .last()
+            .unwrap()
+            .text
+            .starts_with("Comparing openai:gpt-4o, ollama:llama3."));
+
+        app_state.handle_slash_commands("/compare off", &tx)?;
+        assert!(app_state.compare_targets.is_empty());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_returns_error_message_for_invalid_targets() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = AppState::default();
+
+        app_state.handle_slash_commands("/compare openai:gpt-4o", &tx)?;
+        let last_message = app_state.messages.last().unwrap();
+
+        assert!(app_state.compare_targets.is_empty());
+        assert_eq!(last_message.message_type(), MessageType::Error);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_streams_answers_side_by_side() -> Result<()> {
+        let mut app_state = compared_app_state()?;
+        let lines_len = app_state.bubble_list.len();
+
+        app_state.handle_compare_response(
+            0,
+            compare_response("Autumn moonlight\nA worm digs silently", false),
+        );
+        app_state.handle_compare_response(1, compare_response("An old silent pond", false));
+        assert!(app_state.bubble_list.len() > lines_len);
+        assert!(app_state.waiting_for_backend);
+
+        let mut done = compare_response("", true);
+        done.usage = Some(Usage::new(10, 20));
+        app_state.handle_compare_response(0, done);
+        app_state.handle_compare_error(1, "The backend failed");
+
+        assert!(!app_state.waiting_for_backend);
+        assert_eq!(app_state.messages.len(), 1);
+        assert_eq!(
+            app_state.usage.get("gpt-4o").copied(),
+            Some(Usage::new(10, 20))
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_rejects_messages_until_an_answer_is_kept() -> Result<()> {
+        let mut app_state = compared_app_state()?;
+
+        assert!(app_state.reject_during_comparison("Another haiku"));
+        assert!(!app_state.reject_during_comparison("/keep 1"));
+        assert_eq!(
+            app_state.messages.last().unwrap().message_type(),
+            MessageType::Error
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_keeps_an_answer() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = compared_app_state()?;
+        app_state.handle_compare_response(0, compare_response("Autumn moonlight", true));
+        app_state.handle_compare_response(1, compare_response("An old silent pond", true));
+
+        app_state.add_message(Message::new(Author::User, "/keep 3"));
+        let (_, should_continue) = app_state.handle_slash_commands("/keep 3", &tx)?;
+        assert!(should_continue);
+        assert!(app_state.comparison.is_some());
+
+        app_state.add_message(Message::new(Author::User, "/keep 2"));
+        let (_, should_continue) = app_state.handle_slash_commands("/keep 2", &tx)?;
+
+        assert!(!should_continue);
+        assert!(app_state.comparison.is_none());
+        assert!(app_state.compare_targets.is_empty());
+        assert_eq!(
+            HistoryMessage::from_messages(&app_state.messages),
+            vec![
+                HistoryMessage::new(HistoryRole::User, "Write a haiku"),
+                HistoryMessage::new(HistoryRole::Assistant, "An old silent pond"),
+            ]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_waits_for_every_answer_before_keeping_one() -> Result<()> {
+        let (tx, _rx) = mpsc::unbounded_channel::<Action>();
+        let mut app_state = compared_app_state()?;
+        app_state.comparison = Some(Comparison::new(&app_state.compare_targets));
+        app_state.handle_compare_response(0, compare_response("Autumn moonlight", true));
+
+        app_state.add_message(Message::new(Author::User, "/keep 1"));
+        let (_, should_continue) = app_state.handle_slash_commands("/keep 1", &tx)?;
+
+        assert!(should_continue);
+        assert_eq!(
+            app_state.messages.last().unwrap().message_type(),
+            MessageType::Error
+        );
+
+        return Ok(());
+    }
+}
+
+mod regenerate {
+    use super::*;
+
+    fn respond(app_state: &mut AppState, text: &str) {
+        app_state.handle_backend_response(BackendResponse {
+            author: Author::Model,
+            text: text.to_string(),
+            reasoning: "".to_string(),
+            done: true,
+            usage: None,
+            truncated: false,
+        });
+    }
+
+    fn app_state_with_answer() -> AppState<'static> {
+        return AppState {
+            messages: vec![
+                Message::new(Author::User, "Hello"),
+                Message::new(Author::Model, "Hi"),
+                Message::new(Author::User, "Tell me a joke"),
+                Message::new(Author::Model, "First joke"),
+            ],
+            ..AppState::default()
+        };
+    }
+
+    #[test]
+    fn it_regenerates_the_last_response() -> Result<()> {
+        let mut app_state = app_state_with_answer();
+
+        let (prompt, history) = match app_state.regenerate() {
+            Some(res) => res,
+            None => bail!("Expected a prompt to regenerate"),
+        };
+        assert_eq!(prompt.text, "Tell me a joke");
+        assert_eq!(
+            history,
+            vec![
+                HistoryMessage::new(HistoryRole::User, "Hello"),
+                HistoryMessage::new(HistoryRole::Assistant, "Hi"),
+            ]
+        );
+
+        respond(&mut app_state, "Second joke");
+
+        assert_eq!(app_state.messages.len(), 4);
+        let message = &app_state.messages[3];
+        assert_eq!(message.text, "Second joke");
+        assert_eq!(message.alternates[0].text, "First joke");
+        assert_eq!(message.alternate_idx, 1);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_does_not_regenerate_without_a_response() {
+        let mut app_state = app_state_with_answer();
+        app_state
+            .messages
+            .push(Message::new(Author::User, "/modellist"));
+        app_state
+            .messages
+            .push(Message::new(Author::Oatmeal, "Models: ..."));
+
+        assert!(app_state.regenerate().is_none());
+
+        let mut app_state = AppState::default();
+        assert!(app_state.regenerate().is_none());
+    }
+
+    #[test]
+    fn it_sends_the_selected_response_in_the_history() {
+        let mut app_state = app_state_with_answer();
+        app_state.regenerate();
+        respond(&mut app_state, "Second joke");
+
+        assert!(app_state.cycle_alternates(false));
+        assert_eq!(app_state.messages[3].text, "First joke");
+
+        let history = HistoryMessage::from_messages(&app_state.messages);
+        assert_eq!(history.last().unwrap().text, "First joke");
+    }
+
+    #[test]
+    fn it_restores_the_previous_response_when_regenerating_fails() {
+        let mut app_state = app_state_with_answer();
+        app_state.regenerate();
+        app_state.discard_empty_response();
+
+        assert_eq!(app_state.messages[3].text, "First joke");
+        assert!(app_state.messages[3].alternates.is_empty());
+        assert!(!app_state.cycle_alternates(true));
+    }
+}
+
+mod edit_message {
+    use super::*;
+
+    fn app_state_with_conversation() -> AppState<'static> {
+        return AppState {
+            messages: vec![
+                Message::new(Author::User, "Hello"),
+                Message::new(Author::Model, "Hi"),
+                Message::new(Author::User, "/modellist"),
+                Message::new(Author::Oatmeal, "Models: ..."),
+                Message::new(Author::User, "Tell me a joke"),
+                Message::new(Author::Model, "A joke"),
+            ],
+            ..AppState::default()
+        };
+    }
+
+    #[test]
+    fn it_selects_previous_messages_from_the_user() {
+        let mut app_state = app_state_with_conversation();
+        assert!(!app_state.select_message(false));
+        assert_eq!(app_state.selected_message, None);
+
+        assert!(app_state.select_message(true));
+        assert_eq!(app_state.selected_message, Some(4));
+        assert!(app_state.select_message(true));
+        assert_eq!(app_state.selected_message, Some(0));
+        assert!(app_state.select_message(true));
+        assert_eq!(app_state.selected_message, Some(0));
+
+        assert!(app_state.select_message(false));
+        assert_eq!(app_state.selected_message, Some(4));
+        assert!(app_state.select_message(false));
+        assert_eq!(app_state.selected_message, None);
+
+        let mut app_state = AppState::default();
+        assert!(!app_state.select_message(true));
+    }
+
+    #[test]
+    fn it_forks_from_the_edited_message() -> Result<()> {
+        let mut app_state = app_state_with_conversation();
+        app_state.messages[0].attachments = vec![Attachment {
+            kind: AttachmentKind::Text,
+            path: "notes.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            content: "".to_string(),
+        }];
+        app_state.select_message(true);
+        app_state.select_message(true);
+
+        let message = match app_state.edit_selected_message() {
+            Some(message) => message,
+            None => bail!("Expected the selected message to be edited"),
+        };
+        assert_eq!(message.text, "Hello");
+        assert_eq!(app_state.editing_message, Some(0));
+        assert_eq!(app_state.take_attachments("Bonjour").len(), 1);
+
+        app_state.fork_from_edited_message(Message::new(Author::User, "Bonjour"));
+
+        assert_eq!(app_state.messages.len(), 1);
+        assert_eq!(app_state.messages[0].text, "Bonjour");
+        assert_eq!(app_state.messages[0].branches[0].messages.len(), 6);
+        assert_eq!(app_state.editing_message, None);
+        assert_eq!(app_state.selected_message, None);
+        assert!(HistoryMessage::from_messages(&app_state.messages[..0]).is_empty());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn it_adds_messages_when_not_editing() {
+        let mut app_state = app_state_with_conversation();
+        app_state.fork_from_edited_message(Message::new(Author::User, "Another"));
+
+        assert_eq!(app_state.messages.len(), 7);
+        assert!(app_state.messages[6].branches.is_empty());
+    }
+
+    #[test]
+    fn it_cancels_editing() {
+        let mut app_state = app_state_with_conversation();
+        app_state.select_message(true);
+        app_state.edit_selected_message();
+
+        app_state.cancel_selection();
+
+        assert_eq!(app_state.selected_message, None);
+        assert_eq!(app_state.editing_message, None);
+        assert!(app_state.pending_attachments.is_empty());
+    }
+
+    #[test]
+    fn it_switches_between_branches_of_the_selected_message() {
+        let mut app_state = app_state_with_conversation();
+        app_state.select_message(true);
+        app_state.edit_selected_message();
+        app_state.fork_from_edited_message(Message::new(Author::User, "Tell me a poem"));
+        app_state.handle_backend_response(BackendResponse {
+            author: Author::Model,
+            text: "A poem".to_string(),
+            reasoning: "".to_string(),
+            done: true,
+            usage: None,
+            truncated: false,
+        });
+
+        assert!(!app_state.cycle_branches(true));
+
+        app_state.select_message(true);
+        assert!(app_state.cycle_branches(false));
+        assert_eq!(app_state.messages[4].text, "Tell me a joke");
+        assert_eq!(app_state.messages[5].text, "A joke");
+        assert_eq!(app_state.selected_message, Some(4));
+
+        assert!(app_state.cycle_branches(true));
+        assert_eq!(app_state.messages[5].text, "A poem");
+    }
+}
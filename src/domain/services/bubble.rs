@@ -3,6 +3,7 @@
 mod tests;
 
 use ratatui::style::Color;
+use ratatui::style::Modifier;
 use ratatui::style::Style;
 use ratatui::text::Line;
 use ratatui::text::Span;
@@ -26,6 +27,9 @@ pub struct Bubble<'a> {
     message: &'a Message,
     window_max_width: usize,
     codeblock_counter: usize,
+    title: Option<String>,
+    selected: bool,
+    reasoning_expanded: bool,
 }
 
 pub struct BubbleConfig {
@@ -64,9 +68,77 @@ impl<'a> Bubble<'_> {
             message,
             window_max_width,
             codeblock_counter,
+            title: None,
+            selected: false,
+            reasoning_expanded: false,
         };
     }
 
+    /// Shows a title in the top border instead of the author's name.
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        return self;
+    }
+
+    /// Highlights the border, for the message picked to be edited.
+    pub fn with_selected(mut self, selected: bool) -> Self {
+        self.selected = selected;
+        return self;
+    }
+
+    /// Shows the model's reasoning in full, instead of only its header.
+    pub fn with_reasoning_expanded(mut self, reasoning_expanded: bool) -> Self {
+        self.reasoning_expanded = reasoning_expanded;
+        return self;
+    }
+
+    /// The first line of the reasoning block, which is all that's shown of it
+    /// when collapsed.
+    fn reasoning_header(&self) -> Option<String> {
+        if self.message.reasoning.trim().is_empty() {
+            return None;
+        }
+        if self.reasoning_expanded {
+            return Some("- Reasoning (CTRL+T to collapse)".to_string());
+        }
+
+        return Some(format!(
+            "+ Reasoning, {} lines (CTRL+T to expand)",
+            self.reasoning_lines().count()
+        ));
+    }
+
+    fn reasoning_lines(&self) -> impl Iterator<Item = &str> {
+        return self.message.reasoning.trim().lines();
+    }
+
+    /// The author's name, followed by which response is shown when the
+    /// response was regenerated, or which branch is shown when the message
+    /// was edited.
+    fn username(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.to_string();
+        }
+
+        let author = self.message.author.to_string();
+        if self.message.responses_len() > 1 {
+            return format!(
+                "{author} ({}/{})",
+                self.message.alternate_idx + 1,
+                self.message.responses_len()
+            );
+        }
+        if self.message.branches_len() > 1 {
+            return format!(
+                "{author} ({}/{})",
+                self.message.branch_idx + 1,
+                self.message.branches_len()
+            );
+        }
+
+        return author;
+    }
+
     pub fn style_config() -> BubbleConfig {
         return BubbleConfig {
             // Unicode character border + padding.
@@ -86,6 +158,32 @@ impl<'a> Bubble<'_> {
 
         let max_line_length = self.get_max_line_length();
 
+        // Reasoning is dimmed, and never highlighted or numbered as code.
+        if let Some(header) = self.reasoning_header() {
+            let reasoning_style = Style {
+                fg: Some(Color::DarkGray),
+                add_modifier: Modifier::DIM,
+                ..Style::default()
+            };
+            self.push_wrapped_line(
+                &mut lines,
+                vec![Span::styled(header, reasoning_style)],
+                max_line_length,
+            );
+            if self.reasoning_expanded {
+                for line in self.reasoning_lines() {
+                    self.push_wrapped_line(
+                        &mut lines,
+                        vec![Span::styled(line.to_owned(), reasoning_style)],
+                        max_line_length,
+                    );
+                }
+            }
+            if !self.message.text.is_empty() {
+                self.push_wrapped_line(&mut lines, vec![], max_line_length);
+            }
+        }
+
         for line in self.message.text.lines() {
             let mut spans = vec![];
 
@@ -141,39 +239,88 @@ impl<'a> Bubble<'_> {
                 spans = vec![Span::styled(line.to_owned(), Style::default())];
             }
 
-            let mut split_spans = vec![];
-            let mut line_char_count = 0;
+            self.push_wrapped_line(&mut lines, spans, max_line_length);
+        }
 
-            for span in spans {
-                if span.content.len() + line_char_count <= max_line_length {
-                    line_char_count += span.content.len();
-                    split_spans.push(span);
-                    continue;
-                }
+        for attachment in &self.message.attachments {
+            let chip = attachment
+                .chip()
+                .chars()
+                .take(max_line_length)
+                .collect::<String>();
+            let spans = vec![Span::styled(
+                chip,
+                Style {
+                    fg: Some(Color::Cyan),
+                    ..Style::default()
+                },
+            )];
+            lines.push(self.spans_to_line(spans, max_line_length));
+        }
 
-                let mut word_set: Vec<&str> = vec![];
+        for chip in self.source_chips() {
+            let chip = chip.chars().take(max_line_length).collect::<String>();
+            let spans = vec![Span::styled(
+                chip,
+                Style {
+                    fg: Some(Color::Green),
+                    ..Style::default()
+                },
+            )];
+            lines.push(self.spans_to_line(spans, max_line_length));
+        }
 
-                for word in span.content.split(' ') {
-                    if word.len() + line_char_count > max_line_length {
-                        split_spans.push(Span::styled(word_set.join(" "), span.style));
-                        lines.push(self.spans_to_line(split_spans, max_line_length));
+        return self.wrap_lines_in_buddle(lines, max_line_length);
+    }
 
-                        split_spans = vec![];
-                        word_set = vec![];
-                        line_char_count = 0;
-                    }
+    /// Chips for the files and lines of a project retrieved as context for
+    /// the response.
+    fn source_chips(&self) -> impl Iterator<Item = String> + '_ {
+        return self
+            .message
+            .sources
+            .iter()
+            .map(|source| return format!("[source: {source}]"));
+    }
+
+    /// Adds a line of text, wrapping it at word boundaries when it's longer
+    /// than the bubble.
+    fn push_wrapped_line(
+        &self,
+        lines: &mut Vec<Line<'a>>,
+        spans: Vec<Span<'a>>,
+        max_line_length: usize,
+    ) {
+        let mut split_spans = vec![];
+        let mut line_char_count = 0;
+
+        for span in spans {
+            if span.content.len() + line_char_count <= max_line_length {
+                line_char_count += span.content.len();
+                split_spans.push(span);
+                continue;
+            }
+
+            let mut word_set: Vec<&str> = vec![];
 
-                    word_set.push(word);
-                    line_char_count += word.len() + 1;
+            for word in span.content.split(' ') {
+                if word.len() + line_char_count > max_line_length {
+                    split_spans.push(Span::styled(word_set.join(" "), span.style));
+                    lines.push(self.spans_to_line(split_spans, max_line_length));
+
+                    split_spans = vec![];
+                    word_set = vec![];
+                    line_char_count = 0;
                 }
 
-                split_spans.push(Span::styled(word_set.join(" "), span.style));
+                word_set.push(word);
+                line_char_count += word.len() + 1;
             }
 
-            lines.push(self.spans_to_line(split_spans, max_line_length));
+            split_spans.push(Span::styled(word_set.join(" "), span.style));
         }
 
-        return self.wrap_lines_in_buddle(lines, max_line_length);
+        lines.push(self.spans_to_line(split_spans, max_line_length));
     }
 
     fn spans_to_line(&self, mut spans: Vec<Span<'a>>, max_line_length: usize) -> Line<'a> {
@@ -217,14 +364,27 @@ impl<'a> Bubble<'_> {
             .map(|line| {
                 return line.len();
             })
+            .chain(
+                self.message
+                    .attachments
+                    .iter()
+                    .map(|attachment| return attachment.chip().len()),
+            )
+            .chain(self.source_chips().map(|chip| return chip.len()))
+            .chain(self.reasoning_header().map(|header| return header.len()))
+            .chain(
+                self.reasoning_lines()
+                    .filter(|_| return self.reasoning_expanded)
+                    .map(|line| return line.len()),
+            )
             .max()
-            .unwrap();
+            .unwrap_or(0);
 
         if max_line_length > (self.window_max_width - line_border_width) {
             max_line_length = self.window_max_width - line_border_width;
         }
 
-        let username = &self.message.author.to_string();
+        let username = &self.username();
         if max_line_length < username.len() {
             max_line_length = username.len();
         }
@@ -247,7 +407,7 @@ impl<'a> Bubble<'_> {
             ],
         );
 
-        let username = &self.message.author.to_string();
+        let username = &self.username();
 
         if self.alignment == BubbleAlignment::Left {
             let top_replace = ["─"].repeat(username.len()).join("");
@@ -275,7 +435,15 @@ impl<'a> Bubble<'_> {
     }
 
     fn highlight_span(&self, text: String) -> Span<'a> {
-        if self.message.message_type() == MessageType::Error {
+        if self.selected {
+            return Span::styled(
+                text,
+                Style {
+                    fg: Some(Color::Yellow),
+                    ..Style::default()
+                },
+            );
+        } else if self.message.message_type() == MessageType::Error {
             return Span::styled(
                 text,
                 Style {
@@ -3,11 +3,13 @@ use std::collections::HashMap;
 use ratatui::prelude::Buffer;
 use ratatui::prelude::Rect;
 use ratatui::text::Line;
+use ratatui::text::Span;
 use syntect::highlighting::Theme;
 
 use super::Bubble;
 use super::BubbleAlignment;
 use crate::domain::models::Author;
+use crate::domain::models::Comparison;
 use crate::domain::models::Message;
 
 #[cfg(test)]
@@ -17,13 +19,21 @@ mod tests;
 struct BubbleCacheEntry<'a> {
     codeblocks_count: usize,
     text_len: usize,
+    reasoning_len: usize,
     lines: Vec<Line<'a>>,
 }
 
+/// Room left around a comparison answer's title for its borders and padding.
+const COMPARE_TITLE_MARGIN: usize = 10;
+
 pub struct BubbleList<'a> {
     cache: HashMap<usize, BubbleCacheEntry<'a>>,
+    codeblocks_count: usize,
+    comparison_lines: Vec<Line<'a>>,
     line_width: usize,
     lines_len: usize,
+    reasoning_expanded: bool,
+    selected: Option<usize>,
     theme: Theme,
 }
 
@@ -31,8 +41,12 @@ impl<'a> BubbleList<'a> {
     pub fn new(theme: Theme) -> BubbleList<'a> {
         return BubbleList {
             cache: HashMap::new(),
+            codeblocks_count: 0,
+            comparison_lines: vec![],
             line_width: 0,
             lines_len: 0,
+            reasoning_expanded: false,
+            selected: None,
             theme,
         };
     }
@@ -50,7 +64,10 @@ impl<'a> BubbleList<'a> {
             .map(|(idx, message)| {
                 if self.cache.contains_key(&idx) {
                     let cache_entry = self.cache.get(&idx).unwrap();
-                    if idx < (messages.len() - 1) || message.text.len() == cache_entry.text_len {
+                    if idx < (messages.len() - 1)
+                        || (message.text.len() == cache_entry.text_len
+                            && message.reasoning.len() == cache_entry.reasoning_len)
+                    {
                         total_codeblock_counter += cache_entry.codeblocks_count;
                         return cache_entry.lines.len();
                     }
@@ -62,6 +79,8 @@ impl<'a> BubbleList<'a> {
                 }
 
                 let bubble_lines = Bubble::new(message, align, line_width, total_codeblock_counter)
+                    .with_selected(self.selected == Some(idx))
+                    .with_reasoning_expanded(self.reasoning_expanded)
                     .as_lines(&self.theme);
                 let bubble_line_len = bubble_lines.len();
 
@@ -73,6 +92,7 @@ impl<'a> BubbleList<'a> {
                     BubbleCacheEntry {
                         codeblocks_count,
                         text_len: message.text.len(),
+                        reasoning_len: message.reasoning.len(),
                         lines: bubble_lines,
                     },
                 );
@@ -80,38 +100,106 @@ impl<'a> BubbleList<'a> {
                 return bubble_line_len;
             })
             .sum();
+        self.codeblocks_count = total_codeblock_counter;
+    }
+
+    /// Lays out the answers of a comparison side by side, below the messages.
+    pub fn set_comparison(&mut self, comparison: Option<&Comparison>, line_width: usize) {
+        self.comparison_lines = vec![];
+        let comparison = match comparison {
+            Some(comparison) if !comparison.answers.is_empty() => comparison,
+            _ => return,
+        };
+
+        let column_width = line_width / comparison.answers.len();
+        let columns = comparison
+            .answers
+            .iter()
+            .enumerate()
+            .map(|(idx, answer)| {
+                let title = format!("({}) {}", idx + 1, answer.target)
+                    .chars()
+                    .take(column_width.saturating_sub(COMPARE_TITLE_MARGIN))
+                    .collect::<String>();
+
+                return Bubble::new(
+                    &answer.message,
+                    BubbleAlignment::Left,
+                    column_width,
+                    self.codeblocks_count,
+                )
+                .with_title(&title)
+                .with_reasoning_expanded(self.reasoning_expanded)
+                .as_lines(&self.theme);
+            })
+            .collect::<Vec<Vec<Line>>>();
+
+        let rows = columns
+            .iter()
+            .map(|column| return column.len())
+            .max()
+            .unwrap_or(0);
+        for row in 0..rows {
+            let mut spans = vec![];
+            for column in &columns {
+                let mut width = 0;
+                if let Some(line) = column.get(row) {
+                    width = line.width();
+                    spans.extend(line.spans.clone());
+                }
+                spans.push(Span::from(" ".repeat(column_width.saturating_sub(width))));
+            }
+            self.comparison_lines.push(Line::from(spans));
+        }
+    }
+
+    /// Clears cached bubbles, needed when a message is inserted before the
+    /// last one.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Highlights the message at the given position.
+    pub fn set_selected(&mut self, selected: Option<usize>) {
+        if self.selected != selected {
+            self.selected = selected;
+            self.cache.clear();
+        }
+    }
+
+    /// Expands or collapses the reasoning of every message.
+    pub fn toggle_reasoning(&mut self) {
+        self.reasoning_expanded = !self.reasoning_expanded;
+        self.cache.clear();
+    }
+
+    /// The line the message at the given position starts at.
+    pub fn line_offset(&self, idx: usize) -> usize {
+        return self
+            .cache
+            .iter()
+            .filter(|(cache_key, _)| return **cache_key < idx)
+            .map(|(_, cache_entry)| return cache_entry.lines.len())
+            .sum();
     }
 
     pub fn len(&self) -> usize {
-        return self.lines_len;
+        return self.lines_len + self.comparison_lines.len();
     }
 
     pub fn render(&self, rect: Rect, buf: &mut Buffer, scroll_index: u16) {
         let mut cache_keys: Vec<usize> = self.cache.keys().cloned().collect();
         cache_keys.sort();
 
-        let mut line_idx = 0;
-        let mut should_break = false;
-
-        for cache_key in cache_keys {
-            for line in self.cache.get(&cache_key).unwrap().lines.as_slice() {
-                if line_idx < scroll_index {
-                    line_idx += 1;
-                    continue;
-                }
-
-                if (line_idx - scroll_index) >= rect.height {
-                    should_break = true;
-                    break;
-                }
-
-                buf.set_line(0, line_idx - scroll_index, line, rect.width);
-                line_idx += 1;
-            }
+        let lines = cache_keys
+            .iter()
+            .flat_map(|cache_key| return self.cache.get(cache_key).unwrap().lines.iter())
+            .chain(self.comparison_lines.iter())
+            .skip(scroll_index.into())
+            .take(rect.height.into());
 
-            if should_break {
-                break;
-            }
+        for (line_idx, line) in lines.enumerate() {
+            buf.set_line(0, line_idx as u16, line, rect.width);
         }
     }
 }
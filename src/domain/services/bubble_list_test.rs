@@ -3,6 +3,9 @@ use test_utils::codeblock_fixture;
 
 use super::BubbleList;
 use crate::domain::models::Author;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::CompareTarget;
+use crate::domain::models::Comparison;
 use crate::domain::models::Message;
 use crate::domain::services::Themes;
 
@@ -44,3 +47,43 @@ fn it_returns_correct_length() -> Result<()> {
     assert_eq!(bubble_list.len(), 50);
     return Ok(());
 }
+
+#[test]
+fn it_lays_out_comparisons_side_by_side() -> Result<()> {
+    let theme = Themes::get("base16-seti", "")?;
+    let messages = vec![Message::new(Author::User, "Hi there!")];
+    let targets = CompareTarget::parse_list("openai:gpt-4o,ollama:llama3")?;
+    let mut comparison = Comparison::new(&targets);
+    comparison.handle_response(
+        1,
+        &BackendResponse {
+            author: Author::Model,
+            text: "Hello!\nHow can I help?".to_string(),
+            reasoning: "".to_string(),
+            done: true,
+            usage: None,
+            truncated: false,
+        },
+    );
+
+    let mut bubble_list = BubbleList::new(theme);
+    bubble_list.set_messages(&messages, 80);
+    let lines_len = bubble_list.len();
+    bubble_list.set_comparison(Some(&comparison), 80);
+
+    assert_eq!(bubble_list.len(), lines_len + 4);
+    for line in bubble_list.comparison_lines.iter() {
+        assert_eq!(line.width(), 80);
+    }
+    let title = bubble_list.comparison_lines[0]
+        .spans
+        .iter()
+        .map(|span| return span.content.to_string())
+        .collect::<String>();
+    assert!(title.contains("(1) openai:gpt-4o"));
+    assert!(title.contains("(2) ollama:llama3"));
+
+    bubble_list.set_comparison(None, 80);
+    assert_eq!(bubble_list.len(), lines_len);
+    return Ok(());
+}
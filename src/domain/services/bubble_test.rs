@@ -1,4 +1,5 @@
 use anyhow::Result;
+use ratatui::style::Color;
 use test_utils::codeblock_fixture;
 use test_utils::insta_snapshot;
 
@@ -6,7 +7,10 @@ use super::Bubble;
 use super::BubbleAlignment;
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
+use crate::domain::models::Branch;
 use crate::domain::models::Message;
 use crate::domain::services::Themes;
 
@@ -130,3 +134,183 @@ fn it_creates_author_user_text() -> Result<()> {
 
     return Ok(());
 }
+
+#[test]
+fn it_creates_attachment_chips() -> Result<()> {
+    Config::set(ConfigKey::Username, "testuser");
+    let mut message = Message::new(Author::User, "What is this?");
+    message.attachments = vec![Attachment {
+        kind: AttachmentKind::Image,
+        path: "/tmp/cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        content: "".to_string(),
+    }];
+    let theme = Themes::get("base16-seti", "")?;
+    let lines = Bubble::new(&message, BubbleAlignment::Right, 50, 0).as_lines(&theme);
+    let lines_str = lines
+        .iter()
+        .map(|line| {
+            return line
+                .spans
+                .iter()
+                .map(|span| {
+                    return span.content.to_string();
+                })
+                .collect::<Vec<String>>()
+                .join("");
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    insta_snapshot(|| {
+        insta::assert_toml_snapshot!(lines_str);
+    });
+
+    return Ok(());
+}
+
+#[test]
+fn it_creates_source_chips() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+    let mut message = Message::new(Author::Model, "It starts in main.");
+    message.sources = vec![
+        "src/main.rs:1-40".to_string(),
+        "src/application/cli.rs:31-70".to_string(),
+    ];
+    let theme = Themes::get("base16-seti", "")?;
+    let lines = Bubble::new(&message, BubbleAlignment::Left, 50, 0).as_lines(&theme);
+    let lines_str = lines
+        .iter()
+        .map(|line| {
+            return line
+                .spans
+                .iter()
+                .map(|span| {
+                    return span.content.to_string();
+                })
+                .collect::<Vec<String>>()
+                .join("");
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    insta_snapshot(|| {
+        insta::assert_toml_snapshot!(lines_str);
+    });
+
+    return Ok(());
+}
+
+#[test]
+fn it_shows_which_response_is_selected() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+    let mut message = Message::new(Author::Model, "Hi there!");
+    message.start_regenerating();
+    message.append("Hello!");
+
+    let theme = Themes::get("base16-seti", "")?;
+    let lines = Bubble::new(&message, BubbleAlignment::Left, 50, 0).as_lines(&theme);
+    let top_line = lines[0]
+        .spans
+        .iter()
+        .map(|span| {
+            return span.content.to_string();
+        })
+        .collect::<Vec<String>>()
+        .join("");
+
+    assert_eq!(top_line.trim_end(), "╭model-1 (2/2)──╮");
+
+    return Ok(());
+}
+
+#[test]
+fn it_highlights_the_selected_branch() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+    let mut messages = vec![Message::new(Author::Model, "Hi there!")];
+    Branch::fork(&mut messages, 0, Message::new(Author::Model, "Hello!"));
+
+    let theme = Themes::get("base16-seti", "")?;
+    let lines = Bubble::new(&messages[0], BubbleAlignment::Left, 50, 0)
+        .with_selected(true)
+        .as_lines(&theme);
+    let top_line = lines[0]
+        .spans
+        .iter()
+        .map(|span| {
+            return span.content.to_string();
+        })
+        .collect::<Vec<String>>()
+        .join("");
+
+    assert_eq!(top_line.trim_end(), "╭model-1 (2/2)──╮");
+    assert_eq!(lines[0].spans[0].style.fg, Some(Color::Yellow));
+
+    return Ok(());
+}
+
+#[test]
+fn it_collapses_reasoning() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+    let mut message = Message::new(Author::Model, "```rust\nfn main() {}\n```");
+    message.append_reasoning("They want code.\n```python\nprint()\n```");
+
+    let theme = Themes::get("base16-seti", "")?;
+    let lines = Bubble::new(&message, BubbleAlignment::Left, 60, 0).as_lines(&theme);
+    let lines_str = lines
+        .iter()
+        .map(|line| {
+            return line
+                .spans
+                .iter()
+                .map(|span| {
+                    return span.content.to_string();
+                })
+                .collect::<Vec<String>>()
+                .join("")
+                .trim_end()
+                .to_string();
+        })
+        .collect::<Vec<String>>();
+
+    assert_eq!(lines_str[1], "│ + Reasoning, 4 lines (CTRL+T to expand) │");
+    assert_eq!(lines[1].spans[1].style.fg, Some(Color::DarkGray));
+    assert!(!lines_str.join("\n").contains("They want code."));
+    assert!(lines_str[3].starts_with("│ ```rust (1)"));
+
+    return Ok(());
+}
+
+#[test]
+fn it_expands_reasoning() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+    let mut message = Message::new(Author::Model, "```rust\nfn main() {}\n```");
+    message.append_reasoning("They want code.\n```python\nprint()\n```");
+
+    let theme = Themes::get("base16-seti", "")?;
+    let lines = Bubble::new(&message, BubbleAlignment::Left, 60, 0)
+        .with_reasoning_expanded(true)
+        .as_lines(&theme);
+    let lines_str = lines
+        .iter()
+        .map(|line| {
+            return line
+                .spans
+                .iter()
+                .map(|span| {
+                    return span.content.to_string();
+                })
+                .collect::<Vec<String>>()
+                .join("")
+                .trim_end()
+                .to_string();
+        })
+        .collect::<Vec<String>>();
+
+    assert!(lines_str[1].starts_with("│ - Reasoning (CTRL+T to collapse)"));
+    assert!(lines_str[2].starts_with("│ They want code."));
+    assert!(lines_str[3].starts_with("│ ```python "));
+    assert!(lines_str[7].starts_with("│ ```rust (1)"));
+
+    return Ok(());
+}
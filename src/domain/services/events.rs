@@ -10,15 +10,40 @@ use tui_textarea::Key;
 
 use crate::domain::models::Event;
 
+/// Waits for the next terminal event, or forever when there's no terminal.
+async fn next_crossterm_event(
+    crossterm_events: &mut Option<EventStream>,
+) -> Option<CrosstermEvent> {
+    let crossterm_events = match crossterm_events {
+        Some(crossterm_events) => crossterm_events,
+        None => return std::future::pending().await,
+    };
+
+    return match crossterm_events.next().await {
+        Some(Ok(input)) => Some(input),
+        _ => None,
+    };
+}
+
 pub struct EventsService {
-    crossterm_events: EventStream,
+    crossterm_events: Option<EventStream>,
     events: mpsc::UnboundedReceiver<Event>,
 }
 
 impl EventsService {
     pub fn new(events: mpsc::UnboundedReceiver<Event>) -> EventsService {
         return EventsService {
-            crossterm_events: EventStream::new(),
+            crossterm_events: Some(EventStream::new()),
+            events,
+        };
+    }
+
+    /// Only receives events sent on the channel, for running the UI without a
+    /// terminal.
+    #[cfg(test)]
+    pub fn headless(events: mpsc::UnboundedReceiver<Event>) -> EventsService {
+        return EventsService {
+            crossterm_events: None,
             events,
         };
     }
@@ -43,6 +68,22 @@ impl EventsService {
             }
             CrosstermEvent::Key(keyevent) => {
                 match keyevent.into() {
+                    Input {
+                        key: Key::Down,
+                        ctrl: false,
+                        alt: true,
+                        ..
+                    } => {
+                        return Some(Event::KeyboardALTDown());
+                    }
+                    Input {
+                        key: Key::Up,
+                        ctrl: false,
+                        alt: true,
+                        ..
+                    } => {
+                        return Some(Event::KeyboardALTUp());
+                    }
                     Input { key: Key::Down, .. } => {
                         return Some(Event::UIScrollDown());
                     }
@@ -106,6 +147,29 @@ impl EventsService {
                     } => {
                         return Some(Event::KeyboardCTRLR());
                     }
+                    Input {
+                        key: Key::Char('t'),
+                        ctrl: true,
+                        ..
+                    } => {
+                        return Some(Event::KeyboardCTRLT());
+                    }
+                    Input {
+                        key: Key::Left,
+                        ctrl: false,
+                        alt: true,
+                        ..
+                    } => {
+                        return Some(Event::KeyboardALTLeft());
+                    }
+                    Input {
+                        key: Key::Right,
+                        ctrl: false,
+                        alt: true,
+                        ..
+                    } => {
+                        return Some(Event::KeyboardALTRight());
+                    }
                     Input {
                         key: Key::Enter, ..
                     } => {
@@ -124,9 +188,8 @@ impl EventsService {
         loop {
             let evt = tokio::select! {
                 event = self.events.recv() => event,
-                event = self.crossterm_events.next() => match event {
-                    Some(Ok(input)) => self.handle_crossterm(input),
-                    Some(Err(_)) => None,
+                event = next_crossterm_event(&mut self.crossterm_events) => match event {
+                    Some(input) => self.handle_crossterm(input),
                     None => None
                 },
                 _ = time::sleep(time::Duration::from_millis(500)) => Some(Event::UITick())
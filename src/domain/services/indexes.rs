@@ -0,0 +1,220 @@
+#[cfg(test)]
+#[path = "indexes_test.rs"]
+mod tests;
+
+use std::path;
+
+use anyhow::bail;
+use anyhow::Result;
+use tokio::fs;
+use tokio::process::Command;
+
+use crate::domain::models::BackendBox;
+use crate::domain::models::IndexChunk;
+use crate::domain::models::VectorIndex;
+
+/// Files larger than this are left out, as they're rarely written by hand.
+const MAX_FILE_BYTES: usize = 1024 * 1024;
+
+/// Directories left out when a project isn't a git repository, besides hidden
+/// ones, as they hold dependencies and build output.
+const SKIPPED_DIRS: [&str; 4] = ["dist", "node_modules", "target", "vendor"];
+
+/// Chunks embedded with each request to the backend.
+const EMBEDDINGS_BATCH_SIZE: usize = 32;
+
+/// Vector indexes of projects, kept in the cache directory with one file per
+/// project.
+pub struct Indexes {
+    pub cache_dir: path::PathBuf,
+}
+
+impl Default for Indexes {
+    fn default() -> Indexes {
+        let cache_dir = dirs::cache_dir().unwrap().join("oatmeal/indexes");
+
+        return Indexes::new(cache_dir);
+    }
+}
+
+impl Indexes {
+    pub fn new(cache_dir: path::PathBuf) -> Indexes {
+        return Indexes { cache_dir };
+    }
+
+    fn get_file_path(&self, root: &path::Path) -> path::PathBuf {
+        let name = root
+            .to_string_lossy()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    return c;
+                }
+                return '-';
+            })
+            .collect::<String>();
+
+        return self
+            .cache_dir
+            .join(format!("{}.bin", name.trim_matches('-')));
+    }
+
+    /// Lists the files of a project. Git repositories list their files
+    /// without the ignored ones, and other directories are walked.
+    async fn files(root: &path::Path) -> Result<Vec<path::PathBuf>> {
+        let output = Command::new("git")
+            .args([
+                "ls-files",
+                "--cached",
+                "--others",
+                "--exclude-standard",
+                "-z",
+            ])
+            .current_dir(root)
+            .output()
+            .await;
+        if let Ok(output) = output {
+            if output.status.success() {
+                return Ok(String::from_utf8_lossy(&output.stdout)
+                    .split('\0')
+                    .filter(|file| return !file.is_empty())
+                    .map(|file| return root.join(file))
+                    .filter(|file| return file.is_file())
+                    .collect());
+            }
+        }
+
+        let mut files = vec![];
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with('.') {
+                    continue;
+                }
+
+                // Symlinks are skipped, as they can loop.
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() && !SKIPPED_DIRS.contains(&name.as_str()) {
+                    dirs.push(entry.path());
+                } else if file_type.is_file() {
+                    files.push(entry.path());
+                }
+            }
+        }
+        files.sort();
+
+        return Ok(files);
+    }
+
+    /// Splits every text file of a project into chunks and embeds them,
+    /// reporting the number of chunks embedded so far.
+    pub async fn build(
+        root: &path::Path,
+        backend: &BackendBox,
+        model: &str,
+        on_progress: impl Fn(usize, usize),
+    ) -> Result<VectorIndex> {
+        let root = match root.canonicalize() {
+            Ok(root) if root.is_dir() => root,
+            _ => bail!(format!("{} is not a directory", root.display())),
+        };
+
+        let mut chunks = vec![];
+        for file in Indexes::files(&root).await? {
+            let bytes = fs::read(&file).await?;
+            if bytes.len() > MAX_FILE_BYTES || bytes.contains(&0) {
+                continue;
+            }
+            let text = match String::from_utf8(bytes) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+
+            let relative_path = file
+                .strip_prefix(&root)?
+                .components()
+                .map(|component| return component.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+                .join("/");
+            chunks.extend(IndexChunk::split(&relative_path, &text));
+        }
+        if chunks.is_empty() {
+            bail!(format!("There are no text files in {}", root.display()));
+        }
+
+        let total = chunks.len();
+        let mut embedded = 0;
+        for batch in chunks.chunks_mut(EMBEDDINGS_BATCH_SIZE) {
+            // The file path is embedded too, as questions often name files.
+            let texts = batch
+                .iter()
+                .map(|chunk| return chunk.to_prompt_text())
+                .collect::<Vec<String>>();
+            let embeddings = backend.get_embeddings(model, &texts).await?;
+            if embeddings.len() != batch.len() {
+                bail!(format!(
+                    "The backend returned {} embeddings for {} chunks",
+                    embeddings.len(),
+                    batch.len()
+                ));
+            }
+
+            for (chunk, embedding) in batch.iter_mut().zip(embeddings) {
+                chunk.embedding = embedding;
+            }
+            embedded += batch.len();
+            on_progress(embedded, total);
+        }
+
+        return Ok(VectorIndex {
+            root: root.to_string_lossy().to_string(),
+            backend: backend.name().to_string(),
+            model: model.to_string(),
+            chunks,
+        });
+    }
+
+    /// Saves an index, replacing the previous index of its project. Returns
+    /// where it was saved.
+    pub async fn save(&self, index: &VectorIndex) -> Result<path::PathBuf> {
+        let file_path = self.get_file_path(path::Path::new(&index.root));
+        fs::create_dir_all(&self.cache_dir).await?;
+        fs::write(&file_path, bincode::serialize(index)?).await?;
+
+        return Ok(file_path);
+    }
+
+    /// Loads the index of a directory, or of the closest parent directory
+    /// which was indexed.
+    pub async fn find(&self, dir: &path::Path) -> Result<Option<VectorIndex>> {
+        let dir = match dir.canonicalize() {
+            Ok(dir) => dir,
+            Err(_) => bail!(format!("{} doesn't exist", dir.display())),
+        };
+
+        for root in dir.ancestors() {
+            let file_path = self.get_file_path(root);
+            if !file_path.exists() {
+                continue;
+            }
+
+            let index: VectorIndex = match bincode::deserialize(&fs::read(&file_path).await?) {
+                Ok(index) => index,
+                Err(_) => {
+                    bail!(format!(
+                        "The index of {root} can't be read. Create it again with `oatmeal index {root}`.",
+                        root = root.display()
+                    ))
+                }
+            };
+            // Different paths can share a file name once sanitized.
+            if path::Path::new(&index.root) == root {
+                return Ok(Some(index));
+            }
+        }
+
+        return Ok(None);
+    }
+}
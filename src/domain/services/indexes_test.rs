@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::path;
+
+use anyhow::Result;
+use tokio::fs;
+
+use super::Indexes;
+use crate::domain::models::BackendBox;
+use crate::infrastructure::backends::mock::Mock;
+
+async fn create_project() -> Result<path::PathBuf> {
+    let root = std::env::temp_dir().join(format!("oatmeal-index-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(root.join("src")).await?;
+    fs::create_dir_all(root.join("node_modules/left-pad")).await?;
+    fs::create_dir_all(root.join(".git-like")).await?;
+    fs::write(root.join("src/main.rs"), "fn main() {\n    hello();\n}\n").await?;
+    fs::write(root.join("README.md"), "# Project\n\nSays hello.\n").await?;
+    fs::write(
+        root.join("node_modules/left-pad/index.js"),
+        "module.exports = 1;",
+    )
+    .await?;
+    fs::write(root.join(".git-like/config"), "hidden").await?;
+    fs::write(root.join("logo.png"), [137, 80, 78, 71, 0, 1]).await?;
+
+    return Ok(root);
+}
+
+#[tokio::test]
+async fn it_builds_an_index_of_text_files() -> Result<()> {
+    let root = create_project().await?;
+    let backend: BackendBox = Box::<Mock>::default();
+    let progress = RefCell::new(vec![]);
+
+    let index = Indexes::build(&root, &backend, "mock", |embedded, total| {
+        progress.borrow_mut().push((embedded, total));
+    })
+    .await?;
+
+    let citations = index
+        .chunks
+        .iter()
+        .map(|chunk| return chunk.citation())
+        .collect::<Vec<String>>();
+    assert_eq!(citations, vec!["README.md:1-3", "src/main.rs:1-3"]);
+    assert!(index
+        .chunks
+        .iter()
+        .all(|chunk| return chunk.embedding.len() == 26));
+    assert_eq!(index.root, root.canonicalize()?.to_string_lossy());
+    assert_eq!(index.backend, "mock");
+    assert_eq!(progress.into_inner(), vec![(2, 2)]);
+
+    fs::remove_dir_all(root).await?;
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_saves_and_finds_indexes_from_subdirectories() -> Result<()> {
+    let root = create_project().await?;
+    let indexes = Indexes::new(root.join("cache"));
+    let backend: BackendBox = Box::<Mock>::default();
+    let index = Indexes::build(&root.join("src"), &backend, "mock", |_, _| {}).await?;
+
+    assert_eq!(indexes.find(&root.join("src")).await?, None);
+    indexes.save(&index).await?;
+
+    assert_eq!(indexes.find(&root.join("src")).await?, Some(index));
+    assert_eq!(indexes.find(&root).await?, None);
+
+    fs::remove_dir_all(root).await?;
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_build_an_index_without_text_files() -> Result<()> {
+    let root = std::env::temp_dir().join(format!("oatmeal-index-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&root).await?;
+    let backend: BackendBox = Box::<Mock>::default();
+
+    let res = Indexes::build(&root, &backend, "mock", |_, _| {}).await;
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("There are no text files in"));
+
+    fs::remove_dir_all(root).await?;
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_find_indexes_of_missing_or_unreadable_files() -> Result<()> {
+    let root = create_project().await?;
+    let indexes = Indexes::new(root.join("cache"));
+
+    let res = indexes.find(&root.join("srcc")).await;
+    assert!(res.unwrap_err().to_string().ends_with("srcc doesn't exist"));
+
+    let root = root.canonicalize()?;
+    fs::create_dir_all(&indexes.cache_dir).await?;
+    fs::write(indexes.get_file_path(&root), "not an index").await?;
+    let res = indexes.find(&root).await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        format!(
+            "The index of {root} can't be read. Create it again with `oatmeal index {root}`.",
+            root = root.display()
+        )
+    );
+
+    fs::remove_dir_all(root).await?;
+    return Ok(());
+}
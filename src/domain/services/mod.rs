@@ -5,6 +5,7 @@ mod bubble_list;
 pub mod clipboard;
 mod code_blocks;
 pub mod events;
+mod indexes;
 mod scroll;
 mod sessions;
 mod syntaxes;
@@ -14,6 +15,7 @@ pub use app_state::*;
 pub use bubble::*;
 pub use bubble_list::*;
 pub use code_blocks::*;
+pub use indexes::*;
 pub use scroll::*;
 pub use sessions::*;
 pub use syntaxes::*;
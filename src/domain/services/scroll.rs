@@ -56,6 +56,12 @@ impl Scroll {
         self.scrollbar_state.last();
     }
 
+    /// Scrolls to the given line, as far as the list can be scrolled.
+    pub fn to(&mut self, position: usize) {
+        self.position = position.min(self.get_position_as_if_last());
+        self.scrollbar_state = self.scrollbar_state.position(self.position);
+    }
+
     pub fn set_state(&mut self, list_length: usize, viewport_length: usize) {
         self.list_length = list_length;
         self.viewport_length = viewport_length;
@@ -13,9 +13,11 @@ use crate::configuration::Config;
 use crate::configuration::ConfigKey;
 use crate::domain::models::Author;
 use crate::domain::models::EditorContext;
+use crate::domain::models::GenerationOptions;
 use crate::domain::models::Message;
 use crate::domain::models::Session;
 use crate::domain::models::State;
+use crate::domain::models::UsageByModel;
 
 pub struct Sessions {
     pub cache_dir: path::PathBuf,
@@ -77,7 +79,6 @@ impl Sessions {
                 session.state.messages = vec![];
             }
 
-            session.state.backend_context = "".to_string();
             sessions.push(session);
         }
 
@@ -103,17 +104,20 @@ impl Sessions {
     pub async fn save(
         &self,
         id: &str,
-        backend_context: &str,
         editor_context: &Option<EditorContext>,
         messages: &[Message],
+        usage: &UsageByModel,
     ) -> Result<()> {
         let mut state = State {
             // TODO drop pulling this in from config.
             backend_name: Config::get(ConfigKey::Backend),
             backend_model: Config::get(ConfigKey::Model),
-            backend_context: backend_context.to_string(),
             editor_language: "".to_string(),
+            persona: Config::get(ConfigKey::Persona),
+            profile: Config::get(ConfigKey::Profile),
+            generation_options: GenerationOptions::from_config().unwrap_or_default(),
             messages: messages.to_vec(),
+            usage: usage.clone(),
         };
 
         if let Some(context) = editor_context {
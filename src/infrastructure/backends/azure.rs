@@ -0,0 +1,136 @@
+#[cfg(test)]
+#[path = "azure_test.rs"]
+mod tests;
+
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::configuration::Secrets;
+use crate::domain::models::Backend;
+use crate::domain::models::BackendName;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::Event;
+use crate::domain::models::ModelInfo;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::openai::OpenAI;
+use crate::infrastructure::backends::openai::OpenAIApi;
+
+/// OpenAI models hosted on Azure. Requests go through the OpenAI backend, with
+/// the model name being the name of a deployment on the Azure resource.
+pub struct Azure {
+    url: String,
+    token: String,
+    api_version: String,
+    deployments: Vec<String>,
+    timeout: String,
+    http: HttpClient,
+    openai: OpenAI,
+}
+
+impl Default for Azure {
+    fn default() -> Azure {
+        return Azure::new(
+            &Config::get(ConfigKey::AzureURL),
+            &Secrets::get(ConfigKey::AzureToken),
+            &Config::get(ConfigKey::AzureApiVersion),
+            &Config::get(ConfigKey::AzureDeployments),
+            HttpClient::default(),
+        );
+    }
+}
+
+impl Azure {
+    pub fn new(
+        url: &str,
+        token: &str,
+        api_version: &str,
+        deployments: &str,
+        http: HttpClient,
+    ) -> Azure {
+        let url = url.trim_end_matches('/');
+        let api = OpenAIApi::Azure {
+            api_version: api_version.to_string(),
+        };
+
+        return Azure {
+            url: url.to_string(),
+            token: token.to_string(),
+            api_version: api_version.to_string(),
+            deployments: deployments
+                .split(',')
+                .map(|deployment| return deployment.trim().to_string())
+                .filter(|deployment| return !deployment.is_empty())
+                .collect(),
+            timeout: Config::get(ConfigKey::BackendHealthCheckTimeout),
+            http: http.clone(),
+            openai: OpenAI::new(api, url, token, http),
+        };
+    }
+}
+
+#[async_trait]
+impl Backend for Azure {
+    fn name(&self) -> BackendName {
+        return BackendName::Azure;
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn health_check(&self) -> Result<()> {
+        if self.url.is_empty() {
+            bail!("Azure OpenAI URL is not defined");
+        }
+        if self.token.is_empty() {
+            bail!("Azure OpenAI token is not defined");
+        }
+        if self.api_version.is_empty() {
+            bail!("Azure OpenAI API version is not defined");
+        }
+        if self.deployments.is_empty() {
+            bail!("Azure OpenAI deployments are not defined");
+        }
+
+        // The root of an Azure resource returns a 404, so any response means
+        // it's reachable.
+        let res = self
+            .http
+            .client()?
+            .get(&self.url)
+            .timeout(Duration::from_millis(self.timeout.parse::<u64>()?))
+            .send()
+            .await;
+
+        if res.is_err() {
+            tracing::error!(error = ?res.unwrap_err(), "Azure OpenAI is not reachable");
+            bail!("Azure OpenAI is not reachable");
+        }
+
+        return Ok(());
+    }
+
+    /// Deployments are set in the config, as GA api-versions can't list them.
+    #[allow(clippy::implicit_return)]
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        return Ok(self
+            .deployments
+            .iter()
+            .map(|deployment| return ModelInfo::new(deployment))
+            .collect());
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn get_completion<'a>(
+        &self,
+        prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
+        tx: &'a mpsc::UnboundedSender<Event>,
+    ) -> Result<()> {
+        return self.openai.get_completion(prompt, cancel, tx).await;
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::bail;
+use anyhow::Result;
+use mockito::Matcher;
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::Azure;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::domain::models::Author;
+use crate::domain::models::Backend;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::Event;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::infrastructure::backends::http::HttpClient;
+
+impl Azure {
+    fn with_url(url: String, deployments: &str) -> Azure {
+        let mut backend = Azure::new(
+            &url,
+            "abc",
+            "2024-06-01",
+            deployments,
+            HttpClient::new(reqwest::Client::new()),
+        );
+        backend.timeout = "200".to_string();
+        return backend;
+    }
+}
+
+fn to_res(action: Option<Event>) -> Result<BackendResponse> {
+    let act = match action.unwrap() {
+        Event::BackendPromptResponse(res) => res,
+        _ => bail!("Wrong type from recv"),
+    };
+
+    return Ok(act);
+}
+
+#[tokio::test]
+async fn it_successfully_health_checks() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("GET", "/").with_status(404).create();
+
+    let backend = Azure::with_url(server.url(), "gpt-4o");
+    let res = backend.health_check().await;
+
+    assert!(res.is_ok());
+    mock.assert();
+}
+
+#[tokio::test]
+async fn it_fails_health_checks_without_a_token() {
+    let mut backend = Azure::with_url("http://localhost:1".to_string(), "");
+    backend.token = "".to_string();
+    let res = backend.health_check().await;
+
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Azure OpenAI token is not defined"
+    );
+}
+
+#[tokio::test]
+async fn it_fails_health_checks_when_unreachable() {
+    let backend = Azure::with_url("http://localhost:1".to_string(), "");
+    let res = backend.health_check().await;
+
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn it_lists_configured_deployments() -> Result<()> {
+    let backend = Azure::with_url("http://localhost:1".to_string(), "gpt-4o, gpt-35-turbo,");
+    let res = backend.list_models().await?;
+
+    assert_eq!(
+        res,
+        vec![ModelInfo::new("gpt-4o"), ModelInfo::new("gpt-35-turbo")]
+    );
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_health_checks_without_deployments() {
+    let backend = Azure::with_url("http://localhost:1".to_string(), " ,");
+    let res = backend.health_check().await;
+
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Azure OpenAI deployments are not defined"
+    );
+}
+
+#[tokio::test]
+async fn it_gets_completions() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+
+    let body = [
+        json!({"choices": [{"delta": {"content": "Hello "}, "finish_reason": null}]}).to_string(),
+        json!({"choices": [{"delta": {"content": "World"}, "finish_reason": null}]}).to_string(),
+        json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}).to_string(),
+        "[DONE]".to_string(),
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+    let prompt = BackendPrompt::new(
+        "Say hi to the world".to_string(),
+        vec![HistoryMessage::new(HistoryRole::User, "Hello")],
+    );
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock(
+            "POST",
+            Matcher::Regex("^/openai/deployments/.+/chat/completions$".to_string()),
+        )
+        .match_query(Matcher::UrlEncoded(
+            "api-version".to_string(),
+            "2024-06-01".to_string(),
+        ))
+        .match_header("api-key", "abc")
+        .match_header("Authorization", Matcher::Missing)
+        .match_body(Matcher::PartialJson(json!({
+            "messages": [{"role": "user", "content": "Hello"}, {"role": "user", "content": "Say hi to the world"}],
+            "stream": true
+        })))
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = Azure::with_url(server.url(), "");
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
+
+    mock.assert();
+
+    let first_recv = to_res(rx.recv().await)?;
+    let second_recv = to_res(rx.recv().await)?;
+    let third_recv = to_res(rx.recv().await)?;
+
+    assert_eq!(first_recv.author, Author::Model);
+    assert_eq!(first_recv.text, "Hello ".to_string());
+    assert!(!first_recv.done);
+
+    assert_eq!(second_recv.text, "World".to_string());
+    assert!(!second_recv.done);
+
+    assert!(third_recv.text.is_empty());
+    assert!(third_recv.done);
+
+    return Ok(());
+}
@@ -7,32 +7,45 @@ use std::time::Duration;
 use anyhow::bail;
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::stream::TryStreamExt;
 use itertools::Itertools;
 use regex::Regex;
+use reqwest::Method;
 use serde::Deserialize;
 use serde::Serialize;
-use tokio::io::AsyncBufReadExt;
 use tokio::sync::mpsc;
-use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
 
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
+use crate::configuration::Secrets;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendName;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
-
-fn convert_err(err: reqwest::Error) -> std::io::Error {
-    let err_msg = err.to_string();
-    return std::io::Error::new(std::io::ErrorKind::Interrupted, err_msg);
-}
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::Message;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::ToolCall;
+use crate::domain::models::ToolName;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::streaming::EventStream;
+use crate::infrastructure::backends::streaming::StreamFormat;
+use crate::infrastructure::backends::tools::Tools;
+use crate::infrastructure::backends::tools::MAX_TOOL_ROUNDS;
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct Model {
     id: String,
+    /// RFC 3339 timestamp of the model's release.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -40,18 +53,149 @@ struct ModelListResponse {
     data: Vec<Model>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ImageSourceRequest {
+    #[serde(rename = "type")]
+    _type: String,
+    media_type: String,
+    data: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPartRequest {
+    Text {
+        text: String,
+    },
+    Image {
+        source: ImageSourceRequest,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+        is_error: bool,
+    },
+    /// Reasoning sent back in tool use rounds, which the API checks against
+    /// its signature.
+    Thinking {
+        thinking: String,
+        signature: String,
+    },
+    RedactedThinking {
+        data: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum ContentRequest {
+    Text(String),
+    Parts(Vec<ContentPartRequest>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct MessageRequest {
     role: String,
-    content: String,
+    content: ContentRequest,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+impl MessageRequest {
+    fn new(role: &str, text: &str, attachments: &[Attachment]) -> MessageRequest {
+        if attachments.is_empty() {
+            return MessageRequest {
+                role: role.to_string(),
+                content: ContentRequest::Text(text.to_string()),
+            };
+        }
+
+        let mut parts = attachments
+            .iter()
+            .map(|attachment| {
+                return match attachment.kind {
+                    AttachmentKind::Image => {
+                        ContentPartRequest::Image {
+                            source: ImageSourceRequest {
+                                _type: "base64".to_string(),
+                                media_type: attachment.mime_type.to_string(),
+                                data: attachment.content.to_string(),
+                            },
+                        }
+                    }
+                    AttachmentKind::Text => {
+                        ContentPartRequest::Text {
+                            text: attachment.to_prompt_text(),
+                        }
+                    }
+                };
+            })
+            .collect::<Vec<ContentPartRequest>>();
+        parts.push(ContentPartRequest::Text {
+            text: text.to_string(),
+        });
+
+        return MessageRequest {
+            role: role.to_string(),
+            content: ContentRequest::Parts(parts),
+        };
+    }
+
+    fn from_history(message: &HistoryMessage) -> MessageRequest {
+        let role = match message.role {
+            HistoryRole::User => "user",
+            HistoryRole::Assistant => "assistant",
+        };
+
+        return MessageRequest::new(role, &message.text, &message.attachments);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ToolRequest {
+    name: String,
+    description: String,
+    input_schema: serde_json::Value,
+}
+
+impl ToolRequest {
+    fn new(tool_name: &ToolName) -> ToolRequest {
+        return ToolRequest {
+            name: tool_name.to_string(),
+            description: tool_name.description().to_string(),
+            input_schema: tool_name.parameters(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ThinkingRequest {
+    #[serde(rename = "type")]
+    _type: String,
+    budget_tokens: u32,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct CompletionRequest {
     model: String,
     max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
     messages: Vec<MessageRequest>,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<ThinkingRequest>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -59,11 +203,27 @@ struct Healthcheck {
     message: String,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct EventTypeResponse {
+    #[serde(rename = "type")]
+    _type: String,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct CompletionDeltaResponse {
     #[serde(rename = "type")]
     _type: String,
+    #[serde(default)]
     text: String,
+    /// Arguments of a tool call are streamed as pieces of JSON.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    partial_json: String,
+    /// Sent in `thinking` blocks when extended thinking is enabled.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    thinking: String,
+    /// Sent at the end of `thinking` blocks.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    signature: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -73,19 +233,332 @@ struct CompletionResponse {
     delta: CompletionDeltaResponse,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ContentBlockResponse {
+    #[serde(rename = "type")]
+    _type: String,
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    name: String,
+    /// Encrypted reasoning of `redacted_thinking` blocks.
+    #[serde(default)]
+    data: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ContentBlockStartResponse {
+    #[serde(rename = "type")]
+    _type: String,
+    content_block: ContentBlockResponse,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct UsageResponse {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct MessageStartBodyResponse {
+    usage: UsageResponse,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct MessageStartResponse {
+    #[serde(rename = "type")]
+    _type: String,
+    message: MessageStartBodyResponse,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct MessageDeltaBodyResponse {
+    /// Why the model stopped, such as `end_turn`, `tool_use` or `max_tokens`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stop_reason: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct MessageDeltaResponse {
+    #[serde(rename = "type")]
+    _type: String,
+    #[serde(default)]
+    delta: MessageDeltaBodyResponse,
+    usage: UsageResponse,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ErrorBodyResponse {
+    #[serde(rename = "type")]
+    _type: String,
+    message: String,
+}
+
+/// Sent as the body of failed requests, and as an event when a stream fails
+/// part way through, such as when the API is overloaded.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ErrorResponse {
+    #[serde(rename = "type")]
+    _type: String,
+    error: ErrorBodyResponse,
+}
+
+/// A tool call being streamed, with its arguments as a partial JSON string.
+struct PendingToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Result of a single request to the messages API.
+struct CompletionTurn {
+    /// Thinking blocks with their signatures, sent back with tool results.
+    thinking: Vec<ContentPartRequest>,
+    text: String,
+    tool_calls: Vec<ToolCall>,
+    usage: Usage,
+    stop_reason: Option<String>,
+}
+
 pub struct Claude {
     url: String,
     token: String,
     timeout: String,
+    http: HttpClient,
+    retry_policy: RetryPolicy,
+    /// Tokens the model may think with before answering. Extended thinking
+    /// is off when not set.
+    thinking_budget: Option<u32>,
+    tools: Tools,
 }
 
 impl Default for Claude {
     fn default() -> Claude {
         return Claude {
             url: "https://api.anthropic.com".to_string(),
-            token: Config::get(ConfigKey::ClaudeToken),
+            token: Secrets::get(ConfigKey::ClaudeToken),
             timeout: Config::get(ConfigKey::BackendHealthCheckTimeout),
+            http: HttpClient::default(),
+            retry_policy: RetryPolicy::default(),
+            thinking_budget: Config::get(ConfigKey::ClaudeThinkingBudget)
+                .parse::<u32>()
+                .ok(),
+            tools: Tools::default(),
+        };
+    }
+}
+
+impl Claude {
+    /// Lists models from the models API. Returns `None` when it isn't
+    /// available, such as with proxies that only implement the messages API.
+    async fn fetch_models(&self) -> Option<Vec<ModelInfo>> {
+        let request = self
+            .http
+            .request(
+                Method::GET,
+                format!("{url}/v1/models?limit=1000", url = self.url),
+            )
+            .ok()?
+            .header("x-api-key", &self.token)
+            .header("anthropic-version", "2023-06-01");
+        let res = match request.send().await {
+            Ok(res) if res.status().is_success() => res,
+            Ok(res) => {
+                tracing::debug!(status = res.status().as_u16(), "Claude models API failed");
+                return None;
+            }
+            Err(err) => {
+                tracing::debug!(error = ?err, "Claude models API is not reachable");
+                return None;
+            }
+        };
+
+        let models = match res.json::<ModelListResponse>().await {
+            Ok(json) => json.data,
+            Err(err) => {
+                tracing::debug!(error = ?err, "Failed to parse Claude models");
+                return None;
+            }
         };
+
+        return Some(
+            models
+                .iter()
+                .map(|model| {
+                    return ModelInfo {
+                        modalities: vec!["text".to_string(), "image".to_string()],
+                        created: model
+                            .created_at
+                            .as_deref()
+                            .and_then(ModelInfo::date_from_rfc3339),
+                        ..ModelInfo::new(&model.id)
+                    };
+                })
+                .collect(),
+        );
+    }
+
+    /// Streams a completion to the UI, returning the text and any tool calls
+    /// the model responded with. Returns `None` if cancelled before the
+    /// request was sent.
+    async fn stream_completion(
+        &self,
+        req: &CompletionRequest,
+        cancel: &CancellationToken,
+        tx: &mpsc::UnboundedSender<Event>,
+    ) -> Result<Option<CompletionTurn>> {
+        let request = self
+            .http
+            .client()?
+            .post(format!("{url}/v1/messages", url = self.url))
+            .header("x-api-key", &self.token)
+            .header("content-type", "application/json")
+            .header("anthropic-version", "2023-06-01")
+            .header("anthropic-beta", "messages-2023-12-15")
+            .json(req);
+        let res = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Ok(None),
+            res = self.retry_policy.send(request, self.name(), Some(tx), Some(cancel)) => res?,
+        };
+
+        if !res.status().is_success() {
+            tracing::error!(
+                status = res.status().as_u16(),
+                "Failed to make completion request to Claude"
+            );
+            if let Ok(err) = res.json::<ErrorResponse>().await {
+                bail!(format!(
+                    "Failed to make completion request to Claude: {}",
+                    err.error.message
+                ));
+            }
+            bail!("Failed to make completion request to Claude");
+        }
+
+        let mut events = EventStream::new(res, StreamFormat::Sse, cancel);
+
+        let mut turn = CompletionTurn {
+            thinking: vec![],
+            text: "".to_string(),
+            tool_calls: vec![],
+            usage: Usage::default(),
+            stop_reason: None,
+        };
+        let mut pending_tool_calls: Vec<PendingToolCall> = vec![];
+        while let Some(event) = events.next().await? {
+            let event_type: EventTypeResponse = event.json()?;
+            match event_type._type.as_str() {
+                // Prompt tokens are sent at the start of the message, and the
+                // total completion tokens in the last delta before it stops.
+                "message_start" => {
+                    let ores: MessageStartResponse = event.json()?;
+                    turn.usage.prompt_tokens = ores.message.usage.input_tokens;
+                    continue;
+                }
+                "message_delta" => {
+                    let ores: MessageDeltaResponse = event.json()?;
+                    turn.usage.completion_tokens = ores.usage.output_tokens;
+                    turn.stop_reason = ores.delta.stop_reason;
+                    continue;
+                }
+                "message_stop" => break,
+                "error" => {
+                    let ores: ErrorResponse = event.json()?;
+                    tracing::error!(error = ?ores.error, "Claude stream failed");
+                    bail!(format!(
+                        "Claude failed to finish its response: {}",
+                        ores.error.message
+                    ));
+                }
+                // Sent to keep the connection alive.
+                "ping" => continue,
+                "content_block_start" => {
+                    let ores: ContentBlockStartResponse = event.json()?;
+                    match ores.content_block._type.as_str() {
+                        "tool_use" => {
+                            pending_tool_calls.push(PendingToolCall {
+                                id: ores.content_block.id,
+                                name: ores.content_block.name,
+                                arguments: "".to_string(),
+                            });
+                        }
+                        "thinking" => {
+                            turn.thinking.push(ContentPartRequest::Thinking {
+                                thinking: "".to_string(),
+                                signature: "".to_string(),
+                            });
+                        }
+                        "redacted_thinking" => {
+                            turn.thinking.push(ContentPartRequest::RedactedThinking {
+                                data: ores.content_block.data,
+                            });
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                "content_block_delta" => {}
+                "content_block_stop" => continue,
+                // New event types may be added to the API at any time.
+                _ => {
+                    tracing::debug!(event_type = event_type._type, "Unknown Claude event");
+                    continue;
+                }
+            }
+
+            let ores: CompletionResponse = event.json()?;
+            tracing::debug!(body = ?ores, "Completion response");
+
+            if ores.delta._type == "input_json_delta" {
+                if let Some(tool_call) = pending_tool_calls.last_mut() {
+                    tool_call.arguments.push_str(&ores.delta.partial_json);
+                }
+                continue;
+            }
+
+            if let Some(ContentPartRequest::Thinking {
+                thinking,
+                signature,
+            }) = turn.thinking.last_mut()
+            {
+                thinking.push_str(&ores.delta.thinking);
+                signature.push_str(&ores.delta.signature);
+            }
+
+            let text = ores.delta.text.to_string();
+            let reasoning = ores.delta.thinking.to_string();
+            if text.is_empty() && reasoning.is_empty() {
+                continue;
+            }
+            turn.text.push_str(&text);
+
+            let msg = BackendResponse {
+                author: Author::Model,
+                text,
+                reasoning,
+                done: false,
+                usage: None,
+                truncated: false,
+            };
+
+            tx.send(Event::BackendPromptResponse(msg))?;
+        }
+
+        turn.tool_calls = pending_tool_calls
+            .iter()
+            .map(|tool_call| {
+                return ToolCall::new(
+                    &tool_call.id,
+                    &tool_call.name,
+                    ToolCall::parse_arguments(&tool_call.arguments),
+                );
+            })
+            .collect();
+
+        return Ok(Some(turn));
     }
 }
 
@@ -104,7 +577,9 @@ impl Backend for Claude {
             bail!("Claude token is not defined");
         }
 
-        let res = reqwest::Client::new()
+        let res = self
+            .http
+            .client()?
             .get(format!("{url}/healthcheck", url = self.url))
             .timeout(Duration::from_millis(self.timeout.parse::<u64>()?))
             .send()
@@ -131,7 +606,11 @@ impl Backend for Claude {
     }
 
     #[allow(clippy::implicit_return)]
-    async fn list_models(&self) -> Result<Vec<String>> {
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        if let Some(models) = self.fetch_models().await {
+            return Ok(models);
+        }
+
         let backup = vec![
             "claude-3-haiku-20240307".to_string(),
             "claude-3-sonnet-20240229".to_string(),
@@ -139,11 +618,16 @@ impl Backend for Claude {
             "claude-2.1".to_string(),
             "claude-2.0".to_string(),
         ];
-        let res = reqwest::Client::new()
-            .get("https://raw.githubusercontent.com/anthropics/anthropic-sdk-typescript/main/src/resources/messages.ts")
+        let res = self
+            .http
+            .request(Method::GET, "https://raw.githubusercontent.com/anthropics/anthropic-sdk-typescript/main/src/resources/messages.ts")?
             .send()
             .await;
 
+        let backup = backup
+            .iter()
+            .map(|name| return ModelInfo::new(name))
+            .collect();
         let body = match res {
             Ok(html) => html.text().await,
             Err(_) => return Ok(backup),
@@ -152,16 +636,19 @@ impl Backend for Claude {
         match body {
             Ok(html) => {
                 let re = Regex::new(r#"['"](claude-.*)['"]"#).unwrap();
-                let mut results: Vec<String> = vec![];
+                let mut results: Vec<ModelInfo> = vec![];
                 for (_, [model]) in re.captures_iter(&html).map(|c| c.extract()) {
                     let m = model.to_string();
                     let cleaned = Regex::new(r"[^a-zA-Z0-9-\.]")
                         .unwrap()
                         .replace_all(&m, "")
                         .to_string();
-                    results.push(cleaned);
+                    results.push(ModelInfo::new(&cleaned));
                 }
-                return Ok(results.into_iter().unique().collect());
+                return Ok(results
+                    .into_iter()
+                    .unique_by(|model| return model.name.to_string())
+                    .collect());
             }
             Err(_) => return Ok(backup),
         };
@@ -171,98 +658,145 @@ impl Backend for Claude {
     async fn get_completion<'a>(
         &self,
         prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
         tx: &'a mpsc::UnboundedSender<Event>,
     ) -> Result<()> {
-        let mut messages: Vec<MessageRequest> = vec![];
-        if !prompt.backend_context.is_empty() {
-            messages = serde_json::from_str(&prompt.backend_context)?;
+        let mut messages: Vec<MessageRequest> = prompt
+            .history
+            .iter()
+            .map(MessageRequest::from_history)
+            .collect();
+        messages.push(MessageRequest::new(
+            "user",
+            &prompt.text,
+            &prompt.attachments,
+        ));
+
+        // Claude requires max_tokens to always be set, and to leave room for
+        // the answer after thinking.
+        let max_tokens = match (prompt.generation_options.max_tokens, self.thinking_budget) {
+            (Some(max_tokens), Some(budget)) if max_tokens <= budget => {
+                bail!(format!(
+                    "max-tokens ({max_tokens}) must be greater than {} ({budget})",
+                    ConfigKey::ClaudeThinkingBudget
+                ));
+            }
+            (Some(max_tokens), _) => max_tokens,
+            (None, Some(budget)) => budget + 1024,
+            (None, None) => 1024,
+        };
+
+        // Claude rejects temperature and top_p alongside extended thinking, so
+        // they're left out of the request instead.
+        let mut temperature = prompt.generation_options.temperature;
+        let mut top_p = prompt.generation_options.top_p;
+        if self.thinking_budget.is_some() && (temperature.is_some() || top_p.is_some()) {
+            tx.send(Event::BackendStatus(Message::new(
+                Author::Oatmeal,
+                &format!(
+                    "{} and {} aren't supported with extended thinking, so they weren't sent to Claude.",
+                    ConfigKey::Temperature,
+                    ConfigKey::TopP
+                ),
+            )))?;
+            temperature = None;
+            top_p = None;
         }
-        messages.push(MessageRequest {
-            role: "user".to_string(),
-            content: prompt.text,
-        });
 
-        let req = CompletionRequest {
-            model: Config::get(ConfigKey::Model),
-            max_tokens: 1024,
-            messages: messages.clone(),
+        let mut req = CompletionRequest {
+            model: prompt.model(),
+            max_tokens,
+            system: None,
+            messages,
             stream: true,
+            temperature,
+            top_p,
+            stop_sequences: prompt.generation_options.stop,
+            tools: self.tools.names().iter().map(ToolRequest::new).collect(),
+            thinking: self.thinking_budget.map(|budget_tokens| {
+                return ThinkingRequest {
+                    _type: "enabled".to_string(),
+                    budget_tokens,
+                };
+            }),
         };
 
-        let res = reqwest::Client::new()
-            .post(format!("{url}/v1/messages", url = self.url))
-            .header("x-api-key", &self.token)
-            .header("content-type", "application/json")
-            .header("anthropic-version", "2023-06-01")
-            .header("anthropic-beta", "messages-2023-12-15")
-            .json(&req)
-            .send()
-            .await?;
-
-        if !res.status().is_success() {
-            tracing::error!(
-                status = res.status().as_u16(),
-                "Failed to make completion request to Claude"
-            );
-            bail!("Failed to make completion request to Claude");
+        if !prompt.system_prompt.is_empty() {
+            req.system = Some(prompt.system_prompt);
         }
 
-        let stream = res.bytes_stream().map_err(convert_err);
-        let mut lines_reader = StreamReader::new(stream).lines();
-
-        let mut last_message = "".to_string();
-        while let Ok(line) = lines_reader.next_line().await {
-            if line.is_none() {
+        let mut usage = Usage::default();
+        let mut stop_reason = None;
+        for _ in 0..=MAX_TOOL_ROUNDS {
+            let turn = match self.stream_completion(&req, cancel, tx).await? {
+                Some(turn) => turn,
+                None => break,
+            };
+            usage.add(&turn.usage);
+            stop_reason = turn.stop_reason;
+            if turn.tool_calls.is_empty() || cancel.is_cancelled() {
                 break;
             }
 
-            let mut cleaned_line = line.unwrap().trim().to_string();
-            if cleaned_line.starts_with("data:") {
-                cleaned_line = cleaned_line.split_off(5).trim().to_string();
-            }
-            if cleaned_line.is_empty() || cleaned_line.contains("event:") {
-                continue;
+            // Thinking has to be sent back as it was received, ahead of the
+            // tool calls it led to.
+            let mut assistant_parts = turn.thinking;
+            if !turn.text.is_empty() {
+                assistant_parts.push(ContentPartRequest::Text { text: turn.text });
             }
-
-            if cleaned_line.contains("content_block_stop") {
-                break;
+            for call in turn.tool_calls.iter() {
+                assistant_parts.push(ContentPartRequest::ToolUse {
+                    id: call.id.to_string(),
+                    name: call.name.to_string(),
+                    input: call.arguments.clone(),
+                });
             }
-            if !cleaned_line.contains("content_block_delta") {
-                continue;
+            req.messages.push(MessageRequest {
+                role: "assistant".to_string(),
+                content: ContentRequest::Parts(assistant_parts),
+            });
+
+            let mut result_parts = vec![];
+            for call in turn.tool_calls.iter() {
+                let result = match self.tools.call(call, cancel, tx).await? {
+                    Some(result) => result,
+                    None => break,
+                };
+                result_parts.push(ContentPartRequest::ToolResult {
+                    tool_use_id: call.id.to_string(),
+                    content: result.content,
+                    is_error: result.is_error,
+                });
             }
-
-            let ores: CompletionResponse = serde_json::from_str(&cleaned_line).unwrap();
-            tracing::debug!(body = ?ores, "Completion response");
-
-            let text = ores.delta.text.clone().to_string();
-            if text.is_empty() {
-                continue;
+            if cancel.is_cancelled() {
+                break;
             }
-
-            last_message += &text;
-            let msg = BackendResponse {
-                author: Author::Model,
-                text,
-                done: false,
-                context: None,
-            };
-
-            tx.send(Event::BackendPromptResponse(msg))?;
+            req.messages.push(MessageRequest {
+                role: "user".to_string(),
+                content: ContentRequest::Parts(result_parts),
+            });
         }
 
-        messages.push(MessageRequest {
-            role: "assistant".to_string(),
-            content: last_message.to_string(),
-        });
-
         let msg = BackendResponse {
             author: Author::Model,
             text: "".to_string(),
+            reasoning: "".to_string(),
             done: true,
-            context: Some(serde_json::to_string(&messages)?),
+            usage: Some(usage).filter(|e| return e.total_tokens() > 0),
+            truncated: cancel.is_cancelled(),
         };
         tx.send(Event::BackendPromptResponse(msg))?;
 
+        if stop_reason.as_deref() == Some("max_tokens") && !cancel.is_cancelled() {
+            tx.send(Event::BackendMessage(Message::new(
+                Author::Oatmeal,
+                &format!(
+                    "The response was truncated as it reached the limit of {} tokens. Use `/continue` to have the model carry on from where it stopped, or raise the limit with `/set max-tokens`.",
+                    req.max_tokens
+                ),
+            )))?;
+        }
+
         return Ok(());
     }
 }
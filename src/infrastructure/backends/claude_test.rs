@@ -1,18 +1,37 @@
+use std::time::Duration;
+
 use anyhow::bail;
 use anyhow::Result;
-use test_utils::insta_snapshot;
+use mockito::Matcher;
+use serde_json::json;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 use super::Claude;
 use super::CompletionDeltaResponse;
 use super::CompletionResponse;
 use super::Healthcheck;
-use super::MessageRequest;
+use super::MessageDeltaBodyResponse;
+use super::MessageDeltaResponse;
+use super::MessageStartBodyResponse;
+use super::MessageStartResponse;
+use super::UsageResponse;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::ToolName;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::tools::Tools;
 
 impl Claude {
     fn with_url(url: String) -> Claude {
@@ -20,6 +39,10 @@ impl Claude {
             url,
             token: "abc".to_string(),
             timeout: "500".to_string(),
+            http: HttpClient::new(reqwest::Client::new()),
+            retry_policy: RetryPolicy::new(3, Duration::from_millis(1)),
+            thinking_budget: None,
+            tools: Tools::new(vec![], std::env::temp_dir(), ""),
         };
     }
 }
@@ -33,6 +56,19 @@ fn to_res(action: Option<Event>) -> Result<BackendResponse> {
     return Ok(act);
 }
 
+/// Approves or denies the first tool call, returning its summary.
+async fn respond_to_tool_call(rx: &mut mpsc::UnboundedReceiver<Event>, approved: bool) -> String {
+    while let Some(event) = rx.recv().await {
+        if let Event::BackendToolCall(approval) = event {
+            let summary = approval.call.summary();
+            approval.respond(approved);
+            return summary;
+        }
+    }
+
+    return "".to_string();
+}
+
 #[tokio::test]
 async fn it_successfully_health_checks() {
     let body = serde_json::to_string(&Healthcheck {
@@ -76,8 +112,65 @@ async fn it_fails_health_checks() {
 
 #[tokio::test]
 async fn it_lists_models() -> Result<()> {
-    let backend = Claude::with_url("https://api.anthropic.com".to_string());
+    let body = json!({
+        "data": [
+            {
+                "type": "model",
+                "id": "claude-3-5-sonnet-20241022",
+                "display_name": "Claude 3.5 Sonnet (New)",
+                "created_at": "2024-10-22T00:00:00Z"
+            },
+            {
+                "type": "model",
+                "id": "claude-2.1"
+            }
+        ],
+        "has_more": false
+    })
+    .to_string();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("GET", "/v1/models?limit=1000")
+        .match_header("x-api-key", "abc")
+        .match_header("anthropic-version", "2023-06-01")
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let backend = Claude::with_url(server.url());
     let res = backend.list_models().await?;
+    mock.assert();
+
+    assert_eq!(
+        res,
+        vec![
+            ModelInfo {
+                modalities: vec!["text".to_string(), "image".to_string()],
+                created: Some("2024-10-22".to_string()),
+                ..ModelInfo::new("claude-3-5-sonnet-20241022")
+            },
+            ModelInfo {
+                modalities: vec!["text".to_string(), "image".to_string()],
+                ..ModelInfo::new("claude-2.1")
+            },
+        ]
+    );
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_falls_back_to_known_models() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("GET", "/v1/models?limit=1000")
+        .with_status(404)
+        .create();
+
+    let backend = Claude::with_url(server.url());
+    let res = backend.list_models().await?;
+    mock.assert();
 
     assert!(!res.is_empty());
     return Ok(());
@@ -90,6 +183,9 @@ async fn it_gets_completions() -> Result<()> {
         delta: CompletionDeltaResponse {
             _type: "text".to_string(),
             text: "Hello ".to_string(),
+            partial_json: "".to_string(),
+            thinking: "".to_string(),
+            signature: "".to_string(),
         },
     })?;
 
@@ -98,6 +194,9 @@ async fn it_gets_completions() -> Result<()> {
         delta: CompletionDeltaResponse {
             _type: "text".to_string(),
             text: "World".to_string(),
+            partial_json: "".to_string(),
+            thinking: "".to_string(),
+            signature: "".to_string(),
         },
     })?;
 
@@ -106,16 +205,58 @@ async fn it_gets_completions() -> Result<()> {
         delta: CompletionDeltaResponse {
             _type: "end".to_string(),
             text: "".to_string(),
+            partial_json: "".to_string(),
+            thinking: "".to_string(),
+            signature: "".to_string(),
+        },
+    })?;
+
+    let start_line = serde_json::to_string(&MessageStartResponse {
+        _type: "message_start".to_string(),
+        message: MessageStartBodyResponse {
+            usage: UsageResponse {
+                input_tokens: 12,
+                output_tokens: 1,
+            },
         },
     })?;
 
-    let body = [first_line, second_line, third_line].join("\n");
+    let delta_line = serde_json::to_string(&MessageDeltaResponse {
+        _type: "message_delta".to_string(),
+        delta: MessageDeltaBodyResponse {
+            stop_reason: Some("end_turn".to_string()),
+        },
+        usage: UsageResponse {
+            input_tokens: 0,
+            output_tokens: 2,
+        },
+    })?;
+
+    let body = [
+        start_line,
+        first_line,
+        second_line,
+        third_line,
+        delta_line,
+        r#"{"type":"message_stop"}"#.to_string(),
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
     let prompt = BackendPrompt {
         text: "Say hi to the world".to_string(),
-        backend_context: serde_json::to_string(&vec![MessageRequest {
-            role: "assistant".to_string(),
-            content: "How may I help you?".to_string(),
-        }])?,
+        attachments: vec![],
+        history: vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "How may I help you?"),
+        ],
+        system_prompt: "You are a test.".to_string(),
+        generation_options: GenerationOptions {
+            temperature: Some(0.5),
+            max_tokens: Some(100),
+            top_p: None,
+            stop: vec!["END".to_string()],
+        },
+        model: None,
     };
 
     let mut server = mockito::Server::new();
@@ -125,6 +266,7 @@ async fn it_gets_completions() -> Result<()> {
         .match_header("content-type", "application/json")
         .match_header("anthropic-version", "2023-06-01")
         .match_header("anthropic-beta", "messages-2023-12-15")
+        .match_body(Matcher::PartialJson(json!({"system": "You are a test.", "messages": [{"role": "user", "content": "Hello"}, {"role": "assistant", "content": "How may I help you?"}, {"role": "user", "content": "Say hi to the world"}], "temperature": 0.5, "max_tokens": 100, "stop_sequences": ["END"]})))
         .with_status(200)
         .with_body(body)
         .create();
@@ -132,7 +274,9 @@ async fn it_gets_completions() -> Result<()> {
     let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
 
     let backend = Claude::with_url(server.url());
-    backend.get_completion(prompt, &tx).await?;
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
 
     mock.assert();
 
@@ -143,19 +287,441 @@ async fn it_gets_completions() -> Result<()> {
     assert_eq!(first_recv.author, Author::Model);
     assert_eq!(first_recv.text, "Hello ".to_string());
     assert!(!first_recv.done);
-    assert_eq!(first_recv.context, None);
 
     assert_eq!(second_recv.author, Author::Model);
     assert_eq!(second_recv.text, "World".to_string());
     assert!(!second_recv.done);
-    assert_eq!(second_recv.context, None);
 
     assert_eq!(third_recv.author, Author::Model);
     assert!(third_recv.text.is_empty());
     assert!(third_recv.done);
-    insta_snapshot(|| {
-        insta::assert_toml_snapshot!(third_recv.context);
-    });
+    assert_eq!(third_recv.usage, Some(Usage::new(12, 2)));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_image_attachments() -> Result<()> {
+    let mut prompt = BackendPrompt::new("What is this?".to_string(), vec![]);
+    prompt.attachments = vec![Attachment {
+        kind: AttachmentKind::Image,
+        path: "/tmp/cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        content: "b2F0bWVhbA==".to_string(),
+    }];
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(Matcher::PartialJson(json!({"messages": [{"role": "user", "content": [{"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "b2F0bWVhbA=="}}, {"type": "text", "text": "What is this?"}]}]})))
+        .with_status(200)
+        .create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = Claude::with_url(server.url());
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
+
+    mock.assert();
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_denied_tool_calls_as_errors() -> Result<()> {
+    let tool_call_body = [
+        r#"{"type":"message_start","message":{"usage":{"input_tokens":10,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_1","name":"run_tests","input":{}}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":""}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":5}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+    let answer_body = [
+        r#"{"type":"message_start","message":{"usage":{"input_tokens":20,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Okay, I won't."}}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":4}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let tool_call_mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(Matcher::PartialJson(
+            json!({"tools": [{"name": "run_tests", "input_schema": {"type": "object"}}]}),
+        ))
+        .with_status(200)
+        .with_body(tool_call_body)
+        .expect(1)
+        .create();
+    let answer_mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(Matcher::PartialJson(json!({"messages": [
+            {"role": "user", "content": "Run the tests"},
+            {"role": "assistant", "content": [{"type": "tool_use", "id": "toolu_1", "name": "run_tests", "input": {}}]},
+            {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "toolu_1", "content": "The user denied running this tool.", "is_error": true}]}
+        ]})))
+        .with_status(200)
+        .with_body(answer_body)
+        .expect(1)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let mut backend = Claude::with_url(server.url());
+    backend.tools = Tools::new(vec![ToolName::RunTests], std::env::temp_dir(), "true");
+
+    let prompt = BackendPrompt::new("Run the tests".to_string(), vec![]);
+    let cancel = CancellationToken::new();
+    let (res, summary) = tokio::join!(
+        backend.get_completion(prompt, &cancel, &tx),
+        respond_to_tool_call(&mut rx, false)
+    );
+    res?;
+
+    tool_call_mock.assert();
+    answer_mock.assert();
+    assert_eq!(summary, "run_tests()");
+
+    let first_recv = to_res(rx.recv().await)?;
+    let second_recv = to_res(rx.recv().await)?;
+
+    assert_eq!(first_recv.text, "Okay, I won't.");
+    assert!(second_recv.done);
+    assert_eq!(second_recv.usage, Some(Usage::new(30, 9)));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_notifies_when_responses_reach_max_tokens() -> Result<()> {
+    let body = [
+        r#"{"type":"message_start","message":{"usage":{"input_tokens":10,"output_tokens":1}}}"#,
+        r#"{"type":"ping"}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Once upon"}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"max_tokens"},"usage":{"output_tokens":2}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let mut prompt = BackendPrompt::new("Tell me a story".to_string(), vec![]);
+    prompt.generation_options.max_tokens = Some(2);
+
+    let backend = Claude::with_url(server.url());
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
+
+    mock.assert();
+
+    let first_recv = to_res(rx.recv().await)?;
+    let second_recv = to_res(rx.recv().await)?;
+    assert_eq!(first_recv.text, "Once upon");
+    assert!(second_recv.done);
+    assert!(!second_recv.truncated);
+
+    match rx.recv().await {
+        Some(Event::BackendMessage(msg)) => {
+            assert_eq!(msg.author, Author::Oatmeal);
+            assert!(msg.text.contains("limit of 2 tokens"));
+            assert!(msg.text.contains("/continue"));
+        }
+        _ => bail!("Wrong type from recv"),
+    }
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_on_stream_error_events() -> Result<()> {
+    let body = [
+        r#"{"type":"message_start","message":{"usage":{"input_tokens":10,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}"#,
+        r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let prompt = BackendPrompt::new("Hello".to_string(), vec![]);
+
+    let backend = Claude::with_url(server.url());
+    let res = backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await;
+
+    mock.assert();
+    assert_eq!(to_res(rx.recv().await)?.text, "Hel");
+    assert!(res.unwrap_err().to_string().contains("Overloaded"));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_reports_api_errors() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .with_status(400)
+        .with_body(r#"{"type":"error","error":{"type":"invalid_request_error","message":"max_tokens: must be greater than 0"}}"#)
+        .create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+    let prompt = BackendPrompt::new("Hello".to_string(), vec![]);
+
+    let backend = Claude::with_url(server.url());
+    let res = backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await;
+
+    mock.assert();
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .contains("max_tokens: must be greater than 0"));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_streams_thinking_blocks_as_reasoning() -> Result<()> {
+    let body = [
+        r#"{"type":"message_start","message":{"usage":{"input_tokens":10,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"They said hi."}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"abc"}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"content_block_start","index":1,"content_block":{"type":"text","text":""}}"#,
+        r#"{"type":"content_block_delta","index":1,"delta":{"type":"text_delta","text":"Hello!"}}"#,
+        r#"{"type":"content_block_stop","index":1}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":8}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = Claude::with_url(server.url());
+    backend
+        .get_completion(
+            BackendPrompt::new("Hi".to_string(), vec![]),
+            &CancellationToken::new(),
+            &tx,
+        )
+        .await?;
+
+    mock.assert();
+
+    let first_recv = to_res(rx.recv().await)?;
+    let second_recv = to_res(rx.recv().await)?;
+    assert_eq!(first_recv.reasoning, "They said hi.");
+    assert!(first_recv.text.is_empty());
+    assert_eq!(second_recv.text, "Hello!");
+    assert!(second_recv.reasoning.is_empty());
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_thinking_budgets() -> Result<()> {
+    let body = [
+        r#"{"type":"message_start","message":{"usage":{"input_tokens":10,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello!"}}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":8}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(Matcher::PartialJson(json!({
+            "max_tokens": 3072,
+            "thinking": {"type": "enabled", "budget_tokens": 2048}
+        })))
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+    let mut backend = Claude::with_url(server.url());
+    backend.thinking_budget = Some(2048);
+    backend
+        .get_completion(
+            BackendPrompt::new("Hi".to_string(), vec![]),
+            &CancellationToken::new(),
+            &tx,
+        )
+        .await?;
+
+    mock.assert();
+
+    let mut prompt = BackendPrompt::new("Hi".to_string(), vec![]);
+    prompt.generation_options = GenerationOptions {
+        max_tokens: Some(1024),
+        ..Default::default()
+    };
+    let res = backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "max-tokens (1024) must be greater than claude-thinking-budget (2048)"
+    );
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_leaves_out_sampling_options_when_thinking() -> Result<()> {
+    let body = [
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello!"}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let sampling = server
+        .mock("POST", "/v1/messages")
+        .match_body(Matcher::Regex(r#""(temperature|top_p)""#.to_string()))
+        .with_status(400)
+        .create();
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(Matcher::PartialJson(json!({
+            "thinking": {"type": "enabled", "budget_tokens": 2048}
+        })))
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let mut backend = Claude::with_url(server.url());
+    backend.thinking_budget = Some(2048);
+    let mut prompt = BackendPrompt::new("Hi".to_string(), vec![]);
+    prompt.generation_options = GenerationOptions {
+        temperature: Some(0.2),
+        top_p: Some(0.9),
+        ..Default::default()
+    };
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
+
+    assert!(!sampling.matched());
+    mock.assert();
+
+    let msg = match rx.recv().await.unwrap() {
+        Event::BackendStatus(msg) => msg,
+        _ => bail!("Wrong type from recv"),
+    };
+    assert_eq!(msg.author, Author::Oatmeal);
+    insta::assert_snapshot!(msg.text, @"temperature and top-p aren't supported with extended thinking, so they weren't sent to Claude.");
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_thinking_back_with_tool_results() -> Result<()> {
+    let tool_call_body = [
+        r#"{"type":"message_start","message":{"usage":{"input_tokens":10,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Tests first."}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"abc"}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"content_block_start","index":1,"content_block":{"type":"redacted_thinking","data":"xyz"}}"#,
+        r#"{"type":"content_block_stop","index":1}"#,
+        r#"{"type":"content_block_start","index":2,"content_block":{"type":"tool_use","id":"toolu_1","name":"run_tests","input":{}}}"#,
+        r#"{"type":"content_block_delta","index":2,"delta":{"type":"input_json_delta","partial_json":""}}"#,
+        r#"{"type":"content_block_stop","index":2}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":5}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+    let answer_body = [
+        r#"{"type":"message_start","message":{"usage":{"input_tokens":20,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Okay, I won't."}}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":4}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let tool_call_mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(Matcher::PartialJson(
+            json!({"messages": [{"role": "user", "content": "Run the tests"}]}),
+        ))
+        .with_status(200)
+        .with_body(tool_call_body)
+        .expect(1)
+        .create();
+    let answer_mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(Matcher::PartialJson(json!({"messages": [
+            {"role": "user", "content": "Run the tests"},
+            {"role": "assistant", "content": [
+                {"type": "thinking", "thinking": "Tests first.", "signature": "abc"},
+                {"type": "redacted_thinking", "data": "xyz"},
+                {"type": "tool_use", "id": "toolu_1", "name": "run_tests", "input": {}}
+            ]},
+            {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "toolu_1", "content": "The user denied running this tool.", "is_error": true}]}
+        ]})))
+        .with_status(200)
+        .with_body(answer_body)
+        .expect(1)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let mut backend = Claude::with_url(server.url());
+    backend.thinking_budget = Some(2048);
+    backend.tools = Tools::new(vec![ToolName::RunTests], std::env::temp_dir(), "true");
+
+    let prompt = BackendPrompt::new("Run the tests".to_string(), vec![]);
+    let cancel = CancellationToken::new();
+    let (res, _) = tokio::join!(
+        backend.get_completion(prompt, &cancel, &tx),
+        respond_to_tool_call(&mut rx, false)
+    );
+    res?;
+
+    tool_call_mock.assert();
+    answer_mock.assert();
 
     return Ok(());
 }
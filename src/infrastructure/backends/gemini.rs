@@ -7,32 +7,43 @@ use std::time::Duration;
 use anyhow::bail;
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::stream::TryStreamExt;
+use reqwest::Method;
 use serde::Deserialize;
 use serde::Serialize;
-use tokio::io::AsyncBufReadExt;
 use tokio::sync::mpsc;
-use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
 
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
+use crate::configuration::Secrets;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendName;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
-
-fn convert_err(err: reqwest::Error) -> std::io::Error {
-    let err_msg = err.to_string();
-    return std::io::Error::new(std::io::ErrorKind::Interrupted, err_msg);
-}
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::ToolCall;
+use crate::domain::models::ToolName;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::streaming::EventStream;
+use crate::infrastructure::backends::streaming::StreamFormat;
+use crate::infrastructure::backends::tools::Tools;
+use crate::infrastructure::backends::tools::MAX_TOOL_ROUNDS;
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Model {
     name: String,
     supported_generation_methods: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    input_token_limit: Option<u64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -47,11 +58,26 @@ struct ContentPartsBlob {
     data: String,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ContentPartsFunctionCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ContentPartsFunctionResponse {
+    name: String,
+    response: serde_json::Value,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 enum ContentParts {
     Text(String),
     InlineData(ContentPartsBlob),
+    FunctionCall(ContentPartsFunctionCall),
+    FunctionResponse(ContentPartsFunctionResponse),
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -60,33 +86,256 @@ struct Content {
     parts: Vec<ContentParts>,
 }
 
+impl Content {
+    fn new(role: &str, text: &str, attachments: &[Attachment]) -> Content {
+        let mut parts = attachments
+            .iter()
+            .map(|attachment| {
+                return match attachment.kind {
+                    AttachmentKind::Image => {
+                        ContentParts::InlineData(ContentPartsBlob {
+                            mime_type: attachment.mime_type.to_string(),
+                            data: attachment.content.to_string(),
+                        })
+                    }
+                    AttachmentKind::Text => ContentParts::Text(attachment.to_prompt_text()),
+                };
+            })
+            .collect::<Vec<ContentParts>>();
+        parts.push(ContentParts::Text(text.to_string()));
+
+        return Content {
+            role: role.to_string(),
+            parts,
+        };
+    }
+
+    fn from_history(message: &HistoryMessage) -> Content {
+        let role = match message.role {
+            HistoryRole::User => "user",
+            HistoryRole::Assistant => "model",
+        };
+
+        return Content::new(role, &message.text, &message.attachments);
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SystemInstruction {
+    parts: Vec<ContentParts>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FunctionDeclaration {
+    name: String,
+    description: String,
+    // Gemini rejects object schemas without any properties.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parameters: Option<serde_json::Value>,
+}
+
+impl FunctionDeclaration {
+    fn new(tool_name: &ToolName) -> FunctionDeclaration {
+        let parameters = tool_name.parameters();
+        let has_properties = parameters["properties"]
+            .as_object()
+            .map(|properties| return !properties.is_empty())
+            .unwrap_or(false);
+
+        return FunctionDeclaration {
+            name: tool_name.to_string(),
+            description: tool_name.description().to_string(),
+            parameters: Some(parameters).filter(|_| return has_properties),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolRequest {
+    function_declarations: Vec<FunctionDeclaration>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct CompletionRequest {
     contents: Vec<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<SystemInstruction>,
+    generation_config: GenerationConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolRequest>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PartResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function_call: Option<ContentPartsFunctionCall>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CandidateContentResponse {
+    #[serde(default)]
+    parts: Vec<PartResponse>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CandidateResponse {
+    #[serde(default)]
+    content: CandidateContentResponse,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct GenerateContentResponse {
+struct UsageMetadataResponse {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompletionResponse {
+    #[serde(default)]
+    candidates: Vec<CandidateResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage_metadata: Option<UsageMetadataResponse>,
+}
+
+/// Result of a single request to the generate content API.
+struct CompletionTurn {
     text: String,
+    tool_calls: Vec<ToolCall>,
+    usage: Usage,
 }
 
 pub struct Gemini {
     url: String,
     token: String,
     timeout: String,
+    http: HttpClient,
+    retry_policy: RetryPolicy,
+    tools: Tools,
 }
 
 impl Default for Gemini {
     fn default() -> Gemini {
         return Gemini {
             url: "https://generativelanguage.googleapis.com".to_string(),
-            token: Config::get(ConfigKey::GeminiToken),
+            token: Secrets::get(ConfigKey::GeminiToken),
             timeout: Config::get(ConfigKey::BackendHealthCheckTimeout),
+            http: HttpClient::default(),
+            retry_policy: RetryPolicy::default(),
+            tools: Tools::default(),
         };
     }
 }
 
+impl Gemini {
+    /// Streams a completion to the UI, returning the text and any function
+    /// calls the model responded with. Returns `None` if cancelled before the
+    /// request was sent.
+    async fn stream_completion(
+        &self,
+        model: &str,
+        req: &CompletionRequest,
+        cancel: &CancellationToken,
+        tx: &mpsc::UnboundedSender<Event>,
+    ) -> Result<Option<CompletionTurn>> {
+        let request = self
+            .http
+            .client()?
+            .post(format!(
+                "{url}/v1beta/{model}:streamGenerateContent?alt=sse&key={key}",
+                url = self.url,
+                key = self.token,
+            ))
+            .json(req);
+        let res = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Ok(None),
+            res = self.retry_policy.send(request, self.name(), Some(tx), Some(cancel)) => res?,
+        };
+
+        if !res.status().is_success() {
+            tracing::error!(
+                status = res.status().as_u16(),
+                "Failed to make completion request to Gemini"
+            );
+            bail!(format!(
+                "Failed to make completion request to Gemini, {}",
+                res.status().as_u16()
+            ));
+        }
+        let mut events = EventStream::new(res, StreamFormat::Sse, cancel);
+
+        let mut turn = CompletionTurn {
+            text: "".to_string(),
+            tool_calls: vec![],
+            usage: Usage::default(),
+        };
+        while let Some(event) = events.next().await? {
+            let ores: CompletionResponse = event.json()?;
+            tracing::debug!(body = ?ores, "Completion response");
+
+            // Each chunk reports the usage so far, so the last counts are kept.
+            if let Some(usage) = ores.usage_metadata {
+                turn.usage = Usage::new(usage.prompt_token_count, usage.candidates_token_count);
+            }
+
+            let parts = ores
+                .candidates
+                .into_iter()
+                .take(1)
+                .flat_map(|candidate| return candidate.content.parts);
+            for part in parts {
+                if let Some(function_call) = part.function_call {
+                    turn.tool_calls.push(ToolCall::new(
+                        &function_call.name,
+                        &function_call.name,
+                        function_call.args,
+                    ));
+                }
+
+                let text = part.text.unwrap_or_default();
+                if text.is_empty() {
+                    continue;
+                }
+
+                turn.text.push_str(&text);
+                let msg = BackendResponse {
+                    author: Author::Model,
+                    text,
+                    reasoning: "".to_string(),
+                    done: false,
+                    usage: None,
+                    truncated: false,
+                };
+                tx.send(Event::BackendPromptResponse(msg))?;
+            }
+        }
+
+        return Ok(Some(turn));
+    }
+}
+
 #[async_trait]
 impl Backend for Gemini {
     fn name(&self) -> BackendName {
@@ -109,7 +358,9 @@ impl Backend for Gemini {
             key = self.token
         );
 
-        let res = reqwest::Client::new()
+        let res = self
+            .http
+            .client()?
             .get(&url)
             .timeout(Duration::from_millis(self.timeout.parse::<u64>()?))
             .send()
@@ -130,19 +381,23 @@ impl Backend for Gemini {
     }
 
     #[allow(clippy::implicit_return)]
-    async fn list_models(&self) -> Result<Vec<String>> {
-        let res = reqwest::Client::new()
-            .get(format!(
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        let request = self.http.request(
+            Method::GET,
+            format!(
                 "{url}/v1beta/models?key={key}",
                 url = self.url,
                 key = self.token
-            ))
-            .send()
+            ),
+        )?;
+        let res = self
+            .retry_policy
+            .send(request, self.name(), None, None)
             .await?
             .json::<ModelListResponse>()
             .await?;
 
-        let mut models: Vec<String> = res
+        let mut models: Vec<ModelInfo> = res
             .models
             .iter()
             .filter(|model| {
@@ -151,11 +406,14 @@ impl Backend for Gemini {
                     .contains(&"generateContent".to_string())
             })
             .map(|model| {
-                return model.name.to_string();
+                return ModelInfo {
+                    context_length: model.input_token_limit,
+                    ..ModelInfo::new(&model.name)
+                };
             })
             .collect();
 
-        models.sort();
+        models.sort_by(|a, b| return a.name.cmp(&b.name));
 
         return Ok(models);
     }
@@ -164,83 +422,102 @@ impl Backend for Gemini {
     async fn get_completion<'a>(
         &self,
         prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
         tx: &'a mpsc::UnboundedSender<Event>,
     ) -> Result<()> {
-        let mut contents: Vec<Content> = vec![];
-        if !prompt.backend_context.is_empty() {
-            contents = serde_json::from_str(&prompt.backend_context)?;
-        }
-        contents.push(Content {
-            role: "user".to_string(),
-            parts: vec![ContentParts::Text(prompt.text)],
-        });
-
-        let req = CompletionRequest {
-            contents: contents.clone(),
+        let model = prompt.model();
+        let mut contents: Vec<Content> = prompt.history.iter().map(Content::from_history).collect();
+        contents.push(Content::new("user", &prompt.text, &prompt.attachments));
+
+        let mut req = CompletionRequest {
+            contents,
+            system_instruction: None,
+            generation_config: GenerationConfig {
+                temperature: prompt.generation_options.temperature,
+                max_output_tokens: prompt.generation_options.max_tokens,
+                top_p: prompt.generation_options.top_p,
+                stop_sequences: prompt.generation_options.stop,
+            },
+            tools: vec![],
         };
 
-        let res = reqwest::Client::new()
-            .post(format!(
-                "{url}/v1beta/{model}:streamGenerateContent?key={key}",
-                url = self.url,
-                model = Config::get(ConfigKey::Model),
-                key = self.token,
-            ))
-            .json(&req)
-            .send()
-            .await?;
+        if !self.tools.names().is_empty() {
+            req.tools = vec![ToolRequest {
+                function_declarations: self
+                    .tools
+                    .names()
+                    .iter()
+                    .map(FunctionDeclaration::new)
+                    .collect(),
+            }];
+        }
 
-        if !res.status().is_success() {
-            tracing::error!(
-                status = res.status().as_u16(),
-                "Failed to make completion request to Gemini"
-            );
-            bail!(format!(
-                "Failed to make completion request to Gemini, {}",
-                res.status().as_u16()
-            ));
+        if !prompt.system_prompt.is_empty() {
+            req.system_instruction = Some(SystemInstruction {
+                parts: vec![ContentParts::Text(prompt.system_prompt)],
+            });
         }
-        let stream = res.bytes_stream().map_err(convert_err);
-        let mut lines_reader = StreamReader::new(stream).lines();
 
-        let mut last_message = "".to_string();
-        while let Ok(line) = lines_reader.next_line().await {
-            if line.is_none() {
+        let mut usage = Usage::default();
+        for _ in 0..=MAX_TOOL_ROUNDS {
+            let turn = match self.stream_completion(&model, &req, cancel, tx).await? {
+                Some(turn) => turn,
+                None => break,
+            };
+            usage.add(&turn.usage);
+            if turn.tool_calls.is_empty() || cancel.is_cancelled() {
                 break;
             }
 
-            let cleaned_line = line.unwrap().trim().to_string();
-            if !cleaned_line.starts_with("\"text\":") {
-                continue;
+            let mut model_parts = vec![];
+            if !turn.text.is_empty() {
+                model_parts.push(ContentParts::Text(turn.text));
             }
-
-            let ores: GenerateContentResponse =
-                serde_json::from_str(&format!("{{ {text} }}", text = cleaned_line)).unwrap();
-
-            if ores.text.is_empty() || ores.text.is_empty() || ores.text == "\n" {
+            for call in turn.tool_calls.iter() {
+                model_parts.push(ContentParts::FunctionCall(ContentPartsFunctionCall {
+                    name: call.name.to_string(),
+                    args: call.arguments.clone(),
+                }));
+            }
+            req.contents.push(Content {
+                role: "model".to_string(),
+                parts: model_parts,
+            });
+
+            let mut response_parts = vec![];
+            for call in turn.tool_calls.iter() {
+                let result = match self.tools.call(call, cancel, tx).await? {
+                    Some(result) => result,
+                    None => break,
+                };
+                let response = if result.is_error {
+                    serde_json::json!({ "error": result.content })
+                } else {
+                    serde_json::json!({ "content": result.content })
+                };
+                response_parts.push(ContentParts::FunctionResponse(
+                    ContentPartsFunctionResponse {
+                        name: call.name.to_string(),
+                        response,
+                    },
+                ));
+            }
+            if cancel.is_cancelled() {
                 break;
             }
-
-            last_message += &ores.text;
-            let msg = BackendResponse {
-                author: Author::Model,
-                text: ores.text,
-                done: false,
-                context: None,
-            };
-            tx.send(Event::BackendPromptResponse(msg))?;
+            req.contents.push(Content {
+                role: "user".to_string(),
+                parts: response_parts,
+            });
         }
 
-        contents.push(Content {
-            role: "model".to_string(),
-            parts: vec![ContentParts::Text(last_message.clone())],
-        });
-
         let msg = BackendResponse {
             author: Author::Model,
             text: "".to_string(),
+            reasoning: "".to_string(),
             done: true,
-            context: Some(serde_json::to_string(&contents)?),
+            usage: Some(usage).filter(|e| return e.total_tokens() > 0),
+            truncated: cancel.is_cancelled(),
         };
         tx.send(Event::BackendPromptResponse(msg))?;
 
@@ -1,20 +1,33 @@
+use std::time::Duration;
+
 use anyhow::bail;
 use anyhow::Result;
-use test_utils::insta_snapshot;
+use mockito::Matcher;
+use serde_json::json;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 use super::Config;
-use super::Content;
-use super::ContentParts;
 use super::Gemini;
 use super::Model;
 use super::ModelListResponse;
 use crate::configuration::ConfigKey;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::ToolName;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::tools::Tools;
 
 impl Gemini {
     fn with_url(url: String) -> Gemini {
@@ -22,6 +35,9 @@ impl Gemini {
             url,
             token: "abc".to_string(),
             timeout: "200".to_string(),
+            http: HttpClient::new(reqwest::Client::new()),
+            retry_policy: RetryPolicy::new(3, Duration::from_millis(1)),
+            tools: Tools::new(vec![], std::env::temp_dir(), ""),
         };
     }
 }
@@ -35,6 +51,19 @@ fn to_res(action: Option<Event>) -> Result<BackendResponse> {
     return Ok(act);
 }
 
+/// Approves or denies the first tool call, returning its summary.
+async fn respond_to_tool_call(rx: &mut mpsc::UnboundedReceiver<Event>, approved: bool) -> String {
+    while let Some(event) = rx.recv().await {
+        if let Event::BackendToolCall(approval) = event {
+            let summary = approval.call.summary();
+            approval.respond(approved);
+            return summary;
+        }
+    }
+
+    return "".to_string();
+}
+
 #[tokio::test]
 async fn it_successfully_health_checks() {
     Config::set(ConfigKey::Model, "model-1");
@@ -65,6 +94,9 @@ async fn it_successfully_health_checks_with_official_api() {
         url: "https://generativelanguage.googleapis.com".to_string(),
         token,
         timeout: "500".to_string(),
+        http: HttpClient::new(reqwest::Client::new()),
+        retry_policy: RetryPolicy::default(),
+        tools: Tools::default(),
     };
 
     let res = backend.health_check().await;
@@ -94,10 +126,17 @@ async fn it_lists_models() -> Result<()> {
             Model {
                 name: "first".to_string(),
                 supported_generation_methods: vec!["generateContent".to_string()],
+                input_token_limit: Some(1048576),
             },
             Model {
                 name: "second".to_string(),
                 supported_generation_methods: vec!["generateContent".to_string()],
+                input_token_limit: None,
+            },
+            Model {
+                name: "embedding".to_string(),
+                supported_generation_methods: vec!["embedContent".to_string()],
+                input_token_limit: Some(2048),
             },
         ],
     })?;
@@ -113,7 +152,16 @@ async fn it_lists_models() -> Result<()> {
     let res = backend.list_models().await?;
     mock.assert();
 
-    assert_eq!(res, vec!["first".to_string(), "second".to_string()]);
+    assert_eq!(
+        res,
+        vec![
+            ModelInfo {
+                context_length: Some(1048576),
+                ..ModelInfo::new("first")
+            },
+            ModelInfo::new("second"),
+        ]
+    );
 
     return Ok(());
 }
@@ -121,44 +169,39 @@ async fn it_lists_models() -> Result<()> {
 #[tokio::test]
 async fn it_gets_completions() -> Result<()> {
     Config::set(ConfigKey::Model, "model-1");
-    let body = r#"
-{
-  "contents": [
-    {
-      "parts": [
-        {
-          "text": "Hello "
-        }
-      ]
-    },
-    {
-      "parts": [
-        {
-          "text": "World"
-        }
-      ]
-    },
-    {
-      "parts": [
-        {
-          "text": ""
-        }
-      ]
-    }
-  ]
-}
-        "#;
+    let body = [
+        json!({"candidates": [{"content": {"parts": [{"text": "Hello "}], "role": "model"}}]}),
+        json!({"candidates": [{"content": {"parts": [{"text": "World"}], "role": "model"}}]}),
+        json!({
+            "candidates": [{"content": {"parts": [{"text": ""}], "role": "model"}, "finishReason": "STOP"}],
+            "usageMetadata": {"promptTokenCount": 12, "candidatesTokenCount": 2, "totalTokenCount": 14}
+        }),
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
     let prompt = BackendPrompt {
         text: "Say hi to the world".to_string(),
-        backend_context: serde_json::to_string(&vec![Content {
-            role: "model".to_string(),
-            parts: vec![ContentParts::Text("Hello".to_string())],
-        }])?,
+        attachments: vec![],
+        history: vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "How may I help you?"),
+        ],
+        system_prompt: "You are a test.".to_string(),
+        generation_options: GenerationOptions {
+            temperature: Some(0.5),
+            max_tokens: Some(100),
+            top_p: None,
+            stop: vec!["END".to_string()],
+        },
+        model: None,
     };
 
     let mut server = mockito::Server::new();
     let mock = server
-        .mock("POST", "/v1beta/model-1:streamGenerateContent?key=abc")
+        .mock("POST", "/v1beta/model-1:streamGenerateContent?alt=sse&key=abc")
+        .match_body(Matcher::PartialJson(
+            json!({"contents": [{"role": "user", "parts": [{"text": "Hello"}]}, {"role": "model", "parts": [{"text": "How may I help you?"}]}, {"role": "user", "parts": [{"text": "Say hi to the world"}]}], "systemInstruction": {"parts": [{"text": "You are a test."}]}, "generationConfig": {"temperature": 0.5, "maxOutputTokens": 100, "stopSequences": ["END"]}}),
+        ))
         .with_status(200)
         .with_body(body)
         .create();
@@ -166,7 +209,9 @@ async fn it_gets_completions() -> Result<()> {
     let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
 
     let backend = Gemini::with_url(server.url());
-    backend.get_completion(prompt, &tx).await?;
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
 
     mock.assert();
 
@@ -177,19 +222,112 @@ async fn it_gets_completions() -> Result<()> {
     assert_eq!(first_recv.author, Author::Model);
     assert_eq!(first_recv.text, "Hello ".to_string());
     assert!(!first_recv.done);
-    assert_eq!(first_recv.context, None);
 
     assert_eq!(second_recv.author, Author::Model);
     assert_eq!(second_recv.text, "World".to_string());
     assert!(!second_recv.done);
-    assert_eq!(second_recv.context, None);
 
     assert_eq!(third_recv.author, Author::Model);
     assert_eq!(third_recv.text, "".to_string());
     assert!(third_recv.done);
-    insta_snapshot(|| {
-        insta::assert_toml_snapshot!(third_recv.context);
-    });
+    assert_eq!(third_recv.usage, Some(Usage::new(12, 2)));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_image_attachments() -> Result<()> {
+    let mut prompt = BackendPrompt::new("What is this?".to_string(), vec![]);
+    prompt.attachments = vec![Attachment {
+        kind: AttachmentKind::Image,
+        path: "/tmp/cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        content: "b2F0bWVhbA==".to_string(),
+    }];
+
+    Config::set(ConfigKey::Model, "model-1");
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1beta/model-1:streamGenerateContent?alt=sse&key=abc")
+        .match_body(Matcher::PartialJson(json!({"contents": [{"role": "user", "parts": [{"inlineData": {"mimeType": "image/png", "data": "b2F0bWVhbA=="}}, {"text": "What is this?"}]}]})))
+        .with_status(200)
+        .create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = Gemini::with_url(server.url());
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
+
+    mock.assert();
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_runs_approved_function_calls() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+    let workspace = tempfile::tempdir()?;
+    std::fs::create_dir(workspace.path().join("src"))?;
+    std::fs::write(workspace.path().join("README.md"), "# Oatmeal")?;
+
+    let function_call_body = format!(
+        "data: {}\n\n",
+        json!({"candidates": [{"content": {"parts": [{"functionCall": {"name": "list_directory", "args": {"path": "."}}}], "role": "model"}}]})
+    );
+    let answer_body = format!(
+        "data: {}\n\n",
+        json!({"candidates": [{"content": {"parts": [{"text": "A readme and a src directory."}], "role": "model"}}]})
+    );
+
+    let mut server = mockito::Server::new();
+    let function_call_mock = server
+        .mock(
+            "POST",
+            "/v1beta/model-1:streamGenerateContent?alt=sse&key=abc",
+        )
+        .match_body(Matcher::PartialJson(
+            json!({"tools": [{"functionDeclarations": [{"name": "list_directory"}]}]}),
+        ))
+        .with_status(200)
+        .with_body(function_call_body)
+        .expect(1)
+        .create();
+    let answer_mock = server
+        .mock("POST", "/v1beta/model-1:streamGenerateContent?alt=sse&key=abc")
+        .match_body(Matcher::PartialJson(json!({"contents": [
+            {"role": "user", "parts": [{"text": "What's in here?"}]},
+            {"role": "model", "parts": [{"functionCall": {"name": "list_directory", "args": {"path": "."}}}]},
+            {"role": "user", "parts": [{"functionResponse": {"name": "list_directory", "response": {"content": "README.md\nsrc/"}}}]}
+        ]})))
+        .with_status(200)
+        .with_body(answer_body)
+        .expect(1)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let mut backend = Gemini::with_url(server.url());
+    backend.tools = Tools::new(
+        vec![ToolName::ListDirectory],
+        workspace.path().to_path_buf(),
+        "",
+    );
+
+    let prompt = BackendPrompt::new("What's in here?".to_string(), vec![]);
+    let cancel = CancellationToken::new();
+    let (res, summary) = tokio::join!(
+        backend.get_completion(prompt, &cancel, &tx),
+        respond_to_tool_call(&mut rx, true)
+    );
+    res?;
+
+    function_call_mock.assert();
+    answer_mock.assert();
+    assert_eq!(summary, r#"list_directory(path: ".")"#);
+
+    let first_recv = to_res(rx.recv().await)?;
+    assert_eq!(first_recv.text, "A readme and a src directory.");
 
     return Ok(());
 }
@@ -0,0 +1,173 @@
+#[cfg(test)]
+#[path = "http_test.rs"]
+mod tests;
+
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use reqwest::Certificate;
+use reqwest::Identity;
+use reqwest::IntoUrl;
+use reqwest::Method;
+use reqwest::NoProxy;
+use reqwest::Proxy;
+use reqwest::RequestBuilder;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+
+/// The shared client, with the settings it was built from.
+static CLIENT: Mutex<Option<(HttpSettings, reqwest::Client)>> = Mutex::new(None);
+
+/// Network settings shared by every backend's HTTP client.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpSettings {
+    pub proxy: String,
+    pub no_proxy: String,
+    pub ca_bundle: String,
+    pub client_cert: String,
+    pub client_key: String,
+    pub connect_timeout: Option<Duration>,
+    pub read_timeout: Option<Duration>,
+    pub request_timeout: Option<Duration>,
+}
+
+fn duration_from_config(key: ConfigKey) -> Option<Duration> {
+    return Config::get(key)
+        .parse::<u64>()
+        .ok()
+        .filter(|millis| return *millis > 0)
+        .map(Duration::from_millis);
+}
+
+fn read_pem(path: &str, name: &str) -> Result<Vec<u8>> {
+    return match fs::read(path) {
+        Ok(pem) => Ok(pem),
+        Err(err) => bail!(format!("Failed to read {name} {path}: {err}")),
+    };
+}
+
+impl HttpSettings {
+    pub fn from_config() -> HttpSettings {
+        return HttpSettings {
+            proxy: Config::get(ConfigKey::HttpProxy),
+            no_proxy: Config::get(ConfigKey::NoProxy),
+            ca_bundle: Config::get(ConfigKey::CaBundle),
+            client_cert: Config::get(ConfigKey::ClientCert),
+            client_key: Config::get(ConfigKey::ClientKey),
+            connect_timeout: duration_from_config(ConfigKey::HttpConnectTimeout),
+            read_timeout: duration_from_config(ConfigKey::HttpReadTimeout),
+            request_timeout: duration_from_config(ConfigKey::HttpRequestTimeout),
+        };
+    }
+}
+
+/// HTTP client used by backends, so proxies, certificates and connection
+/// pooling are configured in one place.
+#[derive(Clone, Debug, Default)]
+pub struct HttpClient {
+    /// Used instead of the shared client when set, such as by tests, whose
+    /// runtimes pooled connections can't outlive.
+    client: Option<reqwest::Client>,
+}
+
+impl HttpClient {
+    /// Returns the client, which is shared by backends unless one was given.
+    /// The shared client is built again when its settings change, such as
+    /// when switching profiles. Clones share the same connection pool.
+    pub fn client(&self) -> Result<reqwest::Client> {
+        if let Some(client) = &self.client {
+            return Ok(client.clone());
+        }
+
+        let settings = HttpSettings::from_config();
+        let mut shared = CLIENT.lock().unwrap();
+        if let Some((shared_settings, client)) = shared.as_ref() {
+            if *shared_settings == settings {
+                return Ok(client.clone());
+            }
+        }
+
+        let client = HttpClient::build(&settings)?;
+        *shared = Some((settings, client.clone()));
+
+        return Ok(client);
+    }
+
+    /// Starts a request whose response is read whole, such as a model list or
+    /// embeddings, failing once it takes longer than the request timeout.
+    /// Streamed responses use the read timeout instead.
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> Result<RequestBuilder> {
+        let request = self.client()?.request(method, url);
+
+        return match HttpSettings::from_config().request_timeout {
+            Some(timeout) => Ok(request.timeout(timeout)),
+            None => Ok(request),
+        };
+    }
+
+    /// Longest wait for the next piece of a streamed response.
+    pub fn read_timeout() -> Option<Duration> {
+        return HttpSettings::from_config().read_timeout;
+    }
+
+    pub fn build(settings: &HttpSettings) -> Result<reqwest::Client> {
+        // Without a configured proxy, reqwest uses the proxy environment
+        // variables.
+        let mut builder = reqwest::Client::builder();
+
+        if !settings.proxy.is_empty() {
+            let proxy = match Proxy::all(&settings.proxy) {
+                Ok(proxy) => proxy,
+                Err(err) => bail!(format!("Invalid HTTP proxy {}: {err}", settings.proxy)),
+            };
+            builder = builder.proxy(proxy.no_proxy(NoProxy::from_string(&settings.no_proxy)));
+        }
+
+        // Certificates in a PEM bundle are all trusted, alongside the system's
+        // root certificates.
+        if !settings.ca_bundle.is_empty() {
+            let pem = read_pem(&settings.ca_bundle, "CA bundle")?;
+            let cert = match Certificate::from_pem(&pem) {
+                Ok(cert) => cert,
+                Err(err) => bail!(format!("Invalid CA bundle {}: {err}", settings.ca_bundle)),
+            };
+            builder = builder.add_root_certificate(cert);
+        }
+
+        if !settings.client_key.is_empty() && settings.client_cert.is_empty() {
+            bail!("A client certificate is required when setting a client key");
+        }
+        if !settings.client_cert.is_empty() {
+            // The key can also be bundled in the certificate file.
+            let mut pem = read_pem(&settings.client_cert, "client certificate")?;
+            if !settings.client_key.is_empty() {
+                pem.push(b'\n');
+                pem.extend(read_pem(&settings.client_key, "client key")?);
+            }
+
+            let identity = match Identity::from_pem(&pem) {
+                Ok(identity) => identity,
+                Err(err) => {
+                    bail!(format!(
+                        "Invalid client certificate {}: {err}",
+                        settings.client_cert
+                    ))
+                }
+            };
+            builder = builder.identity(identity);
+        }
+
+        if let Some(timeout) = settings.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        return match builder.build() {
+            Ok(client) => Ok(client),
+            Err(err) => bail!(format!("Failed to build the HTTP client: {err}")),
+        };
+    }
+}
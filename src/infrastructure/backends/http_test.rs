@@ -0,0 +1,205 @@
+use std::fs;
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::Method;
+
+use super::HttpClient;
+use super::HttpSettings;
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+
+impl HttpClient {
+    /// Uses the given client instead of the shared one, whose pooled
+    /// connections can't outlive each test's runtime.
+    pub fn new(client: reqwest::Client) -> HttpClient {
+        return HttpClient {
+            client: Some(client),
+        };
+    }
+}
+
+#[tokio::test]
+async fn it_builds_with_defaults() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("GET", "/").with_status(200).create();
+
+    let client = HttpClient::build(&HttpSettings::default())?;
+    let res = client.get(server.url()).send().await?;
+
+    assert_eq!(res.status().as_u16(), 200);
+    mock.assert();
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_requests_through_the_proxy() -> Result<()> {
+    let mut proxy = mockito::Server::new();
+    let mock = proxy
+        .mock("GET", "/api/tags")
+        .match_header("host", "ollama.invalid")
+        .with_status(200)
+        .create();
+
+    let client = HttpClient::build(&HttpSettings {
+        proxy: proxy.url(),
+        ..HttpSettings::default()
+    })?;
+    let res = client.get("http://ollama.invalid/api/tags").send().await?;
+
+    assert_eq!(res.status().as_u16(), 200);
+    mock.assert();
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_bypasses_the_proxy_for_no_proxy_hosts() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("GET", "/").with_status(200).create();
+
+    let client = HttpClient::build(&HttpSettings {
+        proxy: "http://localhost:1".to_string(),
+        no_proxy: "example.com,127.0.0.1".to_string(),
+        ..HttpSettings::default()
+    })?;
+    let res = client.get(server.url()).send().await?;
+
+    assert_eq!(res.status().as_u16(), 200);
+    mock.assert();
+
+    return Ok(());
+}
+
+#[test]
+fn it_fails_on_invalid_proxies() {
+    let res = HttpClient::build(&HttpSettings {
+        proxy: "not a url".to_string(),
+        ..HttpSettings::default()
+    });
+
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("Invalid HTTP proxy not a url"));
+}
+
+#[test]
+fn it_loads_certificates() -> Result<()> {
+    HttpClient::build(&HttpSettings {
+        ca_bundle: "./test/client-cert.pem".to_string(),
+        client_cert: "./test/client-cert.pem".to_string(),
+        client_key: "./test/client-key.pem".to_string(),
+        connect_timeout: Some(Duration::from_millis(100)),
+        ..HttpSettings::default()
+    })?;
+
+    return Ok(());
+}
+
+#[test]
+fn it_loads_client_certificates_bundled_with_their_key() -> Result<()> {
+    let bundle = tempfile::NamedTempFile::new()?;
+    fs::write(
+        bundle.path(),
+        [
+            fs::read_to_string("./test/client-cert.pem")?,
+            fs::read_to_string("./test/client-key.pem")?,
+        ]
+        .concat(),
+    )?;
+
+    HttpClient::build(&HttpSettings {
+        client_cert: bundle.path().to_string_lossy().to_string(),
+        ..HttpSettings::default()
+    })?;
+
+    return Ok(());
+}
+
+#[test]
+fn it_fails_on_missing_ca_bundles() {
+    let res = HttpClient::build(&HttpSettings {
+        ca_bundle: "./test/missing.pem".to_string(),
+        ..HttpSettings::default()
+    });
+
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("Failed to read CA bundle ./test/missing.pem"));
+}
+
+#[test]
+fn it_fails_on_invalid_ca_bundles() -> Result<()> {
+    let bundle = tempfile::NamedTempFile::new()?;
+    fs::write(bundle.path(), "-----BEGIN CERTIFICATE-----\noatmeal\n")?;
+
+    let res = HttpClient::build(&HttpSettings {
+        ca_bundle: bundle.path().to_string_lossy().to_string(),
+        ..HttpSettings::default()
+    });
+
+    assert!(res.is_err());
+
+    return Ok(());
+}
+
+#[test]
+fn it_fails_on_client_certificates_without_a_key() {
+    let res = HttpClient::build(&HttpSettings {
+        client_cert: "./test/client-cert.pem".to_string(),
+        ..HttpSettings::default()
+    });
+
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("Invalid client certificate ./test/client-cert.pem"));
+}
+
+#[test]
+fn it_requires_a_client_certificate_with_a_key() {
+    let res = HttpClient::build(&HttpSettings {
+        client_key: "./test/client-key.pem".to_string(),
+        ..HttpSettings::default()
+    });
+
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "A client certificate is required when setting a client key"
+    );
+}
+
+#[test]
+fn it_builds_the_shared_client_again_when_settings_change() {
+    let client = HttpClient::default();
+    Config::set(ConfigKey::CaBundle, "./test/missing-ca.pem");
+    let res = client.client();
+    Config::set(ConfigKey::CaBundle, "");
+
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("Failed to read CA bundle ./test/missing-ca.pem"));
+    assert!(client.client().is_ok());
+}
+
+#[tokio::test]
+async fn it_times_out_whole_responses() -> Result<()> {
+    // Connections are queued without being accepted, so a response never
+    // arrives.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/api/tags", listener.local_addr()?);
+
+    let client = HttpClient::new(reqwest::Client::new());
+    Config::set(ConfigKey::HttpRequestTimeout, "100");
+    let request = client.request(Method::GET, url);
+    Config::set(ConfigKey::HttpRequestTimeout, "120000");
+    let res = request?.send().await;
+
+    assert!(res.unwrap_err().is_timeout());
+
+    return Ok(());
+}
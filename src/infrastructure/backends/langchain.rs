@@ -5,31 +5,34 @@ mod tests;
 use std::collections::HashMap;
 use std::time::Duration;
 
-use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::stream::TryStreamExt;
 use itertools::Itertools;
+use reqwest::Method;
 use serde::Deserialize;
 use serde::Serialize;
-use tokio::io::AsyncBufReadExt;
+use serde_json::Map;
+use serde_json::Value;
 use tokio::sync::mpsc;
-use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
 
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
+use crate::domain::models::Attachment;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendName;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
-
-fn convert_err(err: reqwest::Error) -> std::io::Error {
-    let err_msg = err.to_string();
-    return std::io::Error::new(std::io::ErrorKind::Interrupted, err_msg);
-}
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::streaming::EventStream;
+use crate::infrastructure::backends::streaming::StreamFormat;
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct Empty {}
@@ -41,19 +44,65 @@ struct OpenAPIJSONResponse {
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct CompletionRequest {
-    input: HashMap<String, String>,
+    input: Map<String, Value>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    config: Map<String, Value>,
+}
+
+/// Chat history in the form LangChain serializes messages, which LangServe
+/// converts back in to message objects.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct HistoryMessageRequest {
+    #[serde(rename = "type")]
+    message_type: String,
+    content: String,
+}
+
+impl HistoryMessageRequest {
+    fn new(message_type: &str, content: &str) -> HistoryMessageRequest {
+        return HistoryMessageRequest {
+            message_type: message_type.to_string(),
+            content: content.to_string(),
+        };
+    }
+
+    fn from_history(message: &HistoryMessage) -> HistoryMessageRequest {
+        let message_type = match message.role {
+            HistoryRole::User => "human",
+            HistoryRole::Assistant => "ai",
+        };
+
+        // LangChain servers only accept text, so images are left out.
+        return HistoryMessageRequest::new(
+            message_type,
+            &Attachment::text_with_attachments(&message.text, &message.attachments),
+        );
+    }
+}
+
+/// Reads a config option holding a JSON object. Options are validated when the
+/// config is loaded.
+fn json_object_from_config(key: ConfigKey) -> Map<String, Value> {
+    return match serde_json::from_str(&Config::get(key)) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct CompletionResponse {
-    status_code: Option<i32>,
-    message: Option<String>,
     content: Option<String>,
 }
 
 pub struct LangChain {
     url: String,
     timeout: String,
+    input_key: String,
+    history_key: String,
+    extra_input: Map<String, Value>,
+    config: Map<String, Value>,
+    http: HttpClient,
+    retry_policy: RetryPolicy,
 }
 
 impl Default for LangChain {
@@ -61,10 +110,51 @@ impl Default for LangChain {
         return LangChain {
             url: Config::get(ConfigKey::LangChainURL),
             timeout: Config::get(ConfigKey::BackendHealthCheckTimeout),
+            input_key: Config::get(ConfigKey::LangChainInputKey),
+            history_key: Config::get(ConfigKey::LangChainHistoryKey),
+            extra_input: json_object_from_config(ConfigKey::LangChainExtraInput),
+            config: json_object_from_config(ConfigKey::LangChainConfig),
+            http: HttpClient::default(),
+            retry_policy: RetryPolicy::default(),
         };
     }
 }
 
+impl LangChain {
+    /// Builds the chain's input from the static extra inputs, the prompt, and
+    /// the prior turns when the chain takes a chat history.
+    fn input(&self, prompt: &BackendPrompt) -> Map<String, Value> {
+        let mut input = self.extra_input.clone();
+        input.insert(
+            self.input_key.to_string(),
+            Value::from(Attachment::text_with_attachments(
+                &prompt.text,
+                &prompt.attachments,
+            )),
+        );
+
+        if !self.history_key.is_empty() {
+            let mut history = vec![];
+            if !prompt.system_prompt.is_empty() {
+                history.push(HistoryMessageRequest::new("system", &prompt.system_prompt));
+            }
+            history.extend(
+                prompt
+                    .history
+                    .iter()
+                    .map(HistoryMessageRequest::from_history),
+            );
+
+            input.insert(
+                self.history_key.to_string(),
+                serde_json::to_value(history).unwrap_or_default(),
+            );
+        }
+
+        return input;
+    }
+}
+
 #[async_trait]
 impl Backend for LangChain {
     fn name(&self) -> BackendName {
@@ -77,7 +167,9 @@ impl Backend for LangChain {
             bail!("LangChain URL is not defined");
         }
 
-        let res = reqwest::Client::new()
+        let res = self
+            .http
+            .client()?
             .get(format!("{url}/openapi.json", url = self.url))
             .timeout(Duration::from_millis(self.timeout.parse::<u64>()?))
             .send()
@@ -98,10 +190,13 @@ impl Backend for LangChain {
     }
 
     #[allow(clippy::implicit_return)]
-    async fn list_models(&self) -> Result<Vec<String>> {
-        let res = reqwest::Client::new()
-            .get(format!("{url}/openapi.json", url = self.url))
-            .send()
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        let request = self
+            .http
+            .request(Method::GET, format!("{url}/openapi.json", url = self.url))?;
+        let res = self
+            .retry_policy
+            .send(request, self.name(), None, None)
             .await?
             .json::<OpenAPIJSONResponse>()
             .await?;
@@ -122,30 +217,50 @@ impl Backend for LangChain {
 
         models.sort();
 
-        return Ok(models);
+        return Ok(models
+            .iter()
+            .map(|model| return ModelInfo::new(model))
+            .collect());
     }
 
     #[allow(clippy::implicit_return)]
     async fn get_completion<'a>(
         &self,
         prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
         tx: &'a mpsc::UnboundedSender<Event>,
     ) -> Result<()> {
-        let mut input = HashMap::new();
-        // TODO consider making the key configurable.
-        input.insert("question".to_string(), prompt.text);
-
-        let req = CompletionRequest { input };
+        // LangChain servers only accept text, so images are left out.
+        let req = CompletionRequest {
+            input: self.input(&prompt),
+            config: self.config.clone(),
+        };
 
-        let res = reqwest::Client::new()
+        let request = self
+            .http
+            .client()?
             .post(format!(
                 "{url}/{model}/stream",
                 url = self.url,
-                model = Config::get(ConfigKey::Model)
+                model = prompt.model()
             ))
-            .json(&req)
-            .send()
-            .await?;
+            .json(&req);
+        let res = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => {
+                let msg = BackendResponse {
+                    author: Author::Model,
+                    text: "".to_string(),
+                    reasoning: "".to_string(),
+                    done: true,
+                    usage: None,
+                    truncated: true,
+                };
+                tx.send(Event::BackendPromptResponse(msg))?;
+                return Ok(());
+            }
+            res = self.retry_policy.send(request, self.name(), Some(tx), Some(cancel)) => res?,
+        };
 
         if !res.status().is_success() {
             tracing::error!(
@@ -155,25 +270,14 @@ impl Backend for LangChain {
             bail!("Failed to make completion request to LangChain");
         }
 
-        let stream = res.bytes_stream().map_err(convert_err);
-        let mut lines_reader = StreamReader::new(stream).lines();
+        let mut events = EventStream::new(res, StreamFormat::Sse, cancel);
 
-        while let Ok(line) = lines_reader.next_line().await {
-            if line.is_none() {
-                break;
-            }
-            let mut cleaned_line = line.unwrap().trim().to_string();
-            if !cleaned_line.starts_with("data:") {
+        while let Some(event) = events.next().await? {
+            // Chains also send metadata events, such as the run ID.
+            if event.event != "data" {
                 continue;
             }
-            cleaned_line = cleaned_line.split_off(5).trim().to_string();
-            let ores: CompletionResponse = serde_json::from_str(&cleaned_line).unwrap();
-
-            if let Some(status_code) = ores.status_code {
-                if status_code >= 400 {
-                    return Err(anyhow!(ores.message.unwrap()));
-                }
-            }
+            let ores: CompletionResponse = event.json()?;
 
             if ores.content.is_none() {
                 continue;
@@ -186,8 +290,10 @@ impl Backend for LangChain {
             let msg = BackendResponse {
                 author: Author::Model,
                 text,
+                reasoning: "".to_string(),
                 done: false,
-                context: None,
+                usage: None,
+                truncated: false,
             };
             tx.send(Event::BackendPromptResponse(msg))?;
         }
@@ -195,8 +301,10 @@ impl Backend for LangChain {
         let msg = BackendResponse {
             author: Author::Model,
             text: "".to_string(),
+            reasoning: "".to_string(),
             done: true,
-            context: Some("not-supported".to_string()),
+            usage: None,
+            truncated: cancel.is_cancelled(),
         };
         tx.send(Event::BackendPromptResponse(msg))?;
 
@@ -1,8 +1,13 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use anyhow::bail;
 use anyhow::Result;
+use mockito::Matcher;
+use serde_json::json;
+use serde_json::Map;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 use super::CompletionResponse;
 use super::LangChain;
@@ -13,14 +18,26 @@ use crate::domain::models::Backend;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::infrastructure::backends::http::HttpClient;
 use crate::infrastructure::backends::langchain::Empty;
 use crate::infrastructure::backends::langchain::OpenAPIJSONResponse;
+use crate::infrastructure::backends::retry::RetryPolicy;
 
 impl LangChain {
     fn with_url(url: String) -> LangChain {
         return LangChain {
             url,
             timeout: "200".to_string(),
+            input_key: "question".to_string(),
+            history_key: "".to_string(),
+            extra_input: Map::new(),
+            config: Map::new(),
+            http: HttpClient::new(reqwest::Client::new()),
+            retry_policy: RetryPolicy::new(3, Duration::from_millis(1)),
         };
     }
 }
@@ -84,7 +101,10 @@ async fn it_lists_models() -> Result<()> {
     let res = backend.list_models().await?;
     mock.assert();
 
-    assert_eq!(res, vec!["model-1".to_string(), "model-2".to_string()]);
+    assert_eq!(
+        res,
+        vec![ModelInfo::new("model-1"), ModelInfo::new("model-2")]
+    );
 
     return Ok(());
 }
@@ -94,34 +114,44 @@ async fn it_gets_completions() -> Result<()> {
     Config::set(ConfigKey::Model, "model-1");
 
     let first_line = serde_json::to_string(&CompletionResponse {
-        status_code: None,
-        message: None,
         content: Some("Hello ".to_string()),
     })?;
 
     let second_line = serde_json::to_string(&CompletionResponse {
-        status_code: None,
-        message: None,
         content: Some("World".to_string()),
     })?;
 
     let body = [
-        "event: garbage",
+        "event: metadata",
+        "data: {\"run_id\": \"abc\"}",
         "",
+        "event: data",
         &format!("data: {first_line}"),
-        "event: garbage",
+        "",
+        ": ping",
+        "",
+        "event: data",
         &format!("data: {second_line}"),
         "",
+        "event: end",
+        "",
     ]
     .join("\n");
     let prompt = BackendPrompt {
         text: "Say hi to the world".to_string(),
-        backend_context: "".to_string(),
+        attachments: vec![],
+        history: vec![],
+        system_prompt: "".to_string(),
+        generation_options: GenerationOptions::default(),
+        model: None,
     };
 
     let mut server = mockito::Server::new();
     let mock = server
         .mock("POST", "/model-1/stream")
+        .match_body(Matcher::Json(
+            json!({"input": {"question": "Say hi to the world"}}),
+        ))
         .with_status(200)
         .with_body(body)
         .create();
@@ -129,7 +159,9 @@ async fn it_gets_completions() -> Result<()> {
     let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
 
     let backend = LangChain::with_url(server.url());
-    backend.get_completion(prompt, &tx).await?;
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
 
     mock.assert();
 
@@ -140,17 +172,82 @@ async fn it_gets_completions() -> Result<()> {
     assert_eq!(first_recv.author, Author::Model);
     assert_eq!(first_recv.text, "Hello ".to_string());
     assert!(!first_recv.done);
-    assert_eq!(first_recv.context, None);
 
     assert_eq!(second_recv.author, Author::Model);
     assert_eq!(second_recv.text, "World".to_string());
     assert!(!second_recv.done);
-    assert_eq!(second_recv.context, None);
 
     assert_eq!(third_recv.author, Author::Model);
     assert!(third_recv.text.is_empty());
     assert!(third_recv.done);
-    assert_eq!(third_recv.context, Some("not-supported".to_string()));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_configured_inputs_with_history() -> Result<()> {
+    Config::set(ConfigKey::Model, "model-1");
+
+    let mut prompt = BackendPrompt::new(
+        "Say hi to the world".to_string(),
+        vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "How may I help you?"),
+        ],
+    );
+    prompt.system_prompt = "You are a test.".to_string();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", Matcher::Regex("/stream$".to_string()))
+        .match_body(Matcher::Json(json!({
+            "input": {
+                "language": "English",
+                "input": "Say hi to the world",
+                "chat_history": [
+                    {"type": "system", "content": "You are a test."},
+                    {"type": "human", "content": "Hello"},
+                    {"type": "ai", "content": "How may I help you?"}
+                ]
+            },
+            "config": {"configurable": {"session_id": "oatmeal"}}
+        })))
+        .with_status(200)
+        .create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+
+    let mut backend = LangChain::with_url(server.url());
+    backend.input_key = "input".to_string();
+    backend.history_key = "chat_history".to_string();
+    backend.extra_input = json!({"language": "English"}).as_object().unwrap().clone();
+    backend.config = json!({"configurable": {"session_id": "oatmeal"}})
+        .as_object()
+        .unwrap()
+        .clone();
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
+
+    mock.assert();
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_finishes_completions_cancelled_before_responding() -> Result<()> {
+    let server = mockito::Server::new();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    let backend = LangChain::with_url(server.url());
+    let prompt = BackendPrompt::new("Say hi to the world".to_string(), vec![]);
+    backend.get_completion(prompt, &cancel, &tx).await?;
+
+    let recv = to_res(rx.recv().await)?;
+    assert!(recv.done);
+    assert!(recv.truncated);
 
     return Ok(());
 }
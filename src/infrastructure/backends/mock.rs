@@ -0,0 +1,300 @@
+#[cfg(test)]
+#[path = "mock_test.rs"]
+mod tests;
+
+use std::path;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use async_trait::async_trait;
+use strum::EnumIter;
+use strum::EnumVariantNames;
+use strum::IntoEnumIterator;
+use tokio::fs;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::domain::models::Author;
+use crate::domain::models::Backend;
+use crate::domain::models::BackendName;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::ContextWindow;
+use crate::domain::models::Event;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+
+/// Model listed when fixtures aren't loaded from a directory.
+const MOCK_MODEL: &str = "mock";
+
+/// Answer replayed in fixtures mode when no fixtures directory is set.
+const BUILTIN_FIXTURE: &str = r#"Here's a function in Java that prints the numbers from 0 to 10.
+
+```java
+public class Numbers {
+    public static void main(String[] args) {
+        for (int i = 0; i <= 10; i++) {
+            System.out.println(i);
+        }
+    }
+}
+```
+
+The loop starts at 0, and runs until `i` is greater than 10. The same in Python is a little shorter.
+
+```python
+for i in range(11):
+    print(i)
+```
+"#;
+
+/// How the mock backend answers prompts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, EnumVariantNames, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum MockMode {
+    /// Streams the prompt back.
+    Echo,
+    /// Replays markdown files from the fixtures directory.
+    Fixtures,
+    /// Fails half way through the response.
+    Error,
+    /// Stops responding half way through the response.
+    Timeout,
+}
+
+impl MockMode {
+    pub fn parse(text: &str) -> Option<MockMode> {
+        return MockMode::iter().find(|e| return e.to_string() == text);
+    }
+}
+
+/// Offline backend streaming scripted responses, for demos and development
+/// without a model server.
+pub struct Mock {
+    mode: MockMode,
+    delay: Duration,
+    fixtures_dir: String,
+}
+
+impl Default for Mock {
+    fn default() -> Mock {
+        return Mock {
+            mode: MockMode::parse(&Config::get(ConfigKey::MockMode)).unwrap_or(MockMode::Echo),
+            delay: Duration::from_millis(
+                Config::get(ConfigKey::MockDelay)
+                    .parse::<u64>()
+                    .unwrap_or_default(),
+            ),
+            fixtures_dir: Config::get(ConfigKey::MockFixturesDir),
+        };
+    }
+}
+
+/// Splits text in to words with their trailing whitespace, streamed one at a
+/// time like tokens.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = vec![];
+    let mut token = "".to_string();
+    for char in text.chars() {
+        if !char.is_whitespace() && token.ends_with(char::is_whitespace) {
+            tokens.push(token);
+            token = "".to_string();
+        }
+        token.push(char);
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    return tokens;
+}
+
+impl Mock {
+    /// Returns the fixture files of the fixtures directory, sorted by name.
+    async fn fixture_paths(&self) -> Result<Vec<path::PathBuf>> {
+        let mut paths = vec![];
+        let mut dir = fs::read_dir(&self.fixtures_dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| return e.to_str()) == Some("md") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        return Ok(paths);
+    }
+
+    /// Picks the fixture named after the prompt's model, otherwise cycles
+    /// through the fixtures with each turn of the conversation.
+    async fn fixture(&self, prompt: &BackendPrompt) -> Result<String> {
+        if self.fixtures_dir.is_empty() {
+            return Ok(BUILTIN_FIXTURE.to_string());
+        }
+
+        let paths = self.fixture_paths().await?;
+        if paths.is_empty() {
+            bail!(format!("No fixtures found in {}", self.fixtures_dir));
+        }
+
+        let model = prompt.model();
+        let turn = prompt
+            .history
+            .iter()
+            .filter(|message| return message.role == HistoryRole::User)
+            .count();
+        let path = paths
+            .iter()
+            .find(|path| return path.file_stem().and_then(|e| return e.to_str()) == Some(&model))
+            .unwrap_or(&paths[turn % paths.len()]);
+
+        return Ok(fs::read_to_string(path).await?);
+    }
+
+    async fn response_text(&self, prompt: &BackendPrompt) -> Result<String> {
+        return match self.mode {
+            MockMode::Fixtures => self.fixture(prompt).await,
+            _ => Ok(prompt.text.to_string()),
+        };
+    }
+
+    /// Returns once the response stalled for as long as the HTTP read
+    /// timeout, or never when no read timeout is set.
+    async fn stall(&self) -> Result<()> {
+        match HttpClient::read_timeout() {
+            Some(read_timeout) => {
+                tokio::time::sleep(read_timeout).await;
+                bail!(format!(
+                    "Timed out waiting for the backend to respond after {}ms",
+                    read_timeout.as_millis()
+                ));
+            }
+            None => {
+                std::future::pending::<()>().await;
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for Mock {
+    fn name(&self) -> BackendName {
+        return BackendName::Mock;
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn health_check(&self) -> Result<()> {
+        if self.mode == MockMode::Fixtures && !self.fixtures_dir.is_empty() {
+            if !path::Path::new(&self.fixtures_dir).is_dir() {
+                bail!(format!(
+                    "Mock fixtures directory {} doesn't exist",
+                    self.fixtures_dir
+                ));
+            }
+            if self.fixture_paths().await?.is_empty() {
+                bail!(format!("No fixtures found in {}", self.fixtures_dir));
+            }
+        }
+
+        return Ok(());
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        if self.mode != MockMode::Fixtures || self.fixtures_dir.is_empty() {
+            return Ok(vec![ModelInfo::new(MOCK_MODEL)]);
+        }
+
+        return Ok(self
+            .fixture_paths()
+            .await?
+            .iter()
+            .filter_map(|path| return path.file_stem().and_then(|e| return e.to_str()))
+            .map(ModelInfo::new)
+            .collect());
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn get_completion<'a>(
+        &self,
+        prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
+        tx: &'a mpsc::UnboundedSender<Event>,
+    ) -> Result<()> {
+        let text = self.response_text(&prompt).await?;
+        let tokens = tokenize(&text);
+
+        // Errors and timeouts happen half way through, so the partial
+        // response is shown.
+        let mut fail_after = tokens.len();
+        if self.mode == MockMode::Error || self.mode == MockMode::Timeout {
+            fail_after = tokens.len() / 2;
+        }
+
+        for token in tokens.iter().take(fail_after) {
+            tokio::select! {
+                _ = tokio::time::sleep(self.delay) => {},
+                _ = cancel.cancelled() => break,
+            };
+
+            let msg = BackendResponse {
+                author: Author::Model,
+                text: token.to_string(),
+                reasoning: "".to_string(),
+                done: false,
+                usage: None,
+                truncated: false,
+            };
+            tx.send(Event::BackendPromptResponse(msg))?;
+        }
+
+        if !cancel.is_cancelled() {
+            if self.mode == MockMode::Error {
+                bail!("Mock backend failed mid-stream");
+            }
+            if self.mode == MockMode::Timeout {
+                tokio::select! {
+                    res = self.stall() => res?,
+                    _ = cancel.cancelled() => {},
+                };
+            }
+        }
+
+        let msg = BackendResponse {
+            author: Author::Model,
+            text: "".to_string(),
+            reasoning: "".to_string(),
+            done: true,
+            usage: Some(Usage::new(
+                ContextWindow::estimate_prompt(&prompt) as u64,
+                ContextWindow::estimate_text(&text) as u64,
+            )),
+            truncated: cancel.is_cancelled(),
+        };
+        tx.send(Event::BackendPromptResponse(msg))?;
+
+        return Ok(());
+    }
+
+    /// Counts the letters of each text, so texts sharing words are close to
+    /// each other without an embeddings model.
+    #[allow(clippy::implicit_return)]
+    async fn get_embeddings(&self, _model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        return Ok(texts
+            .iter()
+            .map(|text| {
+                let mut embedding = vec![0.0; 26];
+                for letter in text.to_lowercase().chars().filter(char::is_ascii_lowercase) {
+                    embedding[(letter as u8 - b'a') as usize] += 1.0;
+                }
+                return embedding;
+            })
+            .collect());
+    }
+}
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use tokio::fs;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::tokenize;
+use super::Mock;
+use super::MockMode;
+use super::BUILTIN_FIXTURE;
+use crate::domain::models::Backend;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::Event;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+
+impl Mock {
+    fn with_mode(mode: MockMode, fixtures_dir: &str) -> Mock {
+        return Mock {
+            mode,
+            delay: Duration::from_millis(0),
+            fixtures_dir: fixtures_dir.to_string(),
+        };
+    }
+}
+
+/// Collects streamed responses until the completion finishes.
+async fn collect(
+    backend: &Mock,
+    prompt: BackendPrompt,
+    cancel: &CancellationToken,
+) -> (Result<()>, Vec<BackendResponse>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let res = backend.get_completion(prompt, cancel, &tx).await;
+    drop(tx);
+
+    let mut responses = vec![];
+    while let Some(event) = rx.recv().await {
+        if let Event::BackendPromptResponse(msg) = event {
+            responses.push(msg);
+        }
+    }
+
+    return (res, responses);
+}
+
+fn joined(responses: &[BackendResponse]) -> String {
+    return responses
+        .iter()
+        .map(|res| return res.text.to_string())
+        .collect::<Vec<String>>()
+        .join("");
+}
+
+async fn fixtures_dir() -> Result<String> {
+    let dir = tempfile::tempdir()?.into_path();
+    fs::write(dir.join("a-rust.md"), "Use `cargo run`.").await?;
+    fs::write(dir.join("b-python.md"), "Use `python main.py`.").await?;
+    fs::write(dir.join("notes.txt"), "Not a fixture.").await?;
+
+    return Ok(dir.to_str().unwrap().to_string());
+}
+
+#[test]
+fn it_tokenizes_words_with_trailing_whitespace() {
+    assert_eq!(
+        tokenize("Hello  world\n```rust\n"),
+        vec!["Hello  ", "world\n", "```rust\n"]
+    );
+    assert_eq!(tokenize(" a"), vec![" ", "a"]);
+    assert!(tokenize("").is_empty());
+}
+
+#[tokio::test]
+async fn it_echoes_the_prompt() -> Result<()> {
+    let backend = Mock::with_mode(MockMode::Echo, "");
+    let prompt = BackendPrompt::new("Say this back".to_string(), vec![]);
+    let (res, responses) = collect(&backend, prompt, &CancellationToken::new()).await;
+    res?;
+
+    assert_eq!(responses.len(), 4);
+    assert_eq!(joined(&responses), "Say this back");
+
+    let last = responses.last().unwrap();
+    assert!(last.done);
+    assert!(!last.truncated);
+    assert_eq!(last.usage.unwrap().completion_tokens, 4);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_replays_the_builtin_fixture() -> Result<()> {
+    let backend = Mock::with_mode(MockMode::Fixtures, "");
+    let prompt = BackendPrompt::new("Hello".to_string(), vec![]);
+    let (res, responses) = collect(&backend, prompt, &CancellationToken::new()).await;
+    res?;
+
+    assert_eq!(joined(&responses), BUILTIN_FIXTURE);
+    assert_eq!(backend.list_models().await?, vec![ModelInfo::new("mock")]);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_replays_fixtures_from_a_directory() -> Result<()> {
+    let dir = fixtures_dir().await?;
+    let backend = Mock::with_mode(MockMode::Fixtures, &dir);
+    backend.health_check().await?;
+
+    let models = backend.list_models().await?;
+    assert_eq!(
+        models,
+        vec![ModelInfo::new("a-rust"), ModelInfo::new("b-python")]
+    );
+
+    // Picks the fixture named after the model.
+    let mut prompt = BackendPrompt::new("Hello".to_string(), vec![]);
+    prompt.model = Some("b-python".to_string());
+    let (res, responses) = collect(&backend, prompt, &CancellationToken::new()).await;
+    res?;
+    assert_eq!(joined(&responses), "Use `python main.py`.");
+
+    // Otherwise cycles through them with each turn.
+    let history = vec![
+        HistoryMessage::new(HistoryRole::User, "Hello"),
+        HistoryMessage::new(HistoryRole::Assistant, "Hi"),
+    ];
+    let mut prompt = BackendPrompt::new("Again".to_string(), history);
+    prompt.model = Some("mock".to_string());
+    let (res, responses) = collect(&backend, prompt, &CancellationToken::new()).await;
+    res?;
+    assert_eq!(joined(&responses), "Use `python main.py`.");
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_health_checks_without_fixtures() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let backend = Mock::with_mode(MockMode::Fixtures, dir.path().to_str().unwrap());
+    assert_eq!(
+        backend.health_check().await.unwrap_err().to_string(),
+        format!("No fixtures found in {}", dir.path().to_str().unwrap())
+    );
+
+    let backend = Mock::with_mode(MockMode::Fixtures, "/does/not/exist");
+    assert_eq!(
+        backend.health_check().await.unwrap_err().to_string(),
+        "Mock fixtures directory /does/not/exist doesn't exist"
+    );
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_mid_stream() -> Result<()> {
+    let backend = Mock::with_mode(MockMode::Error, "");
+    let prompt = BackendPrompt::new("one two three four".to_string(), vec![]);
+    let (res, responses) = collect(&backend, prompt, &CancellationToken::new()).await;
+
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Mock backend failed mid-stream"
+    );
+    assert_eq!(joined(&responses), "one two ");
+    assert!(responses.iter().all(|res| return !res.done));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_stalls_mid_stream_until_cancelled() -> Result<()> {
+    let backend = Mock::with_mode(MockMode::Timeout, "");
+    let prompt = BackendPrompt::new("one two three four".to_string(), vec![]);
+    let cancel = CancellationToken::new();
+
+    let cancel_later = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel_later.cancel();
+    });
+
+    let (res, responses) = collect(&backend, prompt, &cancel).await;
+    res?;
+
+    assert_eq!(joined(&responses), "one two ");
+    let last = responses.last().unwrap();
+    if !last.done || !last.truncated {
+        bail!("Expected a truncated response");
+    }
+
+    return Ok(());
+}
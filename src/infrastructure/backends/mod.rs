@@ -1,18 +1,42 @@
+pub mod azure;
 pub mod claude;
 pub mod gemini;
+pub mod http;
 pub mod langchain;
+pub mod mock;
 pub mod ollama;
 pub mod openai;
+pub mod reasoning;
+pub mod recorder;
+pub mod replay;
+pub mod retry;
+pub mod streaming;
+pub mod tools;
 use anyhow::bail;
 use anyhow::Result;
 
+use crate::configuration::ConfigKey;
+use crate::configuration::Secrets;
 use crate::domain::models::BackendBox;
 use crate::domain::models::BackendName;
 
 pub struct BackendManager {}
 
 impl BackendManager {
-    pub fn get(name: BackendName) -> Result<BackendBox> {
+    pub async fn get(name: BackendName) -> Result<BackendBox> {
+        // Tokens read from files and commands are resolved up front, so
+        // failing to read them is reported instead of a missing token.
+        let token_key = match name {
+            BackendName::OpenAI => Some(ConfigKey::OpenAiToken),
+            BackendName::Azure => Some(ConfigKey::AzureToken),
+            BackendName::Claude => Some(ConfigKey::ClaudeToken),
+            BackendName::Gemini => Some(ConfigKey::GeminiToken),
+            _ => None,
+        };
+        if let Some(token_key) = token_key {
+            Secrets::resolve(token_key).await?;
+        }
+
         if name == BackendName::LangChain {
             return Ok(Box::<langchain::LangChain>::default());
         }
@@ -25,6 +49,10 @@ impl BackendManager {
             return Ok(Box::<openai::OpenAI>::default());
         }
 
+        if name == BackendName::Azure {
+            return Ok(Box::<azure::Azure>::default());
+        }
+
         if name == BackendName::Claude {
             return Ok(Box::<claude::Claude>::default());
         }
@@ -33,6 +61,14 @@ impl BackendManager {
             return Ok(Box::<gemini::Gemini>::default());
         }
 
+        if name == BackendName::Mock {
+            return Ok(Box::<mock::Mock>::default());
+        }
+
+        if name == BackendName::Replay {
+            return Ok(Box::<replay::Replay>::default());
+        }
+
         bail!(format!("No backend implemented for {name}"))
     }
 }
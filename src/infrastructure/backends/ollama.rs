@@ -2,49 +2,186 @@
 #[path = "ollama_test.rs"]
 mod tests;
 
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
 
 use anyhow::bail;
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::stream::TryStreamExt;
+use reqwest::Method;
 use serde::Deserialize;
 use serde::Serialize;
-use tokio::io::AsyncBufReadExt;
+use serde_json::Value;
 use tokio::sync::mpsc;
-use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
 
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendName;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::reasoning::ThinkTags;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::streaming::EventStream;
+use crate::infrastructure::backends::streaming::StreamFormat;
 
-fn convert_err(err: reqwest::Error) -> std::io::Error {
-    let err_msg = err.to_string();
-    return std::io::Error::new(std::io::ErrorKind::Interrupted, err_msg);
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CompletionRequest {
+    model: String,
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+    options: CompletionOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-struct CompletionRequest {
+struct ChatMessageRequest {
+    role: String,
+    content: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+}
+
+impl ChatMessageRequest {
+    /// Text attachments are inlined in to the message they were sent with,
+    /// while images are sent alongside it.
+    fn new(role: &str, text: &str, attachments: &[Attachment]) -> ChatMessageRequest {
+        return ChatMessageRequest {
+            role: role.to_string(),
+            content: Attachment::text_with_attachments(text, attachments),
+            images: attachments
+                .iter()
+                .filter(|attachment| return attachment.kind == AttachmentKind::Image)
+                .map(|attachment| return attachment.content.to_string())
+                .collect(),
+        };
+    }
+
+    fn from_history(message: &HistoryMessage) -> ChatMessageRequest {
+        let role = match message.role {
+            HistoryRole::User => "user",
+            HistoryRole::Assistant => "assistant",
+        };
+
+        return ChatMessageRequest::new(role, &message.text, &message.attachments);
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<ChatMessageRequest>,
+    options: CompletionOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CompletionOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct EmbeddingsRequest {
     model: String,
     prompt: String,
-    context: Option<Vec<i32>>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EmbeddingsResponse {
+    embedding: Vec<f32>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ChatMessageResponse {
+    pub content: String,
+}
+
+/// Response line of either the chat or generate API. Chat responses hold
+/// their text in `message`, and generate responses in `response`.
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct CompletionResponse {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub response: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<ChatMessageResponse>,
     pub done: bool,
-    pub context: Option<Vec<i32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<u64>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ModelDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    family: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    families: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parameter_size: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quantization_level: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct Model {
     name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<ModelDetails>,
+}
+
+impl Model {
+    fn to_model_info(&self) -> ModelInfo {
+        let mut model = ModelInfo::new(&self.name);
+        let details = match &self.details {
+            Some(details) => details,
+            None => return model,
+        };
+
+        // Multimodal models ship with a CLIP projector for images.
+        model.modalities = vec!["text".to_string()];
+        if let Some(families) = &details.families {
+            if families.iter().any(|family| return family == "clip") {
+                model.modalities.push("image".to_string());
+            }
+        }
+        model.family = details.family.clone().filter(|e| return !e.is_empty());
+        model.parameter_size = details
+            .parameter_size
+            .clone()
+            .filter(|e| return !e.is_empty());
+        model.quantization = details
+            .quantization_level
+            .clone()
+            .filter(|e| return !e.is_empty());
+
+        return model;
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -52,9 +189,44 @@ struct ModelListResponse {
     pub models: Vec<Model>,
 }
 
+/// The generate API only accepts a single prompt, so previous turns are
+/// replayed as a transcript ahead of the latest message. Text attachments are
+/// inlined in to the message they were sent with.
+fn build_prompt(prompt: &BackendPrompt) -> String {
+    let text = Attachment::text_with_attachments(&prompt.text, &prompt.attachments);
+    if prompt.history.is_empty() {
+        return text;
+    }
+
+    let transcript = prompt
+        .history
+        .iter()
+        .map(|message| {
+            let role = match message.role {
+                HistoryRole::User => "User",
+                HistoryRole::Assistant => "Assistant",
+            };
+            return format!(
+                "{role}: {}",
+                Attachment::text_with_attachments(&message.text, &message.attachments)
+            );
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    return format!("{transcript}\n\nUser: {text}\n\nAssistant:");
+}
+
 pub struct Ollama {
     url: String,
     timeout: String,
+    num_ctx: Option<u32>,
+    keep_alive: String,
+    http: HttpClient,
+    retry_policy: RetryPolicy,
+    /// Set once the server is found to not have the chat API, which was added
+    /// in Ollama 0.1.14.
+    generate_only: AtomicBool,
 }
 
 impl Default for Ollama {
@@ -62,6 +234,124 @@ impl Default for Ollama {
         return Ollama {
             url: Config::get(ConfigKey::OllamaURL),
             timeout: Config::get(ConfigKey::BackendHealthCheckTimeout),
+            num_ctx: Config::get(ConfigKey::OllamaNumCtx).parse::<u32>().ok(),
+            keep_alive: Config::get(ConfigKey::OllamaKeepAlive),
+            http: HttpClient::default(),
+            retry_policy: RetryPolicy::default(),
+            generate_only: AtomicBool::new(false),
+        };
+    }
+}
+
+impl Ollama {
+    fn options(&self, prompt: &BackendPrompt) -> CompletionOptions {
+        return CompletionOptions {
+            num_ctx: self.num_ctx,
+            temperature: prompt.generation_options.temperature,
+            num_predict: prompt.generation_options.max_tokens,
+            top_p: prompt.generation_options.top_p,
+            stop: prompt.generation_options.stop.clone(),
+        };
+    }
+
+    /// Ollama reads a number as seconds, and a string as a duration such as
+    /// `10m`.
+    fn keep_alive(&self) -> Option<Value> {
+        if self.keep_alive.is_empty() {
+            return None;
+        }
+        if let Ok(seconds) = self.keep_alive.parse::<i64>() {
+            return Some(Value::from(seconds));
+        }
+
+        return Some(Value::from(self.keep_alive.to_string()));
+    }
+
+    fn chat_request(&self, prompt: &BackendPrompt) -> ChatRequest {
+        let mut messages = vec![];
+        if !prompt.system_prompt.is_empty() {
+            messages.push(ChatMessageRequest::new(
+                "system",
+                &prompt.system_prompt,
+                &[],
+            ));
+        }
+        messages.extend(prompt.history.iter().map(ChatMessageRequest::from_history));
+        messages.push(ChatMessageRequest::new(
+            "user",
+            &prompt.text,
+            &prompt.attachments,
+        ));
+
+        return ChatRequest {
+            model: prompt.model(),
+            messages,
+            options: self.options(prompt),
+            keep_alive: self.keep_alive(),
+        };
+    }
+
+    fn generate_request(&self, prompt: &BackendPrompt) -> CompletionRequest {
+        let mut req = CompletionRequest {
+            model: prompt.model(),
+            prompt: build_prompt(prompt),
+            system: None,
+            // Images can only be sent with the latest message.
+            images: prompt
+                .attachments
+                .iter()
+                .filter(|attachment| return attachment.kind == AttachmentKind::Image)
+                .map(|attachment| return attachment.content.to_string())
+                .collect(),
+            options: self.options(prompt),
+            keep_alive: self.keep_alive(),
+        };
+
+        if !prompt.system_prompt.is_empty() {
+            req.system = Some(prompt.system_prompt.to_string());
+        }
+
+        return req;
+    }
+
+    /// Ends a completion cancelled before a response arrived, so the UI stops
+    /// waiting for it.
+    fn send_cancelled(tx: &mpsc::UnboundedSender<Event>) -> Result<()> {
+        let msg = BackendResponse {
+            author: Author::Model,
+            text: "".to_string(),
+            reasoning: "".to_string(),
+            done: true,
+            usage: None,
+            truncated: true,
+        };
+        tx.send(Event::BackendPromptResponse(msg))?;
+
+        return Ok(());
+    }
+
+    /// Sends a completion request, returning `None` if cancelled before a
+    /// response arrives.
+    async fn send<T: Serialize>(
+        &self,
+        path: &str,
+        req: &T,
+        cancel: &CancellationToken,
+        tx: &mpsc::UnboundedSender<Event>,
+    ) -> Result<Option<reqwest::Response>> {
+        let request = self
+            .http
+            .client()?
+            .post(format!("{url}{path}", url = self.url))
+            .json(req);
+
+        let send = self
+            .retry_policy
+            .send(request, self.name(), Some(tx), Some(cancel));
+        return tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Ok(None),
+            res = send => Ok(Some(res?)),
         };
     }
 }
@@ -74,7 +364,9 @@ impl Backend for Ollama {
 
     #[allow(clippy::implicit_return)]
     async fn health_check(&self) -> Result<()> {
-        let res = reqwest::Client::new()
+        let res = self
+            .http
+            .client()?
             .get(&self.url)
             .timeout(Duration::from_millis(self.timeout.parse::<u64>()?))
             .send()
@@ -95,23 +387,20 @@ impl Backend for Ollama {
     }
 
     #[allow(clippy::implicit_return)]
-    async fn list_models(&self) -> Result<Vec<String>> {
-        let res = reqwest::Client::new()
-            .get(format!("{url}/api/tags", url = self.url))
-            .send()
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        let request = self
+            .http
+            .request(Method::GET, format!("{url}/api/tags", url = self.url))?;
+        let res = self
+            .retry_policy
+            .send(request, self.name(), None, None)
             .await?
             .json::<ModelListResponse>()
             .await?;
 
-        let mut models: Vec<String> = res
-            .models
-            .iter()
-            .map(|model| {
-                return model.name.to_string();
-            })
-            .collect();
+        let mut models: Vec<ModelInfo> = res.models.iter().map(Model::to_model_info).collect();
 
-        models.sort();
+        models.sort_by(|a, b| return a.name.cmp(&b.name));
 
         return Ok(models);
     }
@@ -120,23 +409,45 @@ impl Backend for Ollama {
     async fn get_completion<'a>(
         &self,
         prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
         tx: &'a mpsc::UnboundedSender<Event>,
     ) -> Result<()> {
-        let mut req = CompletionRequest {
-            model: Config::get(ConfigKey::Model),
-            prompt: prompt.text,
-            context: None,
-        };
+        let mut res = None;
+        if !self.generate_only.load(Ordering::Relaxed) {
+            res = match self
+                .send("/api/chat", &self.chat_request(&prompt), cancel, tx)
+                .await?
+            {
+                Some(res) => Some(res),
+                None => return Ollama::send_cancelled(tx),
+            };
 
-        if !prompt.backend_context.is_empty() {
-            req.context = Some(serde_json::from_str(&prompt.backend_context)?);
+            // Servers without the chat API respond with a plain text 404,
+            // while a missing model is reported as a JSON error.
+            if res.as_ref().unwrap().status() == 404 {
+                let body = res.take().unwrap().text().await?;
+                if serde_json::from_str::<Value>(&body).is_ok() {
+                    tracing::error!(body = body, "Failed to make completion request to Ollama");
+                    bail!("Failed to make completion request to Ollama");
+                }
+
+                tracing::debug!("Ollama chat API is missing, falling back to the generate API");
+                self.generate_only.store(true, Ordering::Relaxed);
+            }
         }
 
-        let res = reqwest::Client::new()
-            .post(format!("{url}/api/generate", url = self.url))
-            .json(&req)
-            .send()
-            .await?;
+        let res = match res {
+            Some(res) => res,
+            None => {
+                match self
+                    .send("/api/generate", &self.generate_request(&prompt), cancel, tx)
+                    .await?
+                {
+                    Some(res) => res,
+                    None => return Ollama::send_cancelled(tx),
+                }
+            }
+        };
 
         if !res.status().is_success() {
             tracing::error!(
@@ -146,29 +457,93 @@ impl Backend for Ollama {
             bail!("Failed to make completion request to Ollama");
         }
 
-        let stream = res.bytes_stream().map_err(convert_err);
-        let mut lines_reader = StreamReader::new(stream).lines();
-
-        while let Ok(line) = lines_reader.next_line().await {
-            if line.is_none() {
-                break;
-            }
+        let mut events = EventStream::new(res, StreamFormat::Ndjson, cancel);
 
-            let ores: CompletionResponse = serde_json::from_str(&line.unwrap()).unwrap();
+        let mut done = false;
+        let mut think_tags = ThinkTags::default();
+        while let Some(event) = events.next().await? {
+            let ores: CompletionResponse = event.json()?;
             tracing::debug!(body = ?ores, "Completion response");
+            done = ores.done;
+            let content = match ores.message {
+                Some(message) => message.content,
+                None => ores.response,
+            };
+            let (mut text, mut reasoning) = think_tags.push(&content);
+            if ores.done {
+                let (rest_text, rest_reasoning) = think_tags.finish();
+                text.push_str(&rest_text);
+                reasoning.push_str(&rest_reasoning);
+            }
             let mut msg = BackendResponse {
                 author: Author::Model,
-                text: ores.response,
+                text,
+                reasoning,
                 done: ores.done,
-                context: None,
+                usage: None,
+                truncated: false,
             };
-            if ores.done && ores.context.is_some() {
-                msg.context = Some(serde_json::to_string(&ores.context)?);
+            if ores.done && (ores.prompt_eval_count.is_some() || ores.eval_count.is_some()) {
+                msg.usage = Some(Usage::new(
+                    ores.prompt_eval_count.unwrap_or_default(),
+                    ores.eval_count.unwrap_or_default(),
+                ));
             }
 
             tx.send(Event::BackendPromptResponse(msg))?;
         }
 
+        // Ollama only reports done on its last line, which never arrives when
+        // the request is cancelled.
+        if !done {
+            let (text, reasoning) = think_tags.finish();
+            let msg = BackendResponse {
+                author: Author::Model,
+                text,
+                reasoning,
+                done: true,
+                usage: None,
+                truncated: cancel.is_cancelled(),
+            };
+            tx.send(Event::BackendPromptResponse(msg))?;
+        }
+
         return Ok(());
     }
+
+    #[allow(clippy::implicit_return)]
+    async fn get_embeddings(&self, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        // The embeddings API takes a single prompt per request.
+        let mut embeddings = vec![];
+        for text in texts {
+            let req = EmbeddingsRequest {
+                model: model.to_string(),
+                prompt: text.to_string(),
+            };
+            let request = self
+                .http
+                .request(
+                    Method::POST,
+                    format!("{url}/api/embeddings", url = self.url),
+                )?
+                .json(&req);
+            let res = self
+                .retry_policy
+                .send(request, self.name(), None, None)
+                .await?;
+            if !res.status().is_success() {
+                tracing::error!(
+                    status = res.status().as_u16(),
+                    "Failed to make embeddings request to Ollama"
+                );
+                bail!(format!(
+                    "Failed to make embeddings request to Ollama. Is the {model} model pulled?"
+                ));
+            }
+
+            embeddings.push(res.json::<EmbeddingsResponse>().await?.embedding);
+        }
+
+        return Ok(embeddings);
+    }
 }
@@ -1,22 +1,42 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
 use anyhow::bail;
 use anyhow::Result;
+use mockito::Matcher;
+use serde_json::json;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
+use super::ChatMessageResponse;
 use super::CompletionResponse;
-use super::Model;
-use super::ModelListResponse;
 use super::Ollama;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::retry::RetryPolicy;
 
 impl Ollama {
     fn with_url(url: String) -> Ollama {
         return Ollama {
             url,
             timeout: "200".to_string(),
+            num_ctx: None,
+            keep_alive: "".to_string(),
+            http: HttpClient::new(reqwest::Client::new()),
+            retry_policy: RetryPolicy::new(3, Duration::from_millis(1)),
+            generate_only: AtomicBool::new(false),
         };
     }
 }
@@ -56,16 +76,27 @@ async fn it_fails_health_checks() {
 
 #[tokio::test]
 async fn it_lists_models() -> Result<()> {
-    let body = serde_json::to_string(&ModelListResponse {
-        models: vec![
-            Model {
-                name: "first".to_string(),
-            },
-            Model {
-                name: "second".to_string(),
+    let body = json!({
+        "models": [
+            {
+                "name": "second",
+                "modified_at": "2024-02-01T12:00:00.000000000-05:00",
+                "size": 4733363377_u64,
+                "digest": "8dd30f6b0cb19f555f2c7a7ebda861449ea2cc76bf1f44e262931f45fc81d081",
+                "details": {
+                    "format": "gguf",
+                    "family": "llama",
+                    "families": ["llama", "clip"],
+                    "parameter_size": "7B",
+                    "quantization_level": "Q4_0"
+                }
             },
-        ],
-    })?;
+            {
+                "name": "first"
+            }
+        ]
+    })
+    .to_string();
 
     let mut server = mockito::Server::new();
     let mock = server
@@ -77,7 +108,20 @@ async fn it_lists_models() -> Result<()> {
     let backend = Ollama::with_url(server.url());
     let res = backend.list_models().await?;
 
-    assert_eq!(res, vec!["first".to_string(), "second".to_string()]);
+    assert_eq!(
+        res,
+        vec![
+            ModelInfo::new("first"),
+            ModelInfo {
+                name: "second".to_string(),
+                modalities: vec!["text".to_string(), "image".to_string()],
+                family: Some("llama".to_string()),
+                parameter_size: Some("7B".to_string()),
+                quantization: Some("Q4_0".to_string()),
+                ..ModelInfo::default()
+            },
+        ]
+    );
     mock.assert();
 
     return Ok(());
@@ -86,26 +130,47 @@ async fn it_lists_models() -> Result<()> {
 #[tokio::test]
 async fn it_gets_completions() -> Result<()> {
     let first_line = serde_json::to_string(&CompletionResponse {
-        response: "Hello ".to_string(),
+        response: "".to_string(),
+        message: Some(ChatMessageResponse {
+            content: "Hello ".to_string(),
+        }),
         done: false,
-        context: None,
+        prompt_eval_count: None,
+        eval_count: None,
     })?;
 
     let second_line = serde_json::to_string(&CompletionResponse {
-        response: "World".to_string(),
+        response: "".to_string(),
+        message: Some(ChatMessageResponse {
+            content: "World".to_string(),
+        }),
         done: true,
-        context: Some(vec![1, 2, 3]),
+        prompt_eval_count: Some(12),
+        eval_count: Some(2),
     })?;
 
     let body = [first_line, second_line].join("\n");
     let prompt = BackendPrompt {
         text: "Say hi to the world".to_string(),
-        backend_context: serde_json::to_string(&vec![1])?,
+        attachments: vec![],
+        history: vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "How may I help you?"),
+        ],
+        system_prompt: "You are a test.".to_string(),
+        generation_options: GenerationOptions {
+            temperature: Some(0.5),
+            max_tokens: Some(100),
+            top_p: None,
+            stop: vec!["END".to_string()],
+        },
+        model: None,
     };
 
     let mut server = mockito::Server::new();
     let mock = server
-        .mock("POST", "/api/generate")
+        .mock("POST", "/api/chat")
+        .match_body(Matcher::PartialJson(json!({"messages": [{"role": "system", "content": "You are a test."}, {"role": "user", "content": "Hello"}, {"role": "assistant", "content": "How may I help you?"}, {"role": "user", "content": "Say hi to the world"}], "options": {"temperature": 0.5, "num_predict": 100, "stop": ["END"]}})))
         .with_status(200)
         .with_body(body)
         .create();
@@ -113,7 +178,9 @@ async fn it_gets_completions() -> Result<()> {
     let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
 
     let backend = Ollama::with_url(server.url());
-    backend.get_completion(prompt, &tx).await?;
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
 
     mock.assert();
 
@@ -123,12 +190,324 @@ async fn it_gets_completions() -> Result<()> {
     assert_eq!(first_recv.author, Author::Model);
     assert_eq!(first_recv.text, "Hello ".to_string());
     assert!(!first_recv.done);
-    assert_eq!(first_recv.context, None);
 
     assert_eq!(second_recv.author, Author::Model);
     assert_eq!(second_recv.text, "World".to_string());
     assert!(second_recv.done);
-    assert_eq!(second_recv.context, Some("[1,2,3]".to_string()));
+    assert_eq!(second_recv.usage, Some(Usage::new(12, 2)));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_keeps_partial_completions_when_cancelled() -> Result<()> {
+    let first_line = serde_json::to_string(&CompletionResponse {
+        response: "".to_string(),
+        message: Some(ChatMessageResponse {
+            content: "Hello ".to_string(),
+        }),
+        done: false,
+        prompt_eval_count: None,
+        eval_count: None,
+    })?;
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/api/chat")
+        .with_status(200)
+        .with_chunked_body(move |w| {
+            w.write_all(format!("{first_line}\n").as_bytes())?;
+            std::thread::sleep(Duration::from_secs(1));
+            return Ok(());
+        })
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let cancel = CancellationToken::new();
+
+    let backend = Ollama::with_url(server.url());
+    let worker_cancel = cancel.clone();
+    let worker = tokio::spawn(async move {
+        let prompt = BackendPrompt::new("Say hi to the world".to_string(), vec![]);
+        return backend.get_completion(prompt, &worker_cancel, &tx).await;
+    });
+
+    let first_recv = to_res(rx.recv().await)?;
+    cancel.cancel();
+    worker.await??;
+
+    mock.assert();
+
+    let second_recv = to_res(rx.recv().await)?;
+
+    assert_eq!(first_recv.text, "Hello ".to_string());
+    assert!(!first_recv.done);
+    assert!(!first_recv.truncated);
+
+    assert!(second_recv.text.is_empty());
+    assert!(second_recv.done);
+    assert!(second_recv.truncated);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_image_attachments() -> Result<()> {
+    let mut prompt = BackendPrompt::new("What is this?".to_string(), vec![]);
+    prompt.attachments = vec![Attachment {
+        kind: AttachmentKind::Image,
+        path: "/tmp/cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        content: "b2F0bWVhbA==".to_string(),
+    }];
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/api/chat")
+        .match_body(Matcher::PartialJson(
+            json!({"messages": [{"role": "user", "content": "What is this?", "images": ["b2F0bWVhbA=="]}]}),
+        ))
+        .with_status(200)
+        .create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = Ollama::with_url(server.url());
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
+
+    mock.assert();
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_context_options() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/api/chat")
+        .match_body(Matcher::PartialJson(
+            json!({"options": {"num_ctx": 8192}, "keep_alive": -1}),
+        ))
+        .with_status(200)
+        .create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+
+    let mut backend = Ollama::with_url(server.url());
+    backend.num_ctx = Some(8192);
+    backend.keep_alive = "-1".to_string();
+    backend
+        .get_completion(
+            BackendPrompt::new("Hello".to_string(), vec![]),
+            &CancellationToken::new(),
+            &tx,
+        )
+        .await?;
+
+    mock.assert();
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_falls_back_to_the_generate_api() -> Result<()> {
+    let body = serde_json::to_string(&CompletionResponse {
+        response: "Hi".to_string(),
+        message: None,
+        done: true,
+        prompt_eval_count: None,
+        eval_count: None,
+    })?;
+
+    let mut server = mockito::Server::new();
+    let chat_mock = server
+        .mock("POST", "/api/chat")
+        .with_status(404)
+        .with_body("404 page not found")
+        .expect(1)
+        .create();
+    let generate_mock = server
+        .mock("POST", "/api/generate")
+        .match_body(Matcher::PartialJson(json!({"prompt": "User: Hello\n\nAssistant: How may I help you?\n\nUser: Say hi to the world\n\nAssistant:", "system": "You are a test.", "keep_alive": "10m"})))
+        .with_status(200)
+        .with_body(body)
+        .expect(2)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+
+    let mut backend = Ollama::with_url(server.url());
+    backend.keep_alive = "10m".to_string();
+    for _ in 0..2 {
+        let mut prompt = BackendPrompt::new(
+            "Say hi to the world".to_string(),
+            vec![
+                HistoryMessage::new(HistoryRole::User, "Hello"),
+                HistoryMessage::new(HistoryRole::Assistant, "How may I help you?"),
+            ],
+        );
+        prompt.system_prompt = "You are a test.".to_string();
+        backend
+            .get_completion(prompt, &CancellationToken::new(), &tx)
+            .await?;
+
+        let recv = to_res(rx.recv().await)?;
+        assert_eq!(recv.text, "Hi".to_string());
+        assert!(recv.done);
+    }
+
+    chat_mock.assert();
+    generate_mock.assert();
+    assert!(backend.generate_only.load(Ordering::Relaxed));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_on_missing_models() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let chat_mock = server
+        .mock("POST", "/api/chat")
+        .with_status(404)
+        .with_body(json!({"error": "model 'llama' not found"}).to_string())
+        .create();
+    let generate_mock = server.mock("POST", "/api/generate").expect(0).create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = Ollama::with_url(server.url());
+    let res = backend
+        .get_completion(
+            BackendPrompt::new("Hello".to_string(), vec![]),
+            &CancellationToken::new(),
+            &tx,
+        )
+        .await;
+
+    chat_mock.assert();
+    generate_mock.assert();
+    assert!(res.is_err());
+    assert!(!backend.generate_only.load(Ordering::Relaxed));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_splits_reasoning_from_think_tags() -> Result<()> {
+    let body = [
+        r#"{"message":{"role":"assistant","content":"<think>"},"done":false}"#,
+        r#"{"message":{"role":"assistant","content":"They said hi."},"done":false}"#,
+        r#"{"message":{"role":"assistant","content":"</think>\n\n"},"done":false}"#,
+        r#"{"message":{"role":"assistant","content":"Hello!"},"done":true}"#,
+    ]
+    .map(|line| return format!("{line}\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/api/chat")
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = Ollama::with_url(server.url());
+    backend
+        .get_completion(
+            BackendPrompt::new("Hi".to_string(), vec![]),
+            &CancellationToken::new(),
+            &tx,
+        )
+        .await?;
+
+    mock.assert();
+
+    let mut text = "".to_string();
+    let mut reasoning = "".to_string();
+    while let Ok(event) = rx.try_recv() {
+        let res = to_res(Some(event))?;
+        text.push_str(&res.text);
+        reasoning.push_str(&res.reasoning);
+    }
+    assert_eq!(text, "Hello!");
+    assert_eq!(reasoning, "They said hi.");
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_gets_embeddings() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let first_mock = server
+        .mock("POST", "/api/embeddings")
+        .match_body(Matcher::Json(
+            json!({"model": "nomic-embed-text", "prompt": "first"}),
+        ))
+        .with_status(200)
+        .with_body(json!({"embedding": [0.1, 0.2]}).to_string())
+        .create();
+    let second_mock = server
+        .mock("POST", "/api/embeddings")
+        .match_body(Matcher::Json(
+            json!({"model": "nomic-embed-text", "prompt": "second"}),
+        ))
+        .with_status(200)
+        .with_body(json!({"embedding": [0.3, 0.4]}).to_string())
+        .create();
+
+    let backend = Ollama::with_url(server.url());
+    let embeddings = backend
+        .get_embeddings(
+            "nomic-embed-text",
+            &["first".to_string(), "second".to_string()],
+        )
+        .await?;
+
+    first_mock.assert();
+    second_mock.assert();
+    assert_eq!(embeddings, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_to_get_embeddings_of_missing_models() {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/api/embeddings")
+        .with_status(404)
+        .create();
+
+    let backend = Ollama::with_url(server.url());
+    let res = backend
+        .get_embeddings("nomic-embed-text", &["first".to_string()])
+        .await;
+
+    mock.assert();
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Failed to make embeddings request to Ollama. Is the nomic-embed-text model pulled?"
+    );
+}
+
+#[tokio::test]
+async fn it_finishes_completions_cancelled_before_responding() -> Result<()> {
+    let server = mockito::Server::new();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    let backend = Ollama::with_url(server.url());
+    let prompt = BackendPrompt::new("Say hi to the world".to_string(), vec![]);
+    backend.get_completion(prompt, &cancel, &tx).await?;
+
+    let recv = to_res(rx.recv().await)?;
+    assert!(recv.text.is_empty());
+    assert!(recv.done);
+    assert!(recv.truncated);
 
     return Ok(());
 }
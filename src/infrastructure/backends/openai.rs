@@ -7,30 +7,45 @@ use std::time::Duration;
 use anyhow::bail;
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::stream::TryStreamExt;
+use reqwest::Method;
 use serde::Deserialize;
 use serde::Serialize;
-use tokio::io::AsyncBufReadExt;
+use strum::EnumIter;
+use strum::EnumVariantNames;
+use strum::IntoEnumIterator;
 use tokio::sync::mpsc;
-use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
 
 use crate::configuration::Config;
 use crate::configuration::ConfigKey;
+use crate::configuration::Secrets;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendName;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
-
-fn convert_err(err: reqwest::Error) -> std::io::Error {
-    let err_msg = err.to_string();
-    return std::io::Error::new(std::io::ErrorKind::Interrupted, err_msg);
-}
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::ToolCall;
+use crate::domain::models::ToolName;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::streaming::EventStream;
+use crate::infrastructure::backends::streaming::StreamFormat;
+use crate::infrastructure::backends::tools::Tools;
+use crate::infrastructure::backends::tools::MAX_TOOL_ROUNDS;
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct Model {
     id: String,
+    /// Unix timestamp in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<i64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -38,22 +53,183 @@ struct ModelListResponse {
     data: Vec<Model>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ImageUrlRequest {
+    url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPartRequest {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrlRequest },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum ContentRequest {
+    Text(String),
+    Parts(Vec<ContentPartRequest>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FunctionCallRequest {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ToolCallRequest {
+    id: String,
+    #[serde(rename = "type")]
+    tool_type: String,
+    function: FunctionCallRequest,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct MessageRequest {
     role: String,
-    content: String,
+    content: ContentRequest,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<ToolCallRequest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+impl MessageRequest {
+    fn new(role: &str, text: &str, attachments: &[Attachment]) -> MessageRequest {
+        if attachments.is_empty() {
+            return MessageRequest {
+                role: role.to_string(),
+                content: ContentRequest::Text(text.to_string()),
+                tool_calls: vec![],
+                tool_call_id: None,
+            };
+        }
+
+        let mut parts = attachments
+            .iter()
+            .map(|attachment| {
+                return match attachment.kind {
+                    AttachmentKind::Image => {
+                        ContentPartRequest::ImageUrl {
+                            image_url: ImageUrlRequest {
+                                url: format!(
+                                    "data:{};base64,{}",
+                                    attachment.mime_type, attachment.content
+                                ),
+                            },
+                        }
+                    }
+                    AttachmentKind::Text => {
+                        ContentPartRequest::Text {
+                            text: attachment.to_prompt_text(),
+                        }
+                    }
+                };
+            })
+            .collect::<Vec<ContentPartRequest>>();
+        parts.push(ContentPartRequest::Text {
+            text: text.to_string(),
+        });
+
+        return MessageRequest {
+            role: role.to_string(),
+            content: ContentRequest::Parts(parts),
+            tool_calls: vec![],
+            tool_call_id: None,
+        };
+    }
+
+    fn from_history(message: &HistoryMessage) -> MessageRequest {
+        let role = match message.role {
+            HistoryRole::User => "user",
+            HistoryRole::Assistant => "assistant",
+        };
+
+        return MessageRequest::new(role, &message.text, &message.attachments);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FunctionRequest {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ToolRequest {
+    #[serde(rename = "type")]
+    tool_type: String,
+    function: FunctionRequest,
+}
+
+impl ToolRequest {
+    fn new(tool_name: &ToolName) -> ToolRequest {
+        return ToolRequest {
+            tool_type: "function".to_string(),
+            function: FunctionRequest {
+                name: tool_name.to_string(),
+                description: tool_name.description().to_string(),
+                parameters: tool_name.parameters(),
+            },
+        };
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct CompletionRequest {
     model: String,
     messages: Vec<MessageRequest>,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolRequest>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FunctionCallDeltaResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arguments: Option<String>,
+}
+
+/// Tool calls are streamed in pieces, where each piece with the same `index`
+/// belongs to the same call.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ToolCallDeltaResponse {
+    index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function: Option<FunctionCallDeltaResponse>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct CompletionDeltaResponse {
     content: Option<String>,
+    /// Sent by OpenAI compatible APIs serving reasoning models, such as
+    /// DeepSeek and vLLM, before the content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reasoning_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<ToolCallDeltaResponse>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -62,31 +238,299 @@ struct CompletionChoiceResponse {
     finish_reason: Option<String>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CompletionUsageResponse {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 struct CompletionResponse {
+    #[serde(default)]
     choices: Vec<CompletionChoiceResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage: Option<CompletionUsageResponse>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct EmbeddingsRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EmbeddingResponse {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingResponse>,
+}
+
+/// Result of a single request to the completions API.
+struct CompletionTurn {
+    text: String,
+    tool_calls: Vec<ToolCallRequest>,
+    usage: Option<Usage>,
+}
+
+/// Whether streamed completions ask for their token usage, which many OpenAI
+/// compatible servers and older Azure api-versions reject.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, EnumVariantNames, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum StreamUsage {
+    /// Only asks api.openai.com, and Azure api-versions from 2024-09-01.
+    Auto,
+    Always,
+    Never,
+}
+
+impl StreamUsage {
+    pub fn parse(text: &str) -> Option<StreamUsage> {
+        return StreamUsage::iter().find(|e| return e.to_string() == text);
+    }
+}
+
+/// First Azure api-version accepting `stream_options`. Api-versions are
+/// dates, so they're compared as strings.
+const AZURE_STREAM_USAGE_API_VERSION: &str = "2024-09-01";
+
+/// OpenAI compatible APIs, which differ in where requests are sent and how
+/// they're authenticated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpenAIApi {
+    OpenAI,
+    /// Azure serves each model from a named deployment, with the deployment
+    /// used in place of the model name.
+    Azure {
+        api_version: String,
+    },
 }
 
 pub struct OpenAI {
+    api: OpenAIApi,
     url: String,
     token: String,
     timeout: String,
+    http: HttpClient,
+    retry_policy: RetryPolicy,
+    stream_usage: StreamUsage,
+    tools: Tools,
 }
 
 impl Default for OpenAI {
     fn default() -> OpenAI {
+        return OpenAI::new(
+            OpenAIApi::OpenAI,
+            &Config::get(ConfigKey::OpenAiURL),
+            &Secrets::get(ConfigKey::OpenAiToken),
+            HttpClient::default(),
+        );
+    }
+}
+
+impl OpenAI {
+    pub fn new(api: OpenAIApi, url: &str, token: &str, http: HttpClient) -> OpenAI {
         return OpenAI {
-            url: Config::get(ConfigKey::OpenAiURL),
-            token: Config::get(ConfigKey::OpenAiToken),
+            api,
+            url: url.to_string(),
+            token: token.to_string(),
             timeout: Config::get(ConfigKey::BackendHealthCheckTimeout),
+            http,
+            retry_policy: RetryPolicy::default(),
+            stream_usage: StreamUsage::parse(&Config::get(ConfigKey::OpenAiStreamUsage))
+                .unwrap_or(StreamUsage::Auto),
+            tools: Tools::default(),
+        };
+    }
+
+    /// Whether completions ask for their token usage. Responses have no usage
+    /// when they don't.
+    fn include_usage(&self) -> bool {
+        return match self.stream_usage {
+            StreamUsage::Always => true,
+            StreamUsage::Never => false,
+            StreamUsage::Auto => {
+                match &self.api {
+                    OpenAIApi::OpenAI => {
+                        reqwest::Url::parse(&self.url)
+                            .map(|url| return url.host_str() == Some("api.openai.com"))
+                            .unwrap_or(false)
+                    }
+                    OpenAIApi::Azure { api_version } => {
+                        api_version.as_str() >= AZURE_STREAM_USAGE_API_VERSION
+                    }
+                }
+            }
+        };
+    }
+
+    fn title(&self) -> &'static str {
+        return match self.api {
+            OpenAIApi::OpenAI => "OpenAI",
+            OpenAIApi::Azure { .. } => "Azure OpenAI",
+        };
+    }
+
+    fn completions_url(&self, model: &str) -> String {
+        return match &self.api {
+            OpenAIApi::OpenAI => format!("{url}/v1/chat/completions", url = self.url),
+            OpenAIApi::Azure { api_version } => {
+                format!(
+                    "{url}/openai/deployments/{model}/chat/completions?api-version={api_version}",
+                    url = self.url
+                )
+            }
+        };
+    }
+
+    fn embeddings_url(&self, model: &str) -> String {
+        return match &self.api {
+            OpenAIApi::OpenAI => format!("{url}/v1/embeddings", url = self.url),
+            OpenAIApi::Azure { api_version } => {
+                format!(
+                    "{url}/openai/deployments/{model}/embeddings?api-version={api_version}",
+                    url = self.url
+                )
+            }
+        };
+    }
+
+    fn models_url(&self) -> String {
+        return match &self.api {
+            OpenAIApi::OpenAI => format!("{url}/v1/models", url = self.url),
+            OpenAIApi::Azure { api_version } => {
+                format!(
+                    "{url}/openai/deployments?api-version={api_version}",
+                    url = self.url
+                )
+            }
+        };
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        return match self.api {
+            OpenAIApi::OpenAI => request.header("Authorization", format!("Bearer {}", self.token)),
+            OpenAIApi::Azure { .. } => request.header("api-key", &self.token),
+        };
+    }
+
+    /// Streams a completion to the UI, returning the text and any tool calls
+    /// the model responded with. Returns `None` if cancelled before the
+    /// request was sent.
+    async fn stream_completion(
+        &self,
+        req: &CompletionRequest,
+        cancel: &CancellationToken,
+        tx: &mpsc::UnboundedSender<Event>,
+    ) -> Result<Option<CompletionTurn>> {
+        let request = self
+            .authorize(self.http.client()?.post(self.completions_url(&req.model)))
+            .json(req);
+        let res = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Ok(None),
+            res = self.retry_policy.send(request, self.name(), Some(tx), Some(cancel)) => res?,
         };
+
+        if !res.status().is_success() {
+            tracing::error!(
+                status = res.status().as_u16(),
+                "Failed to make completion request to {}",
+                self.title()
+            );
+            bail!(format!(
+                "Failed to make completion request to {}",
+                self.title()
+            ));
+        }
+
+        let mut events = EventStream::new(res, StreamFormat::Sse, cancel);
+
+        let mut turn = CompletionTurn {
+            text: "".to_string(),
+            tool_calls: vec![],
+            usage: None,
+        };
+        while let Some(event) = events.next().await? {
+            let ores: CompletionResponse = event.json()?;
+            tracing::debug!(body = ?ores, "Completion response");
+
+            // Usage is sent in a final chunk with no choices after the finish reason.
+            if let Some(res_usage) = &ores.usage {
+                turn.usage = Some(Usage::new(
+                    res_usage.prompt_tokens,
+                    res_usage.completion_tokens,
+                ));
+            }
+            if ores.choices.is_empty() {
+                continue;
+            }
+
+            let choice = &ores.choices[0];
+            for tool_call_delta in &choice.delta.tool_calls {
+                if turn.tool_calls.len() <= tool_call_delta.index {
+                    turn.tool_calls.push(ToolCallRequest {
+                        id: "".to_string(),
+                        tool_type: "function".to_string(),
+                        function: FunctionCallRequest {
+                            name: "".to_string(),
+                            arguments: "".to_string(),
+                        },
+                    });
+                }
+
+                let tool_call = &mut turn.tool_calls[tool_call_delta.index];
+                if let Some(id) = &tool_call_delta.id {
+                    tool_call.id.push_str(id);
+                }
+                if let Some(function) = &tool_call_delta.function {
+                    tool_call
+                        .function
+                        .name
+                        .push_str(function.name.as_deref().unwrap_or_default());
+                    tool_call
+                        .function
+                        .arguments
+                        .push_str(function.arguments.as_deref().unwrap_or_default());
+                }
+            }
+            if choice.finish_reason.is_some() {
+                continue;
+            }
+
+            let text = choice.delta.content.clone().unwrap_or_default();
+            let reasoning = choice.delta.reasoning_content.clone().unwrap_or_default();
+            if text.is_empty() && reasoning.is_empty() {
+                continue;
+            }
+
+            turn.text.push_str(&text);
+            let msg = BackendResponse {
+                author: Author::Model,
+                text,
+                reasoning,
+                done: false,
+                usage: None,
+                truncated: false,
+            };
+
+            tx.send(Event::BackendPromptResponse(msg))?;
+        }
+
+        return Ok(Some(turn));
     }
 }
 
 #[async_trait]
 impl Backend for OpenAI {
     fn name(&self) -> BackendName {
-        return BackendName::OpenAI;
+        return match self.api {
+            OpenAIApi::OpenAI => BackendName::OpenAI,
+            OpenAIApi::Azure { .. } => BackendName::Azure,
+        };
     }
 
     #[allow(clippy::implicit_return)]
@@ -104,7 +548,9 @@ impl Backend for OpenAI {
             return Ok(());
         }
 
-        let res = reqwest::Client::new()
+        let res = self
+            .http
+            .client()?
             .get(&self.url)
             .timeout(Duration::from_millis(self.timeout.parse::<u64>()?))
             .send()
@@ -125,24 +571,27 @@ impl Backend for OpenAI {
     }
 
     #[allow(clippy::implicit_return)]
-    async fn list_models(&self) -> Result<Vec<String>> {
-        let res = reqwest::Client::new()
-            .get(format!("{url}/v1/models", url = self.url))
-            .header("Authorization", format!("Bearer {}", self.token))
-            .send()
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        let request = self.authorize(self.http.request(Method::GET, self.models_url())?);
+        let res = self
+            .retry_policy
+            .send(request, self.name(), None, None)
             .await?
             .json::<ModelListResponse>()
             .await?;
 
-        let mut models: Vec<String> = res
+        let mut models: Vec<ModelInfo> = res
             .data
             .iter()
             .map(|model| {
-                return model.id.to_string();
+                return ModelInfo {
+                    created: model.created.and_then(ModelInfo::date_from_timestamp),
+                    ..ModelInfo::new(&model.id)
+                };
             })
             .collect();
 
-        models.sort();
+        models.sort_by(|a, b| return a.name.cmp(&b.name));
 
         return Ok(models);
     }
@@ -151,95 +600,129 @@ impl Backend for OpenAI {
     async fn get_completion<'a>(
         &self,
         prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
         tx: &'a mpsc::UnboundedSender<Event>,
     ) -> Result<()> {
-        let mut messages: Vec<MessageRequest> = vec![];
-        if !prompt.backend_context.is_empty() {
-            messages = serde_json::from_str(&prompt.backend_context)?;
-        }
-        messages.push(MessageRequest {
-            role: "user".to_string(),
-            content: prompt.text,
-        });
-
-        let req = CompletionRequest {
-            model: Config::get(ConfigKey::Model),
-            messages: messages.clone(),
+        let mut messages: Vec<MessageRequest> = prompt
+            .history
+            .iter()
+            .map(MessageRequest::from_history)
+            .collect();
+        messages.push(MessageRequest::new(
+            "user",
+            &prompt.text,
+            &prompt.attachments,
+        ));
+
+        let mut req = CompletionRequest {
+            model: prompt.model(),
+            messages,
             stream: true,
+            temperature: prompt.generation_options.temperature,
+            max_tokens: prompt.generation_options.max_tokens,
+            top_p: prompt.generation_options.top_p,
+            stop: prompt.generation_options.stop,
+            stream_options: self.include_usage().then_some(StreamOptions {
+                include_usage: true,
+            }),
+            tools: self.tools.names().iter().map(ToolRequest::new).collect(),
         };
 
-        let res = reqwest::Client::new()
-            .post(format!("{url}/v1/chat/completions", url = self.url))
-            .header("Authorization", format!("Bearer {}", self.token))
-            .json(&req)
-            .send()
-            .await?;
-
-        if !res.status().is_success() {
-            tracing::error!(
-                status = res.status().as_u16(),
-                "Failed to make completion request to OpenAI"
-            );
-            bail!("Failed to make completion request to OpenAI");
+        if !prompt.system_prompt.is_empty() {
+            req.messages
+                .insert(0, MessageRequest::new("system", &prompt.system_prompt, &[]));
         }
 
-        let stream = res.bytes_stream().map_err(convert_err);
-        let mut lines_reader = StreamReader::new(stream).lines();
-
-        let mut last_message = "".to_string();
-        while let Ok(line) = lines_reader.next_line().await {
-            if line.is_none() {
+        let mut usage: Option<Usage> = None;
+        for _ in 0..=MAX_TOOL_ROUNDS {
+            let turn = match self.stream_completion(&req, cancel, tx).await? {
+                Some(turn) => turn,
+                None => break,
+            };
+            if let Some(turn_usage) = turn.usage {
+                usage.get_or_insert_with(Usage::default).add(&turn_usage);
+            }
+            if turn.tool_calls.is_empty() || cancel.is_cancelled() {
                 break;
             }
 
-            let mut cleaned_line = line.unwrap().trim().to_string();
-            if cleaned_line.starts_with("data:") {
-                cleaned_line = cleaned_line.split_off(5).trim().to_string();
+            req.messages.push(MessageRequest {
+                role: "assistant".to_string(),
+                content: ContentRequest::Text(turn.text),
+                tool_calls: turn.tool_calls.clone(),
+                tool_call_id: None,
+            });
+
+            for tool_call in turn.tool_calls {
+                let call = ToolCall::new(
+                    &tool_call.id,
+                    &tool_call.function.name,
+                    ToolCall::parse_arguments(&tool_call.function.arguments),
+                );
+                let result = match self.tools.call(&call, cancel, tx).await? {
+                    Some(result) => result,
+                    None => break,
+                };
+
+                req.messages.push(MessageRequest {
+                    role: "tool".to_string(),
+                    content: ContentRequest::Text(result.content),
+                    tool_calls: vec![],
+                    tool_call_id: Some(tool_call.id),
+                });
             }
-            if cleaned_line.is_empty() {
-                continue;
-            }
-
-            let ores: CompletionResponse = serde_json::from_str(&cleaned_line).unwrap();
-            tracing::debug!(body = ?ores, "Completion response");
-
-            let choice = &ores.choices[0];
-            if choice.finish_reason.is_some() {
+            if cancel.is_cancelled() {
                 break;
             }
-            if choice.delta.content.is_none() {
-                continue;
-            }
-
-            let text = choice.delta.content.clone().unwrap().to_string();
-            if text.is_empty() {
-                continue;
-            }
-
-            last_message += &text;
-            let msg = BackendResponse {
-                author: Author::Model,
-                text,
-                done: false,
-                context: None,
-            };
-
-            tx.send(Event::BackendPromptResponse(msg))?;
         }
 
-        messages.push(MessageRequest {
-            role: "assistant".to_string(),
-            content: last_message.to_string(),
-        });
-
         let msg = BackendResponse {
             author: Author::Model,
             text: "".to_string(),
+            reasoning: "".to_string(),
             done: true,
-            context: Some(serde_json::to_string(&messages)?),
+            usage,
+            truncated: cancel.is_cancelled(),
         };
         tx.send(Event::BackendPromptResponse(msg))?;
 
         return Ok(());
     }
+
+    #[allow(clippy::implicit_return)]
+    async fn get_embeddings(&self, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let req = EmbeddingsRequest {
+            model: model.to_string(),
+            input: texts.to_vec(),
+        };
+        let request = self
+            .authorize(
+                self.http
+                    .request(Method::POST, self.embeddings_url(model))?,
+            )
+            .json(&req);
+        let res = self
+            .retry_policy
+            .send(request, self.name(), None, None)
+            .await?;
+        if !res.status().is_success() {
+            tracing::error!(
+                status = res.status().as_u16(),
+                "Failed to make embeddings request to {}",
+                self.title()
+            );
+            bail!(format!(
+                "Failed to make embeddings request to {}",
+                self.title()
+            ));
+        }
+
+        let mut data = res.json::<EmbeddingsResponse>().await?.data;
+        data.sort_by_key(|embedding| return embedding.index);
+
+        return Ok(data
+            .into_iter()
+            .map(|embedding| return embedding.embedding)
+            .collect());
+    }
 }
@@ -1,27 +1,50 @@
+use std::time::Duration;
+
 use anyhow::bail;
 use anyhow::Result;
-use test_utils::insta_snapshot;
+use mockito::Matcher;
+use serde_json::json;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 use super::CompletionChoiceResponse;
 use super::CompletionDeltaResponse;
+use super::CompletionRequest;
 use super::CompletionResponse;
-use super::MessageRequest;
+use super::CompletionUsageResponse;
 use super::Model;
 use super::ModelListResponse;
 use super::OpenAI;
+use super::OpenAIApi;
+use super::StreamUsage;
+use crate::domain::models::Attachment;
+use crate::domain::models::AttachmentKind;
 use crate::domain::models::Author;
 use crate::domain::models::Backend;
 use crate::domain::models::BackendPrompt;
 use crate::domain::models::BackendResponse;
 use crate::domain::models::Event;
+use crate::domain::models::GenerationOptions;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::ToolName;
+use crate::domain::models::Usage;
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::tools::Tools;
 
 impl OpenAI {
     fn with_url(url: String) -> OpenAI {
         return OpenAI {
+            api: OpenAIApi::OpenAI,
             url,
             token: "abc".to_string(),
             timeout: "200".to_string(),
+            http: HttpClient::new(reqwest::Client::new()),
+            retry_policy: RetryPolicy::new(3, Duration::from_millis(1)),
+            stream_usage: StreamUsage::Always,
+            tools: Tools::new(vec![], std::env::temp_dir(), ""),
         };
     }
 }
@@ -35,6 +58,19 @@ fn to_res(action: Option<Event>) -> Result<BackendResponse> {
     return Ok(act);
 }
 
+/// Approves or denies the first tool call, returning its summary.
+async fn respond_to_tool_call(rx: &mut mpsc::UnboundedReceiver<Event>, approved: bool) -> String {
+    while let Some(event) = rx.recv().await {
+        if let Event::BackendToolCall(approval) = event {
+            let summary = approval.call.summary();
+            approval.respond(approved);
+            return summary;
+        }
+    }
+
+    return "".to_string();
+}
+
 #[tokio::test]
 async fn it_successfully_health_checks() {
     let mut server = mockito::Server::new();
@@ -72,10 +108,12 @@ async fn it_lists_models() -> Result<()> {
     let body = serde_json::to_string(&ModelListResponse {
         data: vec![
             Model {
-                id: "first".to_string(),
+                id: "second".to_string(),
+                created: None,
             },
             Model {
-                id: "second".to_string(),
+                id: "first".to_string(),
+                created: Some(1686935002),
             },
         ],
     })?;
@@ -92,7 +130,16 @@ async fn it_lists_models() -> Result<()> {
     let res = backend.list_models().await?;
     mock.assert();
 
-    assert_eq!(res, vec!["first".to_string(), "second".to_string()]);
+    assert_eq!(
+        res,
+        vec![
+            ModelInfo {
+                created: Some("2023-06-16".to_string()),
+                ..ModelInfo::new("first")
+            },
+            ModelInfo::new("second"),
+        ]
+    );
 
     return Ok(());
 }
@@ -103,40 +150,79 @@ async fn it_gets_completions() -> Result<()> {
         choices: vec![CompletionChoiceResponse {
             delta: CompletionDeltaResponse {
                 content: Some("Hello ".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
             },
             finish_reason: None,
         }],
+        usage: None,
     })?;
 
     let second_line = serde_json::to_string(&CompletionResponse {
         choices: vec![CompletionChoiceResponse {
             delta: CompletionDeltaResponse {
                 content: Some("World".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
             },
             finish_reason: None,
         }],
+        usage: None,
     })?;
 
     let third_line = serde_json::to_string(&CompletionResponse {
         choices: vec![CompletionChoiceResponse {
-            delta: CompletionDeltaResponse { content: None },
+            delta: CompletionDeltaResponse {
+                content: None,
+                reasoning_content: None,
+                tool_calls: vec![],
+            },
             finish_reason: Some("stop".to_string()),
         }],
+        usage: None,
     })?;
 
-    let body = [first_line, second_line, third_line].join("\n");
+    let fourth_line = serde_json::to_string(&CompletionResponse {
+        choices: vec![],
+        usage: Some(CompletionUsageResponse {
+            prompt_tokens: 10,
+            completion_tokens: 2,
+        }),
+    })?;
+
+    let body = [
+        first_line,
+        second_line,
+        third_line,
+        fourth_line,
+        "[DONE]".to_string(),
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
     let prompt = BackendPrompt {
         text: "Say hi to the world".to_string(),
-        backend_context: serde_json::to_string(&vec![MessageRequest {
-            role: "assistant".to_string(),
-            content: "How may I help you?".to_string(),
-        }])?,
+        attachments: vec![],
+        history: vec![
+            HistoryMessage::new(HistoryRole::User, "Hello"),
+            HistoryMessage::new(HistoryRole::Assistant, "How may I help you?"),
+        ],
+        system_prompt: "You are a test.".to_string(),
+        generation_options: GenerationOptions {
+            temperature: Some(0.5),
+            max_tokens: Some(100),
+            top_p: None,
+            stop: vec!["END".to_string()],
+        },
+        model: None,
     };
 
     let mut server = mockito::Server::new();
     let mock = server
         .mock("POST", "/v1/chat/completions")
         .match_header("Authorization", "Bearer abc")
+        .match_body(Matcher::PartialJson(
+            json!({"messages": [{"role": "system", "content": "You are a test."}, {"role": "user", "content": "Hello"}, {"role": "assistant", "content": "How may I help you?"}, {"role": "user", "content": "Say hi to the world"}], "temperature": 0.5, "max_tokens": 100, "stop": ["END"], "stream_options": {"include_usage": true}}),
+        ))
         .with_status(200)
         .with_body(body)
         .create();
@@ -144,7 +230,9 @@ async fn it_gets_completions() -> Result<()> {
     let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
 
     let backend = OpenAI::with_url(server.url());
-    backend.get_completion(prompt, &tx).await?;
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
 
     mock.assert();
 
@@ -155,19 +243,225 @@ async fn it_gets_completions() -> Result<()> {
     assert_eq!(first_recv.author, Author::Model);
     assert_eq!(first_recv.text, "Hello ".to_string());
     assert!(!first_recv.done);
-    assert_eq!(first_recv.context, None);
 
     assert_eq!(second_recv.author, Author::Model);
     assert_eq!(second_recv.text, "World".to_string());
     assert!(!second_recv.done);
-    assert_eq!(second_recv.context, None);
 
     assert_eq!(third_recv.author, Author::Model);
     assert!(third_recv.text.is_empty());
     assert!(third_recv.done);
-    insta_snapshot(|| {
-        insta::assert_toml_snapshot!(third_recv.context);
+    assert_eq!(third_recv.usage, Some(Usage::new(10, 2)));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_sends_image_attachments() -> Result<()> {
+    let mut prompt = BackendPrompt::new("What is this?".to_string(), vec![]);
+    prompt.attachments = vec![Attachment {
+        kind: AttachmentKind::Image,
+        path: "/tmp/cat.png".to_string(),
+        mime_type: "image/png".to_string(),
+        content: "b2F0bWVhbA==".to_string(),
+    }];
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::PartialJson(json!({"messages": [{"role": "user", "content": [{"type": "image_url", "image_url": {"url": "data:image/png;base64,b2F0bWVhbA=="}}, {"type": "text", "text": "What is this?"}]}]})))
+        .with_status(200)
+        .create();
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = OpenAI::with_url(server.url());
+    backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await?;
+
+    mock.assert();
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_runs_approved_tool_calls() -> Result<()> {
+    let workspace = tempfile::tempdir()?;
+    std::fs::write(workspace.path().join("main.rs"), "fn main() {}")?;
+
+    let tool_call_body = [
+        r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"read_file","arguments":""}}]},"finish_reason":null}]}"#,
+        r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"path\": \"main.rs\"}"}}]},"finish_reason":null}]}"#,
+        r#"{"choices":[{"delta":{},"finish_reason":"tool_calls"}]}"#,
+        r#"{"choices":[],"usage":{"prompt_tokens":10,"completion_tokens":5}}"#,
+        "[DONE]",
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+    let answer_body = [
+        r#"{"choices":[{"delta":{"content":"It's empty."},"finish_reason":null}]}"#,
+        r#"{"choices":[],"usage":{"prompt_tokens":20,"completion_tokens":3}}"#,
+        "[DONE]",
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let tool_call_mock = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::PartialJson(
+            json!({"tools": [{"type": "function", "function": {"name": "read_file"}}]}),
+        ))
+        .with_status(200)
+        .with_body(tool_call_body)
+        .expect(1)
+        .create();
+    let answer_mock = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::PartialJson(json!({"messages": [
+            {"role": "user", "content": "What's in main.rs?"},
+            {"role": "assistant", "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "read_file", "arguments": "{\"path\": \"main.rs\"}"}}]},
+            {"role": "tool", "tool_call_id": "call_1", "content": "fn main() {}"}
+        ]})))
+        .with_status(200)
+        .with_body(answer_body)
+        .expect(1)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let mut backend = OpenAI::with_url(server.url());
+    backend.tools = Tools::new(vec![ToolName::ReadFile], workspace.path().to_path_buf(), "");
+
+    let prompt = BackendPrompt::new("What's in main.rs?".to_string(), vec![]);
+    let cancel = CancellationToken::new();
+    let (res, summary) = tokio::join!(
+        backend.get_completion(prompt, &cancel, &tx),
+        respond_to_tool_call(&mut rx, true)
+    );
+    res?;
+
+    tool_call_mock.assert();
+    answer_mock.assert();
+    assert_eq!(summary, r#"read_file(path: "main.rs")"#);
+
+    let first_recv = to_res(rx.recv().await)?;
+    let second_recv = to_res(rx.recv().await)?;
+
+    assert_eq!(first_recv.text, "It's empty.");
+    assert!(second_recv.done);
+    assert_eq!(second_recv.usage, Some(Usage::new(30, 8)));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_streams_reasoning_content() -> Result<()> {
+    let body = [
+        r#"{"choices":[{"delta":{"role":"assistant","content":null,"reasoning_content":"They said hi."},"finish_reason":null}]}"#,
+        r#"{"choices":[{"delta":{"content":"Hello!"},"finish_reason":null}]}"#,
+        r#"{"choices":[{"delta":{},"finish_reason":"stop"}]}"#,
+        "[DONE]",
+    ]
+    .map(|line| return format!("data: {line}\n\n"))
+    .concat();
+
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+
+    let backend = OpenAI::with_url(server.url());
+    backend
+        .get_completion(
+            BackendPrompt::new("Hi".to_string(), vec![]),
+            &CancellationToken::new(),
+            &tx,
+        )
+        .await?;
+
+    mock.assert();
+
+    let first_recv = to_res(rx.recv().await)?;
+    let second_recv = to_res(rx.recv().await)?;
+    assert_eq!(first_recv.reasoning, "They said hi.");
+    assert!(first_recv.text.is_empty());
+    assert_eq!(second_recv.text, "Hello!");
+    assert!(second_recv.reasoning.is_empty());
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_gets_embeddings_in_order() -> Result<()> {
+    let body = json!({
+        "data": [
+            {"index": 1, "embedding": [0.3, 0.4]},
+            {"index": 0, "embedding": [0.1, 0.2]},
+        ]
     });
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/v1/embeddings")
+        .match_header("Authorization", "Bearer abc")
+        .match_body(Matcher::Json(json!({
+            "model": "text-embedding-3-small",
+            "input": ["first", "second"],
+        })))
+        .with_status(200)
+        .with_body(body.to_string())
+        .create();
+
+    let backend = OpenAI::with_url(server.url());
+    let embeddings = backend
+        .get_embeddings(
+            "text-embedding-3-small",
+            &["first".to_string(), "second".to_string()],
+        )
+        .await?;
+
+    mock.assert();
+    assert_eq!(embeddings, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+
+    return Ok(());
+}
+
+#[test]
+fn it_only_asks_for_usage_when_supported() {
+    let mut backend = OpenAI::with_url("https://api.openai.com".to_string());
+    backend.stream_usage = StreamUsage::Auto;
+    assert!(backend.include_usage());
+
+    backend.url = "http://localhost:8080".to_string();
+    assert!(!backend.include_usage());
+
+    backend.stream_usage = StreamUsage::Always;
+    assert!(backend.include_usage());
+
+    backend.stream_usage = StreamUsage::Auto;
+    for (api_version, include_usage) in [
+        ("2024-06-01", false),
+        ("2024-09-01-preview", true),
+        ("2024-10-21", true),
+    ] {
+        backend.api = OpenAIApi::Azure {
+            api_version: api_version.to_string(),
+        };
+        assert_eq!(backend.include_usage(), include_usage, "{api_version}");
+    }
+
+    backend.stream_usage = StreamUsage::Never;
+    assert!(!backend.include_usage());
+}
+
+#[test]
+fn it_leaves_out_stream_options_without_usage() -> Result<()> {
+    let req = CompletionRequest::default();
+    assert!(!serde_json::to_string(&req)?.contains("stream_options"));
 
     return Ok(());
 }
@@ -0,0 +1,101 @@
+#[cfg(test)]
+#[path = "reasoning_test.rs"]
+mod tests;
+
+const THINK_START: &str = "<think>";
+const THINK_END: &str = "</think>";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ThinkState {
+    /// Nothing but whitespace has been seen, so the response may still open
+    /// with a `<think>` tag.
+    #[default]
+    Start,
+    Thinking,
+    /// The reasoning has ended, and whitespace before the answer is dropped.
+    AfterThinking,
+    Answering,
+}
+
+/// Splits the reasoning out of responses from models which wrap it in
+/// `<think>` tags at the start of their response, such as DeepSeek R1 and
+/// Qwen 3 served by Ollama. Responses are streamed, so a tag can be split
+/// across several pieces, and only a tag opening the response is treated as
+/// reasoning.
+#[derive(Default)]
+pub struct ThinkTags {
+    state: ThinkState,
+    pending: String,
+}
+
+impl ThinkTags {
+    /// Returns the answer text and reasoning found in the next piece of a
+    /// response. Text which could be part of a tag is held back until the
+    /// following piece arrives.
+    pub fn push(&mut self, piece: &str) -> (String, String) {
+        self.pending.push_str(piece);
+        let mut text = "".to_string();
+        let mut reasoning = "".to_string();
+
+        loop {
+            match self.state {
+                ThinkState::Start => {
+                    let trimmed = self.pending.trim_start();
+                    if let Some(rest) = trimmed.strip_prefix(THINK_START) {
+                        self.pending = rest.to_string();
+                        self.state = ThinkState::Thinking;
+                        continue;
+                    }
+                    if THINK_START.starts_with(trimmed) {
+                        break;
+                    }
+                    self.state = ThinkState::Answering;
+                }
+                ThinkState::Thinking => {
+                    if let Some(idx) = self.pending.find(THINK_END) {
+                        reasoning.push_str(&self.pending[..idx]);
+                        self.pending = self.pending[idx + THINK_END.len()..].to_string();
+                        self.state = ThinkState::AfterThinking;
+                        continue;
+                    }
+
+                    // The end of the piece could be the start of the closing
+                    // tag.
+                    let held = (1..THINK_END.len())
+                        .rev()
+                        .find(|len| return self.pending.ends_with(&THINK_END[..*len]))
+                        .unwrap_or(0);
+                    let split_idx = self.pending.len() - held;
+                    reasoning.push_str(&self.pending[..split_idx]);
+                    self.pending = self.pending[split_idx..].to_string();
+                    break;
+                }
+                ThinkState::AfterThinking => {
+                    self.pending = self.pending.trim_start().to_string();
+                    if self.pending.is_empty() {
+                        break;
+                    }
+                    self.state = ThinkState::Answering;
+                }
+                ThinkState::Answering => {
+                    text.push_str(&self.pending);
+                    self.pending = "".to_string();
+                    break;
+                }
+            }
+        }
+
+        return (text, reasoning);
+    }
+
+    /// Returns whatever was held back once the response has ended, as the
+    /// answer text and reasoning.
+    pub fn finish(&mut self) -> (String, String) {
+        let pending = std::mem::take(&mut self.pending);
+        return match self.state {
+            ThinkState::Thinking => ("".to_string(), pending),
+            ThinkState::AfterThinking => ("".to_string(), "".to_string()),
+            ThinkState::Start | ThinkState::Answering => (pending, "".to_string()),
+        };
+    }
+}
@@ -0,0 +1,61 @@
+use super::ThinkTags;
+
+fn split(pieces: &[&str]) -> (String, String) {
+    let mut think_tags = ThinkTags::default();
+    let mut text = "".to_string();
+    let mut reasoning = "".to_string();
+    for piece in pieces {
+        let (piece_text, piece_reasoning) = think_tags.push(piece);
+        text.push_str(&piece_text);
+        reasoning.push_str(&piece_reasoning);
+    }
+
+    let (piece_text, piece_reasoning) = think_tags.finish();
+    text.push_str(&piece_text);
+    reasoning.push_str(&piece_reasoning);
+
+    return (text, reasoning);
+}
+
+#[test]
+fn it_splits_reasoning_from_the_answer() {
+    assert_eq!(
+        split(&["<think>\nThey said hi.\n</think>\n\nHello!"]),
+        ("Hello!".to_string(), "\nThey said hi.\n".to_string())
+    );
+}
+
+#[test]
+fn it_splits_tags_across_pieces() {
+    assert_eq!(
+        split(&[
+            "\n<th",
+            "ink>They said",
+            " hi.</th",
+            "ink>",
+            "\n\n",
+            "Hel",
+            "lo!"
+        ]),
+        ("Hello!".to_string(), "They said hi.".to_string())
+    );
+}
+
+#[test]
+fn it_keeps_answers_without_reasoning() {
+    assert_eq!(
+        split(&["<b>Hello</b> ", "and <think> about it"]),
+        (
+            "<b>Hello</b> and <think> about it".to_string(),
+            "".to_string()
+        )
+    );
+}
+
+#[test]
+fn it_keeps_unfinished_reasoning() {
+    assert_eq!(
+        split(&["<think>They said", " hi.</thi"]),
+        ("".to_string(), "They said hi.</thi".to_string())
+    );
+}
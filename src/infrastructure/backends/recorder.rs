@@ -0,0 +1,247 @@
+#[cfg(test)]
+#[path = "recorder_test.rs"]
+mod tests;
+
+use std::path;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use reqwest::Response;
+use tokio::fs;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::configuration::Secrets;
+use crate::domain::models::BackendBox;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::Cassette;
+use crate::domain::models::Event;
+use crate::domain::models::Interaction;
+use crate::domain::models::RecordedChunk;
+use crate::domain::models::RecordedRequest;
+use crate::domain::models::RecordedResponse;
+use crate::domain::models::REDACTED;
+
+/// Completions can be recorded at the same time in compare mode, so writes to
+/// the cassette are serialized.
+static CASSETTE_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| {
+    return tokio::sync::Mutex::new(());
+});
+
+struct Recording {
+    started: Instant,
+    interaction: Interaction,
+}
+
+impl Recording {
+    fn elapsed_ms(&self) -> u64 {
+        return self.started.elapsed().as_millis() as u64;
+    }
+}
+
+tokio::task_local! {
+    /// The completion being recorded, set for the duration of its request so
+    /// the HTTP layer can record to it.
+    static RECORDING: Arc<Mutex<Recording>>;
+}
+
+fn with_recording(f: impl FnOnce(&mut Recording)) {
+    let _ = RECORDING.try_with(|recording| {
+        if let Ok(mut recording) = recording.lock() {
+            f(&mut recording);
+        }
+    });
+}
+
+/// Returns credentials from the config, and those read from files and
+/// commands, which are redacted from cassettes.
+fn secrets() -> Vec<String> {
+    return [
+        ConfigKey::OpenAiToken,
+        ConfigKey::AzureToken,
+        ConfigKey::ClaudeToken,
+        ConfigKey::GeminiToken,
+    ]
+    .into_iter()
+    .map(Config::get)
+    .chain(Secrets::resolved())
+    .filter(|secret| return !secret.is_empty())
+    .collect();
+}
+
+/// Records backend requests, their streamed responses, and the responses
+/// sent to the chat, to the cassette file set by `record-cassette`.
+pub struct Recorder {}
+
+impl Recorder {
+    pub fn is_recording() -> bool {
+        return RECORDING.try_with(|_| return true).unwrap_or(false);
+    }
+
+    /// Sends a prompt to a backend, recording the completion when a cassette
+    /// is configured.
+    pub async fn get_completion(
+        backend: &BackendBox,
+        prompt: BackendPrompt,
+        cancel: &CancellationToken,
+        tx: &mpsc::UnboundedSender<Event>,
+    ) -> Result<()> {
+        let cassette_path = Config::get(ConfigKey::RecordCassette);
+        if cassette_path.is_empty() {
+            return backend.get_completion(prompt, cancel, tx).await;
+        }
+
+        return Recorder::record(&cassette_path, backend, prompt, cancel, tx).await;
+    }
+
+    /// Sends a prompt to a backend, appending the completion to a cassette.
+    /// Failed completions are recorded too, with their error.
+    async fn record(
+        cassette_path: &str,
+        backend: &BackendBox,
+        prompt: BackendPrompt,
+        cancel: &CancellationToken,
+        tx: &mpsc::UnboundedSender<Event>,
+    ) -> Result<()> {
+        let recording = Arc::new(Mutex::new(Recording {
+            started: Instant::now(),
+            interaction: Interaction {
+                backend: backend.name().to_string(),
+                model: prompt.model(),
+                prompt: prompt.text.to_string(),
+                ..Interaction::default()
+            },
+        }));
+
+        let (record_tx, mut record_rx) = mpsc::unbounded_channel::<Event>();
+        let completion = RECORDING.scope(recording.clone(), async move {
+            return backend.get_completion(prompt, cancel, &record_tx).await;
+        });
+
+        let responses = async {
+            while let Some(event) = record_rx.recv().await {
+                if let Event::BackendPromptResponse(res) = &event {
+                    let mut recording = recording.lock().unwrap();
+                    let elapsed_ms = recording.elapsed_ms();
+                    recording
+                        .interaction
+                        .responses
+                        .push(RecordedResponse::new(elapsed_ms, res));
+                }
+                tx.send(event)?;
+            }
+            return anyhow::Ok(());
+        };
+
+        let (res, responses_res) = tokio::join!(completion, responses);
+        responses_res?;
+
+        let mut interaction = recording.lock().unwrap().interaction.clone();
+        if let Err(err) = &res {
+            interaction.error = Some(err.to_string());
+        }
+        interaction.redact(&secrets());
+
+        if let Err(err) = Recorder::append(cassette_path, interaction).await {
+            tracing::error!(error = ?err, path = cassette_path, "Failed to write cassette");
+        }
+
+        return res;
+    }
+
+    async fn append(cassette_path: &str, interaction: Interaction) -> Result<()> {
+        let _lock = CASSETTE_LOCK.lock().await;
+
+        let cassette_path = path::PathBuf::from(cassette_path);
+        let mut cassette = Cassette::default();
+        if cassette_path.exists() {
+            cassette = Cassette::parse(&fs::read_to_string(&cassette_path).await?)?;
+        }
+        cassette.interactions.push(interaction);
+
+        fs::write(&cassette_path, cassette.to_yaml()?).await?;
+        return Ok(());
+    }
+
+    /// Records a request about to be sent.
+    pub fn record_request(request: &reqwest::Request) {
+        with_recording(|recording| {
+            let headers = request
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    let mut value = value.to_str().unwrap_or_default().to_string();
+                    if RecordedRequest::is_secret_header(name.as_str()) {
+                        value = REDACTED.to_string();
+                    }
+                    return (name.to_string(), value);
+                })
+                .collect();
+            let body = request
+                .body()
+                .and_then(|body| return body.as_bytes())
+                .map(|body| return String::from_utf8_lossy(body).to_string())
+                .unwrap_or_default();
+
+            let elapsed_ms = recording.elapsed_ms();
+            recording.interaction.requests.push(RecordedRequest {
+                elapsed_ms,
+                method: request.method().to_string(),
+                url: request.url().to_string(),
+                headers,
+                body,
+                ..RecordedRequest::default()
+            });
+        });
+    }
+
+    /// Records the status of a response. The body of a failed response is
+    /// read and recorded, as backends only report the status, and the
+    /// response is rebuilt so it can still be read.
+    pub async fn record_response(res: Response) -> Result<Response> {
+        if !Recorder::is_recording() {
+            return Ok(res);
+        }
+
+        let status = res.status();
+        with_recording(|recording| {
+            if let Some(request) = recording.interaction.requests.last_mut() {
+                request.status = Some(status.as_u16());
+            }
+        });
+        if status.is_success() {
+            return Ok(res);
+        }
+
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in res.headers() {
+            builder = builder.header(name, value);
+        }
+        let body = res.bytes().await?;
+        with_recording(|recording| {
+            if let Some(request) = recording.interaction.requests.last_mut() {
+                request.error_body = String::from_utf8_lossy(&body).to_string();
+            }
+        });
+
+        return Ok(Response::from(builder.body(body)?));
+    }
+
+    /// Records a line of a streamed response body.
+    pub fn record_chunk(data: &str) {
+        with_recording(|recording| {
+            let elapsed_ms = recording.elapsed_ms();
+            if let Some(request) = recording.interaction.requests.last_mut() {
+                request.chunks.push(RecordedChunk {
+                    elapsed_ms,
+                    data: data.to_string(),
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::fs;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::Recorder;
+use crate::domain::models::Author;
+use crate::domain::models::Backend;
+use crate::domain::models::BackendBox;
+use crate::domain::models::BackendName;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::Cassette;
+use crate::domain::models::Event;
+use crate::domain::models::ModelInfo;
+use crate::infrastructure::backends::retry::RetryPolicy;
+use crate::infrastructure::backends::streaming::EventStream;
+use crate::infrastructure::backends::streaming::StreamFormat;
+
+/// Streams each server-sent event as a response, like the real backends.
+struct StreamingBackend {
+    url: String,
+}
+
+#[async_trait]
+impl Backend for StreamingBackend {
+    fn name(&self) -> BackendName {
+        return BackendName::OpenAI;
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn health_check(&self) -> Result<()> {
+        return Ok(());
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        return Ok(vec![]);
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn get_completion<'a>(
+        &self,
+        prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
+        tx: &'a mpsc::UnboundedSender<Event>,
+    ) -> Result<()> {
+        let request = reqwest::Client::new()
+            .post(format!("{}/chat?key=secret-key", self.url))
+            .header("Authorization", "Bearer secret-token")
+            .body(prompt.text);
+        let res = RetryPolicy::new(1, Duration::from_millis(1))
+            .send(request, self.name(), Some(tx), None)
+            .await?;
+        if !res.status().is_success() {
+            bail!(format!("Request failed: {}", res.text().await?));
+        }
+
+        let mut events = EventStream::new(res, StreamFormat::Sse, cancel);
+        while let Some(event) = events.next().await? {
+            tx.send(Event::BackendPromptResponse(BackendResponse {
+                author: Author::Model,
+                text: event.data,
+                reasoning: "".to_string(),
+                done: false,
+                usage: None,
+                truncated: false,
+            }))?;
+        }
+
+        tx.send(Event::BackendPromptResponse(BackendResponse {
+            author: Author::Model,
+            text: "".to_string(),
+            reasoning: "".to_string(),
+            done: true,
+            usage: None,
+            truncated: false,
+        }))?;
+
+        return Ok(());
+    }
+}
+
+async fn record(url: &str, cassette_path: &str, text: &str) -> (Result<()>, Vec<BackendResponse>) {
+    let backend: BackendBox = Box::new(StreamingBackend {
+        url: url.to_string(),
+    });
+    let mut prompt = BackendPrompt::new(text.to_string(), vec![]);
+    prompt.model = Some("model-1".to_string());
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let res = Recorder::record(
+        cassette_path,
+        &backend,
+        prompt,
+        &CancellationToken::new(),
+        &tx,
+    )
+    .await;
+    drop(tx);
+
+    let mut responses = vec![];
+    while let Some(event) = rx.recv().await {
+        if let Event::BackendPromptResponse(msg) = event {
+            responses.push(msg);
+        }
+    }
+
+    return (res, responses);
+}
+
+#[tokio::test]
+async fn it_records_completions() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/chat?key=secret-key")
+        .with_status(200)
+        .with_body("data: Hello\n\ndata: World\n\n")
+        .create();
+
+    let dir = tempfile::tempdir()?;
+    let cassette_path = dir.path().join("cassette.yaml");
+    let cassette_path = cassette_path.to_str().unwrap();
+
+    let (res, responses) = record(&server.url(), cassette_path, "Say hi").await;
+    res?;
+    mock.assert();
+    assert_eq!(responses.len(), 3);
+
+    // Following completions are appended.
+    let (res, _) = record(&server.url(), cassette_path, "Say hi again").await;
+    res?;
+
+    let cassette = Cassette::parse(&fs::read_to_string(cassette_path).await?)?;
+    assert_eq!(cassette.interactions.len(), 2);
+
+    let interaction = &cassette.interactions[0];
+    assert_eq!(interaction.backend, "openai");
+    assert_eq!(interaction.model, "model-1");
+    assert_eq!(interaction.prompt, "Say hi");
+    assert_eq!(interaction.error, None);
+
+    let request = &interaction.requests[0];
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.url, format!("{}/chat?key=[REDACTED]", server.url()));
+    assert_eq!(request.headers["authorization"], "[REDACTED]");
+    assert_eq!(request.body, "Say hi");
+    assert_eq!(request.status, Some(200));
+    assert_eq!(
+        request
+            .chunks
+            .iter()
+            .map(|chunk| return chunk.data.to_string())
+            .collect::<Vec<String>>(),
+        vec!["data: Hello", "", "data: World", ""]
+    );
+
+    assert_eq!(
+        interaction
+            .responses
+            .iter()
+            .map(|res| return (res.text.to_string(), res.done))
+            .collect::<Vec<(String, bool)>>(),
+        vec![
+            ("Hello".to_string(), false),
+            ("World".to_string(), false),
+            ("".to_string(), true)
+        ]
+    );
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_records_failed_responses() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/chat?key=secret-key")
+        .with_status(400)
+        .with_body("{\"error\": \"Bad model\"}")
+        .create();
+
+    let dir = tempfile::tempdir()?;
+    let cassette_path = dir.path().join("cassette.yaml");
+    let cassette_path = cassette_path.to_str().unwrap();
+
+    let (res, responses) = record(&server.url(), cassette_path, "Say hi").await;
+    mock.assert();
+
+    // The backend can still read the body of the response.
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Request failed: {\"error\": \"Bad model\"}"
+    );
+    assert!(responses.is_empty());
+
+    let cassette = Cassette::parse(&fs::read_to_string(cassette_path).await?)?;
+    let interaction = &cassette.interactions[0];
+    assert_eq!(interaction.requests[0].status, Some(400));
+    assert_eq!(
+        interaction.requests[0].error_body,
+        "{\"error\": \"Bad model\"}"
+    );
+    assert_eq!(
+        interaction.error,
+        Some("Request failed: {\"error\": \"Bad model\"}".to_string())
+    );
+
+    return Ok(());
+}
+
+#[test]
+fn it_is_not_recording_outside_of_completions() {
+    assert!(!Recorder::is_recording());
+}
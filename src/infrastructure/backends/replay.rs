@@ -0,0 +1,142 @@
+#[cfg(test)]
+#[path = "replay_test.rs"]
+mod tests;
+
+use std::path;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use async_trait::async_trait;
+use itertools::Itertools;
+use tokio::fs;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::domain::models::Author;
+use crate::domain::models::Backend;
+use crate::domain::models::BackendName;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::Cassette;
+use crate::domain::models::Event;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::ModelInfo;
+
+/// Replays the responses of completions recorded to a cassette, with their
+/// original timing.
+pub struct Replay {
+    cassette_path: String,
+    realtime: bool,
+}
+
+impl Default for Replay {
+    fn default() -> Replay {
+        return Replay {
+            cassette_path: Config::get(ConfigKey::ReplayCassette),
+            realtime: true,
+        };
+    }
+}
+
+impl Replay {
+    async fn cassette(&self) -> Result<Cassette> {
+        if self.cassette_path.is_empty() {
+            bail!("Replay cassette is not defined");
+        }
+        if !path::Path::new(&self.cassette_path).exists() {
+            bail!(format!("Cassette {} doesn't exist", self.cassette_path));
+        }
+
+        return Cassette::parse(&fs::read_to_string(&self.cassette_path).await?);
+    }
+}
+
+#[async_trait]
+impl Backend for Replay {
+    fn name(&self) -> BackendName {
+        return BackendName::Replay;
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn health_check(&self) -> Result<()> {
+        if self.cassette().await?.interactions.is_empty() {
+            bail!(format!(
+                "Cassette {} has no recorded interactions",
+                self.cassette_path
+            ));
+        }
+
+        return Ok(());
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        return Ok(self
+            .cassette()
+            .await?
+            .interactions
+            .iter()
+            .map(|interaction| return interaction.model.to_string())
+            .unique()
+            .map(|model| return ModelInfo::new(&model))
+            .collect());
+    }
+
+    #[allow(clippy::implicit_return)]
+    async fn get_completion<'a>(
+        &self,
+        prompt: BackendPrompt,
+        cancel: &'a CancellationToken,
+        tx: &'a mpsc::UnboundedSender<Event>,
+    ) -> Result<()> {
+        let cassette = self.cassette().await?;
+        let turn = prompt
+            .history
+            .iter()
+            .filter(|message| return message.role == HistoryRole::User)
+            .count();
+        let interaction = match cassette.find(&prompt.text, turn) {
+            Some(interaction) => interaction,
+            None => {
+                bail!(format!(
+                    "Cassette {} has no recorded interaction for this prompt",
+                    self.cassette_path
+                ))
+            }
+        };
+
+        let started = Instant::now();
+        for response in interaction.responses.iter() {
+            if self.realtime {
+                let at = started + Duration::from_millis(response.elapsed_ms);
+                tokio::select! {
+                    _ = tokio::time::sleep_until(at) => {},
+                    _ = cancel.cancelled() => {
+                        let msg = BackendResponse {
+                            author: Author::Model,
+                            text: "".to_string(),
+                            reasoning: "".to_string(),
+                            done: true,
+                            usage: None,
+                            truncated: true,
+                        };
+                        tx.send(Event::BackendPromptResponse(msg))?;
+                        return Ok(());
+                    },
+                };
+            }
+
+            tx.send(Event::BackendPromptResponse(response.to_backend_response()))?;
+        }
+
+        if let Some(error) = &interaction.error {
+            bail!(error.to_string());
+        }
+
+        return Ok(());
+    }
+}
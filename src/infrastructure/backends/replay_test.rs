@@ -0,0 +1,186 @@
+use anyhow::Result;
+use tokio::fs;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::Replay;
+use crate::domain::models::Author;
+use crate::domain::models::Backend;
+use crate::domain::models::BackendPrompt;
+use crate::domain::models::BackendResponse;
+use crate::domain::models::Cassette;
+use crate::domain::models::Event;
+use crate::domain::models::HistoryMessage;
+use crate::domain::models::HistoryRole;
+use crate::domain::models::Interaction;
+use crate::domain::models::ModelInfo;
+use crate::domain::models::RecordedResponse;
+use crate::domain::models::Usage;
+
+impl Replay {
+    fn with_cassette(cassette_path: &str) -> Replay {
+        return Replay {
+            cassette_path: cassette_path.to_string(),
+            realtime: false,
+        };
+    }
+}
+
+fn response(text: &str, done: bool) -> RecordedResponse {
+    let mut usage = None;
+    if done {
+        usage = Some(Usage::new(10, 2));
+    }
+
+    return RecordedResponse::new(
+        0,
+        &BackendResponse {
+            author: Author::Model,
+            text: text.to_string(),
+            reasoning: "".to_string(),
+            done,
+            usage,
+            truncated: false,
+        },
+    );
+}
+
+async fn write_cassette() -> Result<String> {
+    let cassette = Cassette {
+        interactions: vec![
+            Interaction {
+                backend: "openai".to_string(),
+                model: "gpt-4o".to_string(),
+                prompt: "Say hi".to_string(),
+                responses: vec![response("Hello ", false), response("World", true)],
+                ..Interaction::default()
+            },
+            Interaction {
+                backend: "openai".to_string(),
+                model: "gpt-4o".to_string(),
+                prompt: "Say hi again".to_string(),
+                responses: vec![response("Hel", false)],
+                error: Some("Failed to read streamed response".to_string()),
+                ..Interaction::default()
+            },
+        ],
+    };
+
+    let cassette_path = tempfile::tempdir()?.into_path().join("cassette.yaml");
+    fs::write(&cassette_path, cassette.to_yaml()?).await?;
+
+    return Ok(cassette_path.to_str().unwrap().to_string());
+}
+
+async fn replay(backend: &Replay, prompt: BackendPrompt) -> (Result<()>, Vec<BackendResponse>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let res = backend
+        .get_completion(prompt, &CancellationToken::new(), &tx)
+        .await;
+    drop(tx);
+
+    let mut responses = vec![];
+    while let Some(event) = rx.recv().await {
+        if let Event::BackendPromptResponse(msg) = event {
+            responses.push(msg);
+        }
+    }
+
+    return (res, responses);
+}
+
+#[tokio::test]
+async fn it_replays_recorded_responses() -> Result<()> {
+    let backend = Replay::with_cassette(&write_cassette().await?);
+    backend.health_check().await?;
+    assert_eq!(backend.list_models().await?, vec![ModelInfo::new("gpt-4o")]);
+
+    let (res, responses) = replay(&backend, BackendPrompt::new("Say hi".to_string(), vec![])).await;
+    res?;
+
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0].text, "Hello ");
+    assert!(!responses[0].done);
+    assert_eq!(responses[1].text, "World");
+    assert!(responses[1].done);
+    assert_eq!(responses[1].usage, Some(Usage::new(10, 2)));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_replays_recorded_errors_by_turn() -> Result<()> {
+    let backend = Replay::with_cassette(&write_cassette().await?);
+    let history = vec![
+        HistoryMessage::new(HistoryRole::User, "Hi"),
+        HistoryMessage::new(HistoryRole::Assistant, "Hello"),
+    ];
+
+    let (res, responses) = replay(
+        &backend,
+        BackendPrompt::new("Something else".to_string(), history),
+    )
+    .await;
+
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Failed to read streamed response"
+    );
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].text, "Hel");
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_without_a_recorded_interaction() -> Result<()> {
+    let cassette_path = write_cassette().await?;
+    let backend = Replay::with_cassette(&cassette_path);
+    let history = vec![
+        HistoryMessage::new(HistoryRole::User, "One"),
+        HistoryMessage::new(HistoryRole::User, "Two"),
+    ];
+
+    let (res, _) = replay(&backend, BackendPrompt::new("Three".to_string(), history)).await;
+
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        format!("Cassette {cassette_path} has no recorded interaction for this prompt")
+    );
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_fails_health_checks() -> Result<()> {
+    assert_eq!(
+        Replay::with_cassette("")
+            .health_check()
+            .await
+            .unwrap_err()
+            .to_string(),
+        "Replay cassette is not defined"
+    );
+    assert_eq!(
+        Replay::with_cassette("/does/not/exist.yaml")
+            .health_check()
+            .await
+            .unwrap_err()
+            .to_string(),
+        "Cassette /does/not/exist.yaml doesn't exist"
+    );
+
+    let cassette_path = tempfile::tempdir()?.into_path().join("cassette.yaml");
+    fs::write(&cassette_path, "").await?;
+    let cassette_path = cassette_path.to_str().unwrap();
+    assert_eq!(
+        Replay::with_cassette(cassette_path)
+            .health_check()
+            .await
+            .unwrap_err()
+            .to_string(),
+        format!("Cassette {cassette_path} has no recorded interactions")
+    );
+
+    return Ok(());
+}
@@ -0,0 +1,229 @@
+#[cfg(test)]
+#[path = "retry_test.rs"]
+mod tests;
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::header::HeaderMap;
+use reqwest::header::RETRY_AFTER;
+use reqwest::RequestBuilder;
+use reqwest::Response;
+use reqwest::StatusCode;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::domain::models::Author;
+use crate::domain::models::BackendName;
+use crate::domain::models::Event;
+use crate::domain::models::Message;
+use crate::infrastructure::backends::recorder::Recorder;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Retry policy shared by backends for requests that can fail transiently,
+/// such as rate limits, overloaded servers, and dropped connections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        let max_attempts = Config::get(ConfigKey::BackendMaxAttempts)
+            .parse::<u32>()
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+
+        return RetryPolicy::new(max_attempts, Duration::from_millis(500));
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration) -> RetryPolicy {
+        return RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            max_delay: Duration::from_secs(30),
+        };
+    }
+
+    fn is_retryable_status(status: StatusCode) -> bool {
+        return status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::INTERNAL_SERVER_ERROR
+            || status == StatusCode::BAD_GATEWAY
+            || status == StatusCode::SERVICE_UNAVAILABLE
+            || status == StatusCode::GATEWAY_TIMEOUT
+            // Anthropic's overloaded status.
+            || status.as_u16() == 529;
+    }
+
+    /// Only failures to connect and timeouts are transient, other request
+    /// errors such as invalid URLs or TLS failures would fail again.
+    fn is_retryable_error(err: &reqwest::Error) -> bool {
+        return err.is_connect() || err.is_timeout();
+    }
+
+    /// Reads a `Retry-After` header, either as a number of seconds or as an
+    /// HTTP date.
+    fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+        let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+        if let Ok(seconds) = value.parse::<f64>() {
+            if seconds.is_sign_negative() || !seconds.is_finite() {
+                return None;
+            }
+            return Some(Duration::from_secs_f64(seconds));
+        }
+
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        let millis = date
+            .signed_duration_since(chrono::Utc::now())
+            .num_milliseconds()
+            .max(0);
+
+        return Some(Duration::from_millis(millis as u64));
+    }
+
+    /// Exponential backoff for the given retry with up to 50% random jitter.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(16);
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.max_delay);
+
+        let jitter = RandomState::new().build_hasher().finish() % 1000;
+        return delay / 2 + delay.mul_f64(jitter as f64 / 2000.0);
+    }
+
+    fn notify(
+        tx: Option<&mpsc::UnboundedSender<Event>>,
+        backend_name: &BackendName,
+        reason: &str,
+        delay: Duration,
+        attempt: u32,
+        max_attempts: u32,
+    ) -> Result<()> {
+        tracing::warn!(
+            backend = backend_name.to_string(),
+            reason = reason,
+            delay_ms = delay.as_millis() as u64,
+            attempt = attempt,
+            "Retrying backend request"
+        );
+
+        if let Some(tx) = tx {
+            tx.send(Event::BackendStatus(Message::new(
+                Author::Oatmeal,
+                &format!(
+                    "{backend_name} {reason}. Retrying in {:.1}s (attempt {attempt} of {max_attempts})...",
+                    delay.as_secs_f64()
+                ),
+            )))?;
+        }
+
+        return Ok(());
+    }
+
+    /// Waits out the delay before a retry, returning false when cancelled
+    /// first.
+    async fn wait(delay: Duration, cancel: Option<&CancellationToken>) -> bool {
+        return match cancel {
+            Some(cancel) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => true,
+                    _ = cancel.cancelled() => false,
+                }
+            }
+            None => {
+                tokio::time::sleep(delay).await;
+                true
+            }
+        };
+    }
+
+    /// Sends a request, retrying transient failures until the max attempts is
+    /// reached. The last response is returned as is when retries are exhausted,
+    /// or when it asks to retry after more than the max delay, so callers can
+    /// report the failure as they normally would. Status
+    /// updates are sent to the chat when `tx` is provided, and cancelling
+    /// `cancel` stops waiting for the next retry.
+    pub async fn send(
+        &self,
+        request: RequestBuilder,
+        backend_name: BackendName,
+        tx: Option<&mpsc::UnboundedSender<Event>>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            // Requests with streaming bodies can't be cloned, and so can't be retried.
+            let current_request = match request.try_clone() {
+                Some(req) => req,
+                None => return Ok(request.send().await?),
+            };
+            let can_retry = attempt < self.max_attempts;
+            if Recorder::is_recording() {
+                if let Some(Ok(req)) = request.try_clone().map(|req| return req.build()) {
+                    Recorder::record_request(&req);
+                }
+            }
+
+            match current_request.send().await {
+                Ok(res) => {
+                    let res = Recorder::record_response(res).await?;
+                    if !can_retry || !RetryPolicy::is_retryable_status(res.status()) {
+                        return Ok(res);
+                    }
+
+                    // Servers asking to wait longer than the max delay would
+                    // stall the chat, so their response is returned instead.
+                    let delay = match RetryPolicy::retry_after(res.headers()) {
+                        Some(delay) if delay > self.max_delay => return Ok(res),
+                        Some(delay) => delay,
+                        None => self.backoff(attempt),
+                    };
+                    let reason = format!("responded with {}", res.status());
+                    RetryPolicy::notify(
+                        tx,
+                        &backend_name,
+                        &reason,
+                        delay,
+                        attempt + 1,
+                        self.max_attempts,
+                    )?;
+                    if !RetryPolicy::wait(delay, cancel).await {
+                        return Ok(res);
+                    }
+                }
+                Err(err) => {
+                    if !can_retry || !RetryPolicy::is_retryable_error(&err) {
+                        return Err(err.into());
+                    }
+
+                    let delay = self.backoff(attempt);
+                    RetryPolicy::notify(
+                        tx,
+                        &backend_name,
+                        "is not reachable",
+                        delay,
+                        attempt + 1,
+                        self.max_attempts,
+                    )?;
+                    if !RetryPolicy::wait(delay, cancel).await {
+                        return Err(err.into());
+                    }
+                }
+            }
+
+            attempt += 1;
+        }
+    }
+}
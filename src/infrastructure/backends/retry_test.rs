@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderValue;
+use reqwest::header::RETRY_AFTER;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::RetryPolicy;
+use crate::domain::models::Author;
+use crate::domain::models::BackendName;
+use crate::domain::models::Event;
+
+fn test_policy() -> RetryPolicy {
+    return RetryPolicy::new(3, Duration::from_millis(1));
+}
+
+#[test]
+fn it_reads_retry_after_seconds() {
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("2"));
+
+    assert_eq!(
+        RetryPolicy::retry_after(&headers),
+        Some(Duration::from_secs(2))
+    );
+}
+
+#[test]
+fn it_reads_retry_after_dates_in_the_past() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        RETRY_AFTER,
+        HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+    );
+
+    assert_eq!(RetryPolicy::retry_after(&headers), Some(Duration::ZERO));
+}
+
+#[test]
+fn it_ignores_invalid_retry_after() {
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+
+    assert_eq!(RetryPolicy::retry_after(&headers), None);
+}
+
+#[test]
+fn it_backs_off_exponentially_with_jitter() {
+    let policy = RetryPolicy::new(5, Duration::from_millis(100));
+
+    for retry in 1..=3 {
+        let max = Duration::from_millis(100 * 2u64.pow(retry - 1));
+        let delay = policy.backoff(retry);
+        assert!(delay >= max / 2, "{delay:?} is below {max:?} / 2");
+        assert!(delay <= max, "{delay:?} is above {max:?}");
+    }
+}
+
+#[test]
+fn it_caps_backoff_at_max_delay() {
+    let policy = RetryPolicy::new(50, Duration::from_secs(10));
+
+    assert!(policy.backoff(40) <= policy.max_delay);
+}
+
+#[tokio::test]
+async fn it_retries_rate_limited_requests() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let rate_limited = server
+        .mock("GET", "/")
+        .with_status(429)
+        .with_header("retry-after", "0")
+        .expect(1)
+        .create();
+    let success = server.mock("GET", "/").with_status(200).create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let req = reqwest::Client::new().get(server.url());
+    let res = test_policy()
+        .send(req, BackendName::OpenAI, Some(&tx), None)
+        .await?;
+
+    rate_limited.assert();
+    success.assert();
+    assert_eq!(res.status(), 200);
+
+    let msg = match rx.recv().await.unwrap() {
+        Event::BackendStatus(msg) => msg,
+        _ => bail!("Wrong type from recv"),
+    };
+    assert_eq!(msg.author, Author::Oatmeal);
+    insta::assert_snapshot!(msg.text, @"openai responded with 429 Too Many Requests. Retrying in 0.0s (attempt 2 of 3)...");
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_returns_the_last_response_after_max_attempts() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("GET", "/").with_status(529).expect(3).create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let req = reqwest::Client::new().get(server.url());
+    let res = test_policy()
+        .send(req, BackendName::Claude, Some(&tx), None)
+        .await?;
+
+    mock.assert();
+    assert_eq!(res.status().as_u16(), 529);
+
+    drop(tx);
+    let mut retries = 0;
+    while rx.recv().await.is_some() {
+        retries += 1;
+    }
+    assert_eq!(retries, 2);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_does_not_wait_past_max_delay() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .with_status(429)
+        .with_header("retry-after", "3600")
+        .expect(1)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let req = reqwest::Client::new().get(server.url());
+    let res = test_policy()
+        .send(req, BackendName::OpenAI, Some(&tx), None)
+        .await?;
+
+    mock.assert();
+    assert_eq!(res.status(), 429);
+
+    drop(tx);
+    assert!(rx.recv().await.is_none());
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_does_not_retry_client_errors() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("GET", "/").with_status(401).expect(1).create();
+
+    let req = reqwest::Client::new().get(server.url());
+    let res = test_policy()
+        .send(req, BackendName::OpenAI, None, None)
+        .await?;
+
+    mock.assert();
+    assert_eq!(res.status(), 401);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_retries_unreachable_backends() {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let req = reqwest::Client::new().get("http://127.0.0.1:1");
+    let res = test_policy()
+        .send(req, BackendName::Ollama, Some(&tx), None)
+        .await;
+
+    assert!(res.is_err());
+
+    drop(tx);
+    let mut retries = 0;
+    while rx.recv().await.is_some() {
+        retries += 1;
+    }
+    assert_eq!(retries, 2);
+}
+
+#[tokio::test]
+async fn it_does_not_retry_dropped_requests() -> Result<()> {
+    // The connection is accepted, but closed before responding.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            drop(socket);
+        }
+    });
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let req = reqwest::Client::new().get(url);
+    let res = test_policy()
+        .send(req, BackendName::OpenAI, Some(&tx), None)
+        .await;
+
+    assert!(res.is_err());
+
+    drop(tx);
+    assert!(rx.recv().await.is_none());
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_stops_waiting_to_retry_when_cancelled() -> Result<()> {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("GET", "/").with_status(503).expect(1).create();
+
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let req = reqwest::Client::new().get(server.url());
+    let res = RetryPolicy::new(3, Duration::from_secs(3600))
+        .send(req, BackendName::OpenAI, None, Some(&cancel))
+        .await?;
+
+    mock.assert();
+    assert_eq!(res.status(), 503);
+
+    return Ok(());
+}
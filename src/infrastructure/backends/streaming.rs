@@ -0,0 +1,259 @@
+#[cfg(test)]
+#[path = "streaming_test.rs"]
+mod tests;
+
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::Lines;
+use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
+
+use crate::infrastructure::backends::http::HttpClient;
+use crate::infrastructure::backends::recorder::Recorder;
+
+/// Longest piece of a response included in a parse error.
+const MAX_ERROR_DATA_LENGTH: usize = 200;
+
+fn convert_err(err: reqwest::Error) -> std::io::Error {
+    let err_msg = err.to_string();
+    return std::io::Error::new(std::io::ErrorKind::Interrupted, err_msg);
+}
+
+/// Format of a streamed response body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// Server-sent events, separated by blank lines.
+    Sse,
+    /// Newline delimited JSON, with an event per line.
+    Ndjson,
+}
+
+/// A single event of a streamed response. NDJSON events have no name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamEvent {
+    pub event: String,
+    pub data: String,
+}
+
+impl StreamEvent {
+    /// Parses the event's data as JSON, returning an error rather than
+    /// panicking on malformed or unexpected data.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        return match serde_json::from_str(&self.data) {
+            Ok(res) => Ok(res),
+            Err(err) => {
+                let mut data = self.data.to_string();
+                if data.len() > MAX_ERROR_DATA_LENGTH {
+                    let mut end = MAX_ERROR_DATA_LENGTH;
+                    while !data.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    data = format!("{}...", &data[..end]);
+                }
+
+                tracing::error!(error = ?err, data = self.data, "Failed to parse streamed response");
+                bail!(format!(
+                    "Failed to parse streamed response: {err}\n\n{data}"
+                ))
+            }
+        };
+    }
+
+    /// Message of an error sent by the backend, either as an `error` event or
+    /// as a JSON object with an `error` field.
+    fn error_message(&self) -> Option<String> {
+        let value = serde_json::from_str::<Value>(&self.data)
+            .unwrap_or_else(|_| return Value::String(self.data.to_string()));
+
+        let error = match value.get("error") {
+            Some(Value::Null) => return None,
+            Some(error) => error,
+            None if self.event == "error" => &value,
+            None => return None,
+        };
+
+        if let Value::String(message) = error {
+            return Some(message.to_string());
+        }
+
+        let message = error
+            .get("message")
+            .and_then(|message| return message.as_str())
+            .map(|message| return message.to_string())
+            .unwrap_or_else(|| return error.to_string());
+
+        return Some(message);
+    }
+}
+
+/// Incremental server-sent events parser, following the field rules of the
+/// HTML specification. Fed one line at a time.
+#[derive(Default)]
+pub struct SseParser {
+    event: String,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Returns an event once the blank line ending it is pushed.
+    pub fn push_line(&mut self, line: &str) -> Option<StreamEvent> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        // Comments are often sent as keep-alives.
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => self.data.push(value.to_string()),
+            // Reconnection isn't supported, so ids and retry times are unused.
+            _ => {}
+        }
+
+        return None;
+    }
+
+    /// Events without data aren't dispatched, as per the specification.
+    fn dispatch(&mut self) -> Option<StreamEvent> {
+        let event = std::mem::take(&mut self.event);
+        let data = std::mem::take(&mut self.data);
+        if data.is_empty() {
+            return None;
+        }
+
+        return Some(StreamEvent {
+            event,
+            data: data.join("\n"),
+        });
+    }
+}
+
+/// Reads events from a streamed response body. The stream ends on the end of
+/// the body, a `[DONE]` event, or when the request is cancelled, keeping the
+/// partial response.
+pub struct EventStream<'a> {
+    lines: Lines<Box<dyn AsyncBufRead + Send + Unpin + 'a>>,
+    format: StreamFormat,
+    read_timeout: Option<Duration>,
+    parser: SseParser,
+    done: bool,
+}
+
+impl<'a> EventStream<'a> {
+    pub fn new(
+        res: reqwest::Response,
+        format: StreamFormat,
+        cancel: &'a CancellationToken,
+    ) -> EventStream<'a> {
+        let stream = res
+            .bytes_stream()
+            .take_until(Box::pin(cancel.cancelled()))
+            .map_err(convert_err);
+
+        let mut events = EventStream::from_reader(StreamReader::new(stream), format);
+        events.read_timeout = HttpClient::read_timeout();
+
+        return events;
+    }
+
+    fn from_reader(
+        reader: impl AsyncBufRead + Send + Unpin + 'a,
+        format: StreamFormat,
+    ) -> EventStream<'a> {
+        let reader: Box<dyn AsyncBufRead + Send + Unpin + 'a> = Box::new(reader);
+        return EventStream {
+            lines: reader.lines(),
+            format,
+            read_timeout: None,
+            parser: SseParser::default(),
+            done: false,
+        };
+    }
+
+    /// Returns the next event, or `None` once the stream has ended. Errors
+    /// sent by the backend are returned as errors.
+    pub async fn next(&mut self) -> Result<Option<StreamEvent>> {
+        while !self.done {
+            let next_line = match self.read_timeout {
+                Some(read_timeout) => {
+                    match tokio::time::timeout(read_timeout, self.lines.next_line()).await {
+                        Ok(res) => res,
+                        Err(_) => {
+                            self.done = true;
+                            bail!(format!(
+                                "Timed out waiting for the backend to respond after {}ms",
+                                read_timeout.as_millis()
+                            ));
+                        }
+                    }
+                }
+                None => self.lines.next_line().await,
+            };
+
+            let line = match next_line {
+                Ok(Some(line)) => {
+                    Recorder::record_chunk(&line);
+                    line
+                }
+                Ok(None) => {
+                    // Incomplete server-sent events at the end of a stream
+                    // are discarded.
+                    self.done = true;
+                    break;
+                }
+                Err(err) => {
+                    self.done = true;
+                    tracing::error!(error = ?err, "Failed to read streamed response");
+                    bail!(format!("Failed to read streamed response: {err}"));
+                }
+            };
+
+            let event = match self.format {
+                StreamFormat::Sse => self.parser.push_line(&line),
+                StreamFormat::Ndjson if line.trim().is_empty() => None,
+                StreamFormat::Ndjson => {
+                    Some(StreamEvent {
+                        event: "".to_string(),
+                        data: line.trim().to_string(),
+                    })
+                }
+            };
+
+            let event = match event {
+                Some(event) => event,
+                None => continue,
+            };
+            tracing::debug!(event = event.event, data = event.data, "Stream event");
+
+            if event.data.trim() == "[DONE]" {
+                self.done = true;
+                break;
+            }
+            if let Some(message) = event.error_message() {
+                self.done = true;
+                bail!(format!("Received an error from the backend: {message}"));
+            }
+
+            return Ok(Some(event));
+        }
+
+        return Ok(None);
+    }
+}
@@ -0,0 +1,281 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use super::EventStream;
+use super::SseParser;
+use super::StreamEvent;
+use super::StreamFormat;
+
+fn event(name: &str, data: &str) -> StreamEvent {
+    return StreamEvent {
+        event: name.to_string(),
+        data: data.to_string(),
+    };
+}
+
+async fn collect(body: &[u8], format: StreamFormat) -> Result<Vec<StreamEvent>> {
+    let mut events = EventStream::from_reader(Cursor::new(body.to_vec()), format);
+    let mut res = vec![];
+    while let Some(event) = events.next().await? {
+        res.push(event);
+    }
+
+    return Ok(res);
+}
+
+/// Small xorshift generator, so fuzzed streams are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        return self.0;
+    }
+
+    fn below(&mut self, max: usize) -> usize {
+        return (self.next() % max as u64) as usize;
+    }
+}
+
+#[test]
+fn it_parses_server_sent_events() {
+    let mut parser = SseParser::default();
+    let lines = [
+        ": keep-alive",
+        "event: content_block_delta",
+        "id: 1",
+        "retry: 1000",
+        "data: {\"text\":",
+        "data:\"Hello\"}",
+        "",
+        "data:  two spaces\r",
+        "\r",
+    ];
+
+    let events = lines
+        .iter()
+        .filter_map(|line| return parser.push_line(line))
+        .collect::<Vec<StreamEvent>>();
+
+    assert_eq!(
+        events,
+        vec![
+            event("content_block_delta", "{\"text\":\n\"Hello\"}"),
+            event("", " two spaces"),
+        ]
+    );
+}
+
+#[test]
+fn it_skips_events_without_data() {
+    let mut parser = SseParser::default();
+
+    assert_eq!(parser.push_line("event: ping"), None);
+    assert_eq!(parser.push_line(""), None);
+    assert_eq!(parser.push_line("data: next"), None);
+    assert_eq!(parser.push_line(""), Some(event("", "next")));
+}
+
+#[tokio::test]
+async fn it_ends_on_done() -> Result<()> {
+    let body = b"data: first\n\ndata: [DONE]\n\ndata: after\n\n";
+
+    let events = collect(body, StreamFormat::Sse).await?;
+
+    assert_eq!(events, vec![event("", "first")]);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_discards_incomplete_events() -> Result<()> {
+    let body = b"data: first\n\ndata: {\"partial\":";
+
+    let events = collect(body, StreamFormat::Sse).await?;
+
+    assert_eq!(events, vec![event("", "first")]);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_returns_error_events() -> Result<()> {
+    let body = b"event: message_start\ndata: {}\n\nevent: error\ndata: {\"type\": \"error\", \"error\": {\"type\": \"overloaded_error\", \"message\": \"Overloaded\"}}\n\n";
+    let mut events = EventStream::from_reader(Cursor::new(body.to_vec()), StreamFormat::Sse);
+
+    assert_eq!(events.next().await?, Some(event("message_start", "{}")));
+    assert_eq!(
+        events.next().await.unwrap_err().to_string(),
+        "Received an error from the backend: Overloaded"
+    );
+    assert_eq!(events.next().await?, None);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_returns_error_objects() -> Result<()> {
+    let res = collect(
+        b"data: {\"error\": {\"message\": \"Rate limited\"}}\n\n",
+        StreamFormat::Sse,
+    )
+    .await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Received an error from the backend: Rate limited"
+    );
+
+    let res = collect(b"{\"error\": \"model not found\"}\n", StreamFormat::Ndjson).await;
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Received an error from the backend: model not found"
+    );
+
+    let events = collect(b"{\"error\": null, \"done\": true}", StreamFormat::Ndjson).await?;
+    assert_eq!(events.len(), 1);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_reads_ndjson() -> Result<()> {
+    let body = b"{\"response\": \"Hello\"}\n\n  \n{\"response\": \"World\"}";
+
+    let events = collect(body, StreamFormat::Ndjson).await?;
+
+    assert_eq!(
+        events,
+        vec![
+            event("", "{\"response\": \"Hello\"}"),
+            event("", "{\"response\": \"World\"}"),
+        ]
+    );
+
+    return Ok(());
+}
+
+#[test]
+fn it_returns_parse_errors() {
+    let res = event("", "{\"text\": ").json::<Value>();
+
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("Failed to parse streamed response: EOF while parsing"));
+}
+
+#[tokio::test]
+async fn it_returns_read_errors() {
+    let res = collect(b"data: \xff\xfe\n\n", StreamFormat::Sse).await;
+
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("Failed to read streamed response"));
+}
+
+#[tokio::test]
+async fn it_handles_truncated_server_sent_events() -> Result<()> {
+    let body = "event: data\ndata: {\"content\": \"Hello \"}\n\n: ping\n\ndata: {\"content\":\ndata: \"World\"}\n\ndata: [DONE]\n\n";
+    let all_events = collect(body.as_bytes(), StreamFormat::Sse).await?;
+    assert_eq!(all_events.len(), 2);
+
+    for end in 0..body.len() {
+        let events = collect(&body.as_bytes()[..end], StreamFormat::Sse).await?;
+
+        assert_eq!(events, all_events[..events.len()], "Truncated at {end}");
+        for event in events {
+            event.json::<Value>()?;
+        }
+    }
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_handles_truncated_ndjson() -> Result<()> {
+    let body =
+        "{\"response\": \"Hello \", \"done\": false}\n{\"response\": \"World\", \"done\": true}\n";
+    let all_events = collect(body.as_bytes(), StreamFormat::Ndjson).await?;
+    assert_eq!(all_events.len(), 2);
+
+    for end in 0..body.len() {
+        let events = collect(&body.as_bytes()[..end], StreamFormat::Ndjson).await?;
+
+        // Only the last line can be cut short, which fails to parse rather
+        // than panicking.
+        for (idx, event) in events.iter().enumerate() {
+            if idx + 1 < events.len() {
+                assert_eq!(event, &all_events[idx], "Truncated at {end}");
+                event.json::<Value>()?;
+            } else if event != &all_events[idx] {
+                assert!(event.json::<Value>().is_err(), "Truncated at {end}");
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_survives_garbage_streams() {
+    let pieces: [&[u8]; 14] = [
+        b"data:",
+        b"data: ",
+        b"event: error",
+        b"event:",
+        b":",
+        b"\n",
+        b"\r\n",
+        b"[DONE]",
+        b"{\"error\":",
+        b"{\"content\": \"Hi\"}",
+        b"{",
+        b"}",
+        b"\"",
+        b"\xff",
+    ];
+    let mut rng = Rng(0x5eed_0a7e);
+
+    for _ in 0..500 {
+        let mut body = vec![];
+        for _ in 0..rng.below(40) {
+            if rng.below(4) == 0 {
+                body.push(rng.next() as u8);
+            } else {
+                body.extend_from_slice(pieces[rng.below(pieces.len())]);
+            }
+        }
+
+        for format in [StreamFormat::Sse, StreamFormat::Ndjson] {
+            let mut events = EventStream::from_reader(Cursor::new(body.clone()), format);
+            // Errors end the stream, so this always finishes.
+            while let Ok(Some(event)) = events.next().await {
+                let _ = event.json::<Value>();
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn it_times_out_waiting_for_the_next_line() -> Result<()> {
+    let (mut writer, reader) = tokio::io::duplex(64);
+    tokio::io::AsyncWriteExt::write_all(&mut writer, b"data: first\n\n").await?;
+
+    let mut events = EventStream::from_reader(tokio::io::BufReader::new(reader), StreamFormat::Sse);
+    events.read_timeout = Some(Duration::from_millis(10));
+
+    assert_eq!(events.next().await?, Some(event("", "first")));
+    assert_eq!(
+        events.next().await.unwrap_err().to_string(),
+        "Timed out waiting for the backend to respond after 10ms"
+    );
+    assert_eq!(events.next().await?, None);
+
+    return Ok(());
+}
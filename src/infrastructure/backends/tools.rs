@@ -0,0 +1,322 @@
+#[cfg(test)]
+#[path = "tools_test.rs"]
+mod tests;
+
+use std::env;
+use std::path;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use regex::Regex;
+use tokio::fs;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::configuration::Config;
+use crate::configuration::ConfigKey;
+use crate::domain::models::Event;
+use crate::domain::models::ToolApproval;
+use crate::domain::models::ToolCall;
+use crate::domain::models::ToolName;
+use crate::domain::models::ToolResult;
+
+/// Maximum number of times a model may respond with tool calls during a single
+/// completion, stopping a model from calling tools in a loop.
+pub const MAX_TOOL_ROUNDS: usize = 10;
+
+/// Tool output is truncated to keep it within a model's context window.
+const MAX_OUTPUT_BYTES: usize = 32 * 1024;
+const MAX_GREP_MATCHES: usize = 200;
+const MAX_GREP_FILE_BYTES: u64 = 1024 * 1024;
+const TEST_COMMAND_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Directories skipped when searching the workspace.
+const IGNORED_DIRECTORIES: [&str; 2] = ["node_modules", "target"];
+
+fn truncate_start(text: &str) -> String {
+    if text.len() <= MAX_OUTPUT_BYTES {
+        return text.to_string();
+    }
+
+    let mut end = MAX_OUTPUT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    return format!("{}\n... (truncated)", &text[..end]);
+}
+
+fn truncate_end(text: &str) -> String {
+    if text.len() <= MAX_OUTPUT_BYTES {
+        return text.to_string();
+    }
+
+    let mut start = text.len() - MAX_OUTPUT_BYTES;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+
+    return format!("(truncated) ...\n{}", &text[start..]);
+}
+
+/// Local tools offered to a model, restricted to the workspace the user
+/// started Oatmeal from.
+pub struct Tools {
+    names: Vec<ToolName>,
+    workspace: path::PathBuf,
+    test_command: String,
+}
+
+impl Default for Tools {
+    fn default() -> Tools {
+        let names = ToolName::from_config().unwrap_or_default();
+        let workspace = env::current_dir().unwrap_or_default();
+        return Tools::new(names, workspace, &Config::get(ConfigKey::ToolsTestCommand));
+    }
+}
+
+impl Tools {
+    pub fn new(names: Vec<ToolName>, workspace: path::PathBuf, test_command: &str) -> Tools {
+        return Tools {
+            names,
+            workspace,
+            test_command: test_command.to_string(),
+        };
+    }
+
+    pub fn names(&self) -> &[ToolName] {
+        return &self.names;
+    }
+
+    /// Asks the user to approve a tool call, then runs it. Returns `None` when
+    /// the completion is cancelled while waiting on the user.
+    pub async fn call(
+        &self,
+        call: &ToolCall,
+        cancel: &CancellationToken,
+        tx: &mpsc::UnboundedSender<Event>,
+    ) -> Result<Option<ToolResult>> {
+        let (approval, approval_rx) = ToolApproval::new(call.clone());
+        tx.send(Event::BackendToolCall(approval))?;
+
+        let approved = tokio::select! {
+            res = approval_rx => res.unwrap_or(false),
+            _ = cancel.cancelled() => return Ok(None),
+        };
+        if !approved {
+            return Ok(Some(ToolResult::error(
+                "The user denied running this tool.",
+            )));
+        }
+
+        return Ok(Some(self.execute(call).await));
+    }
+
+    /// Runs a tool call without asking for approval.
+    pub async fn execute(&self, call: &ToolCall) -> ToolResult {
+        let tool_name = match ToolName::parse(&call.name) {
+            Some(tool_name) if self.names.contains(&tool_name) => tool_name,
+            _ => return ToolResult::error(&format!("{} is not an available tool.", call.name)),
+        };
+
+        let res = match tool_name {
+            ToolName::ReadFile => self.read_file(call).await,
+            ToolName::ListDirectory => self.list_directory(call).await,
+            ToolName::Grep => self.grep(call).await,
+            ToolName::RunTests => self.run_tests().await,
+        };
+
+        tracing::debug!(tool = call.summary(), ok = res.is_ok(), "Ran tool");
+
+        return match res {
+            Ok(content) => ToolResult::new(&content),
+            Err(err) => ToolResult::error(&err.to_string()),
+        };
+    }
+
+    /// Resolves a path relative to the workspace, refusing paths which escape
+    /// it.
+    fn resolve_path(&self, file_path: &str) -> Result<path::PathBuf> {
+        let workspace = self.workspace.canonicalize()?;
+        let resolved = match workspace.join(file_path).canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) => bail!(format!("{file_path} does not exist")),
+        };
+
+        if !resolved.starts_with(&workspace) {
+            bail!(format!("{file_path} is outside of the workspace"));
+        }
+
+        return Ok(resolved);
+    }
+
+    fn relative_path(&self, file_path: &path::Path) -> String {
+        let workspace = self.workspace.canonicalize().unwrap_or_default();
+        let relative = file_path.strip_prefix(&workspace).unwrap_or(file_path);
+        if relative.as_os_str().is_empty() {
+            return ".".to_string();
+        }
+
+        return relative.to_string_lossy().replace('\\', "/");
+    }
+
+    async fn read_file(&self, call: &ToolCall) -> Result<String> {
+        let file_path = match call.argument("path") {
+            Some(file_path) => file_path,
+            None => bail!("A path is required"),
+        };
+
+        let resolved = self.resolve_path(&file_path)?;
+        let content = match String::from_utf8(fs::read(&resolved).await?) {
+            Ok(content) => content,
+            Err(_) => bail!(format!("{file_path} is not a text file")),
+        };
+
+        return Ok(truncate_start(&content));
+    }
+
+    async fn list_directory(&self, call: &ToolCall) -> Result<String> {
+        let dir_path = call
+            .argument("path")
+            .unwrap_or_else(|| return ".".to_string());
+        let resolved = self.resolve_path(&dir_path)?;
+        if !resolved.is_dir() {
+            bail!(format!("{dir_path} is not a directory"));
+        }
+
+        let mut entries = vec![];
+        let mut read_dir = fs::read_dir(&resolved).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type().await?.is_dir() {
+                name = format!("{name}/");
+            }
+            entries.push(name);
+        }
+        entries.sort();
+
+        if entries.is_empty() {
+            return Ok(format!("{dir_path} is empty"));
+        }
+
+        return Ok(truncate_start(&entries.join("\n")));
+    }
+
+    async fn grep(&self, call: &ToolCall) -> Result<String> {
+        let pattern = match call.argument("pattern") {
+            Some(pattern) => pattern,
+            None => bail!("A pattern is required"),
+        };
+        let regex = Regex::new(&pattern)?;
+        let search_path = call
+            .argument("path")
+            .unwrap_or_else(|| return ".".to_string());
+
+        let mut matches = vec![];
+        let mut pending = vec![self.resolve_path(&search_path)?];
+        while let Some(current) = pending.pop() {
+            if matches.len() >= MAX_GREP_MATCHES {
+                break;
+            }
+
+            if current.is_dir() {
+                let mut children = vec![];
+                let mut read_dir = fs::read_dir(&current).await?;
+                while let Some(entry) = read_dir.next_entry().await? {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if name.starts_with('.') || IGNORED_DIRECTORIES.contains(&name.as_str()) {
+                        continue;
+                    }
+                    // Symlinks could lead outside of the workspace.
+                    if entry.file_type().await?.is_symlink() {
+                        continue;
+                    }
+                    children.push(entry.path());
+                }
+                // Reversed so files are popped in alphabetical order.
+                children.sort();
+                children.reverse();
+                pending.extend(children);
+                continue;
+            }
+
+            if fs::metadata(&current).await?.len() > MAX_GREP_FILE_BYTES {
+                continue;
+            }
+            let content = match String::from_utf8(fs::read(&current).await?) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            for (idx, line) in content.lines().enumerate() {
+                if regex.is_match(line) {
+                    matches.push(format!(
+                        "{}:{}: {}",
+                        self.relative_path(&current),
+                        idx + 1,
+                        line.trim()
+                    ));
+                }
+                if matches.len() >= MAX_GREP_MATCHES {
+                    break;
+                }
+            }
+        }
+
+        if matches.is_empty() {
+            return Ok(format!("No matches found for {pattern}"));
+        }
+
+        return Ok(truncate_start(&matches.join("\n")));
+    }
+
+    async fn run_tests(&self) -> Result<String> {
+        if self.test_command.is_empty() {
+            bail!("No test command is configured");
+        }
+
+        #[cfg(target_os = "windows")]
+        let mut command = tokio::process::Command::new("cmd");
+        #[cfg(target_os = "windows")]
+        command.arg("/C");
+
+        #[cfg(not(target_os = "windows"))]
+        let mut command = tokio::process::Command::new("sh");
+        #[cfg(not(target_os = "windows"))]
+        command.arg("-c");
+
+        command
+            .arg(&self.test_command)
+            .current_dir(&self.workspace)
+            .kill_on_drop(true);
+
+        let output = match tokio::time::timeout(TEST_COMMAND_TIMEOUT, command.output()).await {
+            Ok(output) => output?,
+            Err(_) => {
+                bail!(format!(
+                    "{} timed out after {} minutes",
+                    self.test_command,
+                    TEST_COMMAND_TIMEOUT.as_secs() / 60
+                ))
+            }
+        };
+
+        let exit_code = output
+            .status
+            .code()
+            .map(|code| return code.to_string())
+            .unwrap_or_else(|| return "none".to_string());
+        let combined = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        // The end of a test run's output holds the failures and summary.
+        return Ok(format!(
+            "Exit code: {exit_code}\n\n{}",
+            truncate_end(combined.trim())
+        ));
+    }
+}
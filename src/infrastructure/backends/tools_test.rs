@@ -0,0 +1,213 @@
+use std::fs;
+
+use anyhow::Result;
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::Tools;
+use crate::domain::models::Event;
+use crate::domain::models::ToolCall;
+use crate::domain::models::ToolName;
+use crate::domain::models::ToolResult;
+
+fn create_workspace() -> Result<tempfile::TempDir> {
+    let workspace = tempfile::tempdir()?;
+    fs::create_dir_all(workspace.path().join("src"))?;
+    fs::create_dir_all(workspace.path().join("target"))?;
+    fs::write(workspace.path().join("README.md"), "# Oatmeal\n")?;
+    fs::write(
+        workspace.path().join("src/main.rs"),
+        "fn main() {\n    println!(\"Hello\");\n}\n",
+    )?;
+    fs::write(workspace.path().join("target/main.rs"), "fn main() {}\n")?;
+
+    return Ok(workspace);
+}
+
+fn all_tools(workspace: &tempfile::TempDir, test_command: &str) -> Tools {
+    return Tools::new(
+        vec![
+            ToolName::ReadFile,
+            ToolName::ListDirectory,
+            ToolName::Grep,
+            ToolName::RunTests,
+        ],
+        workspace.path().to_path_buf(),
+        test_command,
+    );
+}
+
+#[tokio::test]
+async fn it_reads_files() -> Result<()> {
+    let workspace = create_workspace()?;
+    let call = ToolCall::new("1", "read_file", json!({"path": "README.md"}));
+
+    let res = all_tools(&workspace, "").execute(&call).await;
+
+    assert_eq!(res, ToolResult::new("# Oatmeal\n"));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_refuses_paths_outside_the_workspace() -> Result<()> {
+    let workspace = create_workspace()?;
+    let outside = tempfile::NamedTempFile::new()?;
+    let call = ToolCall::new(
+        "1",
+        "read_file",
+        json!({"path": outside.path().to_string_lossy()}),
+    );
+
+    let res = all_tools(&workspace, "").execute(&call).await;
+
+    assert!(res.is_error);
+    assert!(res.content.ends_with("is outside of the workspace"));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_lists_directories() -> Result<()> {
+    let workspace = create_workspace()?;
+    let call = ToolCall::new("1", "list_directory", json!({}));
+
+    let res = all_tools(&workspace, "").execute(&call).await;
+
+    assert_eq!(res, ToolResult::new("README.md\nsrc/\ntarget/"));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_greps_the_workspace() -> Result<()> {
+    let workspace = create_workspace()?;
+    let call = ToolCall::new("1", "grep", json!({"pattern": "fn \\w+"}));
+
+    let res = all_tools(&workspace, "").execute(&call).await;
+
+    assert_eq!(res, ToolResult::new("src/main.rs:1: fn main() {"));
+
+    return Ok(());
+}
+
+#[cfg(not(target_os = "windows"))]
+#[tokio::test]
+async fn it_does_not_grep_symlinks_outside_the_workspace() -> Result<()> {
+    let workspace = create_workspace()?;
+    let outside = tempfile::tempdir()?;
+    fs::write(outside.path().join("secret.rs"), "fn secret() {}\n")?;
+    std::os::unix::fs::symlink(outside.path(), workspace.path().join("src/linked"))?;
+    std::os::unix::fs::symlink(
+        outside.path().join("secret.rs"),
+        workspace.path().join("src/secret.rs"),
+    )?;
+    let call = ToolCall::new("1", "grep", json!({"pattern": "fn \\w+"}));
+
+    let res = all_tools(&workspace, "").execute(&call).await;
+
+    assert_eq!(res, ToolResult::new("src/main.rs:1: fn main() {"));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_reports_invalid_grep_patterns() -> Result<()> {
+    let workspace = create_workspace()?;
+    let call = ToolCall::new("1", "grep", json!({"pattern": "fn ("}));
+
+    let res = all_tools(&workspace, "").execute(&call).await;
+
+    assert!(res.is_error);
+
+    return Ok(());
+}
+
+#[cfg(not(target_os = "windows"))]
+#[tokio::test]
+async fn it_runs_the_test_command() -> Result<()> {
+    let workspace = create_workspace()?;
+    let call = ToolCall::new("1", "run_tests", json!({}));
+
+    let res = all_tools(&workspace, "ls src && exit 3")
+        .execute(&call)
+        .await;
+
+    assert_eq!(res, ToolResult::new("Exit code: 3\n\nmain.rs"));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_refuses_tools_which_are_not_enabled() -> Result<()> {
+    let workspace = create_workspace()?;
+    let tools = Tools::new(vec![ToolName::ReadFile], workspace.path().to_path_buf(), "");
+    let call = ToolCall::new("1", "run_tests", json!({}));
+
+    let res = tools.execute(&call).await;
+
+    assert_eq!(
+        res,
+        ToolResult::error("run_tests is not an available tool.")
+    );
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_waits_for_approval() -> Result<()> {
+    let workspace = create_workspace()?;
+    let tools = all_tools(&workspace, "");
+    let call = ToolCall::new("1", "read_file", json!({"path": "README.md"}));
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let cancel = CancellationToken::new();
+
+    let approve = async {
+        if let Some(Event::BackendToolCall(approval)) = rx.recv().await {
+            approval.respond(true);
+        }
+    };
+    let (res, _) = tokio::join!(tools.call(&call, &cancel, &tx), approve);
+
+    assert_eq!(res?, Some(ToolResult::new("# Oatmeal\n")));
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_does_not_run_denied_calls() -> Result<()> {
+    let workspace = create_workspace()?;
+    let tools = all_tools(&workspace, "");
+    let call = ToolCall::new("1", "read_file", json!({"path": "README.md"}));
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let cancel = CancellationToken::new();
+
+    let deny = async {
+        if let Some(Event::BackendToolCall(approval)) = rx.recv().await {
+            approval.respond(false);
+        }
+    };
+    let (res, _) = tokio::join!(tools.call(&call, &cancel, &tx), deny);
+
+    assert_eq!(
+        res?,
+        Some(ToolResult::error("The user denied running this tool."))
+    );
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn it_stops_waiting_when_cancelled() -> Result<()> {
+    let workspace = create_workspace()?;
+    let tools = all_tools(&workspace, "");
+    let call = ToolCall::new("1", "read_file", json!({"path": "README.md"}));
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    assert_eq!(tools.call(&call, &cancel, &tx).await?, None);
+
+    return Ok(());
+}
@@ -106,7 +106,7 @@ async fn main() {
     background_futures.spawn(async move {
         let backend = BackendName::parse(Config::get(ConfigKey::Backend)).unwrap();
         return ActionsService::start(
-            BackendManager::get(backend).unwrap(),
+            BackendManager::get(backend).await.unwrap(),
             event_tx,
             &mut action_rx,
         )